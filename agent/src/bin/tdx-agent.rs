@@ -0,0 +1,77 @@
+//! `tdx-agent`: serves quotes to the workloads of a TD over a Unix socket.
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use tdx_agent::protocol::DEFAULT_SOCKET_PATH;
+use tdx_agent::server::{bind, serve, Agent, AgentConfig, AuditLog, RateLimit};
+
+#[derive(Parser)]
+#[clap(
+    name = "tdx-agent",
+    version,
+    about = "Serves Intel TDX quotes to the workloads of a TD over a Unix socket"
+)]
+struct Cli {
+    #[clap(long, default_value = DEFAULT_SOCKET_PATH)]
+    socket: PathBuf,
+
+    /// Permissions of the socket, in octal.
+    #[clap(long, default_value = "660")]
+    socket_mode: String,
+
+    /// Group owning the socket, to let the members of a group request quotes.
+    #[clap(long)]
+    socket_gid: Option<u32>,
+
+    /// Quotes per minute each UID can request.
+    #[clap(long, default_value_t = 60)]
+    rate: u32,
+
+    /// Quotes each UID can request at once.
+    #[clap(long, default_value_t = 10)]
+    burst: u32,
+
+    /// Only serve these UIDs. Can be repeated, any UID is served if not set.
+    #[clap(long = "allow-uid")]
+    allowed_uids: Vec<u32>,
+
+    /// File the audit log is appended to, stderr if not set.
+    #[clap(long)]
+    audit_log: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let mode = u32::from_str_radix(&cli.socket_mode, 8)
+        .with_context(|| format!("invalid socket mode {}", cli.socket_mode))?;
+
+    let audit_log = match &cli.audit_log {
+        Some(path) => AuditLog::open(path)?,
+        None => AuditLog::stderr(),
+    };
+    let agent = Arc::new(Agent::new(AgentConfig {
+        rate_limit: RateLimit {
+            per_minute: cli.rate,
+            burst: cli.burst,
+        },
+        allowed_uids: cli.allowed_uids,
+        audit_log,
+    }));
+
+    // Remove the socket left behind by a previous run.
+    if cli.socket.exists() {
+        std::fs::remove_file(&cli.socket)?;
+    }
+    let listener = bind(&cli.socket, mode, cli.socket_gid)?;
+    println!("tdx-agent listening on {}", cli.socket.display());
+
+    serve(listener, agent, async {
+        let _ = tokio::signal::ctrl_c().await;
+    })
+    .await?;
+    let _ = std::fs::remove_file(&cli.socket);
+    Ok(())
+}
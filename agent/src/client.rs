@@ -0,0 +1,198 @@
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::{AgentError, Result};
+use crate::protocol::{
+    ErrorResponse, QuoteRequest, QuoteResponse, DEFAULT_SOCKET_PATH, HEALTH_PATH, QUOTE_PATH,
+};
+
+/// A quote returned by the agent.
+#[derive(Clone, Debug)]
+pub struct Quote {
+    pub quote: Vec<u8>,
+    /// Only available on Azure Confidential VMs.
+    pub var_data: Option<Vec<u8>>,
+}
+
+/// Requests quotes from the agent over its Unix socket.
+///
+/// The client only depends on the standard library, build the crate with
+/// `default-features = false` to leave out the daemon.
+pub struct AgentClient {
+    socket_path: PathBuf,
+    timeout: Option<Duration>,
+}
+
+impl Default for AgentClient {
+    fn default() -> Self {
+        AgentClient::new(DEFAULT_SOCKET_PATH)
+    }
+}
+
+impl AgentClient {
+    pub fn new(socket_path: impl AsRef<Path>) -> Self {
+        AgentClient {
+            socket_path: socket_path.as_ref().to_path_buf(),
+            timeout: Some(Duration::from_secs(30)),
+        }
+    }
+
+    /// Read and write timeout of the requests, defaults to 30 seconds.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Requests a quote with the given report data.
+    pub fn get_quote(&self, report_data: &[u8; 64]) -> Result<Quote> {
+        let request = serde_json::to_vec(&QuoteRequest {
+            report_data: hex::encode(report_data),
+        })?;
+        let body = self.request("POST", QUOTE_PATH, &request)?;
+        let response: QuoteResponse = serde_json::from_slice(&body)
+            .map_err(|e| AgentError::Http(format!("invalid response: {:?}", e)))?;
+
+        Ok(Quote {
+            quote: decode(&response.quote)?,
+            var_data: response.var_data.as_deref().map(decode).transpose()?,
+        })
+    }
+
+    /// Checks that the agent is up.
+    pub fn health(&self) -> Result<()> {
+        self.request("GET", HEALTH_PATH, &[])?;
+        Ok(())
+    }
+
+    /// Sends a HTTP/1.1 request on a new connection and returns the body of a 2xx response.
+    fn request(&self, method: &str, path: &str, body: &[u8]) -> Result<Vec<u8>> {
+        let mut stream = UnixStream::connect(&self.socket_path)?;
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
+
+        let mut request = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n",
+            method,
+            path,
+            body.len()
+        );
+        if !body.is_empty() {
+            request.push_str("Content-Type: application/json\r\n");
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(body)?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        parse_response(&response)
+    }
+}
+
+fn parse_response(response: &[u8]) -> Result<Vec<u8>> {
+    let header_end = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| AgentError::Http("truncated response".to_string()))?;
+    let head = String::from_utf8_lossy(&response[..header_end]);
+    let mut body = response[header_end + 4..].to_vec();
+
+    let mut lines = head.split("\r\n");
+    let status: u16 = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| AgentError::Http("invalid status line".to_string()))?;
+    let chunked = lines.any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("transfer-encoding:") && line.contains("chunked")
+    });
+    if chunked {
+        body = decode_chunked(&body)?;
+    }
+
+    if (200..300).contains(&status) {
+        return Ok(body);
+    }
+    let message = serde_json::from_slice::<ErrorResponse>(&body)
+        .map(|e| e.error)
+        .unwrap_or_else(|_| String::from_utf8_lossy(&body).into_owned());
+    Err(match status {
+        400 => AgentError::BadRequest(message),
+        403 => AgentError::Forbidden(message),
+        429 => AgentError::RateLimited(message),
+        _ => AgentError::Http(format!("{}: {}", status, message)),
+    })
+}
+
+fn decode_chunked(mut data: &[u8]) -> Result<Vec<u8>> {
+    let invalid = || AgentError::Http("invalid chunked body".to_string());
+    let mut body = Vec::new();
+    loop {
+        let line_end = data
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or_else(invalid)?;
+        let size = std::str::from_utf8(&data[..line_end]).map_err(|_| invalid())?;
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| invalid())?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            return Ok(body);
+        }
+        if data.len() < size.checked_add(2).ok_or_else(invalid)? {
+            return Err(invalid());
+        }
+        body.extend_from_slice(&data[..size]);
+        data = &data[size + 2..];
+    }
+}
+
+fn decode(data: &str) -> Result<Vec<u8>> {
+    base64_url::decode(data).map_err(|e| AgentError::Http(format!("invalid base64: {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_responses() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}";
+        assert_eq!(parse_response(response).unwrap(), b"{}");
+
+        let response = b"HTTP/1.1 400 Bad Request\r\n\r\n{\"error\":\"invalid report data\"}";
+        assert!(matches!(
+            parse_response(response),
+            Err(AgentError::BadRequest(message)) if message == "invalid report data"
+        ));
+        let response = b"HTTP/1.1 429 Too Many Requests\r\n\r\nslow down";
+        assert!(matches!(
+            parse_response(response),
+            Err(AgentError::RateLimited(message)) if message == "slow down"
+        ));
+        let response = b"HTTP/1.1 500 Internal Server Error\r\n\r\n";
+        assert!(matches!(parse_response(response), Err(AgentError::Http(_))));
+
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\n").is_err());
+        assert!(parse_response(b"garbage\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn parses_chunked_responses() {
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            4\r\n{\"a\"\r\n4;ext=1\r\n:1}\n\r\n0\r\n\r\n";
+        assert_eq!(parse_response(response).unwrap(), b"{\"a\":1}\n");
+    }
+
+    #[test]
+    fn rejects_invalid_chunks() {
+        assert_eq!(decode_chunked(b"0\r\n\r\n").unwrap(), b"");
+        assert!(decode_chunked(b"5\r\nabc\r\n0\r\n\r\n").is_err());
+        assert!(decode_chunked(b"zz\r\nabc\r\n").is_err());
+        assert!(decode_chunked(b"3\r\nabc\r\n").is_err());
+        assert!(decode_chunked(b"ffffffffffffffff\r\nabc\r\n").is_err());
+    }
+}
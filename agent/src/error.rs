@@ -0,0 +1,51 @@
+use std::fmt::Display;
+
+pub type Result<T> = std::result::Result<T, AgentError>;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AgentError {
+    /// The request is malformed.
+    BadRequest(String),
+    /// The caller is not allowed to request quotes.
+    Forbidden(String),
+    /// The caller exceeded its quote rate.
+    RateLimited(String),
+    /// The quote could not be generated.
+    Quote(String),
+    Http(String),
+    IO(String),
+}
+
+impl Display for AgentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AgentError::BadRequest(err) => write!(f, "BadRequest: {}", err),
+            AgentError::Forbidden(err) => write!(f, "Forbidden: {}", err),
+            AgentError::RateLimited(err) => write!(f, "RateLimited: {}", err),
+            AgentError::Quote(err) => write!(f, "Quote: {}", err),
+            AgentError::Http(err) => write!(f, "Http: {}", err),
+            AgentError::IO(err) => write!(f, "IO: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for AgentError {}
+
+impl From<std::io::Error> for AgentError {
+    fn from(err: std::io::Error) -> Self {
+        AgentError::IO(format!("{:?}", err))
+    }
+}
+
+impl From<serde_json::Error> for AgentError {
+    fn from(err: serde_json::Error) -> Self {
+        AgentError::BadRequest(format!("{:?}", err))
+    }
+}
+
+#[cfg(feature = "server")]
+impl From<tdx::error::TdxError> for AgentError {
+    fn from(err: tdx::error::TdxError) -> Self {
+        AgentError::Quote(err.to_string())
+    }
+}
@@ -0,0 +1,11 @@
+//! Quote generation agent for workloads inside an Intel TDX guest.
+//!
+//! Generating a quote through configfs-tsm or the TPM requires root and device access.
+//! The agent daemon runs with these privileges and serves quotes over HTTP on a Unix socket,
+//! with per-user rate limiting and an audit log. Unprivileged workloads use `AgentClient`
+//! to request quotes with their own report data.
+pub mod client;
+pub mod error;
+pub mod protocol;
+#[cfg(feature = "server")]
+pub mod server;
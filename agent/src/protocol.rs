@@ -0,0 +1,29 @@
+//! Messages exchanged between the client and the agent.
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_SOCKET_PATH: &str = "/run/tdx-agent.sock";
+
+pub const QUOTE_PATH: &str = "/v1/quote";
+pub const HEALTH_PATH: &str = "/v1/health";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuoteRequest {
+    /// Hex encoded report data, 64 bytes.
+    pub report_data: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuoteResponse {
+    /// Base64url encoded quote.
+    pub quote: String,
+    /// Base64url encoded var data, only returned on Azure Confidential VMs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub var_data: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
@@ -0,0 +1,455 @@
+//! The agent daemon: serves quotes over HTTP on a Unix socket.
+use std::collections::HashMap;
+use std::fs::{DirBuilder, File, OpenOptions, Permissions};
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use axum::body::Bytes;
+use axum::extract::connect_info::Connected;
+use axum::extract::{ConnectInfo, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::serve::IncomingStream;
+use axum::{Json, Router};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tdx::device::{Device, DeviceOptions};
+use tokio::net::UnixListener;
+
+use crate::error::{AgentError, Result};
+use crate::protocol::{ErrorResponse, QuoteRequest, QuoteResponse, HEALTH_PATH, QUOTE_PATH};
+
+/// Credentials of the process on the other end of the socket, from `SO_PEERCRED`.
+#[derive(Clone, Copy, Debug)]
+pub struct PeerCred {
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: Option<i32>,
+}
+
+/// Connection info of a client, `None` if its credentials could not be read.
+#[derive(Clone, Copy, Debug)]
+pub struct Peer(pub Option<PeerCred>);
+
+impl Connected<IncomingStream<'_, UnixListener>> for Peer {
+    fn connect_info(stream: IncomingStream<'_, UnixListener>) -> Self {
+        Peer(stream.io().peer_cred().ok().map(|cred| PeerCred {
+            uid: cred.uid(),
+            gid: cred.gid(),
+            pid: cred.pid(),
+        }))
+    }
+}
+
+/// Token bucket rate limit applied to each UID.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    /// Sustained number of quotes per minute.
+    pub per_minute: u32,
+    /// Number of quotes that can be requested at once.
+    pub burst: u32,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit {
+            per_minute: 60,
+            burst: 10,
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct RateLimiter {
+    limit: RateLimit,
+    buckets: Mutex<HashMap<u32, Bucket>>,
+}
+
+impl RateLimiter {
+    fn new(limit: RateLimit) -> Self {
+        RateLimiter {
+            limit,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn acquire(&self, uid: u32) -> Result<()> {
+        let now = Instant::now();
+        let burst = self.limit.burst.max(1) as f64;
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(uid).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+
+        let refill =
+            now.duration_since(bucket.updated).as_secs_f64() * self.limit.per_minute as f64 / 60.0;
+        bucket.tokens = (bucket.tokens + refill).min(burst);
+        bucket.updated = now;
+
+        if bucket.tokens < 1.0 {
+            return Err(AgentError::RateLimited(format!(
+                "uid {} exceeded {} quotes per minute",
+                uid, self.limit.per_minute
+            )));
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+/// One line of the audit log.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AuditRecord<'a> {
+    time: String,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    pid: Option<i32>,
+    report_data: Option<&'a str>,
+    result: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quote_sha256: Option<String>,
+}
+
+/// Appends one JSON object per quote request to a file, or to stderr.
+pub struct AuditLog {
+    file: Option<Mutex<File>>,
+}
+
+impl AuditLog {
+    pub fn stderr() -> Self {
+        AuditLog { file: None }
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(AuditLog {
+            file: Some(Mutex::new(file)),
+        })
+    }
+
+    fn record(&self, record: &AuditRecord) {
+        let mut line = match serde_json::to_string(record) {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        line.push('\n');
+        let written = match &self.file {
+            Some(file) => file.lock().unwrap().write_all(line.as_bytes()),
+            None => std::io::stderr().write_all(line.as_bytes()),
+        };
+        if let Err(err) = written {
+            eprintln!("failed to write the audit log: {:?}", err);
+        }
+    }
+}
+
+pub struct AgentConfig {
+    pub rate_limit: RateLimit,
+    /// UIDs allowed to request quotes, any UID if empty.
+    pub allowed_uids: Vec<u32>,
+    pub audit_log: AuditLog,
+}
+
+pub struct Agent {
+    config: AgentConfig,
+    limiter: RateLimiter,
+    // Quote generation goes through shared configfs-tsm / TPM state, one at a time.
+    device: tokio::sync::Mutex<()>,
+}
+
+impl Agent {
+    pub fn new(config: AgentConfig) -> Self {
+        Agent {
+            limiter: RateLimiter::new(config.rate_limit),
+            config,
+            device: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Checks the caller, generates the quote and records the request in the audit log.
+    pub async fn quote(&self, peer: Peer, body: &[u8]) -> Result<QuoteResponse> {
+        let request: std::result::Result<QuoteRequest, _> = serde_json::from_slice(body);
+        let report_data = request.as_ref().ok().map(|r| r.report_data.as_str());
+
+        let result = match &request {
+            Ok(request) => self.authorize_and_quote(peer, request).await,
+            Err(err) => Err(AgentError::BadRequest(format!("{:?}", err))),
+        };
+
+        let quote_sha256 = result
+            .as_ref()
+            .ok()
+            .map(|(quote, _)| hex::encode(Sha256::digest(quote)));
+        self.config.audit_log.record(&AuditRecord {
+            time: chrono::Utc::now().to_rfc3339(),
+            uid: peer.0.map(|c| c.uid),
+            gid: peer.0.map(|c| c.gid),
+            pid: peer.0.and_then(|c| c.pid),
+            report_data,
+            result: if result.is_ok() { "ok" } else { "error" },
+            error: result.as_ref().err().map(|e| e.to_string()),
+            quote_sha256,
+        });
+
+        let (quote, var_data) = result?;
+        Ok(QuoteResponse {
+            quote: base64_url::encode(&quote),
+            var_data: var_data.map(|v| base64_url::encode(&v)),
+        })
+    }
+
+    async fn authorize_and_quote(
+        &self,
+        peer: Peer,
+        request: &QuoteRequest,
+    ) -> Result<(Vec<u8>, Option<Vec<u8>>)> {
+        let cred = peer
+            .0
+            .ok_or_else(|| AgentError::Forbidden("unknown peer credentials".to_string()))?;
+        if !self.config.allowed_uids.is_empty() && !self.config.allowed_uids.contains(&cred.uid) {
+            return Err(AgentError::Forbidden(format!(
+                "uid {} is not allowed",
+                cred.uid
+            )));
+        }
+        let report_data = parse_report_data(&request.report_data)?;
+        self.limiter.acquire(cred.uid)?;
+
+        let _guard = self.device.lock().await;
+        tokio::task::spawn_blocking(move || -> Result<_> {
+            let device = Device::new(DeviceOptions {
+                report_data: Some(report_data),
+            })?;
+            Ok(device.get_attestation_report_raw()?)
+        })
+        .await
+        .map_err(|e| AgentError::Quote(format!("{:?}", e)))?
+    }
+}
+
+fn parse_report_data(report_data: &str) -> Result<[u8; 64]> {
+    let bytes = hex::decode(report_data.trim_start_matches("0x"))
+        .map_err(|e| AgentError::BadRequest(format!("invalid report data: {:?}", e)))?;
+    bytes.try_into().map_err(|bytes: Vec<u8>| {
+        AgentError::BadRequest(format!("report data is {} bytes, expected 64", bytes.len()))
+    })
+}
+
+/// HTTP routes of the agent.
+pub fn router(agent: Arc<Agent>) -> Router {
+    Router::new()
+        .route(QUOTE_PATH, post(quote))
+        .route(HEALTH_PATH, get(|| async { "ok" }))
+        .with_state(agent)
+}
+
+/// Binds the socket of the agent at `path` with the given permissions and group.
+///
+/// The socket is bound in a private directory next to `path` and only moved into place once
+/// its permissions are set, as it would otherwise be reachable with the permissions of the
+/// umask in between.
+pub fn bind(path: &Path, mode: u32, gid: Option<u32>) -> Result<UnixListener> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let staging = parent.join(format!(".tdx-agent-{}", std::process::id()));
+    DirBuilder::new().mode(0o700).create(&staging)?;
+
+    let staged = staging.join("socket");
+    let bound = (|| -> Result<UnixListener> {
+        let listener = UnixListener::bind(&staged)?;
+        std::fs::set_permissions(&staged, Permissions::from_mode(mode))?;
+        if let Some(gid) = gid {
+            std::os::unix::fs::chown(&staged, None, Some(gid))?;
+        }
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    })();
+    let _ = std::fs::remove_file(&staged);
+    let _ = std::fs::remove_dir(&staging);
+    bound
+}
+
+/// Serves the agent on `listener` until `shutdown` completes.
+pub async fn serve(
+    listener: UnixListener,
+    agent: Arc<Agent>,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    axum::serve(
+        listener,
+        router(agent).into_make_service_with_connect_info::<Peer>(),
+    )
+    .with_graceful_shutdown(shutdown)
+    .await
+}
+
+async fn quote(
+    State(agent): State<Arc<Agent>>,
+    ConnectInfo(peer): ConnectInfo<Peer>,
+    body: Bytes,
+) -> Result<Json<QuoteResponse>> {
+    agent.quote(peer, &body).await.map(Json)
+}
+
+impl IntoResponse for AgentError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            AgentError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AgentError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AgentError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let body = ErrorResponse {
+            error: self.to_string(),
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requires_64_bytes_of_report_data() {
+        let report_data = [0xab; 64];
+        assert_eq!(
+            parse_report_data(&hex::encode(report_data)).unwrap(),
+            report_data
+        );
+        assert_eq!(
+            parse_report_data(&format!("0x{}", hex::encode(report_data))).unwrap(),
+            report_data
+        );
+        for len in [0, 32, 63, 65] {
+            assert!(matches!(
+                parse_report_data(&hex::encode(vec![0xab; len])),
+                Err(AgentError::BadRequest(_))
+            ));
+        }
+        assert!(parse_report_data("zz").is_err());
+    }
+
+    fn quote_request() -> Vec<u8> {
+        serde_json::to_vec(&QuoteRequest {
+            report_data: hex::encode([0xab; 64]),
+        })
+        .unwrap()
+    }
+
+    fn peer(uid: u32) -> Peer {
+        Peer(Some(PeerCred {
+            uid,
+            gid: uid,
+            pid: Some(1),
+        }))
+    }
+
+    #[test]
+    fn limits_the_burst_of_each_uid() {
+        let limiter = RateLimiter::new(RateLimit {
+            per_minute: 1,
+            burst: 3,
+        });
+        for _ in 0..3 {
+            limiter.acquire(1000).unwrap();
+        }
+        assert!(matches!(
+            limiter.acquire(1000),
+            Err(AgentError::RateLimited(_))
+        ));
+        // Other UIDs have their own bucket.
+        limiter.acquire(1001).unwrap();
+    }
+
+    #[test]
+    fn refills_at_the_sustained_rate() {
+        let limiter = RateLimiter::new(RateLimit {
+            per_minute: 60,
+            burst: 2,
+        });
+        limiter.acquire(1000).unwrap();
+        limiter.acquire(1000).unwrap();
+        assert!(limiter.acquire(1000).is_err());
+
+        // One second at 60 quotes per minute refills one token.
+        let rewind = |secs| {
+            let mut buckets = limiter.buckets.lock().unwrap();
+            let bucket = buckets.get_mut(&1000).unwrap();
+            bucket.updated -= std::time::Duration::from_secs(secs);
+        };
+        rewind(1);
+        limiter.acquire(1000).unwrap();
+        assert!(limiter.acquire(1000).is_err());
+
+        // The bucket never holds more than the burst.
+        rewind(10);
+        limiter.acquire(1000).unwrap();
+        limiter.acquire(1000).unwrap();
+        assert!(limiter.acquire(1000).is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_uids_not_allowed() {
+        let dir = std::env::temp_dir().join(format!("tdx-agent-audit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let audit_path = dir.join("audit.log");
+        let agent = Agent::new(AgentConfig {
+            rate_limit: RateLimit::default(),
+            allowed_uids: vec![1000],
+            audit_log: AuditLog::open(&audit_path).unwrap(),
+        });
+
+        let err = agent.quote(peer(1001), &quote_request()).await.unwrap_err();
+        assert_eq!(
+            err,
+            AgentError::Forbidden("uid 1001 is not allowed".to_string())
+        );
+        assert!(matches!(
+            agent.quote(Peer(None), &quote_request()).await,
+            Err(AgentError::Forbidden(_))
+        ));
+
+        let audit = std::fs::read_to_string(&audit_path).unwrap();
+        let records: Vec<serde_json::Value> = audit
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["uid"], 1001);
+        assert_eq!(records[0]["result"], "error");
+        assert_eq!(records[1]["uid"], serde_json::Value::Null);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn binds_the_socket_with_its_permissions() {
+        let dir = std::env::temp_dir().join(format!("tdx-agent-bind-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("agent.sock");
+
+        let _listener = bind(&path, 0o640, None).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        // Nothing is left of the staging directory.
+        let entries: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+
+        tokio::net::UnixStream::connect(&path).await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
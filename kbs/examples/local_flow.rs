@@ -0,0 +1,67 @@
+//! Runs the whole KBS flow locally, without a TD or network access, by replaying a session
+//! recorded with `kbs record` against collaterals stored in a directory.
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::Parser;
+use kbs::broker::{Broker, SecretStore};
+use kbs::client::{KbsClient, RecordedQuote};
+use kbs::crypto::HpkeKeyPair;
+use kbs::policy::Policy;
+use kbs::server::serve;
+use tdx::collateral::CollateralSource;
+
+#[derive(Parser)]
+struct Opt {
+    /// Directory written by `kbs record`.
+    #[clap(long)]
+    session: PathBuf,
+
+    /// Directory written by `tdx collateral fetch`.
+    #[clap(long)]
+    collateral_dir: PathBuf,
+
+    #[clap(long)]
+    policy: PathBuf,
+
+    #[clap(long)]
+    secrets_dir: PathBuf,
+
+    #[clap(long)]
+    resource: String,
+
+    /// Verification time, should be within the validity of the collaterals.
+    #[clap(long)]
+    time: u64,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::parse();
+
+    let quote = std::fs::read(opt.session.join("quote.bin"))?;
+    let nonce = std::fs::read(opt.session.join("nonce.bin"))?;
+    let keys = HpkeKeyPair::from_private_key(&std::fs::read(opt.session.join("hpke_key.bin"))?)?;
+
+    let broker = Broker::new(
+        Policy::read_from_file(&opt.policy)?,
+        SecretStore::new(opt.secrets_dir),
+        CollateralSource::Dir(opt.collateral_dir),
+    )
+    .with_verification_time(opt.time);
+    // The recorded quote binds a known nonce, issue it instead of a random one.
+    broker.challenge_with_nonce(nonce.clone())?;
+
+    let rt = tokio::runtime::Runtime::new()?;
+    let listener = rt.block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))?;
+    let url = format!("http://{}", listener.local_addr()?);
+    rt.spawn(serve(listener, Arc::new(broker)));
+
+    let secret = KbsClient::new(&url).get_secret_with(
+        &opt.resource,
+        &RecordedQuote(quote),
+        &nonce,
+        &keys,
+    )?;
+    println!("Secret: {}", String::from_utf8_lossy(&secret));
+    Ok(())
+}
@@ -0,0 +1,163 @@
+//! `kbs` command line interface.
+//!
+//! Runs the key broker service, or its client inside a TD.
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand};
+use kbs::broker::{Broker, SecretStore, DEFAULT_MAX_NONCES, DEFAULT_NONCE_TTL};
+use kbs::client::KbsClient;
+use kbs::crypto::HpkeKeyPair;
+use kbs::policy::Policy;
+use kbs::protocol::report_data;
+use kbs::server::serve;
+use tdx::collateral::CollateralSource;
+use tdx::pccs::PccsConfig;
+use tdx::Tdx;
+
+// File names of a recorded session.
+const SESSION_QUOTE_FILE: &str = "quote.bin";
+const SESSION_NONCE_FILE: &str = "nonce.bin";
+const SESSION_KEY_FILE: &str = "hpke_key.bin";
+
+#[derive(Parser)]
+#[clap(
+    name = "kbs",
+    version,
+    about = "Key broker for attested Intel TDX guests"
+)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the key broker service.
+    Serve(ServeArgs),
+    /// Request a secret from a KBS, from within a TD.
+    Get(GetArgs),
+    /// Record a quote for a given nonce and a new HPKE key, from within a TD,
+    /// to replay the flow locally later on.
+    Record(RecordArgs),
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// JSON policy listing the allowed measurements.
+    #[clap(long)]
+    policy: PathBuf,
+
+    /// Directory holding the secrets, one file per resource.
+    #[clap(long)]
+    secrets_dir: PathBuf,
+
+    /// Directory holding the collaterals, see `tdx collateral fetch`.
+    /// Collaterals are fetched from the on-chain PCCS if not set.
+    #[clap(long)]
+    collateral_dir: Option<PathBuf>,
+
+    /// RPC URL of the chain hosting the on-chain PCCS.
+    #[clap(long, env = "PCCS_RPC_URL", conflicts_with = "collateral_dir")]
+    rpc_url: Option<String>,
+
+    /// How long a nonce is accepted, in seconds.
+    #[clap(long, default_value_t = DEFAULT_NONCE_TTL)]
+    nonce_ttl: u64,
+
+    /// How many nonces can be outstanding at once, further challenges are refused.
+    #[clap(long, default_value_t = DEFAULT_MAX_NONCES)]
+    max_nonces: usize,
+
+    /// Verify quotes at this time (seconds since the Unix epoch) instead of now.
+    #[clap(long)]
+    time: Option<u64>,
+}
+
+#[derive(Args)]
+struct GetArgs {
+    /// Base URL of the KBS.
+    #[clap(long)]
+    url: String,
+
+    #[clap(long)]
+    resource: String,
+
+    /// Where to write the secret, defaults to stdout.
+    #[clap(long)]
+    out: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct RecordArgs {
+    /// Hex encoded nonce.
+    #[clap(long)]
+    nonce: String,
+
+    /// Directory to write the session to.
+    #[clap(long)]
+    out: PathBuf,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Serve(args) => run_serve(args),
+        Command::Get(args) => {
+            let secret = KbsClient::new(&args.url).get_secret(&args.resource, &Tdx::new())?;
+            match args.out {
+                Some(out) => std::fs::write(out, secret)?,
+                None => std::io::Write::write_all(&mut std::io::stdout(), &secret)?,
+            }
+            Ok(())
+        }
+        Command::Record(args) => {
+            let nonce = hex::decode(args.nonce.trim_start_matches("0x"))?;
+            let keys = HpkeKeyPair::generate();
+            let tdx = Tdx::new();
+            let quote =
+                kbs::client::Attester::quote(&tdx, report_data(&nonce, &keys.public_key()))?;
+
+            std::fs::create_dir_all(&args.out)?;
+            std::fs::write(args.out.join(SESSION_QUOTE_FILE), quote)?;
+            std::fs::write(args.out.join(SESSION_NONCE_FILE), nonce)?;
+            std::fs::write(args.out.join(SESSION_KEY_FILE), keys.private_key())?;
+            println!("Session recorded to {}", args.out.display());
+            Ok(())
+        }
+    }
+}
+
+fn run_serve(args: ServeArgs) -> Result<()> {
+    let policy = Policy::read_from_file(&args.policy)?;
+    let collaterals = match args.collateral_dir {
+        Some(dir) => CollateralSource::Dir(dir),
+        None => {
+            let mut config = PccsConfig::default();
+            if let Some(rpc_url) = args.rpc_url {
+                config.rpc_url = rpc_url;
+            }
+            CollateralSource::Pccs(config)
+        }
+    };
+
+    let mut broker = Broker::new(policy, SecretStore::new(args.secrets_dir), collaterals)
+        .with_nonce_ttl(args.nonce_ttl)
+        .with_max_nonces(args.max_nonces);
+    if let Some(time) = args.time {
+        broker = broker.with_verification_time(time);
+    }
+
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(async {
+        let listener = tokio::net::TcpListener::bind(args.listen).await?;
+        println!("KBS listening on {}", listener.local_addr()?);
+        serve(listener, Arc::new(broker)).await
+    })?;
+    Ok(())
+}
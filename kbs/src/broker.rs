@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use dcap_rs::types::quotes::body::QuoteBody;
+use dcap_rs::types::quotes::version_4::QuoteV4;
+use dcap_rs::types::VerifiedOutput;
+use rand::RngCore;
+use tdx::collateral::{CollateralSource, Collaterals};
+use tdx::error::TdxError;
+use tdx::utils::parse_quote_v4;
+use tdx::Tdx;
+
+use crate::crypto::seal;
+use crate::error::{KbsError, Result};
+use crate::policy::Policy;
+use crate::protocol::{
+    decode, encode, report_data, AttestRequest, ChallengeResponse, SecretResponse, NONCE_LEN,
+};
+
+pub const DEFAULT_NONCE_TTL: u64 = 60;
+/// Outstanding nonces kept at most, new challenges are refused beyond.
+pub const DEFAULT_MAX_NONCES: usize = 10_000;
+
+/// Verifies a quote against its collaterals.
+pub trait QuoteVerifier: Send + Sync {
+    fn verify(
+        &self,
+        quote: &QuoteV4,
+        collaterals: &Collaterals,
+        current_time: u64,
+    ) -> tdx::error::Result<VerifiedOutput>;
+}
+
+impl QuoteVerifier for Tdx {
+    fn verify(
+        &self,
+        quote: &QuoteV4,
+        collaterals: &Collaterals,
+        current_time: u64,
+    ) -> tdx::error::Result<VerifiedOutput> {
+        self.verify_attestation_report_with_collaterals(quote, collaterals, current_time)
+    }
+}
+
+/// Secrets stored as files in a directory, one file per resource.
+#[derive(Clone, Debug)]
+pub struct SecretStore {
+    dir: PathBuf,
+}
+
+impl SecretStore {
+    pub fn new(dir: PathBuf) -> Self {
+        SecretStore { dir }
+    }
+
+    pub fn get(&self, resource: &str) -> Result<Vec<u8>> {
+        let valid = !resource.is_empty()
+            && !resource.starts_with('.')
+            && resource
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+        if !valid {
+            return Err(KbsError::BadRequest(format!(
+                "Invalid resource name: {}",
+                resource
+            )));
+        }
+        std::fs::read(self.dir.join(resource))
+            .map_err(|_| KbsError::NotFound(format!("Unknown resource: {}", resource)))
+    }
+}
+
+/// Transport independent logic of the KBS.
+pub struct Broker {
+    policy: Policy,
+    secrets: SecretStore,
+    collaterals: CollateralSource,
+    verifier: Arc<dyn QuoteVerifier>,
+    nonce_ttl: u64,
+    max_nonces: usize,
+    verification_time: Option<u64>,
+    /// Outstanding nonces and their expiry time.
+    nonces: Mutex<HashMap<Vec<u8>, u64>>,
+}
+
+impl Broker {
+    pub fn new(policy: Policy, secrets: SecretStore, collaterals: CollateralSource) -> Self {
+        Broker {
+            policy,
+            secrets,
+            collaterals,
+            verifier: Arc::new(Tdx::new()),
+            nonce_ttl: DEFAULT_NONCE_TTL,
+            max_nonces: DEFAULT_MAX_NONCES,
+            verification_time: None,
+            nonces: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets how long a nonce is accepted, in seconds.
+    pub fn with_nonce_ttl(mut self, nonce_ttl: u64) -> Self {
+        self.nonce_ttl = nonce_ttl;
+        self
+    }
+
+    /// Sets how many nonces can be outstanding at once.
+    pub fn with_max_nonces(mut self, max_nonces: usize) -> Self {
+        self.max_nonces = max_nonces;
+        self
+    }
+
+    /// Verifies quotes at a fixed time (seconds since the Unix epoch) instead of now,
+    /// so that recorded quotes can be verified against recorded collaterals.
+    pub fn with_verification_time(mut self, time: u64) -> Self {
+        self.verification_time = Some(time);
+        self
+    }
+
+    /// Replaces the DCAP verification of the quotes, `Tdx` by default.
+    pub fn with_verifier(mut self, verifier: impl QuoteVerifier + 'static) -> Self {
+        self.verifier = Arc::new(verifier);
+        self
+    }
+
+    /// Issues a random nonce.
+    /// Fails once `max_nonces` nonces are outstanding, until some expire or are consumed.
+    pub fn challenge(&self) -> Result<ChallengeResponse> {
+        let mut nonce = vec![0u8; NONCE_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        self.issue_nonce(nonce)
+    }
+
+    /// Issues the given nonce, eg the one a recorded quote was generated for.
+    ///
+    /// Only available with the `replay` feature: a known nonce does not prove the freshness
+    /// of a quote.
+    #[cfg(feature = "replay")]
+    pub fn challenge_with_nonce(&self, nonce: Vec<u8>) -> Result<ChallengeResponse> {
+        self.issue_nonce(nonce)
+    }
+
+    fn issue_nonce(&self, nonce: Vec<u8>) -> Result<ChallengeResponse> {
+        let now = now();
+        let expires_at = now + self.nonce_ttl;
+        let mut nonces = self.nonces.lock().unwrap();
+        nonces.retain(|_, expiry| *expiry >= now);
+        if nonces.len() >= self.max_nonces {
+            return Err(KbsError::Unavailable(
+                "Too many outstanding challenges".to_string(),
+            ));
+        }
+        nonces.insert(nonce.clone(), expires_at);
+        Ok(ChallengeResponse {
+            nonce: encode(&nonce),
+            expires_at,
+        })
+    }
+
+    /// Verifies the quote of the request and returns the requested secret sealed to the
+    /// request's public key. The nonce is consumed, whether the request succeeds or not.
+    pub async fn attest(&self, request: &AttestRequest) -> Result<SecretResponse> {
+        let nonce = decode(&request.nonce)?;
+        let expiry = self.nonces.lock().unwrap().remove(&nonce);
+        match expiry {
+            Some(expiry) if expiry >= now() => {}
+            _ => {
+                return Err(KbsError::Attestation(
+                    "Unknown or expired nonce".to_string(),
+                ))
+            }
+        }
+
+        let public_key = decode(&request.public_key)?;
+        let quote = parse_quote_v4(&decode(&request.quote)?)
+            .map_err(|e| KbsError::BadRequest(e.to_string()))?;
+        let QuoteBody::TD10QuoteBody(body) = &quote.quote_body else {
+            return Err(KbsError::BadRequest("Not a TDX quote".to_string()));
+        };
+        if body.report_data != report_data(&nonce, &public_key) {
+            return Err(KbsError::Attestation(
+                "Report data does not bind the nonce and the public key".to_string(),
+            ));
+        }
+
+        let collaterals = self
+            .collaterals
+            .collaterals_for_quote(&quote)
+            .await
+            .map_err(|e| match e {
+                TdxError::NotFound(e) => KbsError::Attestation(e),
+                e => e.into(),
+            })?;
+        let current_time = self.verification_time.unwrap_or_else(now);
+        // The DCAP verification is CPU bound, keep it off the runtime threads.
+        let verifier = self.verifier.clone();
+        let output = tokio::task::spawn_blocking(move || {
+            verifier.verify(&quote, &collaterals, current_time)
+        })
+        .await
+        .map_err(|e| KbsError::Attestation(format!("Quote verification failed: {}", e)))?
+        .map_err(|e| KbsError::Attestation(e.to_string()))?;
+        self.policy.check(&output)?;
+
+        let secret = self.secrets.get(&request.resource)?;
+        let (encapped_key, ciphertext) = seal(&public_key, &secret, &nonce)?;
+        Ok(SecretResponse {
+            encapped_key: encode(&encapped_key),
+            ciphertext: encode(&ciphertext),
+        })
+    }
+}
+
+fn now() -> u64 {
+    chrono::Utc::now().timestamp() as u64
+}
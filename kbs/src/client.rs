@@ -0,0 +1,92 @@
+use tdx::device::DeviceOptions;
+use tdx::Tdx;
+
+use crate::crypto::HpkeKeyPair;
+use crate::error::Result;
+use crate::protocol::{
+    decode, encode, report_data, AttestRequest, ChallengeResponse, SecretResponse, ATTEST_PATH,
+    CHALLENGE_PATH,
+};
+
+/// Produces quotes with the given report data.
+pub trait Attester {
+    fn quote(&self, report_data: [u8; 64]) -> Result<Vec<u8>>;
+}
+
+impl Attester for Tdx {
+    fn quote(&self, report_data: [u8; 64]) -> Result<Vec<u8>> {
+        let (quote, _) = self.get_attestation_report_raw_with_options(DeviceOptions {
+            report_data: Some(report_data),
+        })?;
+        Ok(quote)
+    }
+}
+
+/// Replays a quote recorded for a known nonce and HPKE key, see `kbs record`.
+pub struct RecordedQuote(pub Vec<u8>);
+
+impl Attester for RecordedQuote {
+    fn quote(&self, _report_data: [u8; 64]) -> Result<Vec<u8>> {
+        Ok(self.0.clone())
+    }
+}
+
+pub struct KbsClient {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl KbsClient {
+    /// `url` is the base URL of the KBS, eg "http://127.0.0.1:8080".
+    pub fn new(url: &str) -> Self {
+        KbsClient {
+            url: url.trim_end_matches('/').to_string(),
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    pub fn challenge(&self) -> Result<Vec<u8>> {
+        let response: ChallengeResponse = self
+            .agent
+            .post(&format!("{}{}", self.url, CHALLENGE_PATH))
+            .call()?
+            .into_json()?;
+        decode(&response.nonce)
+    }
+
+    /// Runs the whole flow with a fresh HPKE key pair and returns the secret.
+    pub fn get_secret(&self, resource: &str, attester: &dyn Attester) -> Result<Vec<u8>> {
+        let nonce = self.challenge()?;
+        self.get_secret_with(resource, attester, &nonce, &HpkeKeyPair::generate())
+    }
+
+    /// Requests a secret for a nonce already obtained from the KBS, sealed to `keys`.
+    pub fn get_secret_with(
+        &self,
+        resource: &str,
+        attester: &dyn Attester,
+        nonce: &[u8],
+        keys: &HpkeKeyPair,
+    ) -> Result<Vec<u8>> {
+        let public_key = keys.public_key();
+        let quote = attester.quote(report_data(nonce, &public_key))?;
+
+        let request = AttestRequest {
+            resource: resource.to_string(),
+            nonce: encode(nonce),
+            public_key: encode(&public_key),
+            quote: encode(&quote),
+        };
+        let response: SecretResponse = self
+            .agent
+            .post(&format!("{}{}", self.url, ATTEST_PATH))
+            .send_json(&request)?
+            .into_json()?;
+
+        keys.open(
+            &decode(&response.encapped_key)?,
+            &decode(&response.ciphertext)?,
+            nonce,
+        )
+    }
+}
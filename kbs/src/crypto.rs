@@ -0,0 +1,83 @@
+//! HPKE (RFC 9180) base mode with DHKEM(X25519, HKDF-SHA256), HKDF-SHA256 and
+//! ChaCha20-Poly1305.
+use hpke::aead::ChaCha20Poly1305;
+use hpke::kdf::HkdfSha256;
+use hpke::kem::X25519HkdfSha256;
+use hpke::{Deserializable, Kem, OpModeR, OpModeS, Serializable};
+
+use crate::error::{KbsError, Result};
+
+type KemType = X25519HkdfSha256;
+type PrivateKey = <KemType as Kem>::PrivateKey;
+type PublicKey = <KemType as Kem>::PublicKey;
+type EncappedKey = <KemType as Kem>::EncappedKey;
+
+/// HPKE info string, binding the sealed secrets to this protocol.
+pub const HPKE_INFO: &[u8] = b"tdx-kbs secret v0";
+
+/// Ephemeral key pair of the client.
+pub struct HpkeKeyPair {
+    private_key: PrivateKey,
+    public_key: PublicKey,
+}
+
+impl HpkeKeyPair {
+    pub fn generate() -> Self {
+        let (private_key, public_key) = KemType::gen_keypair(&mut rand::rngs::OsRng);
+        HpkeKeyPair {
+            private_key,
+            public_key,
+        }
+    }
+
+    /// Restores a key pair from its private key, eg to replay a recorded session.
+    pub fn from_private_key(bytes: &[u8]) -> Result<Self> {
+        let private_key =
+            PrivateKey::from_bytes(bytes).map_err(|e| KbsError::Crypto(format!("{:?}", e)))?;
+        let public_key = KemType::sk_to_pk(&private_key);
+        Ok(HpkeKeyPair {
+            private_key,
+            public_key,
+        })
+    }
+
+    pub fn private_key(&self) -> Vec<u8> {
+        self.private_key.to_bytes().to_vec()
+    }
+
+    pub fn public_key(&self) -> Vec<u8> {
+        self.public_key.to_bytes().to_vec()
+    }
+
+    /// Opens a secret sealed with `seal`.
+    pub fn open(&self, encapped_key: &[u8], ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let encapped_key = EncappedKey::from_bytes(encapped_key)
+            .map_err(|e| KbsError::Crypto(format!("{:?}", e)))?;
+        hpke::single_shot_open::<ChaCha20Poly1305, HkdfSha256, KemType>(
+            &OpModeR::Base,
+            &self.private_key,
+            &encapped_key,
+            HPKE_INFO,
+            ciphertext,
+            aad,
+        )
+        .map_err(|e| KbsError::Crypto(format!("{:?}", e)))
+    }
+}
+
+/// Seals `plaintext` to `public_key`, returns the encapsulated key and the ciphertext.
+pub fn seal(public_key: &[u8], plaintext: &[u8], aad: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let public_key =
+        PublicKey::from_bytes(public_key).map_err(|e| KbsError::BadRequest(format!("{:?}", e)))?;
+    let (encapped_key, ciphertext) =
+        hpke::single_shot_seal::<ChaCha20Poly1305, HkdfSha256, KemType, _>(
+            &OpModeS::Base,
+            &public_key,
+            HPKE_INFO,
+            plaintext,
+            aad,
+            &mut rand::rngs::OsRng,
+        )
+        .map_err(|e| KbsError::Crypto(format!("{:?}", e)))?;
+    Ok((encapped_key.to_bytes().to_vec(), ciphertext))
+}
@@ -0,0 +1,70 @@
+use std::fmt::Display;
+
+use tdx::error::TdxError;
+
+pub type Result<T> = std::result::Result<T, KbsError>;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum KbsError {
+    /// The request is malformed.
+    BadRequest(String),
+    /// The quote or the nonce was rejected.
+    Attestation(String),
+    /// The measurements of the quote are not allowed by the policy.
+    Policy(String),
+    NotFound(String),
+    /// The KBS cannot take more requests for now.
+    Unavailable(String),
+    Crypto(String),
+    Http(String),
+    IO(String),
+    Tdx(TdxError),
+}
+
+impl Display for KbsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KbsError::BadRequest(err) => write!(f, "BadRequest: {}", err),
+            KbsError::Attestation(err) => write!(f, "Attestation: {}", err),
+            KbsError::Policy(err) => write!(f, "Policy: {}", err),
+            KbsError::NotFound(err) => write!(f, "NotFound: {}", err),
+            KbsError::Unavailable(err) => write!(f, "Unavailable: {}", err),
+            KbsError::Crypto(err) => write!(f, "Crypto: {}", err),
+            KbsError::Http(err) => write!(f, "Http: {}", err),
+            KbsError::IO(err) => write!(f, "IO: {}", err),
+            KbsError::Tdx(err) => write!(f, "Tdx: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for KbsError {}
+
+impl From<TdxError> for KbsError {
+    fn from(err: TdxError) -> Self {
+        KbsError::Tdx(err)
+    }
+}
+
+impl From<std::io::Error> for KbsError {
+    fn from(err: std::io::Error) -> Self {
+        KbsError::IO(format!("{:?}", err))
+    }
+}
+
+impl From<ureq::Error> for KbsError {
+    fn from(err: ureq::Error) -> Self {
+        match err {
+            ureq::Error::Status(code, response) => {
+                let body = response.into_string().unwrap_or_default();
+                KbsError::Http(format!("{}: {}", code, body))
+            }
+            err => KbsError::Http(format!("{:?}", err)),
+        }
+    }
+}
+
+impl From<base64_url::base64::DecodeError> for KbsError {
+    fn from(err: base64_url::base64::DecodeError) -> Self {
+        KbsError::BadRequest(format!("{:?}", err))
+    }
+}
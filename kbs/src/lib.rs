@@ -0,0 +1,15 @@
+//! Key broker service (KBS) releasing secrets to attested Intel TDX guests.
+//!
+//! The flow is a challenge-response:
+//! 1. The client requests a nonce from the KBS.
+//! 2. The client generates an ephemeral HPKE key pair and requests a quote whose report data
+//!    is SHA-512(nonce || HPKE public key), see `protocol::report_data`.
+//! 3. The KBS verifies the quote, checks its measurements against the policy and returns the
+//!    requested secret sealed to the HPKE public key.
+pub mod broker;
+pub mod client;
+pub mod crypto;
+pub mod error;
+pub mod policy;
+pub mod protocol;
+pub mod server;
@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use dcap_rs::types::quotes::body::QuoteBody;
+use dcap_rs::types::VerifiedOutput;
+use serde::{Deserialize, Serialize};
+use tdx::report::tcb_status_name;
+
+use crate::error::{KbsError, Result};
+
+/// Reference measurements of a TD. Measurements set to `None` match any value.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Measurements {
+    /// Hex encoded MRTD.
+    pub mrtd: Option<String>,
+    /// Hex encoded RTMR0 to RTMR3.
+    #[serde(default)]
+    pub rtmrs: [Option<String>; 4],
+}
+
+impl Measurements {
+    fn matches(&self, mrtd: &[u8; 48], rtmrs: &[[u8; 48]; 4]) -> bool {
+        let matches = |expected: &Option<String>, actual: &[u8; 48]| match expected {
+            Some(expected) => expected
+                .trim_start_matches("0x")
+                .eq_ignore_ascii_case(&hex::encode(actual)),
+            None => true,
+        };
+        matches(&self.mrtd, mrtd)
+            && self
+                .rtmrs
+                .iter()
+                .zip(rtmrs.iter())
+                .all(|(expected, actual)| matches(expected, actual))
+    }
+}
+
+/// Which TDs secrets are released to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Policy {
+    /// A quote is accepted if its measurements match any entry.
+    pub allowed: Vec<Measurements>,
+    /// Accepted TCB statuses, as named by Intel (eg "UpToDate").
+    #[serde(default = "default_tcb_statuses")]
+    pub accepted_tcb_statuses: Vec<String>,
+}
+
+fn default_tcb_statuses() -> Vec<String> {
+    vec!["UpToDate".to_string(), "SWHardeningNeeded".to_string()]
+}
+
+impl Policy {
+    /// Reads a JSON policy.
+    pub fn read_from_file(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        serde_json::from_slice(&bytes).map_err(|e| KbsError::IO(format!("Invalid policy: {}", e)))
+    }
+
+    /// Checks the TCB status and the measurements of a verified quote.
+    pub fn check(&self, output: &VerifiedOutput) -> Result<()> {
+        let tcb_status = tcb_status_name(&output.tcb_status);
+        if !self.accepted_tcb_statuses.iter().any(|s| s == tcb_status) {
+            return Err(KbsError::Policy(format!(
+                "TCB status {} is not accepted",
+                tcb_status
+            )));
+        }
+
+        let QuoteBody::TD10QuoteBody(body) = &output.quote_body else {
+            return Err(KbsError::Policy("Not a TDX quote".to_string()));
+        };
+        let rtmrs = [body.rtmr0, body.rtmr1, body.rtmr2, body.rtmr3];
+        if !self.allowed.iter().any(|m| m.matches(&body.mrtd, &rtmrs)) {
+            return Err(KbsError::Policy(format!(
+                "Measurements are not allowed, MRTD: {}",
+                hex::encode(body.mrtd)
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+//! Messages exchanged between the client and the KBS.
+//! Binary fields are base64url encoded without padding.
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+
+pub const CHALLENGE_PATH: &str = "/kbs/v0/challenge";
+pub const ATTEST_PATH: &str = "/kbs/v0/attest";
+
+pub const NONCE_LEN: usize = 32;
+
+/// Report data binding a quote to a challenge and to the key the secret is sealed to:
+/// SHA-512(nonce || HPKE public key).
+pub fn report_data(nonce: &[u8], public_key: &[u8]) -> [u8; 64] {
+    tdx::utils::report_data_from_nonce(&[nonce, public_key].concat())
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChallengeResponse {
+    pub nonce: String,
+    /// Unix timestamp after which the nonce is no longer accepted.
+    pub expires_at: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttestRequest {
+    /// Name of the requested secret.
+    pub resource: String,
+    pub nonce: String,
+    /// HPKE (X25519) public key the secret is sealed to.
+    pub public_key: String,
+    /// TDX quote with report data `report_data(nonce, public_key)`.
+    pub quote: String,
+}
+
+/// Secret sealed with HPKE base mode, with the nonce as associated data.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretResponse {
+    pub encapped_key: String,
+    pub ciphertext: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+pub fn encode(bytes: &[u8]) -> String {
+    base64_url::encode(bytes)
+}
+
+pub fn decode(s: &str) -> Result<Vec<u8>> {
+    Ok(base64_url::decode(s)?)
+}
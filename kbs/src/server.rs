@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use tokio::net::TcpListener;
+
+use crate::broker::Broker;
+use crate::error::KbsError;
+use crate::protocol::{
+    AttestRequest, ChallengeResponse, ErrorResponse, SecretResponse, ATTEST_PATH, CHALLENGE_PATH,
+};
+
+/// HTTP routes of the KBS.
+pub fn router(broker: Arc<Broker>) -> Router {
+    Router::new()
+        .route(CHALLENGE_PATH, post(challenge))
+        .route(ATTEST_PATH, post(attest))
+        .with_state(broker)
+}
+
+/// Serves the KBS on `listener` until the process exits.
+pub async fn serve(listener: TcpListener, broker: Arc<Broker>) -> std::io::Result<()> {
+    axum::serve(listener, router(broker)).await
+}
+
+async fn challenge(State(broker): State<Arc<Broker>>) -> Result<Json<ChallengeResponse>, KbsError> {
+    broker.challenge().map(Json)
+}
+
+async fn attest(
+    State(broker): State<Arc<Broker>>,
+    Json(request): Json<AttestRequest>,
+) -> Result<Json<SecretResponse>, KbsError> {
+    broker.attest(&request).await.map(Json)
+}
+
+impl IntoResponse for KbsError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            KbsError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            KbsError::Attestation(_) => StatusCode::UNAUTHORIZED,
+            KbsError::Policy(_) => StatusCode::FORBIDDEN,
+            KbsError::NotFound(_) => StatusCode::NOT_FOUND,
+            KbsError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let body = ErrorResponse {
+            error: self.to_string(),
+        };
+        (status, Json(body)).into_response()
+    }
+}
@@ -0,0 +1,254 @@
+//! Drives the KBS over HTTP: challenge, evidence and key release, with a quote and collaterals
+//! recorded on a TDX platform.
+//!
+//! The recorded quote was not generated for a KBS challenge, so the client sends it with its
+//! report data replaced by the expected binding, which breaks its signature. The broker then
+//! verifies the recorded quote instead, see `RecordedVerifier`.
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use dcap_rs::types::quotes::body::QuoteBody;
+use dcap_rs::types::quotes::version_4::QuoteV4;
+use dcap_rs::types::VerifiedOutput;
+use kbs::broker::{Broker, QuoteVerifier, SecretStore};
+use kbs::client::{Attester, KbsClient, RecordedQuote};
+use kbs::crypto::HpkeKeyPair;
+use kbs::error::{KbsError, Result};
+use kbs::policy::{Measurements, Policy};
+use kbs::protocol::report_data;
+use tdx::collateral::{CollateralSource, Collaterals};
+use tdx::report::tcb_status_name;
+use tdx::utils::parse_quote_v4;
+use tdx::Tdx;
+
+const QUOTE: &[u8] = include_bytes!("../../tdx/examples/testdata/tdx_v4_quote_90c06f.bin");
+/// Within the validity of the recorded collaterals.
+const VERIFICATION_TIME: u64 = 1749600000;
+const SECRET: &[u8] = b"hunter2";
+
+/// Offset of the report data in a V4 TDX quote: header and TD report fields before it.
+const REPORT_DATA_OFFSET: usize = 48 + 520;
+
+fn collateral_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../tdx/examples/testdata/collaterals")
+}
+
+fn recorded_quote() -> QuoteV4 {
+    parse_quote_v4(QUOTE).unwrap()
+}
+
+fn recorded_output() -> VerifiedOutput {
+    let collaterals = Collaterals::read_from_dir(&collateral_dir()).unwrap();
+    Tdx::new()
+        .verify_attestation_report_with_collaterals(
+            &recorded_quote(),
+            &collaterals,
+            VERIFICATION_TIME,
+        )
+        .unwrap()
+}
+
+/// Returns the recorded quote with the requested report data.
+struct PatchedQuote;
+
+impl Attester for PatchedQuote {
+    fn quote(&self, report_data: [u8; 64]) -> Result<Vec<u8>> {
+        let mut quote = QUOTE.to_vec();
+        quote[REPORT_DATA_OFFSET..REPORT_DATA_OFFSET + 64].copy_from_slice(&report_data);
+        Ok(quote)
+    }
+}
+
+/// Returns the recorded quote with report data binding `nonce` to another HPKE key than the
+/// one the client sends.
+struct OtherKeyQuote {
+    nonce: Vec<u8>,
+}
+
+impl Attester for OtherKeyQuote {
+    fn quote(&self, _report_data: [u8; 64]) -> Result<Vec<u8>> {
+        let other_key = HpkeKeyPair::generate().public_key();
+        PatchedQuote.quote(report_data(&self.nonce, &other_key))
+    }
+}
+
+/// Verifies the recorded quote against the collaterals of the broker instead of the patched
+/// quote it was given, after checking that they only differ by their report data.
+struct RecordedVerifier;
+
+impl QuoteVerifier for RecordedVerifier {
+    fn verify(
+        &self,
+        quote: &QuoteV4,
+        collaterals: &Collaterals,
+        current_time: u64,
+    ) -> tdx::error::Result<VerifiedOutput> {
+        let recorded = recorded_quote();
+        let (QuoteBody::TD10QuoteBody(body), QuoteBody::TD10QuoteBody(recorded_body)) =
+            (&quote.quote_body, &recorded.quote_body)
+        else {
+            return Err("Not a TDX quote".into());
+        };
+        // The report data is the last field of the TD report.
+        if body.to_bytes()[..520] != recorded_body.to_bytes()[..520] {
+            return Err("Not the recorded quote".into());
+        }
+        Tdx::new().verify_attestation_report_with_collaterals(&recorded, collaterals, current_time)
+    }
+}
+
+fn policy(mrtd: &[u8]) -> Policy {
+    Policy {
+        allowed: vec![Measurements {
+            mrtd: Some(hex::encode(mrtd)),
+            ..Default::default()
+        }],
+        accepted_tcb_statuses: vec![tcb_status_name(&recorded_output().tcb_status).to_string()],
+    }
+}
+
+fn broker(policy: Policy) -> Broker {
+    Broker::new(
+        policy,
+        SecretStore::new(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/secrets")),
+        CollateralSource::Dir(collateral_dir()),
+    )
+    .with_verification_time(VERIFICATION_TIME)
+}
+
+fn recorded_mrtd() -> [u8; 48] {
+    match recorded_quote().quote_body {
+        QuoteBody::TD10QuoteBody(body) => body.mrtd,
+        _ => panic!("Not a TDX quote"),
+    }
+}
+
+/// Serves `broker` on a local port and returns its URL.
+async fn spawn_kbs(broker: Broker) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(kbs::server::serve(listener, Arc::new(broker)));
+    url
+}
+
+/// Runs the blocking client outside of the runtime threads.
+async fn run_client<T: Send + 'static>(
+    url: String,
+    f: impl FnOnce(KbsClient) -> Result<T> + Send + 'static,
+) -> Result<T> {
+    tokio::task::spawn_blocking(move || f(KbsClient::new(&url)))
+        .await
+        .unwrap()
+}
+
+fn is_http_status(err: &KbsError, status: u16) -> bool {
+    matches!(err, KbsError::Http(message) if message.starts_with(&format!("{}:", status)))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn releases_secret() {
+    let url = spawn_kbs(broker(policy(&recorded_mrtd())).with_verifier(RecordedVerifier)).await;
+    let secret = run_client(url, |client| {
+        client.get_secret("db-password", &PatchedQuote)
+    })
+    .await
+    .unwrap();
+    assert_eq!(secret, SECRET);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejects_replayed_nonce() {
+    let url = spawn_kbs(broker(policy(&recorded_mrtd())).with_verifier(RecordedVerifier)).await;
+    let err = run_client(url, |client| {
+        let nonce = client.challenge()?;
+        let keys = HpkeKeyPair::generate();
+        client.get_secret_with("db-password", &PatchedQuote, &nonce, &keys)?;
+        client.get_secret_with("db-password", &PatchedQuote, &nonce, &keys)
+    })
+    .await
+    .unwrap_err();
+    assert!(is_http_status(&err, 401), "{}", err);
+    assert!(err.to_string().contains("Unknown or expired nonce"), "{}", err);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejects_expired_nonce() {
+    let broker = broker(policy(&recorded_mrtd()))
+        .with_verifier(RecordedVerifier)
+        .with_nonce_ttl(0);
+    let url = spawn_kbs(broker).await;
+    let err = run_client(url, |client| {
+        let nonce = client.challenge()?;
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        client.get_secret_with(
+            "db-password",
+            &PatchedQuote,
+            &nonce,
+            &HpkeKeyPair::generate(),
+        )
+    })
+    .await
+    .unwrap_err();
+    assert!(is_http_status(&err, 401), "{}", err);
+    assert!(err.to_string().contains("expired nonce"), "{}", err);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejects_report_data_not_binding_the_key() {
+    let url = spawn_kbs(broker(policy(&recorded_mrtd())).with_verifier(RecordedVerifier)).await;
+    let err = run_client(url, |client| {
+        let nonce = client.challenge()?;
+        let attester = OtherKeyQuote {
+            nonce: nonce.clone(),
+        };
+        client.get_secret_with("db-password", &attester, &nonce, &HpkeKeyPair::generate())
+    })
+    .await
+    .unwrap_err();
+    assert!(is_http_status(&err, 401), "{}", err);
+    assert!(err.to_string().contains("does not bind"), "{}", err);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejects_measurements_outside_policy() {
+    let url = spawn_kbs(broker(policy(&[0u8; 48])).with_verifier(RecordedVerifier)).await;
+    let err = run_client(url, |client| {
+        client.get_secret("db-password", &PatchedQuote)
+    })
+    .await
+    .unwrap_err();
+    assert!(is_http_status(&err, 403), "{}", err);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejects_unbound_or_forged_quotes() {
+    // The DCAP verification of the broker rejects the patched quote, whose signature no
+    // longer matches, and the report data check rejects the recorded one.
+    let url = spawn_kbs(broker(policy(&recorded_mrtd()))).await;
+    let err = run_client(url.clone(), |client| {
+        client.get_secret("db-password", &PatchedQuote)
+    })
+    .await
+    .unwrap_err();
+    assert!(is_http_status(&err, 401), "{}", err);
+
+    let err = run_client(url, |client| {
+        client.get_secret("db-password", &RecordedQuote(QUOTE.to_vec()))
+    })
+    .await
+    .unwrap_err();
+    assert!(is_http_status(&err, 401), "{}", err);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn caps_outstanding_nonces() {
+    let url = spawn_kbs(broker(policy(&recorded_mrtd())).with_max_nonces(2)).await;
+    let err = run_client(url, |client| {
+        client.challenge()?;
+        client.challenge()?;
+        client.challenge()
+    })
+    .await
+    .unwrap_err();
+    assert!(is_http_status(&err, 503), "{}", err);
+}
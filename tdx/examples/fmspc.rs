@@ -1,7 +1,7 @@
 use tdx::Tdx;
-use tdx::utils::get_pck_fmspc_and_issuer;
+use tdx::utils::try_get_pck_fmspc_and_issuer;
 
-fn main() {
+fn main() -> anyhow::Result<()> {
     // Initialise a TDX object
     let tdx = Tdx::new();
 
@@ -9,7 +9,7 @@ fn main() {
     let (report, _) = tdx.get_attestation_report().unwrap();
     // println!("Attestation Report: {:?}", report);
 
-    let (fmspc, _) = get_pck_fmspc_and_issuer(&report);
+    let (fmspc, _) = try_get_pck_fmspc_and_issuer(&report)?;
     println!("FMSPC: {:?}", fmspc.to_uppercase());
     if report.header.tee_type == 0 {
         println!("Platform: SGX");
@@ -17,4 +17,5 @@ fn main() {
         println!("Platform: TDX");
     }
     println!("Version: {}", report.header.version);
+    Ok(())
 }
\ No newline at end of file
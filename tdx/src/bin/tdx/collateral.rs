@@ -0,0 +1,166 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use clap::{Subcommand, ValueEnum};
+use serde_json::json;
+use tdx::collateral::Collaterals;
+use tdx::pccs::pcs::IPCSDao::CA;
+use tdx::pccs::upsert::{upsert_missing_collaterals, CollateralOrigin, UpsertTarget};
+use tdx::pccs::PccsConfig;
+use tdx::utils::{parse_quote_v4, try_get_pck_fmspc_and_issuer};
+use tokio::runtime::Runtime;
+
+use crate::io::{print_json, read_input, Encoding};
+
+#[derive(Subcommand)]
+pub enum CollateralCommand {
+    /// Fetch the collaterals required to verify a quote from the on-chain PCCS
+    /// and write them to a directory.
+    Fetch {
+        /// Quote to fetch the collaterals for.
+        #[clap(long, conflicts_with = "fmspc")]
+        quote: Option<PathBuf>,
+
+        #[clap(long, value_enum, default_value = "raw")]
+        format: Encoding,
+
+        /// FMSPC to fetch the collaterals for, when no quote is available.
+        #[clap(long, requires = "pck_ca")]
+        fmspc: Option<String>,
+
+        /// CA that issued the PCK certificate, required with `--fmspc`.
+        #[clap(long, value_enum)]
+        pck_ca: Option<PckCa>,
+
+        /// Output directory.
+        #[clap(long, short)]
+        out: PathBuf,
+    },
+    /// Upsert the collaterals missing from the on-chain PCCS for a quote or an FMSPC,
+    /// taken from the Intel PCS or a directory.
+    Upsert {
+        /// Quote to upsert the collaterals for.
+        #[clap(long, conflicts_with = "fmspc")]
+        quote: Option<PathBuf>,
+
+        #[clap(long, value_enum, default_value = "raw")]
+        format: Encoding,
+
+        /// FMSPC to upsert the collaterals for, when no quote is available.
+        #[clap(long, requires = "pck_ca")]
+        fmspc: Option<String>,
+
+        /// CA that issued the PCK certificate, required with `--fmspc`.
+        #[clap(long, value_enum)]
+        pck_ca: Option<PckCa>,
+
+        /// Read the collaterals from this directory, with the file names of `fetch`,
+        /// instead of the Intel PCS.
+        #[clap(long)]
+        from_dir: Option<PathBuf>,
+
+        /// Upsert every collateral, even those already stored on-chain.
+        #[clap(long)]
+        force: bool,
+
+        /// Hex encoded private key signing the upsert transactions.
+        #[clap(long, env = "PCCS_PRIVATE_KEY", hide_env_values = true)]
+        private_key: String,
+    },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum PckCa {
+    Platform,
+    Processor,
+}
+
+pub fn run(command: CollateralCommand, config: &PccsConfig, json: bool) -> Result<()> {
+    match command {
+        CollateralCommand::Fetch {
+            quote,
+            format,
+            fmspc,
+            pck_ca,
+            out,
+        } => {
+            let rt = Runtime::new()?;
+            let collaterals = match (quote, fmspc, pck_ca) {
+                (Some(quote), _, _) => {
+                    let quote = parse_quote_v4(&read_input(&quote, format)?)?;
+                    rt.block_on(Collaterals::fetch(config, &quote))?
+                }
+                (None, Some(fmspc), Some(pck_ca)) => {
+                    let pck_type = match pck_ca {
+                        PckCa::Platform => CA::PLATFORM,
+                        PckCa::Processor => CA::PROCESSOR,
+                    };
+                    // TDX quotes are V4.
+                    rt.block_on(Collaterals::fetch_for_fmspc(
+                        config,
+                        &fmspc.to_lowercase(),
+                        pck_type,
+                        4,
+                    ))?
+                }
+                _ => bail!("Either --quote or --fmspc and --pck-ca must be provided"),
+            };
+            collaterals.write_to_dir(&out)?;
+
+            if json {
+                return print_json(&json!({ "collateralDir": out }));
+            }
+            println!("Collaterals written to {}", out.display());
+            Ok(())
+        }
+        CollateralCommand::Upsert {
+            quote,
+            format,
+            fmspc,
+            pck_ca,
+            from_dir,
+            force,
+            private_key,
+        } => {
+            let (fmspc, pck_type) = match (quote, fmspc, pck_ca) {
+                (Some(quote), _, _) => {
+                    let quote = parse_quote_v4(&read_input(&quote, format)?)?;
+                    try_get_pck_fmspc_and_issuer(&quote)?
+                }
+                (None, Some(fmspc), Some(pck_ca)) => {
+                    let pck_type = match pck_ca {
+                        PckCa::Platform => CA::PLATFORM,
+                        PckCa::Processor => CA::PROCESSOR,
+                    };
+                    (fmspc, pck_type)
+                }
+                _ => bail!("Either --quote or --fmspc and --pck-ca must be provided"),
+            };
+            let origin = match from_dir {
+                Some(dir) => CollateralOrigin::Dir(dir),
+                None => CollateralOrigin::IntelPcs,
+            };
+            let signer = private_key.parse()?;
+
+            let rt = Runtime::new()?;
+            let upserted = rt.block_on(upsert_missing_collaterals(
+                config,
+                &signer,
+                &origin,
+                &UpsertTarget::tdx(&fmspc, pck_type),
+                force,
+            ))?;
+
+            if json {
+                return print_json(&upserted);
+            }
+            for collateral in upserted {
+                match collateral.tx_hash {
+                    Some(tx_hash) => println!("{}: upserted in {}", collateral.collateral, tx_hash),
+                    None => println!("{}: already on-chain", collateral.collateral),
+                }
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Args;
+use serde_json::json;
+use tdx::report::platform_name;
+use tdx::utils::{parse_quote_v4, try_get_pck_fmspc_and_issuer};
+use tdx::Tdx;
+
+use crate::io::{print_json, read_input, Encoding};
+
+#[derive(Args)]
+pub struct FmspcArgs {
+    /// Read the FMSPC from this quote instead of generating one on this TD.
+    #[clap(long)]
+    quote: Option<PathBuf>,
+
+    #[clap(long, value_enum, default_value = "raw")]
+    format: Encoding,
+}
+
+pub fn run(args: FmspcArgs, json: bool) -> Result<()> {
+    let quote = match &args.quote {
+        Some(path) => parse_quote_v4(&read_input(path, args.format)?)?,
+        None => Tdx::new().get_attestation_report()?.0,
+    };
+
+    let (fmspc, _) = try_get_pck_fmspc_and_issuer(&quote)?;
+    let platform = platform_name(quote.header.tee_type);
+
+    if json {
+        return print_json(&json!({
+            "fmspc": fmspc.to_uppercase(),
+            "platform": platform,
+            "version": quote.header.version,
+        }));
+    }
+
+    println!("FMSPC: {}", fmspc.to_uppercase());
+    println!("Platform: {}", platform);
+    println!("Version: {}", quote.header.version);
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use clap::Args;
+use dcap_rs::types::quotes::QuoteHeader;
+use tdx::report::{QuoteBodySummary, QuoteSummary};
+use tdx::utils::{parse_quote_v3, parse_quote_v4, QUOTE_HEADER_LEN};
+
+use crate::io::{print_json, read_input, Encoding};
+
+#[derive(Args)]
+pub struct InspectArgs {
+    /// Path to the quote, `-` to read from stdin.
+    quote: PathBuf,
+
+    #[clap(long, value_enum, default_value = "raw")]
+    format: Encoding,
+}
+
+pub fn run(args: InspectArgs, json: bool) -> Result<()> {
+    let raw_quote = read_input(&args.quote, args.format)?;
+    if raw_quote.len() < QUOTE_HEADER_LEN {
+        bail!("Quote is too short");
+    }
+
+    let header = QuoteHeader::from_bytes(&raw_quote[..QUOTE_HEADER_LEN]);
+    let summary = match header.version {
+        3 => QuoteSummary::from_quote_v3(&parse_quote_v3(&raw_quote)?),
+        4 => QuoteSummary::from_quote_v4(&parse_quote_v4(&raw_quote)?),
+        version => bail!("Unsupported quote version: {}", version),
+    };
+
+    if json {
+        return print_json(&summary);
+    }
+
+    println!("Version: V{}", summary.version);
+    println!("Platform: {}", summary.platform);
+    if let Some(fmspc) = &summary.fmspc {
+        println!("FMSPC: {}", fmspc.to_uppercase());
+    }
+    match &summary.body {
+        QuoteBodySummary::Sgx {
+            mrenclave,
+            mrsigner,
+            report_data,
+            ..
+        } => {
+            println!("MRENCLAVE: {}", mrenclave);
+            println!("MRSIGNER: {}", mrsigner);
+            println!("Report Data: {}", report_data);
+        }
+        QuoteBodySummary::Td10 {
+            mrtd,
+            rtmr0,
+            rtmr1,
+            rtmr2,
+            rtmr3,
+            report_data,
+            ..
+        } => {
+            println!("MRTD: {}", mrtd);
+            println!("RTMR0: {}", rtmr0);
+            println!("RTMR1: {}", rtmr1);
+            println!("RTMR2: {}", rtmr2);
+            println!("RTMR3: {}", rtmr3);
+            println!("Report Data: {}", report_data);
+        }
+    }
+    Ok(())
+}
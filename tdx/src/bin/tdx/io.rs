@@ -0,0 +1,70 @@
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use base64_url::base64::{engine::general_purpose::STANDARD, Engine};
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// Encoding of a quote or any other binary input / output.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Encoding {
+    Raw,
+    Hex,
+    Base64,
+}
+
+impl Encoding {
+    pub fn encode(&self, bytes: &[u8]) -> Vec<u8> {
+        match self {
+            Encoding::Raw => bytes.to_vec(),
+            Encoding::Hex => hex::encode(bytes).into_bytes(),
+            Encoding::Base64 => STANDARD.encode(bytes).into_bytes(),
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Encoding::Raw => Ok(bytes.to_vec()),
+            Encoding::Hex => {
+                let s = std::str::from_utf8(bytes)?.trim();
+                Ok(hex::decode(s.strip_prefix("0x").unwrap_or(s))?)
+            }
+            Encoding::Base64 => Ok(STANDARD.decode(std::str::from_utf8(bytes)?.trim())?),
+        }
+    }
+}
+
+/// Reads `path`, or stdin if `path` is `-`, and decodes it.
+pub fn read_input(path: &Path, encoding: Encoding) -> Result<Vec<u8>> {
+    let bytes = if path == Path::new("-") {
+        let mut buf = Vec::new();
+        std::io::stdin().read_to_end(&mut buf)?;
+        buf
+    } else {
+        std::fs::read(path)?
+    };
+    encoding.decode(&bytes)
+}
+
+/// Encodes `bytes` and writes them to `path`, or stdout if `path` is `None`.
+pub fn write_output(path: Option<&PathBuf>, bytes: &[u8], encoding: Encoding) -> Result<()> {
+    let encoded = encoding.encode(bytes);
+    match path {
+        Some(path) => std::fs::write(path, encoded)?,
+        None => {
+            let mut stdout = std::io::stdout();
+            stdout.write_all(&encoded)?;
+            if !matches!(encoding, Encoding::Raw) {
+                stdout.write_all(b"\n")?;
+            }
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+pub fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
@@ -0,0 +1,81 @@
+//! `tdx` command line interface.
+//!
+//! Generates, inspects and verifies Intel TDX quotes. Pass `--json` to any subcommand
+//! to get machine readable output.
+mod collateral;
+mod fmspc;
+mod inspect;
+mod io;
+mod quote;
+mod tcb;
+mod verify;
+
+use clap::{Args, Parser, Subcommand};
+use tdx::pccs::PccsConfig;
+
+#[derive(Parser)]
+#[clap(
+    name = "tdx",
+    version,
+    about = "Intel TDX quote generation and verification"
+)]
+struct Cli {
+    /// Print the output as JSON.
+    #[clap(long, global = true)]
+    json: bool,
+
+    #[clap(flatten)]
+    pccs: PccsArgs,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a quote on this TD.
+    Quote(quote::QuoteArgs),
+    /// Print the contents of a SGX / TDX quote.
+    Inspect(inspect::InspectArgs),
+    /// Verify a TDX quote.
+    Verify(verify::VerifyArgs),
+    /// Manage the collaterals used for verification.
+    #[clap(subcommand)]
+    Collateral(collateral::CollateralCommand),
+    /// Print the FMSPC of this TD or of a quote.
+    Fmspc(fmspc::FmspcArgs),
+    /// Check the TCB Info currently published for FMSPCs or quotes.
+    Tcb(tcb::TcbArgs),
+}
+
+/// Options of the on-chain PCCS used as collateral source.
+#[derive(Args)]
+pub struct PccsArgs {
+    /// RPC URL of the chain hosting the on-chain PCCS.
+    #[clap(long, global = true, env = "PCCS_RPC_URL")]
+    rpc_url: Option<String>,
+}
+
+impl PccsArgs {
+    pub fn config(&self) -> PccsConfig {
+        let mut config = PccsConfig::default();
+        if let Some(rpc_url) = &self.rpc_url {
+            config.rpc_url = rpc_url.clone();
+        }
+        config
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let config = cli.pccs.config();
+
+    match cli.command {
+        Command::Quote(args) => quote::run(args, cli.json),
+        Command::Inspect(args) => inspect::run(args, cli.json),
+        Command::Verify(args) => verify::run(args, &config, cli.json),
+        Command::Collateral(command) => collateral::run(command, &config, cli.json),
+        Command::Fmspc(args) => fmspc::run(args, cli.json),
+        Command::Tcb(args) => tcb::run(args, &config, cli.json),
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use clap::{Args, ValueEnum};
+use serde_json::json;
+use tdx::device::DeviceOptions;
+use tdx::utils::report_data_from_nonce;
+use tdx::Tdx;
+
+use crate::io::{print_json, write_output, Encoding};
+
+#[derive(Args)]
+pub struct QuoteArgs {
+    /// 64 bytes of report data, hex encoded.
+    #[clap(long, conflicts_with = "nonce")]
+    report_data: Option<String>,
+
+    /// Nonce to bind to the quote, the report data is set to SHA-512(nonce).
+    #[clap(long)]
+    nonce: Option<String>,
+
+    /// Where to write the quote. Defaults to stdout.
+    #[clap(long, short)]
+    out: Option<PathBuf>,
+
+    #[clap(long, value_enum, default_value = "raw")]
+    format: Encoding,
+
+    /// Where to write the var data, only available on Azure Confidential VMs.
+    #[clap(long)]
+    var_data_out: Option<PathBuf>,
+
+    /// Write an evidence envelope holding the quote, var data, event log and nonce
+    /// instead of the raw quote. With `--json`, the envelope is printed in JSON whatever
+    /// the format.
+    #[clap(long, value_enum, conflicts_with = "report_data")]
+    evidence: Option<EvidenceFormat>,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum EvidenceFormat {
+    Cbor,
+    Json,
+}
+
+pub fn run(args: QuoteArgs, json: bool) -> Result<()> {
+    if let Some(format) = args.evidence {
+        let evidence = Tdx::new().get_evidence(args.nonce.as_ref().map(|n| n.as_bytes()))?;
+        if json {
+            return print_json(&evidence);
+        }
+        let bytes = match format {
+            EvidenceFormat::Cbor => evidence.to_cbor()?,
+            EvidenceFormat::Json => evidence.to_json()?.into_bytes(),
+        };
+        return write_output(args.out.as_ref(), &bytes, Encoding::Raw);
+    }
+
+    let report_data = match (&args.report_data, &args.nonce) {
+        (Some(report_data), _) => {
+            let bytes = hex::decode(report_data.strip_prefix("0x").unwrap_or(report_data))?;
+            if bytes.len() != 64 {
+                bail!("report data must be 64 bytes, got {}", bytes.len());
+            }
+            let mut report_data = [0u8; 64];
+            report_data.copy_from_slice(&bytes);
+            Some(report_data)
+        }
+        (None, Some(nonce)) => Some(report_data_from_nonce(nonce.as_bytes())),
+        (None, None) => None,
+    };
+
+    let tdx = Tdx::new();
+    let (quote, var_data) = match report_data {
+        Some(report_data) => tdx.get_attestation_report_raw_with_options(DeviceOptions {
+            report_data: Some(report_data),
+        })?,
+        None => tdx.get_attestation_report_raw()?,
+    };
+
+    if let (Some(path), Some(var_data)) = (&args.var_data_out, &var_data) {
+        std::fs::write(path, var_data)?;
+    }
+
+    if json {
+        return print_json(&json!({
+            "quote": hex::encode(&quote),
+            "varData": var_data.as_ref().map(hex::encode),
+        }));
+    }
+
+    write_output(args.out.as_ref(), &quote, args.format)
+}
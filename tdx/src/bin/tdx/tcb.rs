@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Args;
+use tdx::monitor::monitor_tcb;
+use tdx::pccs::PccsConfig;
+use tdx::utils::parse_quote_v4;
+use tokio::runtime::Runtime;
+
+use crate::io::{print_json, read_input, Encoding};
+
+#[derive(Args)]
+pub struct TcbArgs {
+    /// FMSPC to check, can be repeated.
+    #[clap(long)]
+    fmspc: Vec<String>,
+
+    /// Path to a previously verified quote, optionally followed by the TCB status recorded
+    /// at the time, eg `quote.bin=UpToDate`. Can be repeated.
+    #[clap(long)]
+    quote: Vec<String>,
+
+    #[clap(long, value_enum, default_value = "raw")]
+    format: Encoding,
+}
+
+pub fn run(args: TcbArgs, config: &PccsConfig, json: bool) -> Result<()> {
+    let mut quotes = Vec::new();
+    for arg in args.quote.iter() {
+        let (path, previous_status) = match arg.split_once('=') {
+            Some((path, status)) => (path, Some(status.to_string())),
+            None => (arg.as_str(), None),
+        };
+        let raw_quote = read_input(&PathBuf::from(path), args.format)?;
+        quotes.push((parse_quote_v4(&raw_quote)?, previous_status));
+    }
+
+    let report = Runtime::new()?.block_on(monitor_tcb(config, &args.fmspc, &quotes))?;
+
+    if json {
+        print_json(&report)?;
+    } else {
+        for tcb_info in report.tcb_infos.iter() {
+            println!(
+                "FMSPC {}: tcbEvaluationDataNumber {}, issued {}, next update {}{}",
+                tcb_info.fmspc.to_uppercase(),
+                tcb_info.tcb_evaluation_data_number,
+                tcb_info.issue_date,
+                tcb_info.next_update,
+                if tcb_info.expired { " (EXPIRED)" } else { "" }
+            );
+        }
+        for (arg, quote) in args.quote.iter().zip(report.quotes.iter()) {
+            println!(
+                "{}: {}{}",
+                arg,
+                quote.current_status,
+                if quote.downgraded == Some(true) {
+                    " (DOWNGRADED)"
+                } else {
+                    ""
+                }
+            );
+            for shortfall in quote.evaluation.shortfalls.iter() {
+                println!(
+                    "  {:?} {}: {} < {}",
+                    shortfall.component,
+                    shortfall.component_type.as_deref().unwrap_or_default(),
+                    shortfall.current_svn,
+                    shortfall.required_svn
+                );
+            }
+        }
+    }
+
+    // Exit with a non-zero code so that the check can be used for alerting.
+    if report.needs_attention() {
+        std::process::exit(2);
+    }
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Result};
+use clap::{Args, ValueEnum};
+use dcap_rs::types::quotes::version_4::QuoteV4;
+use tdx::certdata::{
+    cert_data_type, resolve_pck_cert_chain, PckCertSource, CERT_DATA_PCK_CERT_CHAIN,
+};
+use tdx::collateral::Collaterals;
+use tdx::eat::{AttestationResult, EatSigningKey};
+use tdx::pccs::PccsConfig;
+use tdx::report::VerificationSummary;
+use tdx::utils::{parse_quote_v4, try_get_pck_fmspc_and_issuer};
+use tdx::Tdx;
+use tokio::runtime::Runtime;
+
+use crate::io::{print_json, read_input, write_output, Encoding};
+
+#[derive(Args)]
+pub struct VerifyArgs {
+    /// Path to the quote, `-` to read from stdin.
+    quote: PathBuf,
+
+    #[clap(long, value_enum, default_value = "raw")]
+    format: Encoding,
+
+    /// Directory holding the collaterals, see `tdx collateral fetch`.
+    /// Missing collaterals are fetched from the on-chain PCCS and written to it,
+    /// unless `--offline` is set.
+    #[clap(long)]
+    collateral_dir: Option<PathBuf>,
+
+    /// Never access the network, all collaterals must be found in `--collateral-dir`.
+    #[clap(long, requires = "collateral_dir")]
+    offline: bool,
+
+    /// PCCS serving the PCK certificates of quotes that only hold the PPID of the platform.
+    /// Defaults to the PCK DAO of the on-chain PCCS.
+    #[clap(long)]
+    pck_pccs_url: Option<String>,
+
+    /// Verification time in seconds since the Unix epoch. Defaults to now.
+    #[clap(long)]
+    time: Option<u64>,
+
+    /// Output a signed attestation result (EAT) instead of the verification summary.
+    #[clap(long, value_enum, requires = "eat_key")]
+    eat: Option<EatFormat>,
+
+    /// P-256 private key in PKCS#8 PEM used to sign the attestation result.
+    #[clap(long)]
+    eat_key: Option<PathBuf>,
+
+    /// Issuer of the attestation result.
+    #[clap(long, default_value = "tdx")]
+    eat_issuer: String,
+
+    /// Validity of the attestation result in seconds.
+    #[clap(long)]
+    eat_validity: Option<i64>,
+
+    /// Hex encoded nonce of the relying party, put in the attestation result.
+    #[clap(long)]
+    eat_nonce: Option<String>,
+
+    /// Where to write the attestation result, defaults to stdout.
+    #[clap(long)]
+    out: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum EatFormat {
+    Jwt,
+    Cwt,
+}
+
+pub fn run(args: VerifyArgs, config: &PccsConfig, json: bool) -> Result<()> {
+    let mut raw_quote = read_input(&args.quote, args.format)?;
+    if cert_data_type(&raw_quote)? != CERT_DATA_PCK_CERT_CHAIN {
+        if args.offline {
+            bail!(
+                "The quote does not embed its PCK certificate chain, it cannot be verified offline"
+            );
+        }
+        let source = match &args.pck_pccs_url {
+            Some(url) => PckCertSource::Pccs { url: url.clone() },
+            None => PckCertSource::OnChain(config.clone()),
+        };
+        raw_quote = Runtime::new()?.block_on(resolve_pck_cert_chain(&raw_quote, &source))?;
+    }
+    let quote = parse_quote_v4(&raw_quote)?;
+
+    let collaterals = match &args.collateral_dir {
+        Some(dir) if args.offline => {
+            let collaterals = Collaterals::read_from_dir(dir)?;
+            check_platform(dir, &collaterals, &quote)?;
+            collaterals
+        }
+        Some(dir) => read_or_fetch_collaterals(dir, config, &quote)?,
+        None => Runtime::new()?.block_on(Collaterals::fetch(config, &quote))?,
+    };
+
+    let current_time = args
+        .time
+        .unwrap_or_else(|| chrono::Utc::now().timestamp() as u64);
+
+    let tdx = Tdx::new();
+    let output =
+        tdx.verify_attestation_report_with_collaterals(&quote, &collaterals, current_time)?;
+
+    if let (Some(format), Some(key)) = (args.eat, &args.eat_key) {
+        let key = EatSigningKey::from_pkcs8_pem(&std::fs::read_to_string(key)?)?;
+        let mut result = AttestationResult::from_verified_output(&output, &args.eat_issuer);
+        if let Some(validity) = args.eat_validity {
+            result = result.with_validity(validity);
+        }
+        if let Some(nonce) = &args.eat_nonce {
+            result = result.with_nonce(&hex::decode(nonce.trim_start_matches("0x"))?);
+        }
+        return match format {
+            EatFormat::Jwt => write_output(
+                args.out.as_ref(),
+                result.to_jwt(&key)?.as_bytes(),
+                Encoding::Raw,
+            ),
+            EatFormat::Cwt => write_output(args.out.as_ref(), &result.to_cwt(&key)?, Encoding::Raw),
+        };
+    }
+
+    let summary = VerificationSummary::from_verified_output(&output);
+
+    if json {
+        return print_json(&summary);
+    }
+
+    println!("Verification successful!");
+    println!("TCB Status: {}", summary.tcb_status);
+    println!("FMSPC: {}", summary.fmspc.to_uppercase());
+    if !summary.advisory_ids.is_empty() {
+        println!("Advisory IDs: {}", summary.advisory_ids.join(", "));
+    }
+    Ok(())
+}
+
+fn read_or_fetch_collaterals(
+    dir: &Path,
+    config: &PccsConfig,
+    quote: &QuoteV4,
+) -> Result<Collaterals> {
+    if let Ok(collaterals) = Collaterals::read_from_dir(dir) {
+        check_platform(dir, &collaterals, quote)?;
+        return Ok(collaterals);
+    }
+    if dir.exists() && !dir.is_dir() {
+        bail!("{} is not a directory", dir.display());
+    }
+    let collaterals = Runtime::new()?.block_on(Collaterals::fetch(config, quote))?;
+    collaterals.write_to_dir(dir)?;
+    Ok(collaterals)
+}
+
+/// Checks that the collaterals read from `dir` are those of the platform of `quote`.
+fn check_platform(dir: &Path, collaterals: &Collaterals, quote: &QuoteV4) -> Result<()> {
+    let (fmspc, pck_type) = try_get_pck_fmspc_and_issuer(quote)?;
+    collaterals.check_platform(&fmspc, pck_type).map_err(|e| {
+        anyhow!(
+            "The collaterals of {} do not match the quote: {}",
+            dir.display(),
+            e
+        )
+    })
+}
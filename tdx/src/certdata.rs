@@ -0,0 +1,700 @@
+//! Certification data of V4 quotes.
+//!
+//! The QE of a quote either embeds the whole PCK certificate chain (certification data type
+//! 5), or only the PPID of the platform, in clear or encrypted, with its CPUSVN, PCESVN and
+//! PCE ID (types 1 to 3). The PCK certificate of the latter must be looked up from the
+//! on-chain PCK DAO or a PCCS, and embedded in the quote before the quote can be verified.
+use base64_url::base64::{engine::general_purpose::STANDARD, Engine};
+use dcap_rs::utils::cert::get_x509_issuer_cn;
+use x509_parser::prelude::*;
+
+use crate::error::{Result, TdxError};
+use crate::pccs::pck::get_pck_certificate_with_config;
+use crate::pccs::pcs::{get_certificate_by_id_with_config, IPCSDao::CA};
+use crate::pccs::PccsConfig;
+use crate::utils::QUOTE_HEADER_LEN;
+
+/// PPID in clear.
+pub const CERT_DATA_PPID_CLEARTEXT: u16 = 1;
+/// PPID encrypted with RSA-2048-OAEP.
+pub const CERT_DATA_PPID_RSA2048: u16 = 2;
+/// PPID encrypted with RSA-3072-OAEP.
+pub const CERT_DATA_PPID_RSA3072: u16 = 3;
+/// PCK certificate chain, in PEM.
+pub const CERT_DATA_PCK_CERT_CHAIN: u16 = 5;
+/// QE report certification data, wrapping one of the above in V4 quotes.
+pub const CERT_DATA_QE_REPORT: u16 = 6;
+
+const SGX_TEE_TYPE: u32 = 0x00000000;
+const TDX_TEE_TYPE: u32 = 0x00000081;
+const QE_REPORT_LEN: usize = 384;
+const ECDSA_SIGNATURE_LEN: usize = 64;
+const ECDSA_KEY_LEN: usize = 64;
+
+/// Identifies the PCK certificate of a platform, as in the PCCS `pckcert` API.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PckCertId {
+    pub qeid: [u8; 16],
+    pub cpusvn: [u8; 16],
+    /// Little endian.
+    pub pcesvn: [u8; 2],
+    /// Little endian.
+    pub pceid: [u8; 2],
+    /// The encrypted PPID (types 2 and 3), or the PPID in clear (type 1).
+    pub ppid: Vec<u8>,
+    pub cert_data_type: u16,
+}
+
+/// Where to look up the PCK certificates of quotes without a PCK certificate chain.
+#[derive(Clone, Debug)]
+pub enum PckCertSource {
+    /// The PCK DAO of the on-chain PCCS. The PCK CA and Root CA certificates of the chain
+    /// are read from its PCS DAO.
+    OnChain(PccsConfig),
+    /// A PCCS, or the Intel PCS, serving `/sgx/certification/v4/pckcert`.
+    Pccs { url: String },
+}
+
+/// Offsets of the certification data of a V4 quote.
+struct CertDataLayout {
+    /// Offset of the signature data length.
+    signature_len_offset: usize,
+    /// Offset of the QE report certification data size.
+    qe_report_cert_size_offset: usize,
+    /// Offset of the inner certification data type.
+    cert_data_offset: usize,
+    cert_data_type: u16,
+    cert_data_len: usize,
+}
+
+impl CertDataLayout {
+    fn parse(raw_quote: &[u8]) -> Result<Self> {
+        if raw_quote.len() < QUOTE_HEADER_LEN {
+            return Err(TdxError::Dcap("Quote is too short".to_string()));
+        }
+        let version = u16::from_le_bytes([raw_quote[0], raw_quote[1]]);
+        if version != 4 {
+            return Err(TdxError::Dcap(format!(
+                "Unsupported quote version: {}",
+                version
+            )));
+        }
+        let tee_type = u32::from_le_bytes(raw_quote[4..8].try_into().unwrap());
+        let body_len = match tee_type {
+            SGX_TEE_TYPE => 384,
+            TDX_TEE_TYPE => 584,
+            _ => {
+                return Err(TdxError::Dcap(format!(
+                    "Unsupported TEE type: {:#x}",
+                    tee_type
+                )))
+            }
+        };
+
+        let signature_len_offset = QUOTE_HEADER_LEN + body_len;
+        let outer_type_offset = signature_len_offset + 4 + ECDSA_SIGNATURE_LEN + ECDSA_KEY_LEN;
+        if read_u16(raw_quote, outer_type_offset)? != CERT_DATA_QE_REPORT {
+            return Err(TdxError::Dcap(
+                "The quote does not hold QE report certification data".to_string(),
+            ));
+        }
+        let qe_report_cert_size_offset = outer_type_offset + 2;
+        let auth_data_offset = qe_report_cert_size_offset + 4 + QE_REPORT_LEN + ECDSA_SIGNATURE_LEN;
+        let auth_data_len = read_u16(raw_quote, auth_data_offset)? as usize;
+        let cert_data_offset = auth_data_offset + 2 + auth_data_len;
+        let cert_data_type = read_u16(raw_quote, cert_data_offset)?;
+        let cert_data_len = read_u32(raw_quote, cert_data_offset + 2)? as usize;
+        if raw_quote.len() < cert_data_offset + 6 + cert_data_len {
+            return Err(TdxError::Dcap("Truncated certification data".to_string()));
+        }
+
+        Ok(CertDataLayout {
+            signature_len_offset,
+            qe_report_cert_size_offset,
+            cert_data_offset,
+            cert_data_type,
+            cert_data_len,
+        })
+    }
+
+    fn cert_data<'a>(&self, raw_quote: &'a [u8]) -> &'a [u8] {
+        &raw_quote[self.cert_data_offset + 6..self.cert_data_offset + 6 + self.cert_data_len]
+    }
+}
+
+/// Returns the type of the certification data of the QE, one of the `CERT_DATA_*` constants.
+pub fn cert_data_type(raw_quote: &[u8]) -> Result<u16> {
+    Ok(CertDataLayout::parse(raw_quote)?.cert_data_type)
+}
+
+/// Reads the identifiers of the PCK certificate of a quote holding a PPID (types 1 to 3).
+pub fn pck_cert_id(raw_quote: &[u8]) -> Result<PckCertId> {
+    let layout = CertDataLayout::parse(raw_quote)?;
+    let ppid_len = match layout.cert_data_type {
+        CERT_DATA_PPID_CLEARTEXT => 16,
+        CERT_DATA_PPID_RSA2048 => 256,
+        CERT_DATA_PPID_RSA3072 => 384,
+        cert_data_type => {
+            return Err(TdxError::Dcap(format!(
+                "The certification data (type {}) does not hold a PPID",
+                cert_data_type
+            )))
+        }
+    };
+    let cert_data = layout.cert_data(raw_quote);
+    if cert_data.len() != ppid_len + 20 {
+        return Err(TdxError::Dcap(format!(
+            "Invalid PPID certification data size: {}",
+            cert_data.len()
+        )));
+    }
+
+    // The QE ID is the first 16 bytes of the user data of the header.
+    Ok(PckCertId {
+        qeid: raw_quote[28..44].try_into().unwrap(),
+        cpusvn: cert_data[ppid_len..ppid_len + 16].try_into().unwrap(),
+        pcesvn: cert_data[ppid_len + 16..ppid_len + 18].try_into().unwrap(),
+        pceid: cert_data[ppid_len + 18..ppid_len + 20].try_into().unwrap(),
+        ppid: cert_data[..ppid_len].to_vec(),
+        cert_data_type: layout.cert_data_type,
+    })
+}
+
+/// Replaces the certification data of the QE with the PEM encoded PCK certificate chain
+/// (type 5). The quote and QE report signatures do not cover the certification data, so the
+/// quote can then be verified as if the QE had embedded the chain.
+pub fn embed_pck_cert_chain(raw_quote: &[u8], pem_chain: &[u8]) -> Result<Vec<u8>> {
+    let layout = CertDataLayout::parse(raw_quote)?;
+    let old_len = layout.cert_data_len;
+    let new_len = pem_chain.len();
+    let cert_data_end = layout.cert_data_offset + 6 + old_len;
+
+    let resize = |raw: &[u8], offset: usize| -> Result<[u8; 4]> {
+        let size = (read_u32(raw, offset)? as usize)
+            .checked_sub(old_len)
+            .ok_or_else(|| TdxError::Dcap("Invalid signature data size".to_string()))?
+            + new_len;
+        u32::try_from(size)
+            .map(u32::to_le_bytes)
+            .map_err(|_| TdxError::Dcap("Certification data is too long".to_string()))
+    };
+    let signature_len = resize(raw_quote, layout.signature_len_offset)?;
+    let qe_report_cert_size = resize(raw_quote, layout.qe_report_cert_size_offset)?;
+    let cert_data_len = u32::try_from(new_len)
+        .map_err(|_| TdxError::Dcap("Certification data is too long".to_string()))?;
+
+    let mut quote = Vec::with_capacity(raw_quote.len() - old_len + new_len);
+    quote.extend_from_slice(&raw_quote[..layout.cert_data_offset]);
+    quote.extend_from_slice(&CERT_DATA_PCK_CERT_CHAIN.to_le_bytes());
+    quote.extend_from_slice(&cert_data_len.to_le_bytes());
+    quote.extend_from_slice(pem_chain);
+    quote.extend_from_slice(&raw_quote[cert_data_end..]);
+    quote[layout.signature_len_offset..layout.signature_len_offset + 4]
+        .copy_from_slice(&signature_len);
+    quote[layout.qe_report_cert_size_offset..layout.qe_report_cert_size_offset + 4]
+        .copy_from_slice(&qe_report_cert_size);
+    Ok(quote)
+}
+
+/// Returns the quote with its PCK certificate chain. Quotes holding a PPID get the chain of
+/// their PCK certificate, looked up from `source`, embedded. Quotes already holding the chain
+/// are returned unchanged.
+pub async fn resolve_pck_cert_chain(raw_quote: &[u8], source: &PckCertSource) -> Result<Vec<u8>> {
+    if cert_data_type(raw_quote)? == CERT_DATA_PCK_CERT_CHAIN {
+        return Ok(raw_quote.to_vec());
+    }
+    let id = pck_cert_id(raw_quote)?;
+    let pem_chain = match source {
+        PckCertSource::OnChain(config) => get_pck_cert_chain_on_chain(config, &id).await?,
+        PckCertSource::Pccs { url } => {
+            let url = url.clone();
+            tokio::task::spawn_blocking(move || get_pck_cert_chain_from_pccs(&url, &id))
+                .await
+                .map_err(|e| TdxError::Http(format!("{:?}", e)))??
+        }
+    };
+    embed_pck_cert_chain(raw_quote, &pem_chain)
+}
+
+async fn get_pck_cert_chain_on_chain(config: &PccsConfig, id: &PckCertId) -> Result<Vec<u8>> {
+    let pck_cert = get_pck_certificate_with_config(
+        config,
+        &hex::encode(id.qeid),
+        &hex::encode(id.cpusvn),
+        &hex::encode(id.pcesvn),
+        &hex::encode(id.pceid),
+    )
+    .await?;
+
+    let (_, pck) =
+        X509Certificate::from_der(&pck_cert).map_err(|e| TdxError::X509(format!("{:?}", e)))?;
+    let pck_ca = match get_x509_issuer_cn(&pck).as_str() {
+        "Intel SGX PCK Platform CA" => CA::PLATFORM,
+        "Intel SGX PCK Processor CA" => CA::PROCESSOR,
+        issuer => return Err(TdxError::X509(format!("Unknown PCK Issuer: {}", issuer))),
+    };
+    let (pck_ca_cert, _) = get_certificate_by_id_with_config(config, pck_ca).await?;
+    let (root_ca_cert, _) = get_certificate_by_id_with_config(config, CA::ROOT).await?;
+    if pck_ca_cert.is_empty() || root_ca_cert.is_empty() {
+        return Err(TdxError::Http("PCK CA or Root CA is empty".to_string()));
+    }
+
+    let mut pem_chain = Vec::new();
+    for der in [&pck_cert, &pck_ca_cert, &root_ca_cert] {
+        pem_chain.extend_from_slice(der_to_pem(der).as_bytes());
+    }
+    Ok(pem_chain)
+}
+
+/// The PCCS returns the PEM encoded PCK certificate, and the URL encoded PEM chain of its
+/// issuer in the `SGX-PCK-Certificate-Issuer-Chain` header. Blocks on the request.
+fn get_pck_cert_chain_from_pccs(url: &str, id: &PckCertId) -> Result<Vec<u8>> {
+    let mut request = ureq::get(&format!(
+        "{}/sgx/certification/v4/pckcert",
+        url.trim_end_matches('/')
+    ))
+    .query("qeid", &hex::encode(id.qeid))
+    .query("cpusvn", &hex::encode(id.cpusvn))
+    .query("pcesvn", &hex::encode(id.pcesvn))
+    .query("pceid", &hex::encode(id.pceid));
+    if id.cert_data_type != CERT_DATA_PPID_CLEARTEXT {
+        request = request.query("encrypted_ppid", &hex::encode(&id.ppid));
+    }
+    let response = request.call()?;
+    let issuer_chain = response
+        .header("SGX-PCK-Certificate-Issuer-Chain")
+        .map(percent_decode)
+        .ok_or_else(|| TdxError::Http("Missing PCK certificate issuer chain".to_string()))??;
+
+    let mut pem_chain = response.into_string()?.into_bytes();
+    pem_chain.extend_from_slice(&issuer_chain);
+    Ok(pem_chain)
+}
+
+pub(crate) fn der_to_pem(der: &[u8]) -> String {
+    let encoded = STANDARD.encode(der);
+    let mut pem = String::from("-----BEGIN CERTIFICATE-----\n");
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).unwrap());
+        pem.push('\n');
+    }
+    pem.push_str("-----END CERTIFICATE-----\n");
+    pem
+}
+
+fn percent_decode(value: &str) -> Result<Vec<u8>> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| TdxError::Encoding(format!("Invalid URL encoding: {}", value)))?;
+            decoded.push(hex);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Ok(decoded)
+}
+
+fn read_u16(raw: &[u8], offset: usize) -> Result<u16> {
+    raw.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| TdxError::Dcap("Truncated quote signature data".to_string()))
+}
+
+fn read_u32(raw: &[u8], offset: usize) -> Result<u32> {
+    raw.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| TdxError::Dcap("Truncated quote signature data".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collateral::Collaterals;
+    use crate::pccs::pck::IPckDao::getCertCall;
+    use crate::pccs::pcs::IPCSDao::{getCertificateByIdCall, getCertificateByIdReturn};
+    use crate::test_rpc::{call_input, data, TestRpc};
+    use crate::utils::parse_quote_v4;
+    use crate::Tdx;
+    use alloy::primitives::Bytes;
+    use alloy::sol_types::SolCall;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use tokio::runtime::Runtime;
+
+    const QUOTE: &[u8] = include_bytes!("../examples/testdata/tdx_v4_quote_90c06f.bin");
+    const SIGNATURE_LEN_OFFSET: usize = QUOTE_HEADER_LEN + 584;
+    const QE_REPORT_CERT_SIZE_OFFSET: usize =
+        SIGNATURE_LEN_OFFSET + 4 + ECDSA_SIGNATURE_LEN + ECDSA_KEY_LEN + 2;
+
+    /// The recorded quote, with the PPID certification data of `cert_data_type` instead of its
+    /// PCK certificate chain.
+    fn ppid_quote(cert_data_type: u16, ppid_len: usize) -> Vec<u8> {
+        let mut cert_data = vec![0xee; ppid_len];
+        cert_data.extend_from_slice(&[0xc0; 16]);
+        cert_data.extend_from_slice(&[0x0d, 0x00]);
+        cert_data.extend_from_slice(&[0x00, 0x00]);
+        let mut quote = embed_pck_cert_chain(QUOTE, &cert_data).unwrap();
+        let offset = CertDataLayout::parse(&quote).unwrap().cert_data_offset;
+        quote[offset..offset + 2].copy_from_slice(&cert_data_type.to_le_bytes());
+        quote
+    }
+
+    /// The DER certificates of the PCK certificate chain of the recorded quote, leaf first.
+    fn pck_cert_chain() -> Vec<Vec<u8>> {
+        let cert_data = CertDataLayout::parse(QUOTE)
+            .unwrap()
+            .cert_data(QUOTE)
+            .to_vec();
+        Pem::iter_from_buffer(&cert_data)
+            .map(|pem| pem.unwrap().contents)
+            .collect()
+    }
+
+    /// Verifies a quote with the collaterals of the recorded quote.
+    fn verify(quote: &[u8]) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/testdata/collaterals");
+        let collaterals = Collaterals::read_from_dir(&dir).unwrap();
+        let verified_output = Tdx::new()
+            .verify_attestation_report_with_collaterals(
+                &parse_quote_v4(quote).unwrap(),
+                &collaterals,
+                1749600000,
+            )
+            .unwrap();
+        assert_eq!(verified_output.fmspc, [0x90, 0xc0, 0x6f, 0, 0, 0]);
+    }
+
+    /// A PCK DAO holding the PCK certificate of the recorded quote, under the identifiers
+    /// of `ppid_quote`, and a PCS DAO holding its issuers.
+    fn pck_dao(chain: Vec<Vec<u8>>) -> TestRpc {
+        TestRpc::start(move |method, params| {
+            if method != "eth_call" {
+                return Err(format!("unexpected {}", method));
+            }
+            let input = call_input(params);
+            let output = match input[..4].try_into().unwrap() {
+                getCertCall::SELECTOR => {
+                    let call = getCertCall::abi_decode(&input).unwrap();
+                    let cert = if call.qeid == hex::encode(&QUOTE[28..44])
+                        && call.platformCpuSvn == "c0".repeat(16)
+                        && call.platformPceSvn == "0d00"
+                        && call.pceid == "0000"
+                    {
+                        chain[0].clone()
+                    } else {
+                        Vec::new()
+                    };
+                    getCertCall::abi_encode_returns(&Bytes::from(cert))
+                }
+                getCertificateByIdCall::SELECTOR => {
+                    let call = getCertificateByIdCall::abi_decode(&input).unwrap();
+                    let cert = match call.ca {
+                        CA::PLATFORM => chain[1].clone(),
+                        CA::ROOT => chain[2].clone(),
+                        _ => Vec::new(),
+                    };
+                    getCertificateByIdCall::abi_encode_returns(&getCertificateByIdReturn {
+                        cert: Bytes::from(cert),
+                        crl: Bytes::new(),
+                    })
+                }
+                selector => return Err(format!("unexpected call {}", hex::encode(selector))),
+            };
+            Ok(data(&output))
+        })
+    }
+
+    /// A PCCS answering every request with the PCK certificate of the recorded quote, and
+    /// its URL encoded issuer chain. Returns its URL and the request lines it received.
+    fn pccs(issuer_chain: Option<String>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        let pck_cert = der_to_pem(&pck_cert_chain()[0]);
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                received
+                    .lock()
+                    .unwrap()
+                    .push(request_line.trim_end().to_string());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let header = match &issuer_chain {
+                    Some(chain) => format!("SGX-PCK-Certificate-Issuer-Chain: {}\r\n", chain),
+                    None => String::new(),
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    header,
+                    pck_cert.len(),
+                    pck_cert
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    fn percent_encode(bytes: &[u8]) -> String {
+        bytes
+            .iter()
+            .map(|&byte| match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => (byte as char).to_string(),
+                _ => format!("%{:02X}", byte),
+            })
+            .collect()
+    }
+
+    fn read_len(quote: &[u8], offset: usize) -> usize {
+        read_u32(quote, offset).unwrap() as usize
+    }
+
+    #[test]
+    fn parses_the_layout_of_pck_cert_chains() {
+        let layout = CertDataLayout::parse(QUOTE).unwrap();
+        assert_eq!(layout.signature_len_offset, SIGNATURE_LEN_OFFSET);
+        assert_eq!(
+            layout.qe_report_cert_size_offset,
+            QE_REPORT_CERT_SIZE_OFFSET
+        );
+        assert_eq!(layout.cert_data_type, CERT_DATA_PCK_CERT_CHAIN);
+        // The recorded quote is padded with zeros after its signature data.
+        let cert_data_end = layout.cert_data_offset + 6 + layout.cert_data_len;
+        assert_eq!(
+            SIGNATURE_LEN_OFFSET + 4 + read_len(QUOTE, SIGNATURE_LEN_OFFSET),
+            cert_data_end
+        );
+        assert!(QUOTE[cert_data_end..].iter().all(|&byte| byte == 0));
+        assert!(layout
+            .cert_data(QUOTE)
+            .starts_with(b"-----BEGIN CERTIFICATE-----"));
+        assert_eq!(cert_data_type(QUOTE).unwrap(), CERT_DATA_PCK_CERT_CHAIN);
+    }
+
+    #[test]
+    fn embeds_pck_cert_chains() {
+        let chain = CertDataLayout::parse(QUOTE)
+            .unwrap()
+            .cert_data(QUOTE)
+            .to_vec();
+        assert_eq!(embed_pck_cert_chain(QUOTE, &chain).unwrap(), QUOTE);
+
+        let mut longer_chain = chain.clone();
+        longer_chain.extend_from_slice(b"\n\n\n");
+        let quote = embed_pck_cert_chain(QUOTE, &longer_chain).unwrap();
+        assert_eq!(quote.len(), QUOTE.len() + 3);
+        assert_eq!(
+            read_len(&quote, SIGNATURE_LEN_OFFSET),
+            read_len(QUOTE, SIGNATURE_LEN_OFFSET) + 3
+        );
+        assert_eq!(
+            read_len(&quote, QE_REPORT_CERT_SIZE_OFFSET),
+            read_len(QUOTE, QE_REPORT_CERT_SIZE_OFFSET) + 3
+        );
+        let layout = CertDataLayout::parse(&quote).unwrap();
+        assert_eq!(layout.cert_data(&quote), longer_chain);
+        parse_quote_v4(&quote).unwrap();
+        assert_eq!(embed_pck_cert_chain(&quote, &chain).unwrap(), QUOTE);
+    }
+
+    #[test]
+    fn reads_pck_cert_ids() {
+        let quote = ppid_quote(CERT_DATA_PPID_RSA3072, 384);
+        assert_eq!(cert_data_type(&quote).unwrap(), CERT_DATA_PPID_RSA3072);
+        let id = pck_cert_id(&quote).unwrap();
+        assert_eq!(id.qeid, QUOTE[28..44]);
+        assert_eq!(id.cpusvn, [0xc0; 16]);
+        assert_eq!(id.pcesvn, [0x0d, 0x00]);
+        assert_eq!(id.pceid, [0x00, 0x00]);
+        assert_eq!(id.ppid, [0xee; 384]);
+        assert_eq!(id.cert_data_type, CERT_DATA_PPID_RSA3072);
+
+        let id = pck_cert_id(&ppid_quote(CERT_DATA_PPID_CLEARTEXT, 16)).unwrap();
+        assert_eq!(id.ppid, [0xee; 16]);
+
+        // Embedding the chain restores the recorded quote.
+        let chain = CertDataLayout::parse(QUOTE)
+            .unwrap()
+            .cert_data(QUOTE)
+            .to_vec();
+        assert_eq!(embed_pck_cert_chain(&quote, &chain).unwrap(), QUOTE);
+    }
+
+    #[test]
+    fn resolves_pck_cert_chains_on_chain() {
+        let runtime = Runtime::new().unwrap();
+        let node = pck_dao(pck_cert_chain());
+        let source = PckCertSource::OnChain(PccsConfig {
+            rpc_url: node.url().to_string(),
+            ..Default::default()
+        });
+
+        for (ppid_type, ppid_len) in [
+            (CERT_DATA_PPID_RSA3072, 384),
+            (CERT_DATA_PPID_CLEARTEXT, 16),
+        ] {
+            let quote = ppid_quote(ppid_type, ppid_len);
+            let resolved = runtime
+                .block_on(resolve_pck_cert_chain(&quote, &source))
+                .unwrap();
+            assert_eq!(cert_data_type(&resolved).unwrap(), CERT_DATA_PCK_CERT_CHAIN);
+            verify(&resolved);
+        }
+
+        // Quotes holding the chain are not looked up.
+        let calls = node.methods().len();
+        let resolved = runtime
+            .block_on(resolve_pck_cert_chain(QUOTE, &source))
+            .unwrap();
+        assert_eq!(resolved, QUOTE);
+        assert_eq!(node.methods().len(), calls);
+
+        // Another platform, with no PCK certificate on-chain.
+        let mut quote = ppid_quote(CERT_DATA_PPID_RSA3072, 384);
+        quote[28] ^= 1;
+        assert!(runtime
+            .block_on(resolve_pck_cert_chain(&quote, &source))
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_incomplete_chains_on_chain() {
+        let runtime = Runtime::new().unwrap();
+        let quote = ppid_quote(CERT_DATA_PPID_RSA3072, 384);
+        let mut chain = pck_cert_chain();
+        chain[2].clear();
+        let node = pck_dao(chain);
+        let config = PccsConfig {
+            rpc_url: node.url().to_string(),
+            ..Default::default()
+        };
+        let id = pck_cert_id(&quote).unwrap();
+        let result = runtime.block_on(get_pck_cert_chain_on_chain(&config, &id));
+        assert!(matches!(result, Err(TdxError::Http(_))));
+
+        // A PCK certificate issued by neither PCK CA.
+        let mut chain = pck_cert_chain();
+        chain[0] = chain[2].clone();
+        let node = pck_dao(chain);
+        let config = PccsConfig {
+            rpc_url: node.url().to_string(),
+            ..Default::default()
+        };
+        let result = runtime.block_on(get_pck_cert_chain_on_chain(&config, &id));
+        assert!(matches!(result, Err(TdxError::X509(e)) if e.contains("Unknown PCK Issuer")));
+    }
+
+    #[test]
+    fn resolves_pck_cert_chains_from_pccs() {
+        let runtime = Runtime::new().unwrap();
+        let chain = pck_cert_chain();
+        let issuer_chain = der_to_pem(&chain[1]) + &der_to_pem(&chain[2]);
+        let (url, requests) = pccs(Some(percent_encode(issuer_chain.as_bytes())));
+        let source = PckCertSource::Pccs {
+            url: format!("{}/", url),
+        };
+
+        let resolved = runtime
+            .block_on(resolve_pck_cert_chain(
+                &ppid_quote(CERT_DATA_PPID_RSA3072, 384),
+                &source,
+            ))
+            .unwrap();
+        verify(&resolved);
+        let resolved = runtime
+            .block_on(resolve_pck_cert_chain(
+                &ppid_quote(CERT_DATA_PPID_CLEARTEXT, 16),
+                &source,
+            ))
+            .unwrap();
+        verify(&resolved);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let query = format!(
+            "GET /sgx/certification/v4/pckcert?qeid={}&cpusvn={}&pcesvn=0d00&pceid=0000",
+            hex::encode(&QUOTE[28..44]),
+            "c0".repeat(16)
+        );
+        assert_eq!(
+            requests[0],
+            format!("{}&encrypted_ppid={} HTTP/1.1", query, "ee".repeat(384))
+        );
+        // The PPID in clear is not sent.
+        assert_eq!(requests[1], format!("{} HTTP/1.1", query));
+    }
+
+    #[test]
+    fn requires_the_issuer_chain_from_pccs() {
+        let (url, _) = pccs(None);
+        let source = PckCertSource::Pccs { url };
+        let result = Runtime::new().unwrap().block_on(resolve_pck_cert_chain(
+            &ppid_quote(CERT_DATA_PPID_RSA3072, 384),
+            &source,
+        ));
+        assert!(matches!(result, Err(TdxError::Http(e)) if e.contains("issuer chain")));
+    }
+
+    #[test]
+    fn decodes_percent_encoding() {
+        assert_eq!(
+            percent_decode("-----BEGIN%20CERTIFICATE-----%0AMII%2B%2f").unwrap(),
+            b"-----BEGIN CERTIFICATE-----\nMII+/"
+        );
+        assert_eq!(percent_decode("").unwrap(), b"");
+        for invalid in ["%", "%2", "abc%zz", "%%20"] {
+            assert!(matches!(
+                percent_decode(invalid),
+                Err(TdxError::Encoding(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_cert_data() {
+        assert!(pck_cert_id(QUOTE).is_err());
+        assert!(pck_cert_id(&ppid_quote(CERT_DATA_PPID_RSA2048, 384)).is_err());
+        assert!(pck_cert_id(&ppid_quote(4, 16)).is_err());
+
+        let layout = CertDataLayout::parse(QUOTE).unwrap();
+        let cert_data_end = layout.cert_data_offset + 6 + layout.cert_data_len;
+        let err = CertDataLayout::parse(&QUOTE[..cert_data_end - 1])
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "Dcap: Truncated certification data");
+        let err = CertDataLayout::parse(&QUOTE[..QE_REPORT_CERT_SIZE_OFFSET])
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "Dcap: Truncated quote signature data");
+        assert!(CertDataLayout::parse(&QUOTE[..QUOTE_HEADER_LEN - 1]).is_err());
+
+        let mut quote = QUOTE.to_vec();
+        quote[0] = 3;
+        assert!(embed_pck_cert_chain(&quote, b"").is_err());
+        let mut quote = QUOTE.to_vec();
+        quote[SIGNATURE_LEN_OFFSET + 4 + ECDSA_SIGNATURE_LEN + ECDSA_KEY_LEN] = 5;
+        assert!(cert_data_type(&quote).is_err());
+
+        // A signature data size smaller than the certification data.
+        let mut quote = QUOTE.to_vec();
+        quote[SIGNATURE_LEN_OFFSET..SIGNATURE_LEN_OFFSET + 4].copy_from_slice(&[0; 4]);
+        assert!(embed_pck_cert_chain(&quote, b"").is_err());
+    }
+}
@@ -0,0 +1,367 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, TdxError};
+use crate::pccs::enclave_id::get_enclave_identity_with_config;
+use crate::pccs::fmspc_tcb::get_tcb_info_with_config;
+use crate::pccs::pcs::{get_certificate_by_id_with_config, IPCSDao::CA};
+use crate::pccs::PccsConfig;
+use crate::tcb::SignedTcbInfo;
+use crate::utils::try_get_pck_fmspc_and_issuer;
+use ciborium::Value;
+use dcap_rs::types::collaterals::IntelCollateral;
+use dcap_rs::types::quotes::version_4::QuoteV4;
+use x509_parser::prelude::{CertificateRevocationList, FromDer};
+
+// File names used when collaterals are stored in a directory.
+pub const TCB_INFO_FILE: &str = "tcbinfo.json";
+pub const QE_IDENTITY_FILE: &str = "identity.json";
+pub const ROOT_CA_FILE: &str = "root_ca.der";
+pub const ROOT_CA_CRL_FILE: &str = "root_ca_crl.der";
+pub const SIGNING_CA_FILE: &str = "signing_ca.der";
+pub const PCK_PLATFORM_CRL_FILE: &str = "pck_platform_crl.der";
+pub const PCK_PROCESSOR_CRL_FILE: &str = "pck_processor_crl.der";
+
+/// Where to get the collaterals required to verify quotes from.
+#[derive(Clone, Debug)]
+pub enum CollateralSource {
+    /// Collaterals written with `Collaterals::write_to_dir`, eg by `tdx collateral fetch`.
+    /// They are only returned for the FMSPC and PCK CA they were fetched for.
+    Dir(PathBuf),
+    /// The on-chain PCCS.
+    Pccs(PccsConfig),
+}
+
+impl CollateralSource {
+    /// Retrieves the collaterals required to verify `quote`.
+    pub async fn collaterals_for_quote(&self, quote: &QuoteV4) -> Result<Collaterals> {
+        match self {
+            CollateralSource::Dir(dir) => {
+                let (fmspc, pck_type) = try_get_pck_fmspc_and_issuer(quote)?;
+                let collaterals = Collaterals::read_from_dir(dir)?;
+                collaterals.check_platform(&fmspc, pck_type)?;
+                Ok(collaterals)
+            }
+            CollateralSource::Pccs(config) => Collaterals::fetch(config, quote).await,
+        }
+    }
+
+    /// Retrieves the collaterals required to verify a TDX quote of the given version,
+    /// issued for `fmspc` by the `pck_type` CA.
+    pub async fn collaterals_for_fmspc(
+        &self,
+        fmspc: &str,
+        pck_type: CA,
+        quote_version: u32,
+    ) -> Result<Collaterals> {
+        match self {
+            CollateralSource::Dir(dir) => {
+                let collaterals = Collaterals::read_from_dir(dir)?;
+                collaterals.check_platform(fmspc, pck_type)?;
+                Ok(collaterals)
+            }
+            CollateralSource::Pccs(config) => {
+                Collaterals::fetch_for_fmspc(config, fmspc, pck_type, quote_version).await
+            }
+        }
+    }
+}
+
+/// All the collaterals required to verify a TDX quote.
+#[derive(Clone, Debug)]
+pub struct Collaterals {
+    /// TCB Info V3 JSON.
+    pub tcb_info: Vec<u8>,
+    /// QE Identity JSON.
+    pub qe_identity: Vec<u8>,
+    /// Intel SGX Root CA certificate in DER.
+    pub root_ca: Vec<u8>,
+    /// Intel SGX Root CA CRL in DER.
+    pub root_ca_crl: Vec<u8>,
+    /// Intel SGX TCB Signing certificate in DER.
+    pub signing_ca: Vec<u8>,
+    /// CRL of the CA that issued the PCK certificate, in DER.
+    pub pck_crl: Vec<u8>,
+    /// Either `CA::PLATFORM` or `CA::PROCESSOR`.
+    pub pck_type: CA,
+}
+
+impl Collaterals {
+    /// Retrieves the collaterals required to verify `quote` from the on-chain PCCS.
+    pub async fn fetch(config: &PccsConfig, quote: &QuoteV4) -> Result<Self> {
+        let (fmspc, pck_type) = try_get_pck_fmspc_and_issuer(quote)?;
+        Self::fetch_for_fmspc(config, &fmspc, pck_type, quote.header.version as u32).await
+    }
+
+    /// Retrieves the collaterals required to verify a TDX quote of the given version,
+    /// issued for `fmspc` by the `pck_type` CA.
+    pub async fn fetch_for_fmspc(
+        config: &PccsConfig,
+        fmspc: &str,
+        pck_type: CA,
+        quote_version: u32,
+    ) -> Result<Self> {
+        let (root_ca, root_ca_crl) = get_certificate_by_id_with_config(config, CA::ROOT).await?;
+        if root_ca.is_empty() || root_ca_crl.is_empty() {
+            return Err(TdxError::Http("Root CA or CRL is empty".to_string()));
+        }
+
+        // tcb_type: 0: SGX, 1: TDX
+        // version: TDX uses TcbInfoV3
+        let tcb_info = get_tcb_info_with_config(config, 1, fmspc, 3).await?;
+
+        let qe_identity = get_enclave_identity_with_config(config, quote_version).await?;
+
+        let (signing_ca, _) = get_certificate_by_id_with_config(config, CA::SIGNING).await?;
+        if signing_ca.is_empty() {
+            return Err(TdxError::Http("Signing CA is empty".to_string()));
+        }
+
+        let (_, pck_crl) = get_certificate_by_id_with_config(config, pck_type).await?;
+        if pck_crl.is_empty() {
+            return Err(TdxError::Http("PCK CRL is empty".to_string()));
+        }
+
+        Ok(Collaterals {
+            tcb_info,
+            qe_identity,
+            root_ca,
+            root_ca_crl,
+            signing_ca,
+            pck_crl,
+            pck_type,
+        })
+    }
+
+    /// Reads collaterals previously written with `write_to_dir`.
+    pub fn read_from_dir(dir: &Path) -> Result<Self> {
+        let (pck_type, pck_crl_file) = if dir.join(PCK_PLATFORM_CRL_FILE).exists() {
+            (CA::PLATFORM, PCK_PLATFORM_CRL_FILE)
+        } else if dir.join(PCK_PROCESSOR_CRL_FILE).exists() {
+            (CA::PROCESSOR, PCK_PROCESSOR_CRL_FILE)
+        } else {
+            return Err(TdxError::IO(format!(
+                "Neither {} nor {} found in {}",
+                PCK_PLATFORM_CRL_FILE,
+                PCK_PROCESSOR_CRL_FILE,
+                dir.display()
+            )));
+        };
+
+        Ok(Collaterals {
+            tcb_info: std::fs::read(dir.join(TCB_INFO_FILE))?,
+            qe_identity: std::fs::read(dir.join(QE_IDENTITY_FILE))?,
+            root_ca: std::fs::read(dir.join(ROOT_CA_FILE))?,
+            root_ca_crl: std::fs::read(dir.join(ROOT_CA_CRL_FILE))?,
+            signing_ca: std::fs::read(dir.join(SIGNING_CA_FILE))?,
+            pck_crl: std::fs::read(dir.join(pck_crl_file))?,
+            pck_type,
+        })
+    }
+
+    /// Writes the collaterals to `dir`, creating it if needed.
+    pub fn write_to_dir(&self, dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(dir.join(TCB_INFO_FILE), &self.tcb_info)?;
+        std::fs::write(dir.join(QE_IDENTITY_FILE), &self.qe_identity)?;
+        std::fs::write(dir.join(ROOT_CA_FILE), &self.root_ca)?;
+        std::fs::write(dir.join(ROOT_CA_CRL_FILE), &self.root_ca_crl)?;
+        std::fs::write(dir.join(SIGNING_CA_FILE), &self.signing_ca)?;
+        let pck_crl_file = match self.pck_type {
+            CA::PROCESSOR => PCK_PROCESSOR_CRL_FILE,
+            _ => PCK_PLATFORM_CRL_FILE,
+        };
+        std::fs::write(dir.join(pck_crl_file), &self.pck_crl)?;
+        Ok(())
+    }
+
+    /// Encodes the collaterals as a CBOR map of byte strings, to be shipped alongside a quote.
+    pub fn to_cbor(&self) -> Result<Vec<u8>> {
+        let pck_type = match self.pck_type {
+            CA::PROCESSOR => "processor",
+            _ => "platform",
+        };
+        let entry = |name: &str, bytes: &[u8]| {
+            (Value::Text(name.to_string()), Value::Bytes(bytes.to_vec()))
+        };
+        let value = Value::Map(vec![
+            entry("tcbInfo", &self.tcb_info),
+            entry("qeIdentity", &self.qe_identity),
+            entry("rootCa", &self.root_ca),
+            entry("rootCaCrl", &self.root_ca_crl),
+            entry("signingCa", &self.signing_ca),
+            entry("pckCrl", &self.pck_crl),
+            (
+                Value::Text("pckType".to_string()),
+                Value::Text(pck_type.to_string()),
+            ),
+        ]);
+
+        let mut out = Vec::new();
+        ciborium::into_writer(&value, &mut out).map_err(|e| TdxError::Encoding(e.to_string()))?;
+        Ok(out)
+    }
+
+    /// Decodes collaterals encoded with `to_cbor`.
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self> {
+        let value: Value =
+            ciborium::from_reader(bytes).map_err(|e| TdxError::Encoding(e.to_string()))?;
+        let Value::Map(entries) = value else {
+            return Err(TdxError::Encoding("Collaterals are not a map".to_string()));
+        };
+        let field = |name: &str| {
+            entries
+                .iter()
+                .find(|(k, _)| k.as_text() == Some(name))
+                .map(|(_, v)| v)
+                .ok_or_else(|| TdxError::Encoding(format!("Missing collateral: {}", name)))
+        };
+        let bytes = |name: &str| {
+            field(name)?
+                .as_bytes()
+                .cloned()
+                .ok_or_else(|| TdxError::Encoding(format!("Invalid collateral: {}", name)))
+        };
+        let pck_type = match field("pckType")?.as_text() {
+            Some("platform") => CA::PLATFORM,
+            Some("processor") => CA::PROCESSOR,
+            _ => {
+                return Err(TdxError::Encoding(
+                    "Invalid collateral: pckType".to_string(),
+                ))
+            }
+        };
+
+        Ok(Collaterals {
+            tcb_info: bytes("tcbInfo")?,
+            qe_identity: bytes("qeIdentity")?,
+            root_ca: bytes("rootCa")?,
+            root_ca_crl: bytes("rootCaCrl")?,
+            signing_ca: bytes("signingCa")?,
+            pck_crl: bytes("pckCrl")?,
+            pck_type,
+        })
+    }
+
+    /// Checks that the collaterals are those of a platform with the given FMSPC whose PCK
+    /// certificate was issued by the `pck_type` CA: the FMSPC of the TCB Info and the issuer
+    /// of the PCK CRL must match, `TdxError::NotFound` is returned otherwise.
+    pub fn check_platform(&self, fmspc: &str, pck_type: CA) -> Result<()> {
+        let tcb_info = SignedTcbInfo::from_bytes(&self.tcb_info)?.tcb_info;
+        if !tcb_info.fmspc.eq_ignore_ascii_case(fmspc) {
+            return Err(TdxError::NotFound(format!(
+                "The TCB Info is for FMSPC {}, not {}",
+                tcb_info.fmspc,
+                fmspc.to_uppercase()
+            )));
+        }
+
+        let expected_issuer = match pck_type {
+            CA::PLATFORM => "Intel SGX PCK Platform CA",
+            CA::PROCESSOR => "Intel SGX PCK Processor CA",
+            _ => return Err(TdxError::Dcap("Unknown PCK Type".to_string())),
+        };
+        let (_, crl) = CertificateRevocationList::from_der(&self.pck_crl)
+            .map_err(|e| TdxError::X509(format!("PCK CRL: {:?}", e)))?;
+        let issuer = crl
+            .issuer()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .unwrap_or_default();
+        if issuer != expected_issuer {
+            return Err(TdxError::NotFound(format!(
+                "The PCK CRL is issued by {}, not {}",
+                issuer, expected_issuer
+            )));
+        }
+        Ok(())
+    }
+
+    /// Converts the collaterals into dcap-rs's `IntelCollateral`.
+    pub fn to_intel_collateral(&self) -> Result<IntelCollateral> {
+        let mut collaterals = IntelCollateral::new();
+
+        collaterals.set_tcbinfo_bytes(&self.tcb_info);
+        collaterals.set_qeidentity_bytes(&self.qe_identity);
+        collaterals.set_intel_root_ca_der(&self.root_ca);
+        collaterals.set_sgx_tcb_signing_der(&self.signing_ca);
+        collaterals.set_sgx_intel_root_ca_crl_der(&self.root_ca_crl);
+        match self.pck_type {
+            CA::PLATFORM => {
+                collaterals.set_sgx_platform_crl_der(&self.pck_crl);
+            }
+            CA::PROCESSOR => {
+                collaterals.set_sgx_processor_crl_der(&self.pck_crl);
+            }
+            _ => {
+                return Err(TdxError::Http("Unknown PCK Type".to_string()));
+            }
+        }
+        Ok(collaterals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::parse_quote_v4;
+
+    fn testdata_collaterals() -> Collaterals {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/testdata/collaterals");
+        Collaterals::read_from_dir(&dir).unwrap()
+    }
+
+    #[test]
+    fn checks_platform() {
+        let collaterals = testdata_collaterals();
+        assert!(matches!(collaterals.pck_type, CA::PLATFORM));
+        collaterals
+            .check_platform("90c06f000000", CA::PLATFORM)
+            .unwrap();
+        assert!(matches!(
+            collaterals.check_platform("00806f050000", CA::PLATFORM),
+            Err(TdxError::NotFound(_))
+        ));
+        assert!(matches!(
+            collaterals.check_platform("90c06f000000", CA::PROCESSOR),
+            Err(TdxError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn dir_source_checks_platform() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/testdata/collaterals");
+        let source = CollateralSource::Dir(dir);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(source.collaterals_for_fmspc("90C06F000000", CA::PLATFORM, 4))
+            .unwrap();
+        assert!(matches!(
+            runtime.block_on(source.collaterals_for_fmspc("00806f050000", CA::PLATFORM, 4)),
+            Err(TdxError::NotFound(_))
+        ));
+
+        let quote = parse_quote_v4(include_bytes!(
+            "../examples/testdata/tdx_v4_quote_90c06f.bin"
+        ))
+        .unwrap();
+        runtime
+            .block_on(source.collaterals_for_quote(&quote))
+            .unwrap();
+        let quote =
+            parse_quote_v4(include_bytes!("../examples/testdata/tdx_v4_quote.bin")).unwrap();
+        assert!(matches!(
+            runtime.block_on(source.collaterals_for_quote(&quote)),
+            Err(TdxError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn cbor_round_trip() {
+        let collaterals = testdata_collaterals();
+        let decoded = Collaterals::from_cbor(&collaterals.to_cbor().unwrap()).unwrap();
+        assert_eq!(decoded.tcb_info, collaterals.tcb_info);
+        assert_eq!(decoded.pck_crl, collaterals.pck_crl);
+        assert!(matches!(decoded.pck_type, CA::PLATFORM));
+    }
+}
@@ -44,6 +44,11 @@ impl Device {
         Ok(Device { options, provider })
     }
 
+    /// Name of the quote provider backing this device, eg `Tpm` or `ConfigFs`.
+    pub fn provider_name(&self) -> String {
+        format!("{:?}", self.provider.device_type)
+    }
+
     pub fn get_attestation_report(&self) -> Result<(QuoteV4, Option<Vec<u8>>)> {
         let (raw_report, var_data) = self.get_attestation_report_raw()?;
         Ok((QuoteV4::from_bytes(&raw_report), var_data))
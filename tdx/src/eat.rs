@@ -0,0 +1,564 @@
+//! Attestation results as Entity Attestation Tokens (EAT).
+//!
+//! The claims follow the EAT Attestation Results (EAR) layout: an overall `ear.status` and an
+//! AR4SI trustworthiness vector per attested component, here a single `tdx` submodule.
+//! Tokens are signed with ES256 and encoded either as a JWT or as a CWT (COSE_Sign1).
+//! In the CWT, the CWT claims (`iss`, `exp`, `iat`) and the EAT claims (`eat_nonce`,
+//! `eat_profile`, `submods`) use their registered integer keys, and the nonce is a byte string.
+//! The EAR claims keep their text names.
+
+use std::collections::BTreeMap;
+
+use crate::error::{Result, TdxError};
+use crate::report::VerificationSummary;
+use base64_url::base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use coset::cbor::value::Value;
+use coset::{iana, CoseSign1, CoseSign1Builder, HeaderBuilder};
+use coset::{RegisteredLabelWithPrivate, TaggedCborSerializable};
+use dcap_rs::types::{TcbStatus, VerifiedOutput};
+use p256::ecdsa::signature::{Signer, Verifier};
+use p256::ecdsa::{Signature, SigningKey, VerifyingKey};
+use p256::pkcs8::{DecodePrivateKey, EncodePublicKey, LineEnding};
+use serde::{Deserialize, Serialize};
+
+/// EAT profile of the EAT Attestation Results.
+pub const EAR_PROFILE: &str = "tag:github.com,2023:veraison/ear";
+
+/// Name of the submodule holding the appraisal of the TDX quote.
+pub const TDX_SUBMOD: &str = "tdx";
+
+// AR4SI trustworthiness claim values.
+/// The verifier makes no assertion about the claim.
+pub const AR4SI_NO_CLAIM: i8 = 0;
+/// The claim is affirmed by the verifier.
+pub const AR4SI_AFFIRMING: i8 = 2;
+/// The claim is not fully trustworthy, eg the platform has known vulnerabilities.
+pub const AR4SI_WARNING: i8 = 32;
+/// The claim is known to be untrustworthy, eg the platform has been revoked.
+pub const AR4SI_CONTRAINDICATED: i8 = 96;
+
+/// Trustworthiness tier, derived from an AR4SI claim value.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrustTier {
+    None,
+    Affirming,
+    Warning,
+    Contraindicated,
+}
+
+impl TrustTier {
+    /// Maps a claim value to its tier. Negative values are reserved for the verifier, and
+    /// mirror the tiers of the positive ones.
+    pub fn from_claim(value: i8) -> Self {
+        match value {
+            i8::MIN..=-97 | 96..=i8::MAX => TrustTier::Contraindicated,
+            -96..=-33 | 32..=95 => TrustTier::Warning,
+            -32..=-2 | 2..=31 => TrustTier::Affirming,
+            -1..=1 => TrustTier::None,
+        }
+    }
+}
+
+/// AR4SI trustworthiness vector. Claims the verifier has not appraised are omitted.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TrustworthinessVector {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance_identity: Option<i8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub configuration: Option<i8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executables: Option<i8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_system: Option<i8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hardware: Option<i8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime_opaque: Option<i8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage_opaque: Option<i8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sourced_data: Option<i8>,
+}
+
+impl TrustworthinessVector {
+    /// Appraises a verified quote.
+    ///
+    /// The quote signature chains up to the Intel root CA, so the instance identity is affirmed.
+    /// The hardware and configuration claims are derived from the TCB status. Executables are
+    /// left unappraised: the measurements are only compared against reference values by the
+    /// relying party.
+    pub fn from_tcb_status(status: &TcbStatus) -> Self {
+        let hardware = match status {
+            TcbStatus::OK => AR4SI_AFFIRMING,
+            TcbStatus::TcbSwHardeningNeeded
+            | TcbStatus::TcbConfigurationNeeded
+            | TcbStatus::TcbConfigurationAndSwHardeningNeeded
+            | TcbStatus::TcbOutOfDate
+            | TcbStatus::TcbOutOfDateConfigurationNeeded => AR4SI_WARNING,
+            TcbStatus::TcbRevoked | TcbStatus::TcbUnrecognized => AR4SI_CONTRAINDICATED,
+        };
+        let configuration = match status {
+            TcbStatus::TcbConfigurationNeeded
+            | TcbStatus::TcbConfigurationAndSwHardeningNeeded
+            | TcbStatus::TcbOutOfDateConfigurationNeeded => Some(AR4SI_WARNING),
+            _ => None,
+        };
+        TrustworthinessVector {
+            instance_identity: Some(AR4SI_AFFIRMING),
+            configuration,
+            hardware: Some(hardware),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the worst tier of the appraised claims, `TrustTier::None` if there is none.
+    pub fn status(&self) -> TrustTier {
+        [
+            self.instance_identity,
+            self.configuration,
+            self.executables,
+            self.file_system,
+            self.hardware,
+            self.runtime_opaque,
+            self.storage_opaque,
+            self.sourced_data,
+        ]
+        .into_iter()
+        .flatten()
+        .map(TrustTier::from_claim)
+        .max()
+        .unwrap_or(TrustTier::None)
+    }
+}
+
+/// Appraisal of one attested component.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Appraisal {
+    #[serde(rename = "ear.status")]
+    pub status: TrustTier,
+    #[serde(rename = "ear.trustworthiness-vector")]
+    pub trust_vector: TrustworthinessVector,
+    /// Measurements, TCB status and report data of the verified quote.
+    #[serde(rename = "tdx.verified-output")]
+    pub verified_output: VerificationSummary,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct VerifierId {
+    pub developer: String,
+    pub build: String,
+}
+
+impl Default for VerifierId {
+    fn default() -> Self {
+        VerifierId {
+            developer: "https://www.ata.network/".to_string(),
+            build: format!("tdx {}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+}
+
+/// Claims of a signed attestation result.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AttestationResult {
+    pub eat_profile: String,
+    pub iss: String,
+    /// Issuance time, in seconds since the Unix epoch.
+    pub iat: i64,
+    /// Expiry time, in seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    /// Base64url encoded nonce of the relying party.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eat_nonce: Option<String>,
+    #[serde(rename = "ear.verifier-id")]
+    pub verifier_id: VerifierId,
+    pub submods: BTreeMap<String, Appraisal>,
+}
+
+impl AttestationResult {
+    /// Builds the attestation result of a verified quote, issued now by `issuer`.
+    pub fn from_verified_output(output: &VerifiedOutput, issuer: &str) -> Self {
+        let trust_vector = TrustworthinessVector::from_tcb_status(&output.tcb_status);
+        let appraisal = Appraisal {
+            status: trust_vector.status(),
+            trust_vector,
+            verified_output: VerificationSummary::from_verified_output(output),
+        };
+        AttestationResult {
+            eat_profile: EAR_PROFILE.to_string(),
+            iss: issuer.to_string(),
+            iat: chrono::Utc::now().timestamp(),
+            exp: None,
+            eat_nonce: None,
+            verifier_id: VerifierId::default(),
+            submods: BTreeMap::from([(TDX_SUBMOD.to_string(), appraisal)]),
+        }
+    }
+
+    /// Sets the token to expire `validity` seconds after its issuance.
+    pub fn with_validity(mut self, validity: i64) -> Self {
+        self.exp = Some(self.iat + validity);
+        self
+    }
+
+    pub fn with_nonce(mut self, nonce: &[u8]) -> Self {
+        self.eat_nonce = Some(URL_SAFE_NO_PAD.encode(nonce));
+        self
+    }
+
+    /// Returns the appraisal of the TDX quote.
+    pub fn tdx(&self) -> Option<&Appraisal> {
+        self.submods.get(TDX_SUBMOD)
+    }
+
+    /// Signs the claims as a JWT.
+    pub fn to_jwt(&self, key: &EatSigningKey) -> Result<String> {
+        let mut header = serde_json::json!({ "alg": "ES256", "typ": "JWT" });
+        if let Some(kid) = &key.key_id {
+            header["kid"] = kid.clone().into();
+        }
+        let header = serde_json::to_vec(&header).map_err(|e| TdxError::Encoding(e.to_string()))?;
+        let claims = serde_json::to_vec(self).map_err(|e| TdxError::Encoding(e.to_string()))?;
+
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header),
+            URL_SAFE_NO_PAD.encode(claims)
+        );
+        let signature: Signature = key.key.sign(signing_input.as_bytes());
+        Ok(format!(
+            "{}.{}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        ))
+    }
+
+    /// Verifies a JWT signed with `to_jwt` and returns its claims.
+    /// The expiry is checked against the current time.
+    pub fn from_jwt(token: &str, key: &VerifyingKey) -> Result<Self> {
+        let (signing_input, signature) = token
+            .rsplit_once('.')
+            .ok_or_else(|| TdxError::Encoding("Malformed JWT".to_string()))?;
+        let (header, claims) = signing_input
+            .split_once('.')
+            .ok_or_else(|| TdxError::Encoding("Malformed JWT".to_string()))?;
+
+        let header: serde_json::Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header)?)
+            .map_err(|e| TdxError::Encoding(e.to_string()))?;
+        if header["alg"] != "ES256" {
+            return Err(TdxError::Signature(format!(
+                "Unsupported JWT algorithm: {}",
+                header["alg"]
+            )));
+        }
+        let signature = Signature::from_slice(&URL_SAFE_NO_PAD.decode(signature)?)
+            .map_err(|e| TdxError::Signature(e.to_string()))?;
+        key.verify(signing_input.as_bytes(), &signature)
+            .map_err(|e| TdxError::Signature(e.to_string()))?;
+
+        let result: AttestationResult = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims)?)
+            .map_err(|e| TdxError::Encoding(e.to_string()))?;
+        result.check_expiry()?;
+        Ok(result)
+    }
+
+    /// Signs the claims as a tagged COSE_Sign1 CWT.
+    pub fn to_cwt(&self, key: &EatSigningKey) -> Result<Vec<u8>> {
+        let Value::Map(entries) =
+            Value::serialized(self).map_err(|e| TdxError::Encoding(e.to_string()))?
+        else {
+            return Err(TdxError::Encoding("Claims are not a map".to_string()));
+        };
+
+        let entries = entries
+            .into_iter()
+            .map(|(name, value)| match name {
+                Value::Text(name) => to_cwt_claim(name, value),
+                name => Ok((name, value)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut payload = Vec::new();
+        coset::cbor::ser::into_writer(&Value::Map(entries), &mut payload)
+            .map_err(|e| TdxError::Encoding(e.to_string()))?;
+
+        let mut protected = HeaderBuilder::new().algorithm(iana::Algorithm::ES256);
+        if let Some(kid) = &key.key_id {
+            protected = protected.key_id(kid.as_bytes().to_vec());
+        }
+        CoseSign1Builder::new()
+            .protected(protected.build())
+            .payload(payload)
+            .create_signature(&[], |data| {
+                let signature: Signature = key.key.sign(data);
+                signature.to_bytes().to_vec()
+            })
+            .build()
+            .to_tagged_vec()
+            .map_err(|e| TdxError::Encoding(e.to_string()))
+    }
+
+    /// Verifies a CWT signed with `to_cwt` and returns its claims.
+    /// The expiry is checked against the current time.
+    pub fn from_cwt(token: &[u8], key: &VerifyingKey) -> Result<Self> {
+        let sign1 =
+            CoseSign1::from_tagged_slice(token).map_err(|e| TdxError::Encoding(e.to_string()))?;
+        if sign1.protected.header.alg
+            != Some(RegisteredLabelWithPrivate::Assigned(iana::Algorithm::ES256))
+        {
+            return Err(TdxError::Signature("Unsupported CWT algorithm".to_string()));
+        }
+        sign1.verify_signature(&[], |signature, data| {
+            let signature =
+                Signature::from_slice(signature).map_err(|e| TdxError::Signature(e.to_string()))?;
+            key.verify(data, &signature)
+                .map_err(|e| TdxError::Signature(e.to_string()))
+        })?;
+
+        let payload = sign1
+            .payload
+            .ok_or_else(|| TdxError::Encoding("CWT has no payload".to_string()))?;
+        let claims: Value = coset::cbor::de::from_reader(payload.as_slice())
+            .map_err(|e| TdxError::Encoding(e.to_string()))?;
+        let Value::Map(claims) = claims else {
+            return Err(TdxError::Encoding("Claims are not a map".to_string()));
+        };
+
+        // Rebuild the JSON-like claims map, with text names for the registered claims.
+        let entries = claims
+            .into_iter()
+            .map(|(name, value)| from_cwt_claim(name, value))
+            .collect::<Result<Vec<_>>>()?;
+
+        let result: AttestationResult = Value::Map(entries)
+            .deserialized()
+            .map_err(|e| TdxError::Encoding(e.to_string()))?;
+        result.check_expiry()?;
+        Ok(result)
+    }
+
+    fn check_expiry(&self) -> Result<()> {
+        match self.exp {
+            Some(exp) if exp < chrono::Utc::now().timestamp() => Err(TdxError::Signature(
+                "Attestation result has expired".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Claims carried with their registered integer keys in a CWT: the claims of RFC 8392 and of
+/// the EAT specification (RFC 9711).
+const CWT_CLAIM_KEYS: [(&str, i64); 6] = [
+    ("iss", 1),
+    ("exp", 4),
+    ("iat", 6),
+    ("eat_nonce", 10),
+    ("eat_profile", 265),
+    ("submods", 266),
+];
+
+const EAT_NONCE: &str = "eat_nonce";
+
+/// Maps a claim to its CWT encoding: its registered integer key if any, and the nonce as a
+/// byte string instead of base64url.
+fn to_cwt_claim(name: String, value: Value) -> Result<(Value, Value)> {
+    let value = match (name.as_str(), value) {
+        (EAT_NONCE, Value::Text(nonce)) => Value::Bytes(URL_SAFE_NO_PAD.decode(nonce)?),
+        (_, value) => value,
+    };
+    let name = match CWT_CLAIM_KEYS.iter().find(|(n, _)| *n == name) {
+        Some((_, key)) => Value::from(*key),
+        None => Value::Text(name),
+    };
+    Ok((name, value))
+}
+
+/// Reverses `to_cwt_claim`.
+fn from_cwt_claim(name: Value, value: Value) -> Result<(Value, Value)> {
+    let name = match name {
+        Value::Integer(key) => {
+            let key = i64::try_from(key).map_err(|e| TdxError::Encoding(e.to_string()))?;
+            CWT_CLAIM_KEYS
+                .iter()
+                .find(|(_, k)| *k == key)
+                .map(|(name, _)| name.to_string())
+                .ok_or_else(|| TdxError::Encoding(format!("Unknown CWT claim: {}", key)))?
+        }
+        Value::Text(name) => name,
+        _ => return Err(TdxError::Encoding("Invalid CWT claim name".to_string())),
+    };
+    let value = match (name.as_str(), value) {
+        (EAT_NONCE, Value::Bytes(nonce)) => Value::Text(URL_SAFE_NO_PAD.encode(nonce)),
+        (_, value) => value,
+    };
+    Ok((Value::Text(name), value))
+}
+
+/// ES256 key used by the verifier to sign attestation results.
+#[derive(Clone)]
+pub struct EatSigningKey {
+    key: SigningKey,
+    /// Key identifier put in the `kid` header of the tokens.
+    key_id: Option<String>,
+}
+
+impl EatSigningKey {
+    pub fn new(key: SigningKey) -> Self {
+        EatSigningKey { key, key_id: None }
+    }
+
+    /// Generates a random key.
+    pub fn generate() -> Self {
+        Self::new(SigningKey::random(&mut rand::rngs::OsRng))
+    }
+
+    /// Reads a P-256 private key in PKCS#8 PEM.
+    pub fn from_pkcs8_pem(pem: &str) -> Result<Self> {
+        let key =
+            SigningKey::from_pkcs8_pem(pem).map_err(|e| TdxError::Signature(e.to_string()))?;
+        Ok(Self::new(key))
+    }
+
+    pub fn with_key_id(mut self, key_id: &str) -> Self {
+        self.key_id = Some(key_id.to_string());
+        self
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        *self.key.verifying_key()
+    }
+
+    /// Returns the public key in SPKI PEM, to be distributed to the relying parties.
+    pub fn public_key_pem(&self) -> Result<String> {
+        self.verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .map_err(|e| TdxError::Signature(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collateral::Collaterals;
+    use crate::utils::parse_quote_v4;
+    use crate::Tdx;
+    use std::path::Path;
+
+    const QUOTE: &[u8] = include_bytes!("../examples/testdata/tdx_v4_quote_90c06f.bin");
+    /// Within the validity of the TCB Info and QE Identity of the test collaterals.
+    const VERIFICATION_TIME: u64 = 1749600000;
+
+    fn verified_output() -> VerifiedOutput {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/testdata/collaterals");
+        let collaterals = Collaterals::read_from_dir(&dir).unwrap();
+        Tdx::new()
+            .verify_attestation_report_with_collaterals(
+                &parse_quote_v4(QUOTE).unwrap(),
+                &collaterals,
+                VERIFICATION_TIME,
+            )
+            .unwrap()
+    }
+
+    fn attestation_result() -> AttestationResult {
+        AttestationResult::from_verified_output(&verified_output(), "https://verifier.example")
+            .with_nonce(b"nonce")
+            .with_validity(3600)
+    }
+
+    fn assert_same_claims(decoded: &AttestationResult, result: &AttestationResult) {
+        assert_eq!(
+            serde_json::to_value(decoded).unwrap(),
+            serde_json::to_value(result).unwrap()
+        );
+    }
+
+    #[test]
+    fn maps_ar4si_claims_to_tiers() {
+        let cases = [
+            (i8::MIN, TrustTier::Contraindicated),
+            (-97, TrustTier::Contraindicated),
+            (-96, TrustTier::Warning),
+            (-33, TrustTier::Warning),
+            (-32, TrustTier::Affirming),
+            (-2, TrustTier::Affirming),
+            (-1, TrustTier::None),
+            (AR4SI_NO_CLAIM, TrustTier::None),
+            (1, TrustTier::None),
+            (AR4SI_AFFIRMING, TrustTier::Affirming),
+            (31, TrustTier::Affirming),
+            (AR4SI_WARNING, TrustTier::Warning),
+            (95, TrustTier::Warning),
+            (AR4SI_CONTRAINDICATED, TrustTier::Contraindicated),
+            (i8::MAX, TrustTier::Contraindicated),
+        ];
+        for (value, tier) in cases {
+            assert_eq!(TrustTier::from_claim(value), tier, "claim {}", value);
+        }
+    }
+
+    #[test]
+    fn jwt_round_trip() {
+        let key = EatSigningKey::generate().with_key_id("verifier-1");
+        let result = attestation_result();
+        let token = result.to_jwt(&key).unwrap();
+        let decoded = AttestationResult::from_jwt(&token, &key.verifying_key()).unwrap();
+        assert_same_claims(&decoded, &result);
+        assert_eq!(decoded.tdx().unwrap().status, result.tdx().unwrap().status);
+
+        let other_key = EatSigningKey::generate();
+        assert!(AttestationResult::from_jwt(&token, &other_key.verifying_key()).is_err());
+    }
+
+    #[test]
+    fn cwt_round_trip() {
+        let key = EatSigningKey::generate().with_key_id("verifier-1");
+        let result = attestation_result();
+        let token = result.to_cwt(&key).unwrap();
+        let decoded = AttestationResult::from_cwt(&token, &key.verifying_key()).unwrap();
+        assert_same_claims(&decoded, &result);
+
+        let other_key = EatSigningKey::generate();
+        assert!(AttestationResult::from_cwt(&token, &other_key.verifying_key()).is_err());
+    }
+
+    #[test]
+    fn cwt_uses_registered_claim_keys() {
+        let key = EatSigningKey::generate();
+        let token = attestation_result().to_cwt(&key).unwrap();
+        let sign1 = CoseSign1::from_tagged_slice(&token).unwrap();
+        let claims: Value =
+            coset::cbor::de::from_reader(sign1.payload.unwrap().as_slice()).unwrap();
+        let Value::Map(claims) = claims else {
+            panic!("Claims are not a map");
+        };
+        let claim = |key: i64| {
+            claims
+                .iter()
+                .find(|(name, _)| *name == Value::from(key))
+                .map(|(_, value)| value)
+        };
+
+        assert_eq!(claim(10), Some(&Value::Bytes(b"nonce".to_vec())));
+        assert_eq!(claim(265), Some(&Value::Text(EAR_PROFILE.to_string())));
+        assert!(matches!(claim(266), Some(Value::Map(_))));
+        assert!(claim(1).is_some() && claim(4).is_some() && claim(6).is_some());
+        assert!(claims
+            .iter()
+            .all(|(name, _)| *name != Value::Text("submods".to_string())));
+    }
+
+    #[test]
+    fn rejects_expired_tokens() {
+        let key = EatSigningKey::generate();
+        let result = AttestationResult::from_verified_output(&verified_output(), "verifier")
+            .with_validity(-1);
+        let token = result.to_jwt(&key).unwrap();
+        assert!(AttestationResult::from_jwt(&token, &key.verifying_key()).is_err());
+        let token = result.to_cwt(&key).unwrap();
+        assert!(AttestationResult::from_cwt(&token, &key.verifying_key()).is_err());
+    }
+}
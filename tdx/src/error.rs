@@ -10,9 +10,12 @@ pub enum TdxError {
     ConfigOptions(String),
     Cpu(String),
     Dcap(String),
+    Encoding(String),
     Firmware(String),
     Http(String),
     IO(String),
+    NotFound(String),
+    Signature(String),
     SSL(String),
     Tpm(String),
     X509(String),
@@ -26,9 +29,12 @@ impl Display for TdxError {
             TdxError::ConfigOptions(err) => write!(f, "ConfigOptions: {}", err),
             TdxError::Cpu(err) => write!(f, "Cpu: {}", err),
             TdxError::Dcap(err) => write!(f, "Dcap: {}", err),
+            TdxError::Encoding(err) => write!(f, "Encoding: {}", err),
             TdxError::Firmware(err) => write!(f, "Firmware: {}", err),
             TdxError::Http(err) => write!(f, "Http: {}", err),
             TdxError::IO(err) => write!(f, "IO: {}", err),
+            TdxError::NotFound(err) => write!(f, "NotFound: {}", err),
+            TdxError::Signature(err) => write!(f, "Signature: {}", err),
             TdxError::SSL(err) => write!(f, "SSL: {}", err),
             TdxError::Tpm(err) => write!(f, "Tpm: {}", err),
             TdxError::X509(err) => write!(f, "X509: {}", err),
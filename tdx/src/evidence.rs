@@ -0,0 +1,376 @@
+use crate::error::{Result, TdxError};
+use crate::report::platform_name;
+use crate::utils::{report_data_from_nonce, var_data_matches_report_data, QUOTE_HEADER_LEN};
+use dcap_rs::types::quotes::QuoteHeader;
+use serde::{Deserialize, Serialize};
+
+/// Version of the evidence envelope format.
+pub const EVIDENCE_VERSION: u32 = 1;
+
+/// Location of the TDX event log (CCEL ACPI table) inside the TD.
+pub const CCEL_EVENT_LOG_PATH: &str = "/sys/firmware/acpi/tables/data/CCEL";
+
+/// Offset of the report data in an SGX enclave report and in a TD report.
+const SGX_REPORT_DATA_OFFSET: usize = 320;
+const TD_REPORT_DATA_OFFSET: usize = 520;
+const REPORT_DATA_LEN: usize = 64;
+/// V5 quotes prefix the body with its type (u16) and size (u32).
+const V5_BODY_DESCRIPTOR_LEN: usize = 6;
+
+/// Self-describing attestation evidence, to be exchanged between services as a single artifact.
+///
+/// The envelope has a CBOR and a JSON encoding. Binary fields are CBOR byte strings,
+/// and standard base64 strings in JSON.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Evidence {
+    /// Version of the envelope format, see `EVIDENCE_VERSION`.
+    pub version: u32,
+    /// Raw quote.
+    #[serde(with = "bytes")]
+    pub quote: Vec<u8>,
+    /// Version found in the quote header.
+    pub quote_version: u16,
+    /// "SGX" or "TDX", from the quote header.
+    pub platform: String,
+    /// Quote provider that produced the quote, eg configfs or TPM, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Var data provided by Azure Confidential VMs alongside the quote.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "bytes::option"
+    )]
+    pub var_data: Option<Vec<u8>>,
+    /// TDX event log (CCEL).
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "bytes::option"
+    )]
+    pub event_log: Option<Vec<u8>>,
+    /// Nonce the report data was derived from, see `utils::report_data_from_nonce`.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "bytes::option"
+    )]
+    pub nonce: Option<Vec<u8>>,
+    /// Time the evidence was produced, in seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl Evidence {
+    /// Wraps a raw quote, reading its version and platform from the quote header.
+    pub fn new(quote: Vec<u8>, var_data: Option<Vec<u8>>) -> Result<Self> {
+        let header = parse_header(&quote)?;
+        Ok(Evidence {
+            version: EVIDENCE_VERSION,
+            quote_version: header.version,
+            platform: platform_name(header.tee_type),
+            quote,
+            provider: None,
+            var_data,
+            event_log: None,
+            nonce: None,
+            timestamp: chrono::Utc::now().timestamp() as u64,
+        })
+    }
+
+    pub fn with_provider(mut self, provider: String) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    pub fn with_event_log(mut self, event_log: Vec<u8>) -> Self {
+        self.event_log = Some(event_log);
+        self
+    }
+
+    pub fn with_nonce(mut self, nonce: Vec<u8>) -> Self {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    pub fn to_cbor(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        ciborium::into_writer(self, &mut out).map_err(|e| TdxError::Encoding(e.to_string()))?;
+        Ok(out)
+    }
+
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self> {
+        let evidence: Evidence =
+            ciborium::from_reader(bytes).map_err(|e| TdxError::Encoding(e.to_string()))?;
+        evidence.validate()?;
+        Ok(evidence)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(|e| TdxError::Encoding(e.to_string()))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let evidence: Evidence =
+            serde_json::from_str(json).map_err(|e| TdxError::Encoding(e.to_string()))?;
+        evidence.validate()?;
+        Ok(evidence)
+    }
+
+    /// Checks that the envelope is supported and consistent with the quote it carries,
+    /// and that the report data of the quote is bound to the nonce or the var data, if any.
+    /// Evidence carrying both is rejected, as only one of them can be bound.
+    fn validate(&self) -> Result<()> {
+        if self.version != EVIDENCE_VERSION {
+            return Err(TdxError::Encoding(format!(
+                "Unsupported evidence version: {}",
+                self.version
+            )));
+        }
+        let header = parse_header(&self.quote)?;
+        if header.version != self.quote_version {
+            return Err(TdxError::Encoding(format!(
+                "Quote version mismatch: envelope says {}, quote header says {}",
+                self.quote_version, header.version
+            )));
+        }
+        if platform_name(header.tee_type) != self.platform {
+            return Err(TdxError::Encoding(format!(
+                "Platform mismatch: envelope says {}, quote header says {}",
+                self.platform,
+                platform_name(header.tee_type)
+            )));
+        }
+        match (&self.nonce, &self.var_data) {
+            // On Azure Confidential VMs, the report data binds the var data, so it cannot
+            // also be derived from a nonce, and the nonce would not prevent replays.
+            (Some(_), Some(_)) => Err(TdxError::Encoding(
+                "A nonce cannot be bound to the report data of a quote with var data".to_string(),
+            )),
+            (Some(nonce), None) => {
+                if report_data(&self.quote, &header)? != report_data_from_nonce(nonce) {
+                    return Err(TdxError::Encoding(
+                        "The report data of the quote is not bound to the nonce".to_string(),
+                    ));
+                }
+                Ok(())
+            }
+            (None, Some(var_data)) => {
+                let report_data = report_data(&self.quote, &header)?;
+                if !var_data_matches_report_data(var_data, &report_data) {
+                    return Err(TdxError::Encoding(
+                        "The report data of the quote is not bound to the var data".to_string(),
+                    ));
+                }
+                Ok(())
+            }
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Reads the report data of the SGX enclave report or TD report of a quote.
+fn report_data(quote: &[u8], header: &QuoteHeader) -> Result<[u8; REPORT_DATA_LEN]> {
+    let mut offset = QUOTE_HEADER_LEN;
+    if header.version >= 5 {
+        offset += V5_BODY_DESCRIPTOR_LEN;
+    }
+    offset += match header.tee_type {
+        0 => SGX_REPORT_DATA_OFFSET,
+        _ => TD_REPORT_DATA_OFFSET,
+    };
+    quote
+        .get(offset..offset + REPORT_DATA_LEN)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| TdxError::Encoding("Quote is too short".to_string()))
+}
+
+fn parse_header(quote: &[u8]) -> Result<QuoteHeader> {
+    if quote.len() < QUOTE_HEADER_LEN {
+        return Err(TdxError::Encoding("Quote is too short".to_string()));
+    }
+    Ok(QuoteHeader::from_bytes(&quote[..QUOTE_HEADER_LEN]))
+}
+
+/// Serializes bytes as a byte string in binary formats, and as base64 in human readable formats.
+mod bytes {
+    use base64_url::base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::de::{Error, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            s.serialize_str(&STANDARD.encode(bytes))
+        } else {
+            s.serialize_bytes(bytes)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        if d.is_human_readable() {
+            d.deserialize_str(BytesVisitor)
+        } else {
+            d.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("bytes or a base64 string")
+        }
+
+        fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+            STANDARD.decode(v).map_err(E::custom)
+        }
+
+        fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut out = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(b) = seq.next_element::<u8>()? {
+                out.push(b);
+            }
+            Ok(out)
+        }
+    }
+
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        #[derive(Serialize)]
+        struct BytesRef<'a>(#[serde(with = "super")] &'a [u8]);
+
+        #[derive(Deserialize)]
+        struct Bytes(#[serde(with = "super")] Vec<u8>);
+
+        pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+            match bytes {
+                Some(b) => s.serialize_some(&BytesRef(b)),
+                None => s.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
+            Ok(Option::<Bytes>::deserialize(d)?.map(|b| b.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    const TDX_QUOTE: &[u8] = include_bytes!("../examples/testdata/tdx_v4_quote.bin");
+    const SGX_QUOTE: &[u8] = include_bytes!("../examples/testdata/sgx_v3_quote.bin");
+
+    /// Returns `quote` with its report data set to SHA-512(`nonce`).
+    fn with_nonce_report_data(quote: &[u8], offset: usize, nonce: &[u8]) -> Vec<u8> {
+        let mut quote = quote.to_vec();
+        let offset = QUOTE_HEADER_LEN + offset;
+        quote[offset..offset + REPORT_DATA_LEN].copy_from_slice(&report_data_from_nonce(nonce));
+        quote
+    }
+
+    fn tdx_evidence(nonce: &[u8]) -> Evidence {
+        let quote = with_nonce_report_data(TDX_QUOTE, TD_REPORT_DATA_OFFSET, nonce);
+        Evidence::new(quote, None)
+            .unwrap()
+            .with_provider("configfs".to_string())
+            .with_event_log(vec![1, 2, 3])
+            .with_nonce(nonce.to_vec())
+    }
+
+    #[test]
+    fn cbor_round_trip() {
+        let evidence = tdx_evidence(b"nonce");
+        assert_eq!(evidence.quote_version, 4);
+        assert_eq!(evidence.platform, "TDX");
+        let decoded = Evidence::from_cbor(&evidence.to_cbor().unwrap()).unwrap();
+        assert_eq!(decoded, evidence);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let evidence = tdx_evidence(b"nonce");
+        let decoded = Evidence::from_json(&evidence.to_json().unwrap()).unwrap();
+        assert_eq!(decoded, evidence);
+    }
+
+    #[test]
+    fn sgx_round_trip() {
+        let quote = with_nonce_report_data(SGX_QUOTE, SGX_REPORT_DATA_OFFSET, b"nonce");
+        let evidence = Evidence::new(quote, None)
+            .unwrap()
+            .with_nonce(b"nonce".to_vec());
+        assert_eq!(evidence.quote_version, 3);
+        assert_eq!(evidence.platform, "SGX");
+        let decoded = Evidence::from_cbor(&evidence.to_cbor().unwrap()).unwrap();
+        assert_eq!(decoded, evidence);
+    }
+
+    #[test]
+    fn rejects_nonce_not_bound_to_report_data() {
+        let evidence = tdx_evidence(b"nonce").with_nonce(b"other nonce".to_vec());
+        assert!(Evidence::from_cbor(&evidence.to_cbor().unwrap()).is_err());
+        assert!(Evidence::from_json(&evidence.to_json().unwrap()).is_err());
+    }
+
+    #[test]
+    fn checks_var_data_binding() {
+        let mut evidence = tdx_evidence(b"nonce");
+        evidence.nonce = None;
+        evidence.var_data = Some(b"{}".to_vec());
+        assert!(Evidence::from_cbor(&evidence.to_cbor().unwrap()).is_err());
+    }
+
+    #[test]
+    fn accepts_var_data_bound_to_report_data() {
+        let var_data = br#"{"user-data":"00"}"#.to_vec();
+        let mut quote = TDX_QUOTE.to_vec();
+        let offset = QUOTE_HEADER_LEN + TD_REPORT_DATA_OFFSET;
+        quote[offset..offset + 32].copy_from_slice(&Sha256::digest(&var_data));
+        let evidence = Evidence::new(quote, Some(var_data)).unwrap();
+        let decoded = Evidence::from_cbor(&evidence.to_cbor().unwrap()).unwrap();
+        assert_eq!(decoded, evidence);
+
+        // The var data binds the report data, so a wrong nonce must not be accepted
+        // alongside it, eg to replay an old quote.
+        let evidence = evidence.with_nonce(b"wrong nonce".to_vec());
+        assert!(Evidence::from_cbor(&evidence.to_cbor().unwrap()).is_err());
+        assert!(Evidence::from_json(&evidence.to_json().unwrap()).is_err());
+    }
+
+    #[test]
+    fn rejects_inconsistent_envelopes() {
+        let mut evidence = tdx_evidence(b"nonce");
+        evidence.version = EVIDENCE_VERSION + 1;
+        assert!(Evidence::from_cbor(&evidence.to_cbor().unwrap()).is_err());
+
+        let mut evidence = tdx_evidence(b"nonce");
+        evidence.quote_version = 3;
+        assert!(Evidence::from_cbor(&evidence.to_cbor().unwrap()).is_err());
+
+        let mut evidence = tdx_evidence(b"nonce");
+        evidence.platform = "SGX".to_string();
+        assert!(Evidence::from_json(&evidence.to_json().unwrap()).is_err());
+
+        let mut evidence = tdx_evidence(b"nonce");
+        evidence
+            .quote
+            .truncate(QUOTE_HEADER_LEN + TD_REPORT_DATA_OFFSET);
+        assert!(Evidence::from_cbor(&evidence.to_cbor().unwrap()).is_err());
+
+        assert!(Evidence::new(TDX_QUOTE[..QUOTE_HEADER_LEN - 1].to_vec(), None).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+//! Retrieves collaterals from the Intel Provisioning Certification Service (PCS), eg to
+//! upsert them to the on-chain PCCS.
+use std::io::Read;
+
+use crate::error::{Result, TdxError};
+use crate::pccs::enclave_id::{ENCLAVE_ID_QE, ENCLAVE_ID_QVE, ENCLAVE_ID_TDQE};
+use crate::pccs::pcs::IPCSDao::CA;
+
+pub const INTEL_PCS_URL: &str = "https://api.trustedservices.intel.com";
+pub const INTEL_ROOT_CA_CRL_URL: &str =
+    "https://certificates.trustedservices.intel.com/IntelSGXRootCA.der";
+
+/// Retrieves the signed TCB Info, ie `{"tcbInfo": {...}, "signature": "..."}`.
+/// `tcb_type` is 0 for SGX and 1 for TDX, `version` is the TCB Info version.
+pub fn get_tcb_info(tcb_type: u8, fmspc: &str, version: u32) -> Result<Vec<u8>> {
+    let path = match (tcb_type, version) {
+        (0, 2) => "sgx/certification/v3",
+        (0, 3) => "sgx/certification/v4",
+        (1, 3) => "tdx/certification/v4",
+        _ => {
+            return Err(TdxError::ConfigOptions(format!(
+                "Unsupported TCB Info: type {}, version {}",
+                tcb_type, version
+            )))
+        }
+    };
+    get(&format!("{}/{}/tcb?fmspc={}", INTEL_PCS_URL, path, fmspc))
+}
+
+/// Retrieves the signed identity of the `enclave_id` enclave, ie
+/// `{"enclaveIdentity": {...}, "signature": "..."}`. `version` is the quote version.
+pub fn get_enclave_identity(enclave_id: u8, version: u32) -> Result<Vec<u8>> {
+    let path = match (enclave_id, version) {
+        (ENCLAVE_ID_QE, 3) => "sgx/certification/v3/qe/identity",
+        (ENCLAVE_ID_QE, 4) => "sgx/certification/v4/qe/identity",
+        (ENCLAVE_ID_QVE, 3) => "sgx/certification/v3/qve/identity",
+        (ENCLAVE_ID_QVE, 4) => "sgx/certification/v4/qve/identity",
+        (ENCLAVE_ID_TDQE, 4) => "tdx/certification/v4/qe/identity",
+        _ => {
+            return Err(TdxError::ConfigOptions(format!(
+                "Unsupported enclave identity: ID {}, version {}",
+                enclave_id, version
+            )))
+        }
+    };
+    get(&format!("{}/{}", INTEL_PCS_URL, path))
+}
+
+/// Retrieves the CRL of the PCK Platform or Processor CA, in DER.
+pub fn get_pck_crl(ca: CA) -> Result<Vec<u8>> {
+    let ca = match ca {
+        CA::PLATFORM => "platform",
+        CA::PROCESSOR => "processor",
+        _ => return Err(TdxError::ConfigOptions("Not a PCK CA".to_string())),
+    };
+    get(&format!(
+        "{}/sgx/certification/v4/pckcrl?ca={}&encoding=der",
+        INTEL_PCS_URL, ca
+    ))
+}
+
+/// Retrieves the CRL of the Intel SGX Root CA, in DER.
+pub fn get_root_ca_crl() -> Result<Vec<u8>> {
+    get(INTEL_ROOT_CA_CRL_URL)
+}
+
+fn get(url: &str) -> Result<Vec<u8>> {
+    let response = ureq::get(url).call()?;
+    let mut body = Vec::new();
+    response.into_reader().read_to_end(&mut body)?;
+    Ok(body)
+}
@@ -0,0 +1,563 @@
+//! Checks the collateral hashes committed to the journal of the DCAP zkVM programs against
+//! the collaterals currently stored in the on-chain PCCS.
+//!
+//! A proof only shows that the quote was verified against some collaterals. Consumers that
+//! need the proof to be made against the current collaterals, eg after a TCB recovery, must
+//! compare the committed hashes with the on-chain ones.
+use dcap_codec::batch::DcapBatchJournal;
+use dcap_codec::{DcapProgramJournal, HASH_LEN};
+use dcap_rs::types::collaterals::IntelCollateral;
+use dcap_rs::types::enclave_identity::EnclaveIdentityV2;
+use dcap_rs::types::tcbinfo::{TcbInfoV2, TcbInfoV3};
+use dcap_rs::types::VerifiedOutput;
+use dcap_rs::utils::cert::{hash_crl_keccak256, hash_x509_keccak256};
+use dcap_rs::utils::enclave_identity::get_enclave_identityv2_content_hash;
+use dcap_rs::utils::tcbinfo::{get_tcbinfov2_content_hash, get_tcbinfov3_content_hash};
+use serde::Serialize;
+use x509_parser::prelude::{CertificateRevocationList, FromDer, X509Certificate};
+
+use crate::error::{Result, TdxError};
+use crate::pccs::enclave_id::{
+    get_enclave_identity_by_id_with_config, ENCLAVE_ID_QE, ENCLAVE_ID_TDQE,
+};
+use crate::pccs::fmspc_tcb::get_tcb_info_with_config;
+use crate::pccs::pcs::{get_certificate_by_id_with_config, IPCSDao::CA};
+use crate::pccs::PccsConfig;
+
+const SGX_TEE_TYPE: u32 = 0x00000000;
+const TDX_TEE_TYPE: u32 = 0x00000081;
+
+/// The collateral hashes committed to a journal, in the order of `DcapProgramOutput`.
+#[derive(Clone, Debug)]
+pub struct CommittedCollaterals {
+    pub tcbinfo_content_hash: [u8; HASH_LEN],
+    pub qeidentity_content_hash: [u8; HASH_LEN],
+    pub sgx_intel_root_ca_cert_hash: [u8; HASH_LEN],
+    pub sgx_tcb_signing_cert_hash: [u8; HASH_LEN],
+    pub sgx_intel_root_ca_crl_hash: [u8; HASH_LEN],
+    pub sgx_pck_crl_hash: [u8; HASH_LEN],
+}
+
+/// A committed hash and the hash of the corresponding on-chain collateral, hex encoded.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashCheck {
+    pub committed: String,
+    /// `None` when the collateral is not stored in the on-chain PCCS.
+    pub on_chain: Option<String>,
+    pub matches: bool,
+}
+
+impl HashCheck {
+    fn new(committed: &[u8; HASH_LEN], on_chain: Option<[u8; HASH_LEN]>) -> Self {
+        HashCheck {
+            committed: hex::encode(committed),
+            on_chain: on_chain.map(hex::encode),
+            matches: on_chain.as_ref() == Some(committed),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollateralCheckReport {
+    pub fmspc: String,
+    pub quote_version: u16,
+    pub tcb_info: HashCheck,
+    pub qe_identity: HashCheck,
+    pub root_ca: HashCheck,
+    pub signing_ca: HashCheck,
+    pub root_ca_crl: HashCheck,
+    /// Checked against the PCK Platform CA CRL and the PCK Processor CA CRL, `onChain` is the
+    /// one that matches, or the Platform CA CRL when neither does.
+    pub pck_crl: HashCheck,
+}
+
+impl CollateralCheckReport {
+    /// Returns `true` if the proof was made against the collaterals currently on-chain.
+    pub fn is_current(&self) -> bool {
+        [
+            &self.tcb_info,
+            &self.qe_identity,
+            &self.root_ca,
+            &self.signing_ca,
+            &self.root_ca_crl,
+            &self.pck_crl,
+        ]
+        .iter()
+        .all(|check| check.matches)
+    }
+}
+
+/// Decodes the journal of the DCAP program (`DcapProgramJournal`) and checks its collateral
+/// hashes against the on-chain PCCS.
+pub async fn check_journal_collaterals(
+    config: &PccsConfig,
+    journal: &[u8],
+) -> Result<CollateralCheckReport> {
+    let journal =
+        DcapProgramJournal::decode(journal).map_err(|e| TdxError::Encoding(e.to_string()))?;
+    let output = match journal {
+        DcapProgramJournal::Success(output) => output,
+        DcapProgramJournal::Failure(code) => {
+            return Err(TdxError::Dcap(format!(
+                "The journal holds a failure: {}",
+                code
+            )))
+        }
+    };
+    let committed = CommittedCollaterals {
+        tcbinfo_content_hash: output.tcbinfo_content_hash,
+        qeidentity_content_hash: output.qeidentity_content_hash,
+        sgx_intel_root_ca_cert_hash: output.sgx_intel_root_ca_cert_hash,
+        sgx_tcb_signing_cert_hash: output.sgx_tcb_signing_cert_hash,
+        sgx_intel_root_ca_crl_hash: output.sgx_intel_root_ca_crl_hash,
+        sgx_pck_crl_hash: output.sgx_pck_crl_hash,
+    };
+    check_collaterals(
+        config,
+        &parse_verified_output(&output.verified_output)?,
+        &committed,
+    )
+    .await
+}
+
+/// Decodes the journal of the batch program (`DcapBatchJournal`) and checks its collateral
+/// hashes against the on-chain PCCS. The quotes of a batch share their collaterals, the
+/// FMSPC is read from the first verified output.
+pub async fn check_batch_journal_collaterals(
+    config: &PccsConfig,
+    journal: &[u8],
+) -> Result<CollateralCheckReport> {
+    let journal =
+        DcapBatchJournal::decode(journal).map_err(|e| TdxError::Encoding(e.to_string()))?;
+    let output = match journal {
+        DcapBatchJournal::Success(output) => output,
+        DcapBatchJournal::Failure(code) => {
+            return Err(TdxError::Dcap(format!(
+                "The journal holds a failure: {}",
+                code
+            )))
+        }
+    };
+    let verified_output = output
+        .verified_outputs
+        .first()
+        .ok_or_else(|| TdxError::Encoding("The batch is empty".to_string()))?;
+    let committed = CommittedCollaterals {
+        tcbinfo_content_hash: output.tcbinfo_content_hash,
+        qeidentity_content_hash: output.qeidentity_content_hash,
+        sgx_intel_root_ca_cert_hash: output.sgx_intel_root_ca_cert_hash,
+        sgx_tcb_signing_cert_hash: output.sgx_tcb_signing_cert_hash,
+        sgx_intel_root_ca_crl_hash: output.sgx_intel_root_ca_crl_hash,
+        sgx_pck_crl_hash: output.sgx_pck_crl_hash,
+    };
+    check_collaterals(config, &parse_verified_output(verified_output)?, &committed).await
+}
+
+/// Fetches the collaterals used to verify a quote with the given verified output from the
+/// on-chain PCCS, and compares their hashes, computed as by the zkVM programs, with
+/// `committed`.
+pub async fn check_collaterals(
+    config: &PccsConfig,
+    verified_output: &VerifiedOutput,
+    committed: &CommittedCollaterals,
+) -> Result<CollateralCheckReport> {
+    let fmspc = hex::encode(verified_output.fmspc);
+    let quote_version = verified_output.quote_version;
+
+    // tcb_type: 0: SGX, 1: TDX
+    let (tcb_type, enclave_id) = match verified_output.tee_type {
+        SGX_TEE_TYPE => (0, ENCLAVE_ID_QE),
+        TDX_TEE_TYPE => (1, ENCLAVE_ID_TDQE),
+        tee_type => {
+            return Err(TdxError::Dcap(format!(
+                "Unsupported TEE type: {:#x}",
+                tee_type
+            )))
+        }
+    };
+    // V3 quotes are verified with the TCB Info V2, V4 quotes with the TCB Info V3.
+    let tcb_info_version = match quote_version {
+        3 => 2,
+        4 => 3,
+        version => {
+            return Err(TdxError::Dcap(format!(
+                "Unsupported quote version: {}",
+                version
+            )))
+        }
+    };
+
+    // Collaterals missing on-chain are reported as such, instead of failing the check.
+    let tcb_info = get_tcb_info_with_config(config, tcb_type, &fmspc, tcb_info_version)
+        .await
+        .ok();
+    let qe_identity =
+        get_enclave_identity_by_id_with_config(config, enclave_id, quote_version as u32)
+            .await
+            .ok();
+    let (root_ca, root_ca_crl) = get_certificate_by_id_with_config(config, CA::ROOT).await?;
+    let (signing_ca, _) = get_certificate_by_id_with_config(config, CA::SIGNING).await?;
+    let (_, platform_crl) = get_certificate_by_id_with_config(config, CA::PLATFORM).await?;
+    let (_, processor_crl) = get_certificate_by_id_with_config(config, CA::PROCESSOR).await?;
+
+    let tcb_info_hash =
+        tcb_info.and_then(|tcb_info| tcb_info_content_hash(&tcb_info, quote_version));
+    let qe_identity_hash =
+        qe_identity.and_then(|qe_identity| qe_identity_content_hash(&qe_identity));
+    let root_ca_hash = non_empty(&root_ca).and_then(|der| {
+        X509Certificate::from_der(der).ok()?;
+        let mut collaterals = IntelCollateral::new();
+        collaterals.set_intel_root_ca_der(der);
+        Some(hash_x509_keccak256(&collaterals.get_sgx_intel_root_ca()))
+    });
+    let signing_ca_hash = non_empty(&signing_ca).and_then(|der| {
+        X509Certificate::from_der(der).ok()?;
+        let mut collaterals = IntelCollateral::new();
+        collaterals.set_sgx_tcb_signing_der(der);
+        Some(hash_x509_keccak256(&collaterals.get_sgx_tcb_signing()))
+    });
+    let root_ca_crl_hash = non_empty(&root_ca_crl).and_then(|der| {
+        CertificateRevocationList::from_der(der).ok()?;
+        let mut collaterals = IntelCollateral::new();
+        collaterals.set_sgx_intel_root_ca_crl_der(der);
+        collaterals
+            .get_sgx_intel_root_ca_crl()
+            .map(|crl| hash_crl_keccak256(&crl))
+    });
+    let platform_crl_hash = non_empty(&platform_crl).and_then(|der| {
+        CertificateRevocationList::from_der(der).ok()?;
+        let mut collaterals = IntelCollateral::new();
+        collaterals.set_sgx_platform_crl_der(der);
+        collaterals
+            .get_sgx_pck_platform_crl()
+            .map(|crl| hash_crl_keccak256(&crl))
+    });
+    let processor_crl_hash = non_empty(&processor_crl).and_then(|der| {
+        CertificateRevocationList::from_der(der).ok()?;
+        let mut collaterals = IntelCollateral::new();
+        collaterals.set_sgx_processor_crl_der(der);
+        collaterals
+            .get_sgx_pck_processor_crl()
+            .map(|crl| hash_crl_keccak256(&crl))
+    });
+    let pck_crl_hash = if processor_crl_hash == Some(committed.sgx_pck_crl_hash) {
+        processor_crl_hash
+    } else {
+        platform_crl_hash
+    };
+
+    Ok(CollateralCheckReport {
+        fmspc,
+        quote_version,
+        tcb_info: HashCheck::new(&committed.tcbinfo_content_hash, tcb_info_hash),
+        qe_identity: HashCheck::new(&committed.qeidentity_content_hash, qe_identity_hash),
+        root_ca: HashCheck::new(&committed.sgx_intel_root_ca_cert_hash, root_ca_hash),
+        signing_ca: HashCheck::new(&committed.sgx_tcb_signing_cert_hash, signing_ca_hash),
+        root_ca_crl: HashCheck::new(&committed.sgx_intel_root_ca_crl_hash, root_ca_crl_hash),
+        pck_crl: HashCheck::new(&committed.sgx_pck_crl_hash, pck_crl_hash),
+    })
+}
+
+// The getters of `IntelCollateral` panic on malformed collaterals and on TCB Infos of another
+// version than the one of the quote, parse them here instead: such collaterals are reported as
+// missing, as they cannot match the committed hashes.
+fn tcb_info_content_hash(tcb_info: &[u8], quote_version: u16) -> Option<[u8; HASH_LEN]> {
+    // V3 quotes are verified with the TCB Info V2, V4 quotes with the TCB Info V3.
+    match quote_version {
+        3 => serde_json::from_slice::<TcbInfoV2>(tcb_info)
+            .ok()
+            .filter(|tcb_info| tcb_info.tcb_info.version == 2)
+            .map(|tcb_info| get_tcbinfov2_content_hash(&tcb_info)),
+        _ => serde_json::from_slice::<TcbInfoV3>(tcb_info)
+            .ok()
+            .filter(|tcb_info| tcb_info.tcb_info.version == 3)
+            .map(|tcb_info| get_tcbinfov3_content_hash(&tcb_info)),
+    }
+}
+
+fn qe_identity_content_hash(qe_identity: &[u8]) -> Option<[u8; HASH_LEN]> {
+    serde_json::from_slice::<EnclaveIdentityV2>(qe_identity)
+        .ok()
+        .map(|qe_identity| get_enclave_identityv2_content_hash(&qe_identity))
+}
+
+pub(crate) fn parse_verified_output(bytes: &[u8]) -> Result<VerifiedOutput> {
+    std::panic::catch_unwind(|| VerifiedOutput::from_bytes(bytes))
+        .map_err(|_| TdxError::Encoding("Malformed verified output".to_string()))
+}
+
+fn non_empty(bytes: &[u8]) -> Option<&[u8]> {
+    (!bytes.is_empty()).then_some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collateral::Collaterals;
+    use crate::pccs::enclave_id::IEnclaveIdentityDao::{self, getEnclaveIdentityCall};
+    use crate::pccs::fmspc_tcb::IFmspcTcbDao::{self, getTcbInfoCall};
+    use crate::pccs::pcs::IPCSDao::{getCertificateByIdCall, getCertificateByIdReturn};
+    use crate::test_rpc::{call_input, data, TestRpc};
+    use crate::utils::parse_quote_v4;
+    use crate::Tdx;
+    use alloy::primitives::Bytes;
+    use alloy::sol_types::SolCall;
+    use dcap_codec::batch::DcapBatchOutput;
+    use dcap_codec::DcapProgramOutput;
+    use serde_json::value::RawValue;
+    use std::collections::HashMap;
+    use std::path::Path;
+    use tokio::runtime::Runtime;
+
+    const QUOTE: &[u8] = include_bytes!("../examples/testdata/tdx_v4_quote_90c06f.bin");
+    const VERIFICATION_TIME: u64 = 1749600000;
+
+    fn collaterals() -> Collaterals {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/testdata/collaterals");
+        Collaterals::read_from_dir(&dir).unwrap()
+    }
+
+    // The journal of the recorded quote verified against the test collaterals, with the
+    // hashes computed as by the zkVM programs.
+    fn journal(collaterals: &Collaterals) -> Vec<u8> {
+        let verified_output = Tdx::new()
+            .verify_attestation_report_with_collaterals(
+                &parse_quote_v4(QUOTE).unwrap(),
+                collaterals,
+                VERIFICATION_TIME,
+            )
+            .unwrap();
+        let intel_collaterals = collaterals.to_intel_collateral().unwrap();
+        DcapProgramJournal::Success(Box::new(DcapProgramOutput {
+            verified_output: verified_output.to_bytes(),
+            current_time: VERIFICATION_TIME,
+            tcbinfo_content_hash: get_tcbinfov3_content_hash(&intel_collaterals.get_tcbinfov3()),
+            qeidentity_content_hash: get_enclave_identityv2_content_hash(
+                &intel_collaterals.get_qeidentityv2(),
+            ),
+            sgx_intel_root_ca_cert_hash: hash_x509_keccak256(
+                &intel_collaterals.get_sgx_intel_root_ca(),
+            ),
+            sgx_tcb_signing_cert_hash: hash_x509_keccak256(
+                &intel_collaterals.get_sgx_tcb_signing(),
+            ),
+            sgx_intel_root_ca_crl_hash: hash_crl_keccak256(
+                &intel_collaterals.get_sgx_intel_root_ca_crl().unwrap(),
+            ),
+            sgx_pck_crl_hash: hash_crl_keccak256(
+                &intel_collaterals.get_sgx_pck_platform_crl().unwrap(),
+            ),
+            time_window: None,
+        }))
+        .encode()
+        .unwrap()
+    }
+
+    /// What the PCCS DAOs return, the body of the TCB Info and the QE Identity as stored
+    /// on-chain, without their signature.
+    struct OnChain {
+        tcb_info: String,
+        qe_identity: String,
+        signature: Vec<u8>,
+        root_ca: Vec<u8>,
+        root_ca_crl: Vec<u8>,
+        signing_ca: Vec<u8>,
+        platform_crl: Vec<u8>,
+        processor_crl: Vec<u8>,
+    }
+
+    impl OnChain {
+        fn new(collaterals: &Collaterals) -> Self {
+            let body = |json: &[u8], key: &str| {
+                let signed: HashMap<String, Box<RawValue>> = serde_json::from_slice(json).unwrap();
+                signed[key].get().to_string()
+            };
+            OnChain {
+                tcb_info: body(&collaterals.tcb_info, "tcbInfo"),
+                qe_identity: body(&collaterals.qe_identity, "enclaveIdentity"),
+                signature: vec![0xab; 64],
+                root_ca: collaterals.root_ca.clone(),
+                root_ca_crl: collaterals.root_ca_crl.clone(),
+                signing_ca: collaterals.signing_ca.clone(),
+                platform_crl: collaterals.pck_crl.clone(),
+                processor_crl: Vec::new(),
+            }
+        }
+
+        fn serve(self) -> TestRpc {
+            TestRpc::start(move |method, params| {
+                if method != "eth_call" {
+                    return Err(format!("unexpected {}", method));
+                }
+                let input = call_input(params);
+                let output = match input[..4].try_into().unwrap() {
+                    getTcbInfoCall::SELECTOR => {
+                        let call = getTcbInfoCall::abi_decode(&input).unwrap();
+                        assert_eq!(call.tcbType, 1);
+                        assert_eq!(call.fmspc, "90c06f000000");
+                        assert_eq!(call.version, 3);
+                        getTcbInfoCall::abi_encode_returns(&IFmspcTcbDao::TcbInfoJsonObj {
+                            tcbInfoStr: self.tcb_info.clone(),
+                            signature: Bytes::from(self.signature.clone()),
+                        })
+                    }
+                    getEnclaveIdentityCall::SELECTOR => {
+                        let call = getEnclaveIdentityCall::abi_decode(&input).unwrap();
+                        assert_eq!(call.id, ENCLAVE_ID_TDQE);
+                        getEnclaveIdentityCall::abi_encode_returns(
+                            &IEnclaveIdentityDao::EnclaveIdentityJsonObj {
+                                identityStr: self.qe_identity.clone(),
+                                signature: Bytes::from(self.signature.clone()),
+                            },
+                        )
+                    }
+                    getCertificateByIdCall::SELECTOR => {
+                        let call = getCertificateByIdCall::abi_decode(&input).unwrap();
+                        let (cert, crl) = match call.ca {
+                            CA::ROOT => (&self.root_ca, &self.root_ca_crl),
+                            CA::SIGNING => (&self.signing_ca, &Vec::new()),
+                            CA::PLATFORM => (&Vec::new(), &self.platform_crl),
+                            _ => (&Vec::new(), &self.processor_crl),
+                        };
+                        getCertificateByIdCall::abi_encode_returns(&getCertificateByIdReturn {
+                            cert: Bytes::from(cert.clone()),
+                            crl: Bytes::from(crl.clone()),
+                        })
+                    }
+                    selector => return Err(format!("unexpected call {}", hex::encode(selector))),
+                };
+                Ok(data(&output))
+            })
+        }
+    }
+
+    // The journal of a batch of `count` copies of the recorded quote.
+    fn batch_journal(collaterals: &Collaterals, count: usize) -> Vec<u8> {
+        let DcapProgramJournal::Success(output) =
+            DcapProgramJournal::decode(&journal(collaterals)).unwrap()
+        else {
+            panic!("The journal of the recorded quote holds a failure");
+        };
+        DcapBatchJournal::Success(Box::new(DcapBatchOutput {
+            verified_outputs: vec![output.verified_output; count],
+            current_time: output.current_time,
+            tcbinfo_content_hash: output.tcbinfo_content_hash,
+            qeidentity_content_hash: output.qeidentity_content_hash,
+            sgx_intel_root_ca_cert_hash: output.sgx_intel_root_ca_cert_hash,
+            sgx_tcb_signing_cert_hash: output.sgx_tcb_signing_cert_hash,
+            sgx_intel_root_ca_crl_hash: output.sgx_intel_root_ca_crl_hash,
+            sgx_pck_crl_hash: output.sgx_pck_crl_hash,
+            time_window: output.time_window,
+        }))
+        .encode()
+        .unwrap()
+    }
+
+    fn check(on_chain: OnChain, journal: &[u8]) -> CollateralCheckReport {
+        check_with(on_chain, journal, false).unwrap()
+    }
+
+    fn check_with(on_chain: OnChain, journal: &[u8], batch: bool) -> Result<CollateralCheckReport> {
+        let rpc = on_chain.serve();
+        let config = PccsConfig {
+            rpc_url: rpc.url().to_string(),
+            ..Default::default()
+        };
+        let runtime = Runtime::new().unwrap();
+        let report = if batch {
+            runtime.block_on(check_batch_journal_collaterals(&config, journal))
+        } else {
+            runtime.block_on(check_journal_collaterals(&config, journal))
+        };
+        assert!(rpc.methods().iter().all(|method| method == "eth_call"));
+        report
+    }
+
+    #[test]
+    fn checks_current_collaterals() {
+        let collaterals = collaterals();
+        let report = check(OnChain::new(&collaterals), &journal(&collaterals));
+        assert_eq!(report.fmspc, "90c06f000000");
+        assert_eq!(report.quote_version, 4);
+        assert!(report.is_current(), "{:?}", report);
+    }
+
+    #[test]
+    fn reports_stale_and_malformed_collaterals() {
+        let collaterals = collaterals();
+        let mut on_chain = OnChain::new(&collaterals);
+        // A TCB Info of another version, a malformed QE Identity and root CA, and the PCK
+        // CRL stored as the Processor CA one.
+        on_chain.tcb_info = on_chain
+            .tcb_info
+            .replacen("\"version\":3", "\"version\":2", 1);
+        on_chain.qe_identity = "{\"id\":".to_string();
+        on_chain.root_ca = vec![0x30, 0x03, 0x02];
+        on_chain.processor_crl = std::mem::take(&mut on_chain.platform_crl);
+        on_chain.signing_ca = collaterals.root_ca.clone();
+
+        let report = check(on_chain, &journal(&collaterals));
+        assert!(!report.is_current());
+        assert!(report.tcb_info.on_chain.is_none() && !report.tcb_info.matches);
+        assert!(report.qe_identity.on_chain.is_none() && !report.qe_identity.matches);
+        assert!(report.root_ca.on_chain.is_none() && !report.root_ca.matches);
+        assert!(report.signing_ca.on_chain.is_some() && !report.signing_ca.matches);
+        assert!(report.root_ca_crl.matches);
+        assert!(report.pck_crl.matches);
+    }
+
+    #[test]
+    fn checks_batch_journals() {
+        let collaterals = collaterals();
+        let report = check_with(
+            OnChain::new(&collaterals),
+            &batch_journal(&collaterals, 3),
+            true,
+        )
+        .unwrap();
+        assert_eq!(report.fmspc, "90c06f000000");
+        assert_eq!(report.quote_version, 4);
+        assert!(report.is_current(), "{:?}", report);
+
+        let mut on_chain = OnChain::new(&collaterals);
+        on_chain.platform_crl = collaterals.root_ca_crl.clone();
+        let report = check_with(on_chain, &batch_journal(&collaterals, 1), true).unwrap();
+        assert!(!report.is_current());
+        assert!(!report.pck_crl.matches);
+        assert!(report.tcb_info.matches && report.root_ca_crl.matches);
+
+        // Single and batch journals are not interchangeable.
+        let result = check_with(OnChain::new(&collaterals), &journal(&collaterals), true);
+        assert!(matches!(result, Err(TdxError::Encoding(_))));
+        let result = check_with(
+            OnChain::new(&collaterals),
+            &batch_journal(&collaterals, 2),
+            false,
+        );
+        assert!(matches!(result, Err(TdxError::Encoding(_))));
+
+        let failure = DcapBatchJournal::Failure(dcap_codec::ErrorCode::BatchVersionMismatch)
+            .encode()
+            .unwrap();
+        let result = Runtime::new()
+            .unwrap()
+            .block_on(check_batch_journal_collaterals(
+                &PccsConfig::default(),
+                &failure,
+            ));
+        assert!(matches!(result, Err(TdxError::Dcap(_))));
+    }
+
+    #[test]
+    fn rejects_failure_journals() {
+        let journal = DcapProgramJournal::Failure(dcap_codec::ErrorCode::InvalidQuote)
+            .encode()
+            .unwrap();
+        let result = Runtime::new()
+            .unwrap()
+            .block_on(check_journal_collaterals(&PccsConfig::default(), &journal));
+        assert!(matches!(result, Err(TdxError::Dcap(_))));
+    }
+}
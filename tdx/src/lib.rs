@@ -1,18 +1,36 @@
+pub mod certdata;
+pub mod collateral;
 pub mod device;
+pub mod eat;
 pub mod error;
+pub mod evidence;
+pub mod intel_pcs;
+pub mod journal;
+pub mod monitor;
+pub mod onchain;
 pub mod pccs;
+pub mod pck;
+#[cfg(feature = "ratls")]
+pub mod ratls;
+pub mod report;
+pub mod tcb;
+#[cfg(all(test, feature = "ratls"))]
+mod test_quote;
+#[cfg(test)]
+mod test_rpc;
 pub mod utils;
+#[cfg(feature = "zk")]
+pub mod zk;
 
-use dcap_rs::types::collaterals::IntelCollateral;
+use collateral::Collaterals;
 use dcap_rs::types::quotes::version_4::QuoteV4;
+use dcap_rs::types::VerifiedOutput;
 use dcap_rs::utils::quotes::version_4::verify_quote_dcapv4;
 use error::{Result, TdxError};
-use pccs::enclave_id::get_enclave_identity;
-use pccs::fmspc_tcb::get_tcb_info;
-use pccs::pcs::{get_certificate_by_id, IPCSDao::CA};
+use evidence::Evidence;
+use pccs::PccsConfig;
 use std::panic;
 use tokio::runtime::Runtime;
-use utils::get_pck_fmspc_and_issuer;
 
 pub struct Tdx;
 
@@ -69,56 +87,62 @@ impl Tdx {
         device.get_attestation_report_raw()
     }
 
+    /// Retrieve an Attestation Report wrapped in an evidence envelope, together with the
+    /// var data and the TDX event log when available.
+    ///
+    /// When a nonce is provided, the report data is set to SHA-512(nonce) and the nonce is
+    /// recorded in the envelope. This cannot be used on Azure Confidential VM.
+    pub fn get_evidence(&self, nonce: Option<&[u8]>) -> Result<Evidence> {
+        let options = device::DeviceOptions {
+            report_data: nonce.map(utils::report_data_from_nonce),
+        };
+        let device = device::Device::new(options)?;
+        let (quote, var_data) = device.get_attestation_report_raw()?;
+
+        let mut evidence = Evidence::new(quote, var_data)?.with_provider(device.provider_name());
+        if let Some(nonce) = nonce {
+            evidence = evidence.with_nonce(nonce.to_vec());
+        }
+        // The event log is optional, it is only exposed by recent kernels.
+        if let Ok(event_log) = std::fs::read(evidence::CCEL_EVENT_LOG_PATH) {
+            evidence = evidence.with_event_log(event_log);
+        }
+        Ok(evidence)
+    }
+
     /// This function verifies the chain of trust for the attestation report.
     pub fn verify_attestation_report(&self, report: &QuoteV4) -> Result<()> {
+        self.verify_attestation_report_with_output(report)?;
+        Ok(())
+    }
+
+    /// Verifies the attestation report with collaterals from the on-chain PCCS and returns
+    /// the verified output.
+    pub fn verify_attestation_report_with_output(
+        &self,
+        report: &QuoteV4,
+    ) -> Result<VerifiedOutput> {
         // First retrieve all the required collaterals.
         let rt = Runtime::new().unwrap();
-        let (root_ca, root_ca_crl) = rt.block_on(get_certificate_by_id(CA::ROOT))?;
-        if root_ca.is_empty() || root_ca_crl.is_empty() {
-            return Err(TdxError::Http("Root CA or CRL is empty".to_string()));
-        }
-
-        let (fmspc, pck_type) = get_pck_fmspc_and_issuer(report);
-        // tcb_type: 0: SGX, 1: TDX
-        // version: TDX uses TcbInfoV3
-        let tcb_info = rt.block_on(get_tcb_info(1, &fmspc, 3))?;
+        let collaterals = rt.block_on(Collaterals::fetch(&PccsConfig::default(), report))?;
 
-        let quote_version = report.header.version;
-        let qe_identity = rt.block_on(get_enclave_identity(quote_version as u32))?;
-
-        let (signing_ca, _) = rt.block_on(get_certificate_by_id(CA::SIGNING))?;
-        if signing_ca.is_empty() {
-            return Err(TdxError::Http("Signing CA is empty".to_string()));
-        }
-
-        let (_, pck_crl) = rt.block_on(get_certificate_by_id(pck_type))?;
-        if pck_crl.is_empty() {
-            return Err(TdxError::Http("PCK CRL is empty".to_string()));
-        }
-
-        // Pass all the collaterals into a struct for verifying the quote.
         let current_time = chrono::Utc::now().timestamp() as u64;
-        let mut collaterals = IntelCollateral::new();
+        self.verify_attestation_report_with_collaterals(report, &collaterals, current_time)
+    }
 
-        collaterals.set_tcbinfo_bytes(&tcb_info);
-        collaterals.set_qeidentity_bytes(&qe_identity);
-        collaterals.set_intel_root_ca_der(&root_ca);
-        collaterals.set_sgx_tcb_signing_der(&signing_ca);
-        collaterals.set_sgx_intel_root_ca_crl_der(&root_ca_crl);
-        match pck_type {
-            CA::PLATFORM => {
-                collaterals.set_sgx_platform_crl_der(&pck_crl);
-            }
-            CA::PROCESSOR => {
-                collaterals.set_sgx_processor_crl_der(&pck_crl);
-            }
-            _ => {
-                return Err(TdxError::Http("Unknown PCK Type".to_string()));
-            }
-        }
+    /// Verifies the attestation report against the given collaterals at `current_time`
+    /// (seconds since the Unix epoch). No network access is required.
+    pub fn verify_attestation_report_with_collaterals(
+        &self,
+        report: &QuoteV4,
+        collaterals: &Collaterals,
+        current_time: u64,
+    ) -> Result<VerifiedOutput> {
+        // Pass all the collaterals into a struct for verifying the quote.
+        let collaterals = collaterals.to_intel_collateral()?;
 
         match panic::catch_unwind(|| verify_quote_dcapv4(report, &collaterals, current_time)) {
-            Ok(_) => Ok(()),
+            Ok(output) => Ok(output),
             Err(e) => Err(TdxError::Dcap(format!("DCAP Error: {:?}", e))),
         }
     }
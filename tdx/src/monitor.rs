@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use crate::error::{Result, TdxError};
+use crate::pccs::fmspc_tcb::get_tcb_info_with_config;
+use crate::pccs::PccsConfig;
+use crate::tcb::{evaluate_quote_tcb, SignedTcbInfo, TcbEvaluation, TcbInfoV3};
+use crate::utils::try_get_pck_fmspc_and_issuer;
+use chrono::{DateTime, Utc};
+use dcap_rs::types::quotes::version_4::QuoteV4;
+use serde::Serialize;
+
+/// Freshness of the TCB Info currently published for an FMSPC.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcbInfoStatus {
+    pub fmspc: String,
+    pub tcb_evaluation_data_number: u32,
+    pub issue_date: String,
+    pub next_update: String,
+    /// `true` if `nextUpdate` has already passed, ie the collateral must be refreshed.
+    pub expired: bool,
+}
+
+/// TCB status of a quote evaluated against the TCB Info currently published for its FMSPC.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuoteTcbStatus {
+    pub fmspc: String,
+    /// Status recorded when the quote was last verified, if known.
+    pub previous_status: Option<String>,
+    /// Status of the quote against the current TCB Info.
+    pub current_status: String,
+    /// `true` if the current status is worse than `previous_status`.
+    /// `None` when no previous status was provided.
+    pub downgraded: Option<bool>,
+    pub evaluation: TcbEvaluation,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcbMonitorReport {
+    /// Unix timestamp of the check.
+    pub checked_at: i64,
+    pub tcb_infos: Vec<TcbInfoStatus>,
+    pub quotes: Vec<QuoteTcbStatus>,
+}
+
+impl TcbMonitorReport {
+    /// Returns `true` if any TCB Info has expired or any quote has been downgraded.
+    pub fn needs_attention(&self) -> bool {
+        self.tcb_infos.iter().any(|t| t.expired)
+            || self.quotes.iter().any(|q| q.downgraded == Some(true))
+    }
+}
+
+/// Status of a platform below every TCB level of the TCB Info.
+pub const UNRECOGNIZED_TCB_STATUS: &str = "Unrecognized";
+
+/// Ranks TCB statuses from the best (0) to the worst.
+/// Returns `None` for statuses that are not defined by the TCB Info V3 specification.
+pub fn tcb_status_rank(status: &str) -> Option<u8> {
+    let rank = match status {
+        "UpToDate" => 0,
+        "SWHardeningNeeded" => 1,
+        "ConfigurationNeeded" => 2,
+        "ConfigurationAndSWHardeningNeeded" => 3,
+        "OutOfDate" => 4,
+        "OutOfDateConfigurationNeeded" => 5,
+        "Revoked" => 6,
+        UNRECOGNIZED_TCB_STATUS => 7,
+        _ => return None,
+    };
+    Some(rank)
+}
+
+fn try_tcb_status_rank(status: &str) -> Result<u8> {
+    tcb_status_rank(status).ok_or_else(|| TdxError::Dcap(format!("Unknown TCB status: {}", status)))
+}
+
+/// Fetches the current TDX TCB Info of an FMSPC from the on-chain PCCS.
+pub async fn get_current_tcb_info(config: &PccsConfig, fmspc: &str) -> Result<TcbInfoV3> {
+    // tcb_type: 0: SGX, 1: TDX
+    // version: TDX uses TcbInfoV3
+    let tcb_info = get_tcb_info_with_config(config, 1, fmspc, 3).await?;
+    Ok(SignedTcbInfo::from_bytes(&tcb_info)?.tcb_info)
+}
+
+pub fn tcb_info_status(tcb_info: &TcbInfoV3, now: DateTime<Utc>) -> Result<TcbInfoStatus> {
+    let next_update = DateTime::parse_from_rfc3339(&tcb_info.next_update)
+        .map_err(|e| TdxError::Dcap(format!("Invalid nextUpdate: {}", e)))?;
+    Ok(TcbInfoStatus {
+        fmspc: tcb_info.fmspc.clone(),
+        tcb_evaluation_data_number: tcb_info.tcb_evaluation_data_number,
+        issue_date: tcb_info.issue_date.clone(),
+        next_update: tcb_info.next_update.clone(),
+        expired: next_update < now,
+    })
+}
+
+pub fn quote_tcb_status(
+    quote: &QuoteV4,
+    previous_status: Option<&str>,
+    tcb_info: &TcbInfoV3,
+) -> Result<QuoteTcbStatus> {
+    let evaluation = evaluate_quote_tcb(quote, tcb_info)?;
+
+    // The quote status is the worst of the platform and TDX module statuses.
+    let platform_status = evaluation
+        .matched_level
+        .as_ref()
+        .map_or(UNRECOGNIZED_TCB_STATUS, |l| l.tcb_status.as_str());
+    let mut current_status = platform_status.to_string();
+    if let Some(module) = &evaluation.tdx_module_level {
+        if try_tcb_status_rank(&module.tcb_status)? > try_tcb_status_rank(platform_status)? {
+            current_status = module.tcb_status.clone();
+        }
+    }
+    let current_rank = try_tcb_status_rank(&current_status)?;
+
+    let downgraded = match previous_status {
+        Some(prev) => Some(current_rank > try_tcb_status_rank(prev)?),
+        None => None,
+    };
+
+    Ok(QuoteTcbStatus {
+        fmspc: evaluation.fmspc.clone(),
+        previous_status: previous_status.map(str::to_string),
+        current_status,
+        downgraded,
+        evaluation,
+    })
+}
+
+/// Queries the current TCB Info of every given FMSPC and of the FMSPC of every given quote,
+/// and reports whether the collateral has expired or the quotes have been downgraded.
+///
+/// Each quote may be paired with the TCB status recorded when it was last verified.
+pub async fn monitor_tcb(
+    config: &PccsConfig,
+    fmspcs: &[String],
+    quotes: &[(QuoteV4, Option<String>)],
+) -> Result<TcbMonitorReport> {
+    let now = Utc::now();
+    let mut tcb_infos: HashMap<String, TcbInfoV3> = HashMap::new();
+    let mut order = Vec::new();
+
+    let quote_fmspcs = quotes
+        .iter()
+        .map(|(quote, _)| try_get_pck_fmspc_and_issuer(quote).map(|(fmspc, _)| fmspc))
+        .collect::<Result<Vec<_>>>()?;
+
+    for fmspc in fmspcs.iter().chain(quote_fmspcs.iter()) {
+        let fmspc = fmspc.to_lowercase();
+        if tcb_infos.contains_key(&fmspc) {
+            continue;
+        }
+        let tcb_info = get_current_tcb_info(config, &fmspc).await?;
+        tcb_infos.insert(fmspc.clone(), tcb_info);
+        order.push(fmspc);
+    }
+
+    let tcb_info_statuses = order
+        .iter()
+        .map(|fmspc| tcb_info_status(&tcb_infos[fmspc], now))
+        .collect::<Result<Vec<_>>>()?;
+
+    let quote_statuses = quotes
+        .iter()
+        .zip(quote_fmspcs.iter())
+        .map(|((quote, previous_status), fmspc)| {
+            quote_tcb_status(
+                quote,
+                previous_status.as_deref(),
+                &tcb_infos[&fmspc.to_lowercase()],
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(TcbMonitorReport {
+        checked_at: now.timestamp(),
+        tcb_infos: tcb_info_statuses,
+        quotes: quote_statuses,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::parse_quote_v4;
+
+    const TCB_INFO: &[u8] = include_bytes!("../examples/testdata/collaterals/tcbinfo.json");
+    const QUOTE: &[u8] = include_bytes!("../examples/testdata/tdx_v4_quote_90c06f.bin");
+
+    #[test]
+    fn rejects_unknown_statuses() {
+        assert_eq!(tcb_status_rank("UpToDate"), Some(0));
+        assert_eq!(tcb_status_rank("Revoked"), Some(6));
+        assert_eq!(tcb_status_rank("uptodate"), None);
+        assert_eq!(tcb_status_rank("TCB_NOT_SUPPORTED"), None);
+    }
+
+    #[test]
+    fn reports_downgraded_quote() {
+        let quote = parse_quote_v4(QUOTE).unwrap();
+        let tcb_info = SignedTcbInfo::from_bytes(TCB_INFO).unwrap().tcb_info;
+
+        // The platform of the recorded quote is below every TCB level.
+        let status = quote_tcb_status(&quote, Some("UpToDate"), &tcb_info).unwrap();
+        assert_eq!(status.current_status, UNRECOGNIZED_TCB_STATUS);
+        assert_eq!(status.downgraded, Some(true));
+
+        let status = quote_tcb_status(&quote, None, &tcb_info).unwrap();
+        assert_eq!(status.downgraded, None);
+
+        assert!(quote_tcb_status(&quote, Some("Fine"), &tcb_info).is_err());
+    }
+}
@@ -0,0 +1,522 @@
+//! Client of the Automata DCAP Attestation contract, to verify quotes and ZK proofs of their
+//! verification on-chain.
+use alloy::network::EthereumWallet;
+use alloy::primitives::{Address, Bytes, TxHash, U256};
+use alloy::providers::ProviderBuilder;
+use alloy::signers::local::PrivateKeySigner;
+use alloy::sol;
+use alloy::transports::http::reqwest::Url;
+use dcap_rs::types::VerifiedOutput;
+
+use crate::error::{Result, TdxError};
+use crate::pccs::{parse_address, DEFAULT_DCAP_CONTRACT, DEFAULT_RPC_URL};
+
+pub use IDcapAttestation::ZkCoProcessorType;
+
+sol! {
+    #[sol(rpc)]
+    interface IDcapAttestation {
+        #[derive(Debug)]
+        enum ZkCoProcessorType {
+            Unknown,
+            RiscZero,
+            Succinct
+        }
+
+        #[derive(Debug)]
+        function verifyAndAttestOnChain(bytes calldata rawQuote) external payable returns (bool success, bytes memory output);
+
+        #[derive(Debug)]
+        function verifyAndAttestWithZKProof(bytes calldata output, ZkCoProcessorType zkCoprocessor, bytes calldata proofBytes) external payable returns (bool success, bytes memory verifiedOutput);
+    }
+}
+
+/// Result of a verification by the contract.
+#[derive(Clone, Debug)]
+pub struct AttestationOutput {
+    pub success: bool,
+    /// The serialized `VerifiedOutput` on success, the error message otherwise.
+    pub output: Vec<u8>,
+}
+
+impl AttestationOutput {
+    /// Decodes the `VerifiedOutput` of a successful verification.
+    pub fn verified_output(&self) -> Result<VerifiedOutput> {
+        if !self.success {
+            return Err(TdxError::Dcap(format!(
+                "Verification failed: {}",
+                self.error_message()
+            )));
+        }
+        std::panic::catch_unwind(|| VerifiedOutput::from_bytes(&self.output))
+            .map_err(|_| TdxError::Encoding("Malformed verified output".to_string()))
+    }
+
+    /// The error message returned by a failed verification.
+    pub fn error_message(&self) -> String {
+        String::from_utf8_lossy(&self.output).into_owned()
+    }
+}
+
+/// A verification sent in a transaction.
+#[derive(Clone, Debug)]
+pub struct AttestationSubmission {
+    pub tx_hash: TxHash,
+    /// Output of the verification simulated before sending the transaction. The contract
+    /// does not emit it, transactions only return their status.
+    pub output: AttestationOutput,
+}
+
+/// Calls the DCAP Attestation contract, with `eth_call` or in transactions signed by the
+/// configured signer. Defaults to the Automata Testnet deployment.
+#[derive(Clone, Debug)]
+pub struct DcapAttestationClient {
+    rpc_url: String,
+    contract_address: Address,
+    signer: Option<PrivateKeySigner>,
+    value: U256,
+}
+
+impl DcapAttestationClient {
+    pub fn new(rpc_url: &str, contract_address: &str) -> Result<Self> {
+        Ok(DcapAttestationClient {
+            rpc_url: rpc_url.to_string(),
+            contract_address: parse_address(contract_address)?,
+            signer: None,
+            value: U256::ZERO,
+        })
+    }
+
+    /// Signs the transactions, and sets the sender of the calls, with `signer`.
+    pub fn with_signer(mut self, signer: PrivateKeySigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Signs the transactions with a hex encoded secp256k1 private key.
+    pub fn with_private_key(self, private_key: &str) -> Result<Self> {
+        let signer = private_key
+            .parse::<PrivateKeySigner>()
+            .map_err(|e| TdxError::Signature(e.to_string()))?;
+        Ok(self.with_signer(signer))
+    }
+
+    /// Attaches `value` wei to the calls, for deployments that charge a verification fee.
+    pub fn with_value(mut self, value: U256) -> Self {
+        self.value = value;
+        self
+    }
+
+    /// Verifies the raw quote on-chain with `eth_call`, with the collaterals of the on-chain
+    /// PCCS.
+    pub async fn call_verify_on_chain(&self, raw_quote: &[u8]) -> Result<AttestationOutput> {
+        let provider = ProviderBuilder::new().connect_http(self.parsed_rpc_url()?);
+        let contract = IDcapAttestation::new(self.contract_address, &provider);
+        let mut call = contract
+            .verifyAndAttestOnChain(Bytes::copy_from_slice(raw_quote))
+            .value(self.value);
+        if let Some(signer) = &self.signer {
+            call = call.from(signer.address());
+        }
+        let ret = call.call().await.map_err(rpc_error)?;
+        Ok(AttestationOutput {
+            success: ret.success,
+            output: ret.output.to_vec(),
+        })
+    }
+
+    /// Verifies a ZK proof of the quote verification with `eth_call`. `output` is the journal
+    /// (Risc0) or the public values (SP1) of the proof.
+    pub async fn call_verify_with_zk_proof(
+        &self,
+        output: &[u8],
+        zk_coprocessor: ZkCoProcessorType,
+        proof: &[u8],
+    ) -> Result<AttestationOutput> {
+        let provider = ProviderBuilder::new().connect_http(self.parsed_rpc_url()?);
+        let contract = IDcapAttestation::new(self.contract_address, &provider);
+        let mut call = contract
+            .verifyAndAttestWithZKProof(
+                Bytes::copy_from_slice(output),
+                zk_coprocessor,
+                Bytes::copy_from_slice(proof),
+            )
+            .value(self.value);
+        if let Some(signer) = &self.signer {
+            call = call.from(signer.address());
+        }
+        let ret = call.call().await.map_err(rpc_error)?;
+        Ok(AttestationOutput {
+            success: ret.success,
+            output: ret.verifiedOutput.to_vec(),
+        })
+    }
+
+    /// Sends a transaction verifying the raw quote on-chain, and waits for its receipt.
+    /// Failed verifications are not sent.
+    pub async fn send_verify_on_chain(&self, raw_quote: &[u8]) -> Result<AttestationSubmission> {
+        let output = self.call_verify_on_chain(raw_quote).await?;
+        if !output.success {
+            return Err(TdxError::Dcap(format!(
+                "Verification failed: {}",
+                output.error_message()
+            )));
+        }
+
+        let provider = ProviderBuilder::new()
+            .wallet(self.wallet()?)
+            .connect_http(self.parsed_rpc_url()?);
+        let contract = IDcapAttestation::new(self.contract_address, &provider);
+        let receipt = contract
+            .verifyAndAttestOnChain(Bytes::copy_from_slice(raw_quote))
+            .value(self.value)
+            .send()
+            .await
+            .map_err(rpc_error)?
+            .get_receipt()
+            .await
+            .map_err(rpc_error)?;
+        if !receipt.status() {
+            return Err(TdxError::Http(format!(
+                "Transaction {} reverted",
+                receipt.transaction_hash
+            )));
+        }
+
+        Ok(AttestationSubmission {
+            tx_hash: receipt.transaction_hash,
+            output,
+        })
+    }
+
+    /// Sends a transaction verifying a ZK proof of the quote verification, and waits for its
+    /// receipt. Failed verifications are not sent.
+    pub async fn send_verify_with_zk_proof(
+        &self,
+        output: &[u8],
+        zk_coprocessor: ZkCoProcessorType,
+        proof: &[u8],
+    ) -> Result<AttestationSubmission> {
+        let simulated = self
+            .call_verify_with_zk_proof(output, zk_coprocessor, proof)
+            .await?;
+        if !simulated.success {
+            return Err(TdxError::Dcap(format!(
+                "Verification failed: {}",
+                simulated.error_message()
+            )));
+        }
+
+        let provider = ProviderBuilder::new()
+            .wallet(self.wallet()?)
+            .connect_http(self.parsed_rpc_url()?);
+        let contract = IDcapAttestation::new(self.contract_address, &provider);
+        let receipt = contract
+            .verifyAndAttestWithZKProof(
+                Bytes::copy_from_slice(output),
+                zk_coprocessor,
+                Bytes::copy_from_slice(proof),
+            )
+            .value(self.value)
+            .send()
+            .await
+            .map_err(rpc_error)?
+            .get_receipt()
+            .await
+            .map_err(rpc_error)?;
+        if !receipt.status() {
+            return Err(TdxError::Http(format!(
+                "Transaction {} reverted",
+                receipt.transaction_hash
+            )));
+        }
+
+        Ok(AttestationSubmission {
+            tx_hash: receipt.transaction_hash,
+            output: simulated,
+        })
+    }
+
+    fn parsed_rpc_url(&self) -> Result<Url> {
+        self.rpc_url
+            .parse()
+            .map_err(|_| TdxError::ConfigOptions(format!("Invalid RPC URL: {}", self.rpc_url)))
+    }
+
+    fn wallet(&self) -> Result<EthereumWallet> {
+        let signer = self.signer.clone().ok_or_else(|| {
+            TdxError::ConfigOptions("A signer is required to send transactions".to_string())
+        })?;
+        Ok(EthereumWallet::from(signer))
+    }
+}
+
+impl Default for DcapAttestationClient {
+    fn default() -> Self {
+        DcapAttestationClient::new(DEFAULT_RPC_URL, DEFAULT_DCAP_CONTRACT).unwrap()
+    }
+}
+
+fn rpc_error(err: impl std::fmt::Display) -> TdxError {
+    TdxError::Http(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rpc::{
+        call_input, data, raw_transaction_input, transaction, TestRpc, PRIVATE_KEY,
+    };
+    use alloy::sol_types::SolCall;
+    use tokio::runtime::Runtime;
+    use IDcapAttestation::{verifyAndAttestOnChainCall, verifyAndAttestWithZKProofCall};
+
+    const CONTRACT: &str = "0x1111111111111111111111111111111111111111";
+    const QUOTE: &[u8] = &[4, 0, 2, 0];
+    const JOURNAL: &[u8] = &[0, 1, 2, 3, 4];
+    const PROOF: &[u8] = &[0xf5, 0x36, 0x08, 0x5a, 1, 2, 3];
+
+    /// The return data of the contract for `input`: `verifyAndAttestOnChain` returns the quote
+    /// as output, `verifyAndAttestWithZKProof` the `zkCoprocessor` byte read from the calldata
+    /// followed by the journal.
+    fn verify(input: &[u8], success: bool) -> Vec<u8> {
+        match input[..4].try_into().unwrap() {
+            verifyAndAttestOnChainCall::SELECTOR => {
+                let call = verifyAndAttestOnChainCall::abi_decode(input).unwrap();
+                let ret = IDcapAttestation::verifyAndAttestOnChainReturn {
+                    success,
+                    output: call.rawQuote,
+                };
+                verifyAndAttestOnChainCall::abi_encode_returns(&ret)
+            }
+            verifyAndAttestWithZKProofCall::SELECTOR => {
+                let call = verifyAndAttestWithZKProofCall::abi_decode(input).unwrap();
+                assert_eq!(call.proofBytes.as_ref(), PROOF);
+                // The enum is the second, uint8 encoded, word of the arguments.
+                let zk_coprocessor = &input[4 + 32..4 + 64];
+                assert!(zk_coprocessor[..31].iter().all(|byte| *byte == 0));
+                let ret = IDcapAttestation::verifyAndAttestWithZKProofReturn {
+                    success,
+                    verifiedOutput: [&zk_coprocessor[31..], call.output.as_ref()]
+                        .concat()
+                        .into(),
+                };
+                verifyAndAttestWithZKProofCall::abi_encode_returns(&ret)
+            }
+            selector => panic!("unexpected call {}", hex::encode(selector)),
+        }
+    }
+
+    /// A node where the verifications return `success`, see `verify`, and transactions are
+    /// mined with the status `mined`.
+    fn node(success: bool, mined: bool) -> TestRpc {
+        TestRpc::start(move |method, params| match method {
+            "eth_call" => {
+                let tx = &params[0];
+                assert_eq!(tx["to"], CONTRACT);
+                assert_eq!(tx["value"], "0x2a");
+                Ok(data(&verify(&call_input(params), success)))
+            }
+            "eth_sendRawTransaction" => {
+                let input = raw_transaction_input(params);
+                match input[..4].try_into().unwrap() {
+                    verifyAndAttestOnChainCall::SELECTOR => {
+                        let call = verifyAndAttestOnChainCall::abi_decode(&input).unwrap();
+                        assert_eq!(call.rawQuote.as_ref(), QUOTE);
+                    }
+                    _ => {
+                        let call = verifyAndAttestWithZKProofCall::abi_decode(&input).unwrap();
+                        assert_eq!(call.output.as_ref(), JOURNAL);
+                        assert_eq!(call.proofBytes.as_ref(), PROOF);
+                    }
+                }
+                Ok(transaction(method, params, mined).unwrap())
+            }
+            _ => transaction(method, params, mined).ok_or(format!("unexpected {}", method)),
+        })
+    }
+
+    fn client(node: &TestRpc) -> DcapAttestationClient {
+        DcapAttestationClient::new(node.url(), CONTRACT)
+            .unwrap()
+            .with_value(U256::from(42))
+    }
+
+    #[test]
+    fn defaults_to_the_testnet_deployment() {
+        let client = DcapAttestationClient::default();
+        assert_eq!(client.rpc_url, DEFAULT_RPC_URL);
+        assert_eq!(
+            client.contract_address,
+            parse_address(DEFAULT_DCAP_CONTRACT).unwrap()
+        );
+        assert!(client.signer.is_none());
+        assert_eq!(client.value, U256::ZERO);
+    }
+
+    #[test]
+    fn calls_verify_on_chain() {
+        let node = node(true, true);
+        let output = Runtime::new()
+            .unwrap()
+            .block_on(client(&node).call_verify_on_chain(QUOTE))
+            .unwrap();
+        assert!(output.success);
+        assert_eq!(output.output, QUOTE);
+        assert_eq!(node.methods(), ["eth_call"]);
+
+        let node = self::node(false, true);
+        let output = Runtime::new()
+            .unwrap()
+            .block_on(client(&node).call_verify_on_chain(b"Invalid quote"))
+            .unwrap();
+        assert!(!output.success);
+        assert_eq!(output.error_message(), "Invalid quote");
+        assert!(matches!(output.verified_output(), Err(TdxError::Dcap(_))));
+    }
+
+    #[test]
+    fn sends_verify_on_chain() {
+        let runtime = Runtime::new().unwrap();
+        let node = node(true, true);
+        let client = client(&node).with_private_key(PRIVATE_KEY).unwrap();
+        let submission = runtime
+            .block_on(client.send_verify_on_chain(QUOTE))
+            .unwrap();
+        assert_eq!(submission.output.output, QUOTE);
+        let methods = node.methods();
+        assert!(methods.contains(&"eth_sendRawTransaction".to_string()));
+        assert!(methods.contains(&"eth_getTransactionReceipt".to_string()));
+
+        let node = self::node(true, false);
+        let client = self::client(&node).with_private_key(PRIVATE_KEY).unwrap();
+        let result = runtime.block_on(client.send_verify_on_chain(QUOTE));
+        assert!(matches!(result, Err(TdxError::Http(e)) if e.ends_with("reverted")));
+    }
+
+    #[test]
+    fn calls_verify_with_zk_proof() {
+        let runtime = Runtime::new().unwrap();
+        let node = node(true, true);
+        for (zk_coprocessor, encoded) in [
+            (ZkCoProcessorType::RiscZero, 1),
+            (ZkCoProcessorType::Succinct, 2),
+        ] {
+            let output = runtime
+                .block_on(client(&node).call_verify_with_zk_proof(JOURNAL, zk_coprocessor, PROOF))
+                .unwrap();
+            assert!(output.success);
+            assert_eq!(output.output, [&[encoded], JOURNAL].concat());
+        }
+        assert_eq!(node.methods(), ["eth_call", "eth_call"]);
+
+        let node = self::node(false, true);
+        let output = runtime
+            .block_on(client(&node).call_verify_with_zk_proof(
+                b"Invalid proof",
+                ZkCoProcessorType::RiscZero,
+                PROOF,
+            ))
+            .unwrap();
+        assert!(!output.success);
+        assert!(matches!(output.verified_output(), Err(TdxError::Dcap(_))));
+    }
+
+    #[test]
+    fn sends_verify_with_zk_proof() {
+        let runtime = Runtime::new().unwrap();
+        let node = node(true, true);
+        let client = client(&node).with_private_key(PRIVATE_KEY).unwrap();
+        let submission = runtime
+            .block_on(client.send_verify_with_zk_proof(JOURNAL, ZkCoProcessorType::Succinct, PROOF))
+            .unwrap();
+        assert_eq!(submission.output.output, [&[2], JOURNAL].concat());
+        let methods = node.methods();
+        assert!(methods.contains(&"eth_sendRawTransaction".to_string()));
+        assert!(methods.contains(&"eth_getTransactionReceipt".to_string()));
+
+        let node = self::node(true, false);
+        let client = self::client(&node).with_private_key(PRIVATE_KEY).unwrap();
+        let result = runtime.block_on(client.send_verify_with_zk_proof(
+            JOURNAL,
+            ZkCoProcessorType::RiscZero,
+            PROOF,
+        ));
+        assert!(matches!(result, Err(TdxError::Http(e)) if e.ends_with("reverted")));
+
+        let node = self::node(false, true);
+        let client = self::client(&node).with_private_key(PRIVATE_KEY).unwrap();
+        let result = runtime.block_on(client.send_verify_with_zk_proof(
+            JOURNAL,
+            ZkCoProcessorType::RiscZero,
+            PROOF,
+        ));
+        assert!(matches!(result, Err(TdxError::Dcap(_))));
+        assert_eq!(node.methods(), ["eth_call"]);
+    }
+
+    /// Verifies the test quote against a DCAP Attestation deployment on a local anvil node,
+    /// eg one forking the Automata Testnet:
+    ///
+    /// ```sh
+    /// anvil --fork-url https://1rpc.io/ata/testnet
+    /// ANVIL_RPC_URL=http://127.0.0.1:8545 cargo test -p tdx -- --ignored anvil
+    /// ```
+    ///
+    /// `DCAP_CONTRACT` overrides the address of the deployment. Transactions are sent from the
+    /// first anvil development account.
+    #[test]
+    #[ignore = "needs an anvil node with the DCAP Attestation contracts, see ANVIL_RPC_URL"]
+    fn verifies_on_anvil() {
+        let rpc_url =
+            std::env::var("ANVIL_RPC_URL").unwrap_or_else(|_| "http://127.0.0.1:8545".to_string());
+        let contract =
+            std::env::var("DCAP_CONTRACT").unwrap_or_else(|_| DEFAULT_DCAP_CONTRACT.to_string());
+        let quote = include_bytes!("../examples/testdata/tdx_v4_quote_90c06f.bin");
+        let client = DcapAttestationClient::new(&rpc_url, &contract)
+            .unwrap()
+            .with_private_key(PRIVATE_KEY)
+            .unwrap();
+        let runtime = Runtime::new().unwrap();
+
+        let output = runtime
+            .block_on(client.call_verify_on_chain(quote))
+            .unwrap();
+        let verified_output = output.verified_output().unwrap();
+        assert_eq!(verified_output.quote_version, 4);
+        assert_eq!(
+            verified_output.fmspc,
+            hex::decode("90c06f000000").unwrap()[..]
+        );
+
+        let submission = runtime
+            .block_on(client.send_verify_on_chain(quote))
+            .unwrap();
+        assert_eq!(submission.output.output, output.output);
+
+        let result = runtime.block_on(client.call_verify_with_zk_proof(
+            &[0; 4],
+            ZkCoProcessorType::RiscZero,
+            &[0; 4],
+        ));
+        assert!(!matches!(
+            result,
+            Ok(AttestationOutput { success: true, .. })
+        ));
+    }
+
+    #[test]
+    fn does_not_send_failed_verifications() {
+        let runtime = Runtime::new().unwrap();
+        let node = node(false, true);
+        let client = client(&node).with_private_key(PRIVATE_KEY).unwrap();
+        let result = runtime.block_on(client.send_verify_on_chain(QUOTE));
+        assert!(matches!(result, Err(TdxError::Dcap(_))));
+        assert_eq!(node.methods(), ["eth_call"]);
+
+        // Without a signer, the call succeeds but the transaction cannot be signed.
+        let node = self::node(true, true);
+        let result = runtime.block_on(self::client(&node).send_verify_on_chain(QUOTE));
+        assert!(matches!(result, Err(TdxError::ConfigOptions(_))));
+    }
+}
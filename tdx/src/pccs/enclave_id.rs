@@ -1,11 +1,7 @@
 use anyhow::Result;
 
-use super::{remove_prefix_if_found, DEFAULT_RPC_URL, ENCLAVE_ID_DAO_ADDRESS};
-use alloy::{
-    primitives::{Address, U256},
-    providers::ProviderBuilder,
-    sol,
-};
+use super::{parse_address, remove_prefix_if_found, PccsConfig};
+use alloy::{primitives::U256, providers::ProviderBuilder, sol};
 
 sol! {
     #[sol(rpc)]
@@ -18,23 +14,41 @@ sol! {
 
         #[derive(Debug)]
         function getEnclaveIdentity(uint256 id, uint256 version) returns (EnclaveIdentityJsonObj memory enclaveIdObj);
+
+        #[derive(Debug)]
+        function upsertEnclaveIdentity(uint256 id, uint256 version, EnclaveIdentityJsonObj calldata enclaveIdentityObj) returns (bytes32 attestationId);
     }
 }
 
 pub async fn get_enclave_identity(version: u32) -> Result<Vec<u8>> {
-    let rpc_url = DEFAULT_RPC_URL.parse().expect("Failed to parse RPC URL");
-    let provider = ProviderBuilder::new().connect_http(rpc_url);
+    get_enclave_identity_with_config(&PccsConfig::default(), version).await
+}
 
-    let enclave_id_dao_address_slice =
-        hex::decode(ENCLAVE_ID_DAO_ADDRESS).expect("Invalid address hex");
+// EnclaveIdType
+pub const ENCLAVE_ID_QE: u8 = 0;
+pub const ENCLAVE_ID_QVE: u8 = 1;
+pub const ENCLAVE_ID_TDQE: u8 = 2;
 
-    let enclave_id_dao_contract = IEnclaveIdentityDao::new(
-        Address::from_slice(&enclave_id_dao_address_slice),
-        &provider,
-    );
+pub async fn get_enclave_identity_with_config(
+    config: &PccsConfig,
+    version: u32,
+) -> Result<Vec<u8>> {
+    get_enclave_identity_by_id_with_config(config, ENCLAVE_ID_TDQE, version).await
+}
+
+/// Retrieves the identity of the `enclave_id` enclave, one of the `ENCLAVE_ID_*` constants.
+pub async fn get_enclave_identity_by_id_with_config(
+    config: &PccsConfig,
+    enclave_id: u8,
+    version: u32,
+) -> Result<Vec<u8>> {
+    let rpc_url = config.rpc_url.parse()?;
+    let provider = ProviderBuilder::new().connect_http(rpc_url);
+
+    let enclave_id_dao_contract =
+        IEnclaveIdentityDao::new(parse_address(&config.enclave_id_dao_address)?, &provider);
 
-    // EnclaveIdType::TDQE
-    let enclave_id_type_uint256 = U256::from(2);
+    let enclave_id_type_uint256 = U256::from(enclave_id);
 
     let call_builder =
         enclave_id_dao_contract.getEnclaveIdentity(enclave_id_type_uint256, U256::from(version));
@@ -46,8 +60,8 @@ pub async fn get_enclave_identity(version: u32) -> Result<Vec<u8>> {
 
     if identity_str.len() == 0 || signature_bytes.len() == 0 {
         return Err(anyhow::Error::msg(format!(
-            "QEIdentity for TDX; Version: {} is missing and must be upserted to on-chain pccs",
-            version
+            "QEIdentity for enclave ID: {}; Version: {} is missing and must be upserted to on-chain pccs",
+            enclave_id, version
         )));
     }
 
@@ -1,11 +1,7 @@
-use super::{remove_prefix_if_found, DEFAULT_RPC_URL, FMSPC_TCB_DAO_ADDRESS};
+use super::{parse_address, remove_prefix_if_found, PccsConfig};
 use anyhow::Result;
 
-use alloy::{
-    primitives::{Address, U256},
-    providers::ProviderBuilder,
-    sol,
-};
+use alloy::{primitives::U256, providers::ProviderBuilder, sol};
 
 sol! {
     #[sol(rpc)]
@@ -18,17 +14,27 @@ sol! {
 
         #[derive(Debug)]
         function getTcbInfo(uint256 tcbType, string calldata fmspc, uint256 version) returns (TcbInfoJsonObj memory tcbObj);
+
+        #[derive(Debug)]
+        function upsertFmspcTcb(TcbInfoJsonObj calldata tcbInfoObj) returns (bytes32 attestationId);
     }
 }
 
 pub async fn get_tcb_info(tcb_type: u8, fmspc: &str, version: u32) -> Result<Vec<u8>> {
-    let rpc_url = DEFAULT_RPC_URL.parse().expect("Failed to parse RPC URL");
+    get_tcb_info_with_config(&PccsConfig::default(), tcb_type, fmspc, version).await
+}
+
+pub async fn get_tcb_info_with_config(
+    config: &PccsConfig,
+    tcb_type: u8,
+    fmspc: &str,
+    version: u32,
+) -> Result<Vec<u8>> {
+    let rpc_url = config.rpc_url.parse()?;
     let provider = ProviderBuilder::new().connect_http(rpc_url);
 
-    let fmspc_tcb_dao_address_slice =
-        hex::decode(FMSPC_TCB_DAO_ADDRESS).expect("Invalid address hex");
     let fmspc_tcb_dao_contract =
-        IFmspcTcbDao::new(Address::from_slice(&fmspc_tcb_dao_address_slice), &provider);
+        IFmspcTcbDao::new(parse_address(&config.fmspc_tcb_dao_address)?, &provider);
 
     let call_builder = fmspc_tcb_dao_contract.getTcbInfo(
         U256::from(tcb_type),
@@ -1,6 +1,11 @@
 pub mod enclave_id;
 pub mod fmspc_tcb;
+pub mod pck;
 pub mod pcs;
+pub mod upsert;
+
+use alloy::primitives::Address;
+use anyhow::Result;
 
 // Chain Defaults
 pub const DEFAULT_RPC_URL: &str = "https://1rpc.io/ata/testnet";
@@ -12,6 +17,37 @@ pub const FMSPC_TCB_DAO_ADDRESS: &str = "d3A3f34E8615065704cCb5c304C0cEd41bB8148
 pub const PCS_DAO_ADDRESS: &str = "B270cD8550DA117E3accec36A90c4b0b48daD342";
 pub const PCK_DAO_ADDRESS: &str = "a4615C2a260413878241ff7605AD9577feB356A5";
 
+/// Location of the on-chain PCCS.
+/// Defaults to the Automata Testnet deployment.
+#[derive(Clone, Debug)]
+pub struct PccsConfig {
+    pub rpc_url: String,
+    pub enclave_id_dao_address: String,
+    pub fmspc_tcb_dao_address: String,
+    pub pcs_dao_address: String,
+    pub pck_dao_address: String,
+}
+
+impl Default for PccsConfig {
+    fn default() -> Self {
+        PccsConfig {
+            rpc_url: DEFAULT_RPC_URL.to_string(),
+            enclave_id_dao_address: ENCLAVE_ID_DAO_ADDRESS.to_string(),
+            fmspc_tcb_dao_address: FMSPC_TCB_DAO_ADDRESS.to_string(),
+            pcs_dao_address: PCS_DAO_ADDRESS.to_string(),
+            pck_dao_address: PCK_DAO_ADDRESS.to_string(),
+        }
+    }
+}
+
+pub fn parse_address(h: &str) -> Result<Address> {
+    let address_slice = hex::decode(remove_prefix_if_found(h))?;
+    if address_slice.len() != 20 {
+        return Err(anyhow::Error::msg(format!("Invalid address: {}", h)));
+    }
+    Ok(Address::from_slice(&address_slice))
+}
+
 pub fn remove_prefix_if_found(h: &str) -> &str {
     if h.starts_with("0x") {
         &h[2..]
@@ -0,0 +1,51 @@
+use super::{parse_address, PccsConfig};
+use alloy::{providers::ProviderBuilder, sol};
+use anyhow::Result;
+
+sol! {
+    #[sol(rpc)]
+    interface IPckDao {
+        #[derive(Debug)]
+        function getCert(string calldata qeid, string calldata platformCpuSvn, string calldata platformPceSvn, string calldata pceid) external view returns (bytes memory pckCert);
+    }
+}
+
+/// Retrieves the DER encoded PCK certificate of a platform, identified by hex strings of the
+/// QE ID, the raw CPUSVN, and the little endian PCESVN and PCE ID.
+pub async fn get_pck_certificate(
+    qeid: &str,
+    cpusvn: &str,
+    pcesvn: &str,
+    pceid: &str,
+) -> Result<Vec<u8>> {
+    get_pck_certificate_with_config(&PccsConfig::default(), qeid, cpusvn, pcesvn, pceid).await
+}
+
+pub async fn get_pck_certificate_with_config(
+    config: &PccsConfig,
+    qeid: &str,
+    cpusvn: &str,
+    pcesvn: &str,
+    pceid: &str,
+) -> Result<Vec<u8>> {
+    let rpc_url = config.rpc_url.parse()?;
+    let provider = ProviderBuilder::new().connect_http(rpc_url);
+
+    let pck_dao_contract = IPckDao::new(parse_address(&config.pck_dao_address)?, &provider);
+
+    let call_builder = pck_dao_contract.getCert(
+        String::from(qeid),
+        String::from(cpusvn),
+        String::from(pcesvn),
+        String::from(pceid),
+    );
+
+    let pck_cert = call_builder.call().await?.to_vec();
+    if pck_cert.is_empty() {
+        return Err(anyhow::Error::msg(format!(
+            "PCK certificate for QEID: {}; CPUSVN: {}; PCESVN: {}; PCEID: {} is missing and must be upserted to on-chain pccs",
+            qeid, cpusvn, pcesvn, pceid
+        )));
+    }
+    Ok(pck_cert)
+}
@@ -1,5 +1,5 @@
-use super::{DEFAULT_RPC_URL, PCS_DAO_ADDRESS};
-use alloy::{primitives::Address, providers::ProviderBuilder, sol};
+use super::{parse_address, PccsConfig};
+use alloy::{providers::ProviderBuilder, sol};
 use anyhow::Result;
 
 sol! {
@@ -15,15 +15,27 @@ sol! {
 
         #[derive(Debug)]
         function getCertificateById(CA ca) external view returns (bytes memory cert, bytes memory crl);
+
+        #[derive(Debug)]
+        function upsertPckCrl(CA ca, bytes calldata crl) external returns (bytes32 attestationId);
+
+        #[derive(Debug)]
+        function upsertRootCACrl(bytes calldata rootcacrl) external returns (bytes32 attestationId);
     }
 }
 
 pub async fn get_certificate_by_id(ca_id: IPCSDao::CA) -> Result<(Vec<u8>, Vec<u8>)> {
-    let rpc_url = DEFAULT_RPC_URL.parse().expect("Failed to parse RPC URL");
+    get_certificate_by_id_with_config(&PccsConfig::default(), ca_id).await
+}
+
+pub async fn get_certificate_by_id_with_config(
+    config: &PccsConfig,
+    ca_id: IPCSDao::CA,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let rpc_url = config.rpc_url.parse()?;
     let provider = ProviderBuilder::new().connect_http(rpc_url);
 
-    let pcs_dao_address_slice = hex::decode(PCS_DAO_ADDRESS).expect("invalid address hex");
-    let pcs_dao_contract = IPCSDao::new(Address::from_slice(&pcs_dao_address_slice), &provider);
+    let pcs_dao_contract = IPCSDao::new(parse_address(&config.pcs_dao_address)?, &provider);
 
     let call_builder = pcs_dao_contract.getCertificateById(ca_id);
 
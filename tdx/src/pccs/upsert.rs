@@ -0,0 +1,572 @@
+//! Upserts collaterals to the on-chain PCCS, so that quotes of a new FMSPC can be verified.
+//!
+//! The DAOs check the signatures of the TCB Info and enclave identities, and that the CRLs
+//! are issued by the certificates already stored on-chain.
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use alloy::network::EthereumWallet;
+use alloy::primitives::{Bytes, TxHash, U256};
+use alloy::providers::ProviderBuilder;
+use alloy::signers::local::PrivateKeySigner;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+use super::enclave_id::{
+    get_enclave_identity_by_id_with_config, IEnclaveIdentityDao, ENCLAVE_ID_QE, ENCLAVE_ID_QVE,
+    ENCLAVE_ID_TDQE,
+};
+use super::fmspc_tcb::{get_tcb_info_with_config, IFmspcTcbDao};
+use super::pcs::{get_certificate_by_id_with_config, IPCSDao, IPCSDao::CA};
+use super::{parse_address, PccsConfig};
+use crate::collateral::{
+    PCK_PLATFORM_CRL_FILE, PCK_PROCESSOR_CRL_FILE, QE_IDENTITY_FILE, ROOT_CA_CRL_FILE,
+    TCB_INFO_FILE,
+};
+use crate::intel_pcs;
+
+/// Version of the enclave identities served by the Intel PCS, for V3 and V4 quotes.
+const ENCLAVE_IDENTITY_VERSION: u32 = 2;
+
+/// Where to get the collaterals missing on-chain from.
+#[derive(Clone, Debug)]
+pub enum CollateralOrigin {
+    /// The Intel PCS.
+    IntelPcs,
+    /// Collaterals written with `Collaterals::write_to_dir`, or downloaded from the Intel PCS
+    /// with the same file names.
+    Dir(PathBuf),
+}
+
+/// The collaterals required to verify the quotes of an FMSPC.
+#[derive(Clone, Debug)]
+pub struct UpsertTarget {
+    pub fmspc: String,
+    /// 0: SGX, 1: TDX
+    pub tcb_type: u8,
+    pub tcb_info_version: u32,
+    /// One of the `ENCLAVE_ID_*` constants.
+    pub enclave_id: u8,
+    /// The quote version.
+    pub enclave_identity_version: u32,
+    pub pck_ca: CA,
+}
+
+impl UpsertTarget {
+    /// The collaterals of TDX (V4) quotes.
+    pub fn tdx(fmspc: &str, pck_ca: CA) -> Self {
+        UpsertTarget {
+            fmspc: fmspc.to_lowercase(),
+            tcb_type: 1,
+            tcb_info_version: 3,
+            enclave_id: ENCLAVE_ID_TDQE,
+            enclave_identity_version: 4,
+            pck_ca,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpsertedCollateral {
+    pub collateral: String,
+    /// `None` when the collateral was already stored on-chain.
+    pub tx_hash: Option<TxHash>,
+}
+
+/// Upserts the collaterals of `target` that are missing from the on-chain PCCS, taken from
+/// `origin`, in transactions signed by `signer`. With `force`, every collateral is upserted,
+/// eg to replace an outdated TCB Info.
+pub async fn upsert_missing_collaterals(
+    config: &PccsConfig,
+    signer: &PrivateKeySigner,
+    origin: &CollateralOrigin,
+    target: &UpsertTarget,
+    force: bool,
+) -> Result<Vec<UpsertedCollateral>> {
+    // The DAOs store a TCB Info under its own FMSPC, and an enclave identity under the ID it
+    // is upserted with, whatever the target.
+    if let CollateralOrigin::Dir(dir) = origin {
+        check_tcb_info(&std::fs::read(dir.join(TCB_INFO_FILE))?, target)?;
+        check_qe_identity(&std::fs::read(dir.join(QE_IDENTITY_FILE))?, target)?;
+    }
+
+    let mut upserted = Vec::new();
+
+    let (_, root_ca_crl) = get_certificate_by_id_with_config(config, CA::ROOT).await?;
+    let tx_hash = if force || root_ca_crl.is_empty() {
+        let crl = match origin {
+            CollateralOrigin::IntelPcs => fetch(intel_pcs::get_root_ca_crl).await?,
+            CollateralOrigin::Dir(dir) => std::fs::read(dir.join(ROOT_CA_CRL_FILE))?,
+        };
+        Some(upsert_root_ca_crl_with_config(config, signer, &crl).await?)
+    } else {
+        None
+    };
+    upserted.push(UpsertedCollateral {
+        collateral: "rootCaCrl".to_string(),
+        tx_hash,
+    });
+
+    let (_, pck_crl) = get_certificate_by_id_with_config(config, target.pck_ca).await?;
+    let tx_hash = if force || pck_crl.is_empty() {
+        let crl = match origin {
+            CollateralOrigin::IntelPcs => {
+                let ca = target.pck_ca;
+                fetch(move || intel_pcs::get_pck_crl(ca)).await?
+            }
+            CollateralOrigin::Dir(dir) => {
+                let file = match target.pck_ca {
+                    CA::PROCESSOR => PCK_PROCESSOR_CRL_FILE,
+                    _ => PCK_PLATFORM_CRL_FILE,
+                };
+                std::fs::read(dir.join(file))?
+            }
+        };
+        Some(upsert_pck_crl_with_config(config, signer, target.pck_ca, &crl).await?)
+    } else {
+        None
+    };
+    upserted.push(UpsertedCollateral {
+        collateral: "pckCrl".to_string(),
+        tx_hash,
+    });
+
+    // The getters fail when the collateral is missing.
+    let tcb_info_missing = get_tcb_info_with_config(
+        config,
+        target.tcb_type,
+        &target.fmspc,
+        target.tcb_info_version,
+    )
+    .await
+    .is_err();
+    let tx_hash = if force || tcb_info_missing {
+        let tcb_info = match origin {
+            CollateralOrigin::IntelPcs => {
+                let (tcb_type, fmspc, version) = (
+                    target.tcb_type,
+                    target.fmspc.clone(),
+                    target.tcb_info_version,
+                );
+                fetch(move || intel_pcs::get_tcb_info(tcb_type, &fmspc, version)).await?
+            }
+            CollateralOrigin::Dir(dir) => std::fs::read(dir.join(TCB_INFO_FILE))?,
+        };
+        Some(upsert_tcb_info_with_config(config, signer, &tcb_info).await?)
+    } else {
+        None
+    };
+    upserted.push(UpsertedCollateral {
+        collateral: "tcbInfo".to_string(),
+        tx_hash,
+    });
+
+    let identity_missing = get_enclave_identity_by_id_with_config(
+        config,
+        target.enclave_id,
+        target.enclave_identity_version,
+    )
+    .await
+    .is_err();
+    let tx_hash = if force || identity_missing {
+        let identity = match origin {
+            CollateralOrigin::IntelPcs => {
+                let (enclave_id, version) = (target.enclave_id, target.enclave_identity_version);
+                fetch(move || intel_pcs::get_enclave_identity(enclave_id, version)).await?
+            }
+            CollateralOrigin::Dir(dir) => std::fs::read(dir.join(QE_IDENTITY_FILE))?,
+        };
+        Some(
+            upsert_enclave_identity_with_config(
+                config,
+                signer,
+                target.enclave_id,
+                target.enclave_identity_version,
+                &identity,
+            )
+            .await?,
+        )
+    } else {
+        None
+    };
+    upserted.push(UpsertedCollateral {
+        collateral: "qeIdentity".to_string(),
+        tx_hash,
+    });
+
+    Ok(upserted)
+}
+
+/// Runs a request to the Intel PCS, which blocks, on the blocking thread pool.
+async fn fetch(
+    request: impl FnOnce() -> crate::error::Result<Vec<u8>> + Send + 'static,
+) -> Result<Vec<u8>> {
+    Ok(tokio::task::spawn_blocking(request).await??)
+}
+
+/// Upserts a signed TCB Info, ie `{"tcbInfo": {...}, "signature": "..."}`.
+pub async fn upsert_tcb_info_with_config(
+    config: &PccsConfig,
+    signer: &PrivateKeySigner,
+    signed_tcb_info: &[u8],
+) -> Result<TxHash> {
+    let (tcb_info_str, signature) = split_signed_json(signed_tcb_info, "tcbInfo")?;
+
+    let rpc_url = config.rpc_url.parse()?;
+    let provider = ProviderBuilder::new()
+        .wallet(EthereumWallet::from(signer.clone()))
+        .connect_http(rpc_url);
+    let fmspc_tcb_dao_contract =
+        IFmspcTcbDao::new(parse_address(&config.fmspc_tcb_dao_address)?, &provider);
+
+    let receipt = fmspc_tcb_dao_contract
+        .upsertFmspcTcb(IFmspcTcbDao::TcbInfoJsonObj {
+            tcbInfoStr: tcb_info_str,
+            signature: signature.into(),
+        })
+        .send()
+        .await?
+        .get_receipt()
+        .await?;
+    if !receipt.status() {
+        bail!("TCBInfo upsert {} reverted", receipt.transaction_hash);
+    }
+    Ok(receipt.transaction_hash)
+}
+
+/// Upserts a signed enclave identity, ie `{"enclaveIdentity": {...}, "signature": "..."}`,
+/// for the `enclave_id` enclave and the quote `version`.
+pub async fn upsert_enclave_identity_with_config(
+    config: &PccsConfig,
+    signer: &PrivateKeySigner,
+    enclave_id: u8,
+    version: u32,
+    signed_identity: &[u8],
+) -> Result<TxHash> {
+    let (identity_str, signature) = split_signed_json(signed_identity, "enclaveIdentity")?;
+
+    let rpc_url = config.rpc_url.parse()?;
+    let provider = ProviderBuilder::new()
+        .wallet(EthereumWallet::from(signer.clone()))
+        .connect_http(rpc_url);
+    let enclave_id_dao_contract =
+        IEnclaveIdentityDao::new(parse_address(&config.enclave_id_dao_address)?, &provider);
+
+    let receipt = enclave_id_dao_contract
+        .upsertEnclaveIdentity(
+            U256::from(enclave_id),
+            U256::from(version),
+            IEnclaveIdentityDao::EnclaveIdentityJsonObj {
+                identityStr: identity_str,
+                signature: signature.into(),
+            },
+        )
+        .send()
+        .await?
+        .get_receipt()
+        .await?;
+    if !receipt.status() {
+        bail!("QEIdentity upsert {} reverted", receipt.transaction_hash);
+    }
+    Ok(receipt.transaction_hash)
+}
+
+/// Upserts the DER encoded CRL of the PCK Platform or Processor CA.
+pub async fn upsert_pck_crl_with_config(
+    config: &PccsConfig,
+    signer: &PrivateKeySigner,
+    ca: CA,
+    crl: &[u8],
+) -> Result<TxHash> {
+    let rpc_url = config.rpc_url.parse()?;
+    let provider = ProviderBuilder::new()
+        .wallet(EthereumWallet::from(signer.clone()))
+        .connect_http(rpc_url);
+    let pcs_dao_contract = IPCSDao::new(parse_address(&config.pcs_dao_address)?, &provider);
+
+    let receipt = pcs_dao_contract
+        .upsertPckCrl(ca, Bytes::copy_from_slice(crl))
+        .send()
+        .await?
+        .get_receipt()
+        .await?;
+    if !receipt.status() {
+        bail!("PCK CRL upsert {} reverted", receipt.transaction_hash);
+    }
+    Ok(receipt.transaction_hash)
+}
+
+/// Upserts the DER encoded CRL of the Intel SGX Root CA.
+pub async fn upsert_root_ca_crl_with_config(
+    config: &PccsConfig,
+    signer: &PrivateKeySigner,
+    crl: &[u8],
+) -> Result<TxHash> {
+    let rpc_url = config.rpc_url.parse()?;
+    let provider = ProviderBuilder::new()
+        .wallet(EthereumWallet::from(signer.clone()))
+        .connect_http(rpc_url);
+    let pcs_dao_contract = IPCSDao::new(parse_address(&config.pcs_dao_address)?, &provider);
+
+    let receipt = pcs_dao_contract
+        .upsertRootCACrl(Bytes::copy_from_slice(crl))
+        .send()
+        .await?
+        .get_receipt()
+        .await?;
+    if !receipt.status() {
+        bail!("Root CA CRL upsert {} reverted", receipt.transaction_hash);
+    }
+    Ok(receipt.transaction_hash)
+}
+
+/// The fields of a TCB Info identifying it on-chain.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TcbInfoKey {
+    /// Missing from the TCB Info V2, which are all SGX ones.
+    id: Option<String>,
+    version: u32,
+    fmspc: String,
+}
+
+/// Checks that a signed TCB Info is the one of `target`.
+fn check_tcb_info(signed_tcb_info: &[u8], target: &UpsertTarget) -> Result<()> {
+    let (tcb_info, _) = split_signed_json(signed_tcb_info, "tcbInfo")?;
+    let key: TcbInfoKey = serde_json::from_str(&tcb_info)?;
+    let id = key.id.as_deref().unwrap_or("SGX");
+    let expected_id = match target.tcb_type {
+        0 => "SGX",
+        _ => "TDX",
+    };
+    if !key.fmspc.eq_ignore_ascii_case(&target.fmspc)
+        || id != expected_id
+        || key.version != target.tcb_info_version
+    {
+        bail!(
+            "The TCB Info is the {} V{} one of FMSPC {}, not the {} V{} one of FMSPC {}",
+            id,
+            key.version,
+            key.fmspc,
+            expected_id,
+            target.tcb_info_version,
+            target.fmspc.to_uppercase()
+        );
+    }
+    Ok(())
+}
+
+/// The fields of an enclave identity identifying it on-chain, with the `enclave_id` it is
+/// upserted with.
+#[derive(Deserialize)]
+struct EnclaveIdentityKey {
+    id: String,
+    version: u32,
+}
+
+/// Checks that a signed enclave identity is the one of `target`. The identities of the V3
+/// and V4 quotes are all V2 enclave identities.
+fn check_qe_identity(signed_identity: &[u8], target: &UpsertTarget) -> Result<()> {
+    let (identity, _) = split_signed_json(signed_identity, "enclaveIdentity")?;
+    let key: EnclaveIdentityKey = serde_json::from_str(&identity)?;
+    let expected_id = match target.enclave_id {
+        ENCLAVE_ID_QE => "QE",
+        ENCLAVE_ID_QVE => "QVE",
+        _ => "TD_QE",
+    };
+    if key.id != expected_id || key.version != ENCLAVE_IDENTITY_VERSION {
+        bail!(
+            "The enclave identity is the {} V{} one, not the {} V{} one",
+            key.id,
+            key.version,
+            expected_id,
+            ENCLAVE_IDENTITY_VERSION
+        );
+    }
+    Ok(())
+}
+
+/// Splits a signed collateral into the JSON string of `field`, exactly as signed, and the
+/// signature.
+fn split_signed_json(json: &[u8], field: &str) -> Result<(String, Vec<u8>)> {
+    let values: HashMap<String, Box<RawValue>> = serde_json::from_slice(json)?;
+    let Some(body) = values.get(field) else {
+        bail!("Missing {} in the signed collateral", field);
+    };
+    let Some(signature) = values.get("signature") else {
+        bail!("Missing signature in the signed collateral");
+    };
+    let signature: String = serde_json::from_str(signature.get())?;
+    Ok((
+        body.get().to_string(),
+        hex::decode(super::remove_prefix_if_found(&signature))?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rpc::{
+        call_input, data, raw_transaction_input, transaction, TestRpc, PRIVATE_KEY,
+    };
+    use alloy::sol_types::SolCall;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use tokio::runtime::Runtime;
+    use IEnclaveIdentityDao::{getEnclaveIdentityCall, upsertEnclaveIdentityCall};
+    use IFmspcTcbDao::{getTcbInfoCall, upsertFmspcTcbCall};
+    use IPCSDao::{getCertificateByIdCall, upsertPckCrlCall, upsertRootCACrlCall};
+
+    fn dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/testdata/collaterals")
+    }
+
+    /// A PCCS where every collateral is stored, or none is. Returns the node and the calldata
+    /// of the transactions sent to it.
+    fn pccs(stored: bool) -> (TestRpc, Arc<Mutex<Vec<Vec<u8>>>>) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let transactions = sent.clone();
+        let node = TestRpc::start(move |method, params| match method {
+            "eth_call" => {
+                let input = call_input(params);
+                let bytes = |bytes: &[u8]| Bytes::copy_from_slice(if stored { bytes } else { &[] });
+                let json = if stored { "{}" } else { "" }.to_string();
+                let output = match input[..4].try_into().unwrap() {
+                    getCertificateByIdCall::SELECTOR => getCertificateByIdCall::abi_encode_returns(
+                        &IPCSDao::getCertificateByIdReturn {
+                            cert: bytes(&[1]),
+                            crl: bytes(&[1]),
+                        },
+                    ),
+                    getTcbInfoCall::SELECTOR => {
+                        getTcbInfoCall::abi_encode_returns(&IFmspcTcbDao::TcbInfoJsonObj {
+                            tcbInfoStr: json,
+                            signature: bytes(&[1]),
+                        })
+                    }
+                    getEnclaveIdentityCall::SELECTOR => getEnclaveIdentityCall::abi_encode_returns(
+                        &IEnclaveIdentityDao::EnclaveIdentityJsonObj {
+                            identityStr: json,
+                            signature: bytes(&[1]),
+                        },
+                    ),
+                    selector => return Err(format!("unexpected call {}", hex::encode(selector))),
+                };
+                Ok(data(&output))
+            }
+            "eth_sendRawTransaction" => {
+                transactions
+                    .lock()
+                    .unwrap()
+                    .push(raw_transaction_input(params));
+                Ok(transaction(method, params, true).unwrap())
+            }
+            _ => transaction(method, params, true).ok_or(format!("unexpected {}", method)),
+        });
+        (node, sent)
+    }
+
+    fn upsert(
+        node: &TestRpc,
+        target: &UpsertTarget,
+        force: bool,
+    ) -> Result<Vec<UpsertedCollateral>> {
+        let config = PccsConfig {
+            rpc_url: node.url().to_string(),
+            ..Default::default()
+        };
+        let signer: PrivateKeySigner = PRIVATE_KEY.parse().unwrap();
+        Runtime::new().unwrap().block_on(upsert_missing_collaterals(
+            &config,
+            &signer,
+            &CollateralOrigin::Dir(dir()),
+            target,
+            force,
+        ))
+    }
+
+    #[test]
+    fn upserts_missing_collaterals() {
+        let (node, sent) = pccs(false);
+        let target = UpsertTarget::tdx("90C06F000000", CA::PLATFORM);
+        let upserted = upsert(&node, &target, false).unwrap();
+        let collaterals: Vec<_> = upserted.iter().map(|c| c.collateral.as_str()).collect();
+        assert_eq!(
+            collaterals,
+            ["rootCaCrl", "pckCrl", "tcbInfo", "qeIdentity"]
+        );
+        assert!(upserted.iter().all(|c| c.tx_hash.is_some()));
+
+        let read = |file| std::fs::read(dir().join(file)).unwrap();
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 4);
+        let call = upsertRootCACrlCall::abi_decode(&sent[0]).unwrap();
+        assert_eq!(call.rootcacrl, read(ROOT_CA_CRL_FILE));
+        let call = upsertPckCrlCall::abi_decode(&sent[1]).unwrap();
+        assert!(matches!(call.ca, CA::PLATFORM));
+        assert_eq!(call.crl, read(PCK_PLATFORM_CRL_FILE));
+        let call = upsertFmspcTcbCall::abi_decode(&sent[2]).unwrap();
+        let (tcb_info, signature) = split_signed_json(&read(TCB_INFO_FILE), "tcbInfo").unwrap();
+        assert_eq!(call.tcbInfoObj.tcbInfoStr, tcb_info);
+        assert_eq!(call.tcbInfoObj.signature, signature);
+        let call = upsertEnclaveIdentityCall::abi_decode(&sent[3]).unwrap();
+        assert_eq!(call.id, U256::from(ENCLAVE_ID_TDQE));
+        assert_eq!(call.version, U256::from(4));
+    }
+
+    #[test]
+    fn skips_stored_collaterals() {
+        let (node, sent) = pccs(true);
+        let target = UpsertTarget::tdx("90C06F000000", CA::PLATFORM);
+        let upserted = upsert(&node, &target, false).unwrap();
+        assert_eq!(upserted.len(), 4);
+        assert!(upserted.iter().all(|c| c.tx_hash.is_none()));
+        assert!(sent.lock().unwrap().is_empty());
+
+        let upserted = upsert(&node, &target, true).unwrap();
+        assert!(upserted.iter().all(|c| c.tx_hash.is_some()));
+        assert_eq!(sent.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn rejects_tcb_infos_of_other_platforms() {
+        let (node, sent) = pccs(false);
+        let target = UpsertTarget::tdx("00806F050000", CA::PLATFORM);
+        let err = upsert(&node, &target, false).unwrap_err();
+        assert!(err.to_string().contains("FMSPC 90C06F000000"), "{}", err);
+
+        let target = UpsertTarget {
+            tcb_type: 0,
+            tcb_info_version: 2,
+            ..UpsertTarget::tdx("90C06F000000", CA::PLATFORM)
+        };
+        assert!(upsert(&node, &target, true).is_err());
+        assert!(node.methods().is_empty());
+        assert!(sent.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_identities_of_other_enclaves() {
+        let (node, sent) = pccs(false);
+        for enclave_id in [ENCLAVE_ID_QE, ENCLAVE_ID_QVE] {
+            let target = UpsertTarget {
+                enclave_id,
+                ..UpsertTarget::tdx("90C06F000000", CA::PLATFORM)
+            };
+            let err = upsert(&node, &target, true).unwrap_err();
+            assert!(err.to_string().contains("TD_QE V2 one, not"), "{}", err);
+        }
+        assert!(node.methods().is_empty());
+        assert!(sent.lock().unwrap().is_empty());
+
+        let identity = std::fs::read(dir().join(QE_IDENTITY_FILE)).unwrap();
+        let target = UpsertTarget::tdx("90C06F000000", CA::PLATFORM);
+        check_qe_identity(&identity, &target).unwrap();
+        let v3 = String::from_utf8(identity)
+            .unwrap()
+            .replacen("\"version\":2", "\"version\":3", 1);
+        assert!(check_qe_identity(v3.as_bytes(), &target).is_err());
+    }
+}
@@ -0,0 +1,337 @@
+use crate::error::{Result, TdxError};
+use serde::Serialize;
+use std::borrow::Cow;
+use x509_parser::oid_registry::asn1_rs::{
+    oid, Any, Boolean, Enumerated, FromDer, OctetString, Oid, Sequence,
+};
+use x509_parser::prelude::*;
+
+/// OID of the Intel SGX extensions embedded in a PCK certificate.
+const SGX_EXTENSIONS_OID: Oid<'static> = oid!(1.2.840 .113741 .1 .13 .1);
+
+/// The SGX extensions of a PCK certificate.
+///
+/// Refer to the Intel SGX PCK Certificate and Certificate Revocation List Profile Specification
+/// for the definition of each field.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct SgxExtensions {
+    /// Platform Provisioning ID.
+    #[serde(with = "hex_bytes")]
+    pub ppid: [u8; 16],
+    /// TCB level of the platform the PCK certificate was issued for.
+    pub tcb: PckTcb,
+    /// PCE ID.
+    #[serde(with = "hex_bytes")]
+    pub pce_id: [u8; 2],
+    /// Family-Model-Stepping-Platform-CustomSKU.
+    #[serde(with = "hex_bytes")]
+    pub fmspc: [u8; 6],
+    pub sgx_type: SgxType,
+    /// Only present in PCK certificates issued by the Intel SGX PCK Platform CA.
+    #[serde(with = "hex_bytes::option")]
+    pub platform_instance_id: Option<[u8; 16]>,
+    /// Only present in PCK certificates issued by the Intel SGX PCK Platform CA.
+    pub configuration: Option<PckConfiguration>,
+}
+
+/// The TCB sequence of the SGX extensions.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct PckTcb {
+    /// SVNs of the 16 SGX TCB components.
+    pub sgxtcbcomponents: [u8; 16],
+    pub pcesvn: u16,
+    #[serde(with = "hex_bytes")]
+    pub cpusvn: [u8; 16],
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum SgxType {
+    Standard,
+    Scalable,
+    ScalableWithIntegrity,
+}
+
+/// The Configuration sequence of the SGX extensions.
+/// Each flag is optional and is only reported when the platform provides it.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct PckConfiguration {
+    pub dynamic_platform: Option<bool>,
+    pub cached_keys: Option<bool>,
+    pub smt_enabled: Option<bool>,
+}
+
+impl SgxExtensions {
+    /// Parses the SGX extensions from a PCK certificate.
+    pub fn from_cert<'a>(cert: &'a X509Certificate<'a>) -> Result<Self> {
+        let extension = cert
+            .get_extension_unique(&SGX_EXTENSIONS_OID)
+            .map_err(|e| TdxError::X509(format!("{:?}", e)))?
+            .ok_or_else(|| TdxError::X509("SGX extensions not found".to_string()))?;
+        Self::from_der(extension.value)
+    }
+
+    /// Parses the DER encoded value of the SGX extensions.
+    pub fn from_der(bytes: &[u8]) -> Result<Self> {
+        let mut ppid = None;
+        let mut tcb = None;
+        let mut pce_id = None;
+        let mut fmspc = None;
+        let mut sgx_type = None;
+        let mut platform_instance_id = None;
+        let mut configuration = None;
+
+        for (oid, value) in parse_sequence_of_pairs(bytes)? {
+            match oid.to_id_string().as_str() {
+                "1.2.840.113741.1.13.1.1" => ppid = Some(parse_octets(value)?),
+                "1.2.840.113741.1.13.1.2" => tcb = Some(parse_tcb(value)?),
+                "1.2.840.113741.1.13.1.3" => pce_id = Some(parse_octets(value)?),
+                "1.2.840.113741.1.13.1.4" => fmspc = Some(parse_octets(value)?),
+                "1.2.840.113741.1.13.1.5" => {
+                    let sgx_type_value = Enumerated::try_from(value)
+                        .map_err(|e| TdxError::X509(format!("SGX Type: {:?}", e)))?;
+                    sgx_type = Some(match sgx_type_value.0 {
+                        0 => SgxType::Standard,
+                        1 => SgxType::Scalable,
+                        2 => SgxType::ScalableWithIntegrity,
+                        v => return Err(TdxError::X509(format!("Unknown SGX Type: {}", v))),
+                    });
+                }
+                "1.2.840.113741.1.13.1.6" => platform_instance_id = Some(parse_octets(value)?),
+                "1.2.840.113741.1.13.1.7" => configuration = Some(parse_configuration(value)?),
+                _ => continue,
+            }
+        }
+
+        Ok(SgxExtensions {
+            ppid: ppid.ok_or_else(|| missing("PPID"))?,
+            tcb: tcb.ok_or_else(|| missing("TCB"))?,
+            pce_id: pce_id.ok_or_else(|| missing("PCE-ID"))?,
+            fmspc: fmspc.ok_or_else(|| missing("FMSPC"))?,
+            sgx_type: sgx_type.ok_or_else(|| missing("SGX Type"))?,
+            platform_instance_id,
+            configuration,
+        })
+    }
+}
+
+fn missing(field: &str) -> TdxError {
+    TdxError::X509(format!("{} is missing from the SGX extensions", field))
+}
+
+/// Every SGX extension is encoded as a SEQUENCE of SEQUENCE { OID, value }.
+/// Returns the (OID, value) pairs in the order they were found.
+fn parse_sequence_of_pairs<'a>(bytes: &'a [u8]) -> Result<Vec<(Oid<'a>, Any<'a>)>> {
+    let (rest, sequence) =
+        Sequence::from_der(bytes).map_err(|e| TdxError::X509(format!("{:?}", e)))?;
+    if !rest.is_empty() {
+        return Err(TdxError::X509(
+            "Trailing bytes after SGX extensions".to_string(),
+        ));
+    }
+    parse_pairs(sequence_content(sequence)?)
+}
+
+/// Same as `parse_sequence_of_pairs`, for a value that has already been parsed as `Any`.
+fn parse_nested_pairs<'a>(value: Any<'a>) -> Result<Vec<(Oid<'a>, Any<'a>)>> {
+    let sequence = Sequence::try_from(value).map_err(|e| TdxError::X509(format!("{:?}", e)))?;
+    parse_pairs(sequence_content(sequence)?)
+}
+
+fn parse_pairs<'a>(content: &'a [u8]) -> Result<Vec<(Oid<'a>, Any<'a>)>> {
+    let mut pairs = Vec::new();
+    let mut i = content;
+    while !i.is_empty() {
+        let (j, current_sequence) =
+            Sequence::from_der(i).map_err(|e| TdxError::X509(format!("{:?}", e)))?;
+        i = j;
+        let (k, current_oid) = Oid::from_der(sequence_content(current_sequence)?)
+            .map_err(|e| TdxError::X509(format!("{:?}", e)))?;
+        let (k, value) = Any::from_der(k).map_err(|e| TdxError::X509(format!("{:?}", e)))?;
+        if !k.is_empty() {
+            return Err(TdxError::X509(format!(
+                "Trailing bytes after SGX extension {}",
+                current_oid.to_id_string()
+            )));
+        }
+        pairs.push((current_oid, value));
+    }
+    Ok(pairs)
+}
+
+/// DER parsing never copies, so the content of a SEQUENCE always borrows from the input.
+fn sequence_content<'a>(sequence: Sequence<'a>) -> Result<&'a [u8]> {
+    match sequence.content {
+        Cow::Borrowed(content) => Ok(content),
+        Cow::Owned(_) => Err(TdxError::X509(
+            "Unexpected owned SEQUENCE content".to_string(),
+        )),
+    }
+}
+
+fn parse_octets<const N: usize>(value: Any) -> Result<[u8; N]> {
+    let octets = OctetString::try_from(value).map_err(|e| TdxError::X509(format!("{:?}", e)))?;
+    octets.as_ref().try_into().map_err(|_| {
+        TdxError::X509(format!(
+            "Expected {} bytes, found {}",
+            N,
+            octets.as_ref().len()
+        ))
+    })
+}
+
+fn parse_tcb(value: Any) -> Result<PckTcb> {
+    let mut sgxtcbcomponents = [0u8; 16];
+    let mut pcesvn = None;
+    let mut cpusvn = None;
+    let mut components_found = 0;
+
+    for (oid, value) in parse_nested_pairs(value)? {
+        let id = oid.to_id_string();
+        let index = id
+            .strip_prefix("1.2.840.113741.1.13.1.2.")
+            .and_then(|s| s.parse::<usize>().ok());
+        match index {
+            Some(n @ 1..=16) => {
+                sgxtcbcomponents[n - 1] = u8::try_from(value)
+                    .map_err(|e| TdxError::X509(format!("TCB component {}: {:?}", n, e)))?;
+                components_found += 1;
+            }
+            Some(17) => {
+                pcesvn = Some(
+                    u16::try_from(value).map_err(|e| TdxError::X509(format!("PCESVN: {:?}", e)))?,
+                );
+            }
+            Some(18) => cpusvn = Some(parse_octets(value)?),
+            _ => continue,
+        }
+    }
+
+    if components_found != 16 {
+        return Err(TdxError::X509(format!(
+            "Expected 16 TCB components, found {}",
+            components_found
+        )));
+    }
+
+    Ok(PckTcb {
+        sgxtcbcomponents,
+        pcesvn: pcesvn.ok_or_else(|| missing("PCESVN"))?,
+        cpusvn: cpusvn.ok_or_else(|| missing("CPUSVN"))?,
+    })
+}
+
+fn parse_configuration(value: Any) -> Result<PckConfiguration> {
+    let mut configuration = PckConfiguration::default();
+    for (oid, value) in parse_nested_pairs(value)? {
+        let flag = Boolean::try_from(value)
+            .map_err(|e| TdxError::X509(format!("Configuration: {:?}", e)))?
+            .bool();
+        match oid.to_id_string().as_str() {
+            "1.2.840.113741.1.13.1.7.1" => configuration.dynamic_platform = Some(flag),
+            "1.2.840.113741.1.13.1.7.2" => configuration.cached_keys = Some(flag),
+            "1.2.840.113741.1.13.1.7.3" => configuration.smt_enabled = Some(flag),
+            _ => continue,
+        }
+    }
+    Ok(configuration)
+}
+
+/// Serializes fixed size byte arrays as hex strings.
+pub(crate) mod hex_bytes {
+    use serde::Serializer;
+
+    pub fn serialize<S: Serializer, T: AsRef<[u8]>>(bytes: &T, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&hex::encode(bytes))
+    }
+
+    pub mod option {
+        use serde::Serializer;
+
+        pub fn serialize<S: Serializer, T: AsRef<[u8]>>(
+            bytes: &Option<T>,
+            s: S,
+        ) -> Result<S::Ok, S::Error> {
+            match bytes {
+                Some(b) => s.serialize_some(&hex::encode(b)),
+                None => s.serialize_none(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The PCK certificate embedded in `tdx_v4_quote.bin`.
+    const PCK_CERT_PEM: &[u8] = include_bytes!("../examples/testdata/pck_cert.pem");
+
+    fn pck_extensions_der() -> Vec<u8> {
+        let (_, pem) = x509_parser::pem::parse_x509_pem(PCK_CERT_PEM).unwrap();
+        let cert = pem.parse_x509().unwrap();
+        cert.get_extension_unique(&SGX_EXTENSIONS_OID)
+            .unwrap()
+            .unwrap()
+            .value
+            .to_vec()
+    }
+
+    #[test]
+    fn parses_recorded_pck_cert() {
+        let (_, pem) = x509_parser::pem::parse_x509_pem(PCK_CERT_PEM).unwrap();
+        let cert = pem.parse_x509().unwrap();
+        let extensions = SgxExtensions::from_cert(&cert).unwrap();
+
+        assert_eq!(
+            hex::encode(extensions.ppid),
+            "52e85f3a8e334936300e5f059bae8227"
+        );
+        assert_eq!(
+            extensions.tcb.sgxtcbcomponents,
+            [8, 8, 2, 2, 4, 1, 0, 6, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(extensions.tcb.pcesvn, 11);
+        assert_eq!(
+            hex::encode(extensions.tcb.cpusvn),
+            "08080202040100060000000000000000"
+        );
+        assert_eq!(extensions.pce_id, [0, 0]);
+        assert_eq!(hex::encode(extensions.fmspc), "00806f050000");
+        assert_eq!(extensions.sgx_type, SgxType::Scalable);
+        assert_eq!(
+            extensions.platform_instance_id.map(hex::encode).as_deref(),
+            Some("cf4cf635e4badf49b3dabf59065c1c2a")
+        );
+        assert_eq!(
+            extensions.configuration,
+            Some(PckConfiguration {
+                dynamic_platform: Some(true),
+                cached_keys: Some(false),
+                smt_enabled: Some(true),
+            })
+        );
+    }
+
+    #[test]
+    fn extract_fmspc_matches_extensions() {
+        let (_, pem) = x509_parser::pem::parse_x509_pem(PCK_CERT_PEM).unwrap();
+        let cert = pem.parse_x509().unwrap();
+        assert_eq!(
+            crate::utils::extract_fmspc_from_extension(&cert).unwrap(),
+            [0x00, 0x80, 0x6f, 0x05, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn rejects_trailing_bytes() {
+        let mut der = pck_extensions_der();
+        der.push(0);
+        assert!(SgxExtensions::from_der(&der).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_extensions() {
+        let der = pck_extensions_der();
+        assert!(SgxExtensions::from_der(&der[..der.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,629 @@
+//! Attested TLS (RA-TLS).
+//!
+//! The TD generates an ephemeral P-256 key and requests a quote whose report data is
+//! SHA-512 of the key's SubjectPublicKeyInfo. The quote, and optionally the collaterals
+//! required to verify it, are put in custom extensions of a self-signed X.509 certificate
+//! used as the TLS certificate. The peer extracts and verifies the quote during the
+//! handshake with `RaTlsVerifier`, instead of checking a certificate chain.
+
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::collateral::Collaterals;
+use crate::device::DeviceOptions;
+use crate::error::{Result, TdxError};
+use crate::report::tcb_status_name;
+use crate::utils::{parse_quote_v4, report_data_from_nonce};
+use crate::Tdx;
+use dcap_rs::types::quotes::body::QuoteBody;
+use dcap_rs::types::VerifiedOutput;
+use der::asn1::{BitString, OctetString};
+use der::{Decode, Encode, Header, SliceReader, Tag, TagNumber};
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{DerSignature, SigningKey};
+use p256::pkcs8::EncodePrivateKey;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use rustls::{DigitallySignedStruct, DistinguishedName, SignatureScheme};
+use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+use x509_cert::name::Name;
+use x509_cert::serial_number::SerialNumber;
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+use x509_cert::time::Validity;
+use x509_cert::Certificate;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// OID of the extension holding the raw TDX quote, as used by interoperable RA-TLS
+/// implementations.
+pub const TDX_QUOTE_OID: &str = "1.2.840.113741.1.5.5.1.6";
+
+/// OID of the extension holding the collaterals, CBOR encoded with `Collaterals::to_cbor`.
+/// This OID is derived from a UUID (ITU-T X.667) and is specific to this SDK.
+pub const TDX_COLLATERAL_OID: &str = "2.25.42551354755831998284724103025512248111";
+
+/// Intel SGX Root CA, the trust anchor of the quotes.
+pub const INTEL_ROOT_CA_DER: &[u8] =
+    include_bytes!("../data/Intel_SGX_Provisioning_Certification_RootCA.cer");
+
+/// Report data binding a public key to a quote: SHA-512 of its DER SubjectPublicKeyInfo.
+pub fn report_data_from_public_key(spki_der: &[u8]) -> [u8; 64] {
+    report_data_from_nonce(spki_der)
+}
+
+/// Options of the RA-TLS certificate.
+#[derive(Clone, Debug)]
+pub struct RaTlsOptions {
+    /// Subject of the certificate, eg "CN=my-service".
+    pub subject: String,
+    pub validity: Duration,
+    /// Collaterals to embed in the certificate, so that the peer can verify the quote
+    /// without accessing the PCCS. See `Collaterals::fetch`.
+    pub collaterals: Option<Collaterals>,
+}
+
+impl Default for RaTlsOptions {
+    fn default() -> Self {
+        RaTlsOptions {
+            subject: "CN=TDX RA-TLS".to_string(),
+            validity: Duration::from_secs(24 * 60 * 60),
+            collaterals: None,
+        }
+    }
+}
+
+/// A self-signed certificate carrying a TDX quote bound to its key.
+pub struct RaTlsCertificate {
+    cert_der: Vec<u8>,
+    key: SigningKey,
+}
+
+impl RaTlsCertificate {
+    /// Generates an ephemeral key, a quote binding it, and the certificate.
+    /// This cannot be used on Azure Confidential VM, which does not support custom report data.
+    pub fn generate(tdx: &Tdx, options: &RaTlsOptions) -> Result<Self> {
+        Self::generate_with(options, |report_data| {
+            let (quote, _) = tdx.get_attestation_report_raw_with_options(DeviceOptions {
+                report_data: Some(*report_data),
+            })?;
+            Ok(quote)
+        })
+    }
+
+    /// Generates the certificate with a quote of the given report data from `get_quote`.
+    fn generate_with(
+        options: &RaTlsOptions,
+        get_quote: impl FnOnce(&[u8; 64]) -> Result<Vec<u8>>,
+    ) -> Result<Self> {
+        let key = SigningKey::random(&mut rand::rngs::OsRng);
+        let spki = SubjectPublicKeyInfoOwned::from_key(*key.verifying_key())
+            .map_err(|e| TdxError::X509(e.to_string()))?;
+        let spki_der = spki.to_der().map_err(|e| TdxError::X509(e.to_string()))?;
+
+        let quote = get_quote(&report_data_from_public_key(&spki_der))?;
+
+        let subject =
+            Name::from_str(&options.subject).map_err(|e| TdxError::X509(e.to_string()))?;
+        let mut serial = [0u8; 16];
+        rand::Rng::fill(&mut rand::thread_rng(), &mut serial);
+
+        let builder = CertificateBuilder::new(
+            Profile::Leaf {
+                issuer: subject.clone(),
+                enable_key_agreement: false,
+                enable_key_encipherment: false,
+            },
+            SerialNumber::new(&serial).map_err(|e| TdxError::X509(e.to_string()))?,
+            Validity::from_now(options.validity).map_err(|e| TdxError::X509(e.to_string()))?,
+            subject,
+            spki,
+            &key,
+        )
+        .map_err(|e| TdxError::X509(e.to_string()))?;
+
+        let cert = builder
+            .build::<DerSignature>()
+            .map_err(|e| TdxError::X509(e.to_string()))?;
+        let mut extensions = vec![(TDX_QUOTE_OID, quote)];
+        if let Some(collaterals) = &options.collaterals {
+            extensions.push((TDX_COLLATERAL_OID, collaterals.to_cbor()?));
+        }
+        let cert_der = append_extensions(cert, &key, &extensions)
+            .map_err(|e| TdxError::X509(e.to_string()))?;
+        Ok(RaTlsCertificate { cert_der, key })
+    }
+
+    pub fn cert_der(&self) -> &[u8] {
+        &self.cert_der
+    }
+
+    /// Certificate chain to configure rustls with.
+    pub fn cert_chain(&self) -> Vec<CertificateDer<'static>> {
+        vec![CertificateDer::from(self.cert_der.clone())]
+    }
+
+    /// Private key to configure rustls with.
+    pub fn private_key(&self) -> Result<PrivateKeyDer<'static>> {
+        let der = self
+            .key
+            .to_pkcs8_der()
+            .map_err(|e| TdxError::X509(e.to_string()))?;
+        Ok(PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
+            der.as_bytes().to_vec(),
+        )))
+    }
+}
+
+/// Adds non-critical extensions holding raw values to a certificate, and signs it again
+/// with `key`.
+///
+/// `x509-cert` only supports OIDs whose arcs fit in 32 bits, which excludes UUID based OIDs
+/// such as `TDX_COLLATERAL_OID`, so the extensions are DER encoded here.
+fn append_extensions(
+    cert: Certificate,
+    key: &SigningKey,
+    extensions: &[(&str, Vec<u8>)],
+) -> der::Result<Vec<u8>> {
+    let mut tbs = cert.tbs_certificate;
+    let mut encoded = Vec::new();
+    for extension in tbs.extensions.take().unwrap_or_default() {
+        encoded.extend(extension.to_der()?);
+    }
+    for (oid, value) in extensions {
+        let oid = encode_oid(oid).ok_or_else(|| der::Tag::ObjectIdentifier.value_error())?;
+        let content = [
+            der_tlv(Tag::ObjectIdentifier, &oid)?,
+            OctetString::new(value.as_slice())?.to_der()?,
+        ]
+        .concat();
+        encoded.extend(der_tlv(Tag::Sequence, &content)?);
+    }
+
+    // The extensions are the last field of the TBS certificate, explicitly tagged [3].
+    let tbs_der = tbs.to_der()?;
+    let header_len =
+        usize::try_from(Header::decode(&mut SliceReader::new(&tbs_der)?)?.encoded_len()?)?;
+    let extensions_tag = Tag::ContextSpecific {
+        constructed: true,
+        number: TagNumber::N3,
+    };
+    let tbs_content = [
+        &tbs_der[header_len..],
+        &der_tlv(extensions_tag, &der_tlv(Tag::Sequence, &encoded)?)?,
+    ]
+    .concat();
+    let tbs_der = der_tlv(Tag::Sequence, &tbs_content)?;
+
+    let signature: DerSignature = key.sign(&tbs_der);
+    let cert_content = [
+        tbs_der,
+        cert.signature_algorithm.to_der()?,
+        BitString::from_bytes(signature.as_bytes())?.to_der()?,
+    ]
+    .concat();
+    der_tlv(Tag::Sequence, &cert_content)
+}
+
+fn der_tlv(tag: Tag, content: &[u8]) -> der::Result<Vec<u8>> {
+    let mut out = Header::new(tag, content.len())?.to_der()?;
+    out.extend_from_slice(content);
+    Ok(out)
+}
+
+/// Encodes the value of a dotted OID, with arcs of up to 128 bits.
+fn encode_oid(oid: &str) -> Option<Vec<u8>> {
+    let arcs = oid
+        .split('.')
+        .map(|arc| arc.parse::<u128>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let [first, second, rest @ ..] = &arcs[..] else {
+        return None;
+    };
+    if *first > 2 || (*first < 2 && *second >= 40) {
+        return None;
+    }
+    let mut out = Vec::new();
+    for arc in std::iter::once(first * 40 + second).chain(rest.iter().copied()) {
+        let mut base128 = vec![(arc & 0x7f) as u8];
+        let mut arc = arc >> 7;
+        while arc > 0 {
+            base128.push((arc & 0x7f) as u8 | 0x80);
+            arc >>= 7;
+        }
+        out.extend(base128.iter().rev());
+    }
+    Some(out)
+}
+
+/// Custom check run on the verified output of the peer's quote, eg to compare RTMRs
+/// against reference values.
+pub type OutputCheck = Arc<dyn Fn(&VerifiedOutput) -> Result<()> + Send + Sync>;
+
+/// What the peer's quote must satisfy.
+#[derive(Clone)]
+pub struct RaTlsPolicy {
+    /// Trust anchor the collaterals must be issued by, the Intel SGX Root CA by default.
+    pub root_ca: Vec<u8>,
+    /// Collaterals used when the certificate does not embed them.
+    pub collaterals: Option<Collaterals>,
+    /// Accepted TCB statuses, as named by Intel (eg "UpToDate").
+    pub accepted_tcb_statuses: Vec<String>,
+    /// Accepted MRTDs. Any MRTD is accepted if empty.
+    pub allowed_mrtds: Vec<[u8; 48]>,
+    pub output_check: Option<OutputCheck>,
+}
+
+impl Default for RaTlsPolicy {
+    fn default() -> Self {
+        RaTlsPolicy {
+            root_ca: INTEL_ROOT_CA_DER.to_vec(),
+            collaterals: None,
+            accepted_tcb_statuses: vec!["UpToDate".to_string(), "SWHardeningNeeded".to_string()],
+            allowed_mrtds: Vec::new(),
+            output_check: None,
+        }
+    }
+}
+
+impl Debug for RaTlsPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RaTlsPolicy")
+            .field("accepted_tcb_statuses", &self.accepted_tcb_statuses)
+            .field(
+                "allowed_mrtds",
+                &self
+                    .allowed_mrtds
+                    .iter()
+                    .map(hex::encode)
+                    .collect::<Vec<_>>(),
+            )
+            .field("collaterals", &self.collaterals.is_some())
+            .field("output_check", &self.output_check.is_some())
+            .finish()
+    }
+}
+
+/// Verifies an RA-TLS certificate at `current_time` (seconds since the Unix epoch):
+/// the certificate must be within its validity period, and the quote must be valid, bind
+/// the certificate's public key and satisfy the policy.
+pub fn verify_ra_tls_certificate(
+    cert_der: &[u8],
+    policy: &RaTlsPolicy,
+    current_time: u64,
+) -> Result<VerifiedOutput> {
+    let (_, cert) =
+        X509Certificate::from_der(cert_der).map_err(|e| TdxError::X509(e.to_string()))?;
+
+    let validity = cert.validity();
+    let now = current_time as i64;
+    if now < validity.not_before.timestamp() || now > validity.not_after.timestamp() {
+        return Err(TdxError::X509(
+            "Certificate is not valid at the current time".to_string(),
+        ));
+    }
+
+    // Compare the encoded OIDs, as x509-parser does not print arcs above 64 bits.
+    let extension = |oid: &str| {
+        let oid = encode_oid(oid)?;
+        cert.extensions()
+            .iter()
+            .find(|ext| ext.oid.as_bytes() == oid)
+            .map(|ext| ext.value)
+    };
+
+    let quote = extension(TDX_QUOTE_OID)
+        .ok_or_else(|| TdxError::X509("Certificate has no TDX quote".to_string()))?;
+    let quote = parse_quote_v4(quote)?;
+
+    let report_data = match &quote.quote_body {
+        QuoteBody::TD10QuoteBody(body) => body.report_data,
+        QuoteBody::SGXQuoteBody(_) => {
+            return Err(TdxError::X509(
+                "Certificate carries an SGX quote".to_string(),
+            ))
+        }
+    };
+    if report_data != report_data_from_public_key(cert.public_key().raw) {
+        return Err(TdxError::X509(
+            "Quote report data does not bind the certificate key".to_string(),
+        ));
+    }
+
+    let collaterals = match extension(TDX_COLLATERAL_OID) {
+        Some(collaterals) => Collaterals::from_cbor(collaterals)?,
+        None => policy.collaterals.clone().ok_or_else(|| {
+            TdxError::X509("Certificate has no collaterals and none are configured".to_string())
+        })?,
+    };
+    // Collaterals embedded by the peer are only trusted up to our own trust anchor.
+    if collaterals.root_ca != policy.root_ca {
+        return Err(TdxError::X509(
+            "Collaterals are not issued by the trusted root CA".to_string(),
+        ));
+    }
+
+    let output = Tdx::new().verify_attestation_report_with_collaterals(
+        &quote,
+        &collaterals,
+        current_time,
+    )?;
+
+    let tcb_status = tcb_status_name(&output.tcb_status);
+    if !policy.accepted_tcb_statuses.iter().any(|s| s == tcb_status) {
+        return Err(TdxError::Dcap(format!(
+            "TCB status {} is not accepted",
+            tcb_status
+        )));
+    }
+
+    if !policy.allowed_mrtds.is_empty() {
+        let mrtd = match &output.quote_body {
+            QuoteBody::TD10QuoteBody(body) => body.mrtd,
+            QuoteBody::SGXQuoteBody(_) => [0; 48],
+        };
+        if !policy.allowed_mrtds.contains(&mrtd) {
+            return Err(TdxError::Dcap(format!(
+                "MRTD {} is not allowed",
+                hex::encode(mrtd)
+            )));
+        }
+    }
+
+    if let Some(check) = &policy.output_check {
+        check(&output)?;
+    }
+    Ok(output)
+}
+
+/// rustls certificate verifier accepting peers that present a valid RA-TLS certificate.
+///
+/// The verifier replaces the Web PKI: the server name is not checked, the identity of the
+/// peer is the attested TD described by the policy.
+#[derive(Debug)]
+pub struct RaTlsVerifier {
+    policy: RaTlsPolicy,
+    provider: Arc<CryptoProvider>,
+}
+
+impl RaTlsVerifier {
+    /// Creates a verifier using the process default crypto provider, or ring if none is
+    /// installed.
+    pub fn new(policy: RaTlsPolicy) -> Self {
+        let provider = CryptoProvider::get_default()
+            .cloned()
+            .unwrap_or_else(|| Arc::new(rustls::crypto::ring::default_provider()));
+        Self::with_provider(policy, provider)
+    }
+
+    pub fn with_provider(policy: RaTlsPolicy, provider: Arc<CryptoProvider>) -> Self {
+        RaTlsVerifier { policy, provider }
+    }
+
+    fn verify_end_entity(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        now: UnixTime,
+    ) -> std::result::Result<(), rustls::Error> {
+        verify_ra_tls_certificate(end_entity, &self.policy, now.as_secs())
+            .map(|_| ())
+            .map_err(|e| rustls::Error::General(format!("RA-TLS verification failed: {}", e)))
+    }
+}
+
+impl ServerCertVerifier for RaTlsVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        self.verify_end_entity(end_entity, now)?;
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+impl ClientCertVerifier for RaTlsVerifier {
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+    ) -> std::result::Result<ClientCertVerified, rustls::Error> {
+        self.verify_end_entity(end_entity, now)?;
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_quote::TestPlatform;
+
+    fn now() -> u64 {
+        chrono::Utc::now().timestamp() as u64
+    }
+
+    fn generate(platform: &TestPlatform, options: &RaTlsOptions) -> RaTlsCertificate {
+        RaTlsCertificate::generate_with(options, |report_data| Ok(platform.quote(report_data)))
+            .unwrap()
+    }
+
+    fn policy(platform: &TestPlatform) -> RaTlsPolicy {
+        RaTlsPolicy {
+            root_ca: platform.collaterals.root_ca.clone(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn verifies_generated_certificate() {
+        let platform = TestPlatform::new();
+        let options = RaTlsOptions {
+            collaterals: Some(platform.collaterals.clone()),
+            ..Default::default()
+        };
+        let cert = generate(&platform, &options);
+
+        // The extension value is the raw quote, not a nested OCTET STRING.
+        let (_, parsed) = X509Certificate::from_der(cert.cert_der()).unwrap();
+        let quote = parsed
+            .extensions()
+            .iter()
+            .find(|ext| ext.oid.to_id_string() == TDX_QUOTE_OID)
+            .unwrap()
+            .value;
+        assert_eq!(u16::from_le_bytes([quote[0], quote[1]]), 4);
+
+        let output = verify_ra_tls_certificate(cert.cert_der(), &policy(&platform), now()).unwrap();
+        let QuoteBody::TD10QuoteBody(body) = output.quote_body else {
+            panic!("Expected a TD quote body");
+        };
+        assert_eq!(
+            body.report_data,
+            report_data_from_public_key(parsed.public_key().raw)
+        );
+
+        // Without embedded collaterals, the ones of the policy are used.
+        let cert = generate(&platform, &RaTlsOptions::default());
+        assert!(verify_ra_tls_certificate(cert.cert_der(), &policy(&platform), now()).is_err());
+        let policy = RaTlsPolicy {
+            collaterals: Some(platform.collaterals.clone()),
+            ..policy(&platform)
+        };
+        verify_ra_tls_certificate(cert.cert_der(), &policy, now()).unwrap();
+    }
+
+    #[test]
+    fn rejects_quote_not_binding_the_key() {
+        let platform = TestPlatform::new();
+        let options = RaTlsOptions {
+            collaterals: Some(platform.collaterals.clone()),
+            ..Default::default()
+        };
+        let cert =
+            RaTlsCertificate::generate_with(&options, |_| Ok(platform.quote(&[0; 64]))).unwrap();
+        let err = verify_ra_tls_certificate(cert.cert_der(), &policy(&platform), now());
+        assert!(err.unwrap_err().to_string().contains("does not bind"));
+    }
+
+    #[test]
+    fn rejects_certificate_outside_its_validity() {
+        let platform = TestPlatform::new();
+        let options = RaTlsOptions {
+            validity: Duration::from_secs(60),
+            collaterals: Some(platform.collaterals.clone()),
+            ..Default::default()
+        };
+        let cert = generate(&platform, &options);
+        let policy = policy(&platform);
+        verify_ra_tls_certificate(cert.cert_der(), &policy, now()).unwrap();
+
+        for time in [now() - 3600, now() + 3600] {
+            let err = verify_ra_tls_certificate(cert.cert_der(), &policy, time);
+            assert!(err.unwrap_err().to_string().contains("not valid"));
+        }
+    }
+
+    #[test]
+    fn rejects_collaterals_of_untrusted_root_ca() {
+        let platform = TestPlatform::new();
+        let options = RaTlsOptions {
+            collaterals: Some(platform.collaterals.clone()),
+            ..Default::default()
+        };
+        let cert = generate(&platform, &options);
+
+        // The Intel Root CA is trusted by default.
+        let err = verify_ra_tls_certificate(cert.cert_der(), &RaTlsPolicy::default(), now());
+        assert!(err.unwrap_err().to_string().contains("trusted root CA"));
+
+        // Collaterals of another platform, eg embedded by an attacker.
+        let other = TestPlatform::new();
+        let cert = RaTlsCertificate::generate_with(
+            &RaTlsOptions {
+                collaterals: Some(other.collaterals.clone()),
+                ..Default::default()
+            },
+            |report_data| Ok(other.quote(report_data)),
+        )
+        .unwrap();
+        assert!(verify_ra_tls_certificate(cert.cert_der(), &policy(&platform), now()).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use crate::pck::SgxExtensions;
+use crate::utils::{get_pck_sgx_extensions, get_sgx_extensions_from_pem_chain};
+use dcap_rs::types::quotes::body::{EnclaveReport, QuoteBody};
+use dcap_rs::types::quotes::{version_3::QuoteV3, version_4::QuoteV4};
+use dcap_rs::types::{TcbStatus, VerifiedOutput};
+use serde::{Deserialize, Serialize};
+
+/// Human and machine readable summary of a quote, used for JSON output.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuoteSummary {
+    pub version: u16,
+    /// "SGX" or "TDX".
+    pub platform: String,
+    pub fmspc: Option<String>,
+    pub pck: Option<SgxExtensions>,
+    pub body: QuoteBodySummary,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum QuoteBodySummary {
+    #[serde(rename_all = "camelCase")]
+    Sgx {
+        mrenclave: String,
+        mrsigner: String,
+        isv_prod_id: u16,
+        isv_svn: u16,
+        report_data: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Td10 {
+        tee_tcb_svn: String,
+        mrseam: String,
+        mrsignerseam: String,
+        mrtd: String,
+        mrconfigid: String,
+        mrowner: String,
+        mrownerconfig: String,
+        rtmr0: String,
+        rtmr1: String,
+        rtmr2: String,
+        rtmr3: String,
+        report_data: String,
+    },
+}
+
+impl QuoteBodySummary {
+    pub fn from_enclave_report(report: &EnclaveReport) -> Self {
+        QuoteBodySummary::Sgx {
+            mrenclave: hex::encode(report.mrenclave),
+            mrsigner: hex::encode(report.mrsigner),
+            isv_prod_id: report.isv_prod_id,
+            isv_svn: report.isv_svn,
+            report_data: hex::encode(report.report_data),
+        }
+    }
+
+    pub fn from_quote_body(body: &QuoteBody) -> Self {
+        match body {
+            QuoteBody::SGXQuoteBody(report) => Self::from_enclave_report(report),
+            QuoteBody::TD10QuoteBody(report) => QuoteBodySummary::Td10 {
+                tee_tcb_svn: hex::encode(report.tee_tcb_svn),
+                mrseam: hex::encode(report.mrseam),
+                mrsignerseam: hex::encode(report.mrsignerseam),
+                mrtd: hex::encode(report.mrtd),
+                mrconfigid: hex::encode(report.mrconfigid),
+                mrowner: hex::encode(report.mrowner),
+                mrownerconfig: hex::encode(report.mrownerconfig),
+                rtmr0: hex::encode(report.rtmr0),
+                rtmr1: hex::encode(report.rtmr1),
+                rtmr2: hex::encode(report.rtmr2),
+                rtmr3: hex::encode(report.rtmr3),
+                report_data: hex::encode(report.report_data),
+            },
+        }
+    }
+}
+
+impl QuoteSummary {
+    pub fn from_quote_v3(quote: &QuoteV3) -> Self {
+        let pck = get_sgx_extensions_from_pem_chain(&quote.signature.qe_cert_data.cert_data).ok();
+        QuoteSummary {
+            version: quote.header.version,
+            platform: platform_name(quote.header.tee_type),
+            fmspc: pck.as_ref().map(|p| hex::encode(p.fmspc)),
+            pck,
+            body: QuoteBodySummary::from_enclave_report(&quote.isv_enclave_report),
+        }
+    }
+
+    pub fn from_quote_v4(quote: &QuoteV4) -> Self {
+        // The PCK certificate is only available when the quote embeds the PCK cert chain.
+        let pck = get_pck_sgx_extensions(quote).ok();
+        QuoteSummary {
+            version: quote.header.version,
+            platform: platform_name(quote.header.tee_type),
+            fmspc: pck.as_ref().map(|p| hex::encode(p.fmspc)),
+            pck,
+            body: QuoteBodySummary::from_quote_body(&quote.quote_body),
+        }
+    }
+}
+
+/// Summary of a successful verification, used for JSON output.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationSummary {
+    pub quote_version: u16,
+    pub platform: String,
+    pub tcb_status: String,
+    pub fmspc: String,
+    #[serde(rename = "advisoryIDs")]
+    pub advisory_ids: Vec<String>,
+    pub body: QuoteBodySummary,
+}
+
+impl VerificationSummary {
+    pub fn from_verified_output(output: &VerifiedOutput) -> Self {
+        VerificationSummary {
+            quote_version: output.quote_version,
+            platform: platform_name(output.tee_type),
+            tcb_status: tcb_status_name(&output.tcb_status).to_string(),
+            fmspc: hex::encode(output.fmspc),
+            advisory_ids: output.advisory_ids.clone().unwrap_or_default(),
+            body: QuoteBodySummary::from_quote_body(&output.quote_body),
+        }
+    }
+}
+
+pub fn platform_name(tee_type: u32) -> String {
+    match tee_type {
+        0 => "SGX".to_string(),
+        _ => "TDX".to_string(),
+    }
+}
+
+/// Returns the name used by Intel in the TCB Info for a TCB status.
+pub fn tcb_status_name(status: &TcbStatus) -> &'static str {
+    match status {
+        TcbStatus::OK => "UpToDate",
+        TcbStatus::TcbSwHardeningNeeded => "SWHardeningNeeded",
+        TcbStatus::TcbConfigurationAndSwHardeningNeeded => "ConfigurationAndSWHardeningNeeded",
+        TcbStatus::TcbConfigurationNeeded => "ConfigurationNeeded",
+        TcbStatus::TcbOutOfDate => "OutOfDate",
+        TcbStatus::TcbOutOfDateConfigurationNeeded => "OutOfDateConfigurationNeeded",
+        TcbStatus::TcbRevoked => "Revoked",
+        TcbStatus::TcbUnrecognized => "Unrecognized",
+    }
+}
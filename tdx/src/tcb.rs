@@ -0,0 +1,453 @@
+use crate::error::{Result, TdxError};
+use crate::pck::PckTcb;
+use crate::utils::get_pck_sgx_extensions;
+use dcap_rs::types::quotes::{body::QuoteBody, version_4::QuoteV4};
+use serde::{Deserialize, Serialize};
+
+/// TCB Info V3 as returned by the PCCS, ie `{"tcbInfo": {...}, "signature": "..."}`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedTcbInfo {
+    pub tcb_info: TcbInfoV3,
+    pub signature: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcbInfoV3 {
+    pub id: String,
+    pub version: u32,
+    pub issue_date: String,
+    pub next_update: String,
+    pub fmspc: String,
+    pub pce_id: String,
+    pub tcb_type: u32,
+    pub tcb_evaluation_data_number: u32,
+    pub tdx_module: Option<TdxModule>,
+    pub tdx_module_identities: Option<Vec<TdxModuleIdentity>>,
+    pub tcb_levels: Vec<TcbLevel>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TdxModule {
+    pub mrsigner: String,
+    pub attributes: String,
+    pub attributes_mask: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TdxModuleIdentity {
+    pub id: String,
+    pub mrsigner: String,
+    pub attributes: String,
+    pub attributes_mask: String,
+    pub tcb_levels: Vec<TdxModuleTcbLevel>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TdxModuleTcbLevel {
+    pub tcb: TdxModuleTcb,
+    pub tcb_date: String,
+    pub tcb_status: String,
+    #[serde(rename = "advisoryIDs", default)]
+    pub advisory_ids: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TdxModuleTcb {
+    pub isvsvn: u8,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcbLevel {
+    pub tcb: Tcb,
+    pub tcb_date: String,
+    pub tcb_status: String,
+    #[serde(rename = "advisoryIDs", default)]
+    pub advisory_ids: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Tcb {
+    pub sgxtcbcomponents: Vec<TcbComponent>,
+    pub pcesvn: u16,
+    pub tdxtcbcomponents: Option<Vec<TcbComponent>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TcbComponent {
+    pub svn: u8,
+    pub category: Option<String>,
+    #[serde(rename = "type")]
+    pub component_type: Option<String>,
+}
+
+impl SignedTcbInfo {
+    /// Parses a TCB Info V3 JSON document.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let tcb_info: SignedTcbInfo =
+            serde_json::from_slice(bytes).map_err(|e| TdxError::Dcap(e.to_string()))?;
+        if tcb_info.tcb_info.version != 3 {
+            return Err(TdxError::Dcap(format!(
+                "Unsupported TCB Info version: {}",
+                tcb_info.tcb_info.version
+            )));
+        }
+        Ok(tcb_info)
+    }
+}
+
+/// The TCB level that the platform was matched against.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchedTcbLevel {
+    /// Position of the level in `tcbLevels`, 0 being the highest.
+    pub index: usize,
+    pub tcb_status: String,
+    pub tcb_date: String,
+    #[serde(rename = "advisoryIDs")]
+    pub advisory_ids: Vec<String>,
+}
+
+/// Identifies a single TCB component of a platform.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "index", rename_all = "camelCase")]
+pub enum TcbComponentKind {
+    /// SGX TCB component found in the PCK certificate, 0-indexed.
+    Sgx(usize),
+    /// PCESVN found in the PCK certificate.
+    Pcesvn,
+    /// Component of the TEE_TCB_SVN reported in the TD quote body, 0-indexed.
+    Tdx(usize),
+    /// ISVSVN of the TDX module, ie TEE_TCB_SVN[0] checked against the TDX module identity.
+    TdxModule,
+}
+
+/// A component whose SVN is lower than what the target TCB level requires.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentShortfall {
+    pub component: TcbComponentKind,
+    pub category: Option<String>,
+    #[serde(rename = "type")]
+    pub component_type: Option<String>,
+    pub current_svn: u16,
+    pub required_svn: u16,
+}
+
+/// Result of evaluating the TCB of a platform against a TCB Info V3 document.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcbEvaluation {
+    pub fmspc: String,
+    pub tcb_evaluation_data_number: u32,
+    /// `None` when the platform is below every TCB level, ie its TCB is unrecognized.
+    pub matched_level: Option<MatchedTcbLevel>,
+    /// Level of the TDX module identity when TEE_TCB_SVN reports a TDX module major version.
+    pub tdx_module_level: Option<MatchedTcbLevel>,
+    /// The closest UpToDate level above the matched level, if any.
+    pub target_level: Option<MatchedTcbLevel>,
+    /// Components that must be updated to reach `target_level`.
+    pub shortfalls: Vec<ComponentShortfall>,
+}
+
+impl TcbEvaluation {
+    /// Returns `true` if the platform already meets an UpToDate TCB level.
+    pub fn is_up_to_date(&self) -> bool {
+        let platform_up_to_date = match &self.matched_level {
+            Some(level) => level.tcb_status == "UpToDate",
+            None => false,
+        };
+        let tdx_module_up_to_date = match &self.tdx_module_level {
+            Some(level) => level.tcb_status == "UpToDate",
+            None => true,
+        };
+        platform_up_to_date && tdx_module_up_to_date
+    }
+}
+
+/// Walks the `tcbLevels` of a TCB Info V3 document and returns the level matched by
+/// the PCK TCB and TEE_TCB_SVN, together with the components that fall short of the
+/// next-higher UpToDate level.
+pub fn evaluate_tcb_level(
+    pck_tcb: &PckTcb,
+    tee_tcb_svn: &[u8; 16],
+    tcb_info: &TcbInfoV3,
+) -> Result<TcbEvaluation> {
+    // When TEE_TCB_SVN[1] is set, the first two bytes identify the TDX module and are
+    // evaluated against the TDX module identities instead of the TCB levels.
+    let tdx_module_version = tee_tcb_svn[1];
+    let tdx_skip = if tdx_module_version > 0 { 2 } else { 0 };
+
+    let matched_index = tcb_info
+        .tcb_levels
+        .iter()
+        .position(|level| shortfalls(pck_tcb, tee_tcb_svn, tdx_skip, level).is_empty());
+
+    let higher_levels = &tcb_info.tcb_levels[..matched_index.unwrap_or(tcb_info.tcb_levels.len())];
+    let target_index = higher_levels
+        .iter()
+        .rposition(|level| level.tcb_status == "UpToDate");
+
+    let mut target_shortfalls = match (matched_index, target_index) {
+        (Some(m), _) if tcb_info.tcb_levels[m].tcb_status == "UpToDate" => Vec::new(),
+        (_, Some(t)) => shortfalls(pck_tcb, tee_tcb_svn, tdx_skip, &tcb_info.tcb_levels[t]),
+        (_, None) => Vec::new(),
+    };
+
+    let tdx_module_level = if tdx_module_version > 0 {
+        let (level, shortfall) = evaluate_tdx_module(tee_tcb_svn, tcb_info)?;
+        target_shortfalls.extend(shortfall);
+        Some(level)
+    } else {
+        None
+    };
+
+    Ok(TcbEvaluation {
+        fmspc: tcb_info.fmspc.clone(),
+        tcb_evaluation_data_number: tcb_info.tcb_evaluation_data_number,
+        matched_level: matched_index.map(|i| matched_level(i, &tcb_info.tcb_levels[i])),
+        tdx_module_level,
+        target_level: target_index.map(|i| matched_level(i, &tcb_info.tcb_levels[i])),
+        shortfalls: target_shortfalls,
+    })
+}
+
+fn matched_level(index: usize, level: &TcbLevel) -> MatchedTcbLevel {
+    MatchedTcbLevel {
+        index,
+        tcb_status: level.tcb_status.clone(),
+        tcb_date: level.tcb_date.clone(),
+        advisory_ids: level.advisory_ids.clone(),
+    }
+}
+
+/// Lists every component of the platform whose SVN is lower than the one required by `level`.
+/// An empty list means that the platform matches the level.
+fn shortfalls(
+    pck_tcb: &PckTcb,
+    tee_tcb_svn: &[u8; 16],
+    tdx_skip: usize,
+    level: &TcbLevel,
+) -> Vec<ComponentShortfall> {
+    let mut ret = Vec::new();
+
+    for (i, component) in level.tcb.sgxtcbcomponents.iter().enumerate().take(16) {
+        if pck_tcb.sgxtcbcomponents[i] < component.svn {
+            ret.push(ComponentShortfall {
+                component: TcbComponentKind::Sgx(i),
+                category: component.category.clone(),
+                component_type: component.component_type.clone(),
+                current_svn: pck_tcb.sgxtcbcomponents[i] as u16,
+                required_svn: component.svn as u16,
+            });
+        }
+    }
+
+    if pck_tcb.pcesvn < level.tcb.pcesvn {
+        ret.push(ComponentShortfall {
+            component: TcbComponentKind::Pcesvn,
+            category: None,
+            component_type: None,
+            current_svn: pck_tcb.pcesvn,
+            required_svn: level.tcb.pcesvn,
+        });
+    }
+
+    if let Some(tdxtcbcomponents) = &level.tcb.tdxtcbcomponents {
+        for (i, component) in tdxtcbcomponents.iter().enumerate().take(16).skip(tdx_skip) {
+            if tee_tcb_svn[i] < component.svn {
+                ret.push(ComponentShortfall {
+                    component: TcbComponentKind::Tdx(i),
+                    category: component.category.clone(),
+                    component_type: component.component_type.clone(),
+                    current_svn: tee_tcb_svn[i] as u16,
+                    required_svn: component.svn as u16,
+                });
+            }
+        }
+    }
+
+    ret
+}
+
+/// Matches TEE_TCB_SVN[0] against the TDX module identity `TDX_<TEE_TCB_SVN[1]>`.
+/// Also returns the shortfall against the closest UpToDate module level, if any.
+fn evaluate_tdx_module(
+    tee_tcb_svn: &[u8; 16],
+    tcb_info: &TcbInfoV3,
+) -> Result<(MatchedTcbLevel, Option<ComponentShortfall>)> {
+    let id = format!("TDX_{:02X}", tee_tcb_svn[1]);
+    let identity = tcb_info
+        .tdx_module_identities
+        .as_ref()
+        .and_then(|ids| ids.iter().find(|i| i.id == id))
+        .ok_or_else(|| TdxError::Dcap(format!("TDX module identity {} not found", id)))?;
+
+    let isvsvn = tee_tcb_svn[0];
+    let (index, level) = identity
+        .tcb_levels
+        .iter()
+        .enumerate()
+        .find(|(_, level)| isvsvn >= level.tcb.isvsvn)
+        .ok_or_else(|| {
+            TdxError::Dcap(format!(
+                "TDX module ISVSVN {} is below every level of {}",
+                isvsvn, id
+            ))
+        })?;
+
+    let shortfall = identity.tcb_levels[..index]
+        .iter()
+        .rfind(|level| level.tcb_status == "UpToDate")
+        .map(|target| ComponentShortfall {
+            component: TcbComponentKind::TdxModule,
+            category: None,
+            component_type: Some(identity.id.clone()),
+            current_svn: isvsvn as u16,
+            required_svn: target.tcb.isvsvn as u16,
+        });
+
+    let matched = MatchedTcbLevel {
+        index,
+        tcb_status: level.tcb_status.clone(),
+        tcb_date: level.tcb_date.clone(),
+        advisory_ids: level.advisory_ids.clone(),
+    };
+    Ok((matched, shortfall))
+}
+
+/// Evaluates the TCB of a TD quote, using the PCK certificate embedded in the quote.
+pub fn evaluate_quote_tcb(quote: &QuoteV4, tcb_info: &TcbInfoV3) -> Result<TcbEvaluation> {
+    let sgx_extensions = get_pck_sgx_extensions(quote)?;
+    let tee_tcb_svn = match &quote.quote_body {
+        QuoteBody::TD10QuoteBody(body) => body.tee_tcb_svn,
+        _ => {
+            return Err(TdxError::Dcap(
+                "Quote does not contain a TD report".to_string(),
+            ))
+        }
+    };
+    evaluate_tcb_level(&sgx_extensions.tcb, &tee_tcb_svn, tcb_info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::parse_quote_v4;
+
+    /// TCB Info V3 of the FMSPC 90C06F000000, whose levels are:
+    /// 0: UpToDate, PCESVN 13; 1: OutOfDate, PCESVN 5. Both require the SGX components
+    /// [2, 2, 2, 2, 3, 1, 0, 5, 0, ...] and the TDX components [5, 0, 2, 0, ...].
+    const TCB_INFO: &[u8] = include_bytes!("../examples/testdata/collaterals/tcbinfo.json");
+    const QUOTE: &[u8] = include_bytes!("../examples/testdata/tdx_v4_quote_90c06f.bin");
+
+    fn tcb_info() -> TcbInfoV3 {
+        SignedTcbInfo::from_bytes(TCB_INFO).unwrap().tcb_info
+    }
+
+    fn pck_tcb(pcesvn: u16) -> PckTcb {
+        PckTcb {
+            sgxtcbcomponents: [2, 2, 2, 2, 3, 1, 0, 5, 0, 0, 0, 0, 0, 0, 0, 0],
+            pcesvn,
+            cpusvn: [0; 16],
+        }
+    }
+
+    fn tee_tcb_svn(prefix: &[u8]) -> [u8; 16] {
+        let mut svn = [0u8; 16];
+        svn[..prefix.len()].copy_from_slice(prefix);
+        svn
+    }
+
+    #[test]
+    fn rejects_other_tcb_info_versions() {
+        let json = String::from_utf8(TCB_INFO.to_vec())
+            .unwrap()
+            .replace("\"version\":3", "\"version\":2");
+        assert!(SignedTcbInfo::from_bytes(json.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn matches_up_to_date_level() {
+        let evaluation =
+            evaluate_tcb_level(&pck_tcb(13), &tee_tcb_svn(&[5, 0, 2]), &tcb_info()).unwrap();
+        let matched = evaluation.matched_level.as_ref().unwrap();
+        assert_eq!(matched.index, 0);
+        assert_eq!(matched.tcb_status, "UpToDate");
+        assert!(evaluation.tdx_module_level.is_none());
+        assert!(evaluation.shortfalls.is_empty());
+        assert!(evaluation.is_up_to_date());
+        assert_eq!(evaluation.fmspc, "90C06F000000");
+    }
+
+    #[test]
+    fn reports_pcesvn_shortfall() {
+        let evaluation =
+            evaluate_tcb_level(&pck_tcb(10), &tee_tcb_svn(&[5, 0, 2]), &tcb_info()).unwrap();
+        assert_eq!(evaluation.matched_level.as_ref().unwrap().index, 1);
+        assert_eq!(evaluation.target_level.as_ref().unwrap().index, 0);
+        assert!(!evaluation.is_up_to_date());
+        assert_eq!(evaluation.shortfalls.len(), 1);
+        let shortfall = &evaluation.shortfalls[0];
+        assert_eq!(shortfall.component, TcbComponentKind::Pcesvn);
+        assert_eq!((shortfall.current_svn, shortfall.required_svn), (10, 13));
+    }
+
+    #[test]
+    fn unrecognized_below_every_level() {
+        // Without a TDX module version, TEE_TCB_SVN[0] is checked against the TCB levels.
+        let evaluation =
+            evaluate_tcb_level(&pck_tcb(13), &tee_tcb_svn(&[4, 0, 2]), &tcb_info()).unwrap();
+        assert!(evaluation.matched_level.is_none());
+        assert_eq!(evaluation.target_level.as_ref().unwrap().index, 0);
+        assert_eq!(evaluation.shortfalls.len(), 1);
+        assert_eq!(evaluation.shortfalls[0].component, TcbComponentKind::Tdx(0));
+    }
+
+    #[test]
+    fn evaluates_tdx_module_identity() {
+        // TEE_TCB_SVN[1] > 0: the first two bytes are skipped by the TCB levels and
+        // TEE_TCB_SVN[0] is matched against the levels of TDX_01, ie 4: UpToDate, 2: OutOfDate.
+        let evaluation =
+            evaluate_tcb_level(&pck_tcb(13), &tee_tcb_svn(&[3, 1, 2]), &tcb_info()).unwrap();
+        assert_eq!(evaluation.matched_level.as_ref().unwrap().index, 0);
+        let module = evaluation.tdx_module_level.as_ref().unwrap();
+        assert_eq!(module.index, 1);
+        assert_eq!(module.tcb_status, "OutOfDate");
+        assert!(!evaluation.is_up_to_date());
+        assert_eq!(evaluation.shortfalls.len(), 1);
+        let shortfall = &evaluation.shortfalls[0];
+        assert_eq!(shortfall.component, TcbComponentKind::TdxModule);
+        assert_eq!(shortfall.component_type.as_deref(), Some("TDX_01"));
+        assert_eq!((shortfall.current_svn, shortfall.required_svn), (3, 4));
+    }
+
+    #[test]
+    fn rejects_unknown_tdx_module_identity() {
+        let result = evaluate_tcb_level(&pck_tcb(13), &tee_tcb_svn(&[4, 2, 2]), &tcb_info());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn evaluates_recorded_quote() {
+        let quote = parse_quote_v4(QUOTE).unwrap();
+        let evaluation = evaluate_quote_tcb(&quote, &tcb_info()).unwrap();
+        // The PCK certificate reports SVN 3 for the 8th SGX component, every level requires 5.
+        assert!(evaluation.matched_level.is_none());
+        assert_eq!(evaluation.target_level.as_ref().unwrap().index, 0);
+        assert_eq!(evaluation.shortfalls.len(), 1);
+        let shortfall = &evaluation.shortfalls[0];
+        assert_eq!(shortfall.component, TcbComponentKind::Sgx(7));
+        assert_eq!((shortfall.current_svn, shortfall.required_svn), (3, 5));
+        // TEE_TCB_SVN is 04 01 ..: TDX module 1 at ISVSVN 4.
+        assert_eq!(
+            evaluation.tdx_module_level.as_ref().unwrap().tcb_status,
+            "UpToDate"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! A TDX platform standing in for real hardware in the tests of the quote consumers.
+//!
+//! The certificates, CRLs, TCB info and QE identity of the test platform
+//! (`examples/testdata/tdx_v4_quote_90c06f.bin` and its collaterals) are signed again with
+//! keys generated by the test, so that quotes with any report data can be issued and
+//! verified end to end against the returned collaterals.
+use dcap_rs::types::enclave_identity::EnclaveIdentityV2;
+use dcap_rs::types::quotes::CertDataType;
+use dcap_rs::types::tcbinfo::TcbInfoV3;
+use der::asn1::BitString;
+use der::{Decode, Encode};
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use sha2::{Digest, Sha256};
+use x509_cert::crl::CertificateList;
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+use x509_cert::Certificate;
+use x509_parser::pem::Pem;
+
+use crate::certdata::{der_to_pem, embed_pck_cert_chain};
+use crate::collateral::Collaterals;
+use crate::pccs::pcs::IPCSDao::CA;
+use crate::utils::{parse_quote_v4, QUOTE_HEADER_LEN};
+
+const QUOTE: &[u8] = include_bytes!("../examples/testdata/tdx_v4_quote_90c06f.bin");
+const TCB_INFO: &[u8] = include_bytes!("../examples/testdata/collaterals/tcbinfo.json");
+const QE_IDENTITY: &[u8] = include_bytes!("../examples/testdata/collaterals/identity.json");
+const ROOT_CA_CRL: &[u8] = include_bytes!("../examples/testdata/collaterals/root_ca_crl.der");
+const SIGNING_CA: &[u8] = include_bytes!("../examples/testdata/collaterals/signing_ca.der");
+const PCK_CRL: &[u8] = include_bytes!("../examples/testdata/collaterals/pck_platform_crl.der");
+
+// The TCB info and QE identity are valid from `ISSUE_DATE` to `NEXT_UPDATE`.
+const ISSUE_DATE: &str = "2020-01-01T00:00:00Z";
+const NEXT_UPDATE: &str = "2100-01-01T00:00:00Z";
+
+// The TEE TCB SVN of the test quote is below the TCB levels of its TCB info. Quotes of the
+// test platform are raised to the latest level, with the same TDX module, to be up to date.
+const UP_TO_DATE_TEE_TCB_SVN: [u8; 16] = [5, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+const TD_BODY_LEN: usize = 584;
+const TD_REPORT_DATA_OFFSET: usize = 520;
+const ECDSA_SIGNATURE_LEN: usize = 64;
+const ECDSA_KEY_LEN: usize = 64;
+const QE_REPORT_LEN: usize = 384;
+
+pub(crate) struct TestPlatform {
+    /// Collaterals issued by the test Root CA, see `Collaterals::root_ca`.
+    pub(crate) collaterals: Collaterals,
+    pck_chain: Vec<u8>,
+    pck_key: SigningKey,
+    attestation_key: SigningKey,
+}
+
+impl TestPlatform {
+    pub(crate) fn new() -> Self {
+        let root_key = random_key();
+        let signing_key = random_key();
+        let pck_ca_key = random_key();
+        let pck_key = random_key();
+
+        let chain = pck_cert_chain();
+        let [pck_cert, pck_ca, root_ca] = &chain[..] else {
+            panic!("The PCK certificate chain of the test quote is not of length 3");
+        };
+        let root_ca = resign_cert(root_ca, &root_key, &root_key);
+        let pck_chain = [
+            resign_cert(pck_cert, &pck_key, &pck_ca_key),
+            resign_cert(pck_ca, &pck_ca_key, &root_key),
+            root_ca.clone(),
+        ]
+        .iter()
+        .map(|der| der_to_pem(der))
+        .collect::<String>();
+
+        let mut tcb_info: TcbInfoV3 = serde_json::from_slice(TCB_INFO).unwrap();
+        tcb_info.tcb_info.issue_date = ISSUE_DATE.to_string();
+        tcb_info.tcb_info.next_update = NEXT_UPDATE.to_string();
+        tcb_info.signature = sign_json(&signing_key, &tcb_info.tcb_info);
+
+        let mut qe_identity: EnclaveIdentityV2 = serde_json::from_slice(QE_IDENTITY).unwrap();
+        qe_identity.enclave_identity.issue_date = ISSUE_DATE.to_string();
+        qe_identity.enclave_identity.next_update = NEXT_UPDATE.to_string();
+        qe_identity.signature = sign_json(&signing_key, &qe_identity.enclave_identity);
+
+        TestPlatform {
+            collaterals: Collaterals {
+                tcb_info: serde_json::to_vec(&tcb_info).unwrap(),
+                qe_identity: serde_json::to_vec(&qe_identity).unwrap(),
+                root_ca,
+                root_ca_crl: resign_crl(ROOT_CA_CRL, &root_key),
+                signing_ca: resign_cert(SIGNING_CA, &signing_key, &root_key),
+                pck_crl: resign_crl(PCK_CRL, &pck_ca_key),
+                pck_type: CA::PLATFORM,
+            },
+            pck_chain: pck_chain.into_bytes(),
+            pck_key,
+            attestation_key: random_key(),
+        }
+    }
+
+    /// Issues an up to date V4 TDX quote with the given report data.
+    pub(crate) fn quote(&self, report_data: &[u8; 64]) -> Vec<u8> {
+        let mut quote = embed_pck_cert_chain(QUOTE, &self.pck_chain).unwrap();
+        quote[QUOTE_HEADER_LEN..QUOTE_HEADER_LEN + 16].copy_from_slice(&UP_TO_DATE_TEE_TCB_SVN);
+
+        let report_data_offset = QUOTE_HEADER_LEN + TD_REPORT_DATA_OFFSET;
+        quote[report_data_offset..report_data_offset + 64].copy_from_slice(report_data);
+
+        let signed_len = QUOTE_HEADER_LEN + TD_BODY_LEN;
+        let signature_offset = signed_len + 4;
+        let key_offset = signature_offset + ECDSA_SIGNATURE_LEN;
+        let attestation_key = self.attestation_key.verifying_key().to_encoded_point(false);
+        quote[key_offset..key_offset + ECDSA_KEY_LEN]
+            .copy_from_slice(&attestation_key.as_bytes()[1..]);
+
+        // The QE report binds the attestation key and the QE authentication data.
+        let qe_report_offset = key_offset + ECDSA_KEY_LEN + 6;
+        let qe_signature_offset = qe_report_offset + QE_REPORT_LEN;
+        let auth_data_offset = qe_signature_offset + ECDSA_SIGNATURE_LEN;
+        let auth_data_len =
+            u16::from_le_bytes([quote[auth_data_offset], quote[auth_data_offset + 1]]) as usize;
+        let auth_data = &quote[auth_data_offset + 2..auth_data_offset + 2 + auth_data_len];
+        let mut qe_report_data = [0u8; 64];
+        qe_report_data[..32].copy_from_slice(
+            &Sha256::new_with_prefix(&attestation_key.as_bytes()[1..])
+                .chain_update(auth_data)
+                .finalize(),
+        );
+        let qe_report_data_offset = qe_report_offset + QE_REPORT_LEN - 64;
+        quote[qe_report_data_offset..qe_report_offset + QE_REPORT_LEN]
+            .copy_from_slice(&qe_report_data);
+
+        let qe_signature = sign(&self.pck_key, &quote[qe_report_offset..qe_signature_offset]);
+        quote[qe_signature_offset..auth_data_offset].copy_from_slice(&qe_signature);
+        let signature = sign(&self.attestation_key, &quote[..signed_len]);
+        quote[signature_offset..key_offset].copy_from_slice(&signature);
+        quote
+    }
+}
+
+fn random_key() -> SigningKey {
+    SigningKey::random(&mut rand::rngs::OsRng)
+}
+
+/// Raw (r || s) ECDSA signature of `data`, as found in quotes.
+fn sign(key: &SigningKey, data: &[u8]) -> [u8; 64] {
+    let signature: Signature = key.sign(data);
+    signature.to_bytes().into()
+}
+
+/// Hex signature of the JSON serialization of `body`, as checked by dcap-rs.
+fn sign_json<T: serde::Serialize>(key: &SigningKey, body: &T) -> String {
+    hex::encode(sign(key, &serde_json::to_vec(body).unwrap()))
+}
+
+/// DER certificates of the PCK certificate chain of the test quote, leaf first.
+fn pck_cert_chain() -> Vec<Vec<u8>> {
+    let quote = parse_quote_v4(QUOTE).unwrap();
+    let CertDataType::QeReportCertData(cert_data) = quote.signature.qe_cert_data.get_cert_data()
+    else {
+        panic!("The test quote does not hold QE report certification data");
+    };
+    Pem::iter_from_buffer(&cert_data.qe_cert_data.cert_data)
+        .map(|pem| pem.unwrap().contents)
+        .collect()
+}
+
+/// Replaces the public key of a certificate with the one of `subject`, and signs it with
+/// `issuer`. The names and extensions, including the SGX extension, are kept.
+fn resign_cert(der: &[u8], subject: &SigningKey, issuer: &SigningKey) -> Vec<u8> {
+    let mut cert = Certificate::from_der(der).unwrap();
+    cert.tbs_certificate.subject_public_key_info =
+        SubjectPublicKeyInfoOwned::from_key(*subject.verifying_key()).unwrap();
+    let signature: Signature = issuer.sign(&cert.tbs_certificate.to_der().unwrap());
+    cert.signature = BitString::from_bytes(signature.to_der().as_bytes()).unwrap();
+    cert.to_der().unwrap()
+}
+
+fn resign_crl(der: &[u8], issuer: &SigningKey) -> Vec<u8> {
+    let mut crl = CertificateList::from_der(der).unwrap();
+    let signature: Signature = issuer.sign(&crl.tbs_cert_list.to_der().unwrap());
+    crl.signature = BitString::from_bytes(signature.to_der().as_bytes()).unwrap();
+    crl.to_der().unwrap()
+}
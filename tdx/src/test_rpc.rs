@@ -0,0 +1,170 @@
+//! A JSON-RPC endpoint standing in for a node in the tests of the on-chain clients.
+//!
+//! Each request is passed to a handler with its method and params, and the endpoint records the
+//! methods it was called with. Connections are closed after each response.
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+use alloy::consensus::{Transaction, TxEnvelope};
+use alloy::eips::Decodable2718;
+use alloy::primitives::keccak256;
+use serde_json::{json, Value};
+
+type Handler = dyn Fn(&str, &Value) -> Result<Value, String> + Send + Sync;
+
+pub(crate) struct TestRpc {
+    url: String,
+    methods: Arc<Mutex<Vec<String>>>,
+}
+
+impl TestRpc {
+    /// Serves `handler` on a local port until the end of the test process. An `Err` is
+    /// returned to the client as a JSON-RPC error with the given message.
+    pub(crate) fn start(
+        handler: impl Fn(&str, &Value) -> Result<Value, String> + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let methods = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let recorded = methods.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let handler = handler.clone();
+                let recorded = recorded.clone();
+                std::thread::spawn(move || serve(stream, &*handler, &recorded));
+            }
+        });
+        TestRpc { url, methods }
+    }
+
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The methods called so far, in order.
+    pub(crate) fn methods(&self) -> Vec<String> {
+        self.methods.lock().unwrap().clone()
+    }
+}
+
+/// The first Anvil development account, to sign transactions.
+pub(crate) const PRIVATE_KEY: &str =
+    "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+/// The calldata of an `eth_call` or `eth_estimateGas` request.
+pub(crate) fn call_input(params: &Value) -> Vec<u8> {
+    let tx = &params[0];
+    let input = tx["input"].as_str().or(tx["data"].as_str()).unwrap_or("0x");
+    hex::decode(input.trim_start_matches("0x")).unwrap()
+}
+
+/// The calldata of a transaction sent with `eth_sendRawTransaction`.
+pub(crate) fn raw_transaction_input(params: &Value) -> Vec<u8> {
+    let raw = hex::decode(params[0].as_str().unwrap().trim_start_matches("0x")).unwrap();
+    TxEnvelope::decode_2718(&mut raw.as_slice())
+        .unwrap()
+        .input()
+        .to_vec()
+}
+
+/// Answers the requests made to send a transaction and wait for its receipt, the transaction
+/// being mined with the given `status`. Returns `None` for the other methods.
+pub(crate) fn transaction(method: &str, params: &Value, status: bool) -> Option<Value> {
+    let result = match method {
+        "eth_chainId" => json!("0x7a69"),
+        "eth_blockNumber" => json!("0x1"),
+        "eth_getTransactionCount" => json!("0x0"),
+        "eth_estimateGas" => json!("0x5208"),
+        "eth_gasPrice" | "eth_maxPriorityFeePerGas" => json!("0x1"),
+        "eth_feeHistory" => json!({
+            "oldestBlock": "0x1",
+            "baseFeePerGas": ["0x1", "0x1"],
+            "gasUsedRatio": [0.5],
+            "reward": [["0x1"]],
+        }),
+        "eth_sendRawTransaction" => {
+            let raw = hex::decode(params[0].as_str().unwrap().trim_start_matches("0x"));
+            json!(keccak256(raw.unwrap()))
+        }
+        "eth_getTransactionReceipt" => json!({
+            "type": "0x2",
+            "status": if status { "0x1" } else { "0x0" },
+            "transactionHash": params[0],
+            "transactionIndex": "0x0",
+            "blockHash": format!("0x{}", "22".repeat(32)),
+            "blockNumber": "0x1",
+            "from": "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+            "to": null,
+            "contractAddress": null,
+            "cumulativeGasUsed": "0x5208",
+            "gasUsed": "0x5208",
+            "effectiveGasPrice": "0x1",
+            "logs": [],
+            "logsBloom": format!("0x{}", "00".repeat(256)),
+        }),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Encodes bytes as a JSON-RPC data value.
+pub(crate) fn data(bytes: &[u8]) -> Value {
+    json!(format!("0x{}", hex::encode(bytes)))
+}
+
+fn serve(stream: TcpStream, handler: &Handler, recorded: &Mutex<Vec<String>>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            return;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+
+    let request: Value = serde_json::from_slice(&body).unwrap();
+    let response = match &request {
+        Value::Array(requests) => Value::Array(
+            requests
+                .iter()
+                .map(|request| respond(request, handler, recorded))
+                .collect(),
+        ),
+        request => respond(request, handler, recorded),
+    };
+    let body = serde_json::to_vec(&response).unwrap();
+    let mut stream = stream;
+    let _ = write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    let _ = stream.write_all(&body);
+}
+
+fn respond(request: &Value, handler: &Handler, recorded: &Mutex<Vec<String>>) -> Value {
+    let method = request["method"].as_str().unwrap_or_default();
+    recorded.lock().unwrap().push(method.to_string());
+    match handler(method, &request["params"]) {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }),
+        Err(message) => json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "error": { "code": -32000, "message": message },
+        }),
+    }
+}
@@ -1,11 +1,17 @@
-use crate::CA;
+use crate::error::{Result, TdxError};
+use crate::pccs::pcs::IPCSDao::CA;
+use crate::pck::SgxExtensions;
+use dcap_rs::types::quotes::version_3::QuoteV3;
 use dcap_rs::types::quotes::version_4::QuoteV4;
-use dcap_rs::types::quotes::QeReportCertData;
+use dcap_rs::types::quotes::{QeReportCertData, QuoteHeader};
 use dcap_rs::utils::cert::{get_x509_issuer_cn, parse_certchain, parse_pem};
 use rand::RngCore;
-use x509_parser::oid_registry::asn1_rs::{oid, FromDer, OctetString, Oid, Sequence};
+use sha2::{Digest, Sha256, Sha512};
+use std::panic;
 use x509_parser::prelude::*;
 
+pub const QUOTE_HEADER_LEN: usize = 48;
+
 /// Generates 64 bytes of random data
 /// Always guaranted to return something (ie, unwrap() can be safely called)
 pub fn generate_random_data() -> Option<[u8; 64]> {
@@ -14,58 +20,129 @@ pub fn generate_random_data() -> Option<[u8; 64]> {
     Some(data)
 }
 
+/// Panics if the quote does not embed a valid PCK certificate chain.
+#[deprecated(note = "panics on malformed PCK certificates, use `try_get_pck_fmspc_and_issuer`")]
 pub fn get_pck_fmspc_and_issuer(quote: &QuoteV4) -> (String, CA) {
+    try_get_pck_fmspc_and_issuer(quote).expect("Failed to parse the PCK certificate")
+}
+
+/// Returns the FMSPC and the issuer of the PCK certificate embedded in the quote,
+/// or an error if the quote does not embed a valid PCK certificate chain.
+pub fn try_get_pck_fmspc_and_issuer(quote: &QuoteV4) -> Result<(String, CA)> {
     let raw_cert_data = QeReportCertData::from_bytes(&quote.signature.qe_cert_data.cert_data);
 
-    let pem = parse_pem(&raw_cert_data.qe_cert_data.cert_data).expect("Failed to parse cert data");
+    let pem = parse_pem(&raw_cert_data.qe_cert_data.cert_data)
+        .map_err(|e| TdxError::X509(format!("{:?}", e)))?;
     // Cert Chain:
     // [0]: pck ->
     // [1]: pck ca ->
     // [2]: root ca
     let cert_chain = parse_certchain(&pem);
-    let pck = &cert_chain[0];
+    let pck = cert_chain
+        .first()
+        .ok_or_else(|| TdxError::X509("PCK certificate not found".to_string()))?;
 
     let pck_issuer = get_x509_issuer_cn(pck);
 
     let pck_ca = match pck_issuer.as_str() {
         "Intel SGX PCK Platform CA" => CA::PLATFORM,
         "Intel SGX PCK Processor CA" => CA::PROCESSOR,
-        _ => panic!("Unknown PCK Issuer"),
+        _ => {
+            return Err(TdxError::X509(format!(
+                "Unknown PCK Issuer: {}",
+                pck_issuer
+            )))
+        }
     };
 
-    let fmspc_slice = extract_fmspc_from_extension(pck);
-    let fmspc = hex::encode(fmspc_slice);
+    let fmspc = hex::encode(extract_fmspc_from_extension(pck)?);
 
-    (fmspc, pck_ca)
+    Ok((fmspc, pck_ca))
 }
 
-pub fn extract_fmspc_from_extension<'a>(cert: &'a X509Certificate<'a>) -> [u8; 6] {
-    let sgx_extensions_bytes = cert
-        .get_extension_unique(&oid!(1.2.840 .113741 .1 .13 .1))
-        .unwrap()
-        .unwrap()
-        .value;
-
-    let (_, sgx_extensions) = Sequence::from_der(sgx_extensions_bytes).unwrap();
-
-    let mut fmspc = [0; 6];
-
-    let mut i = sgx_extensions.content.as_ref();
-
-    while i.len() > 0 {
-        let (j, current_sequence) = Sequence::from_der(i).unwrap();
-        i = j;
-        let (j, current_oid) = Oid::from_der(current_sequence.content.as_ref()).unwrap();
-        match current_oid.to_id_string().as_str() {
-            "1.2.840.113741.1.13.1.4" => {
-                let (k, fmspc_bytes) = OctetString::from_der(j).unwrap();
-                assert_eq!(k.len(), 0);
-                fmspc.copy_from_slice(fmspc_bytes.as_ref());
-                break;
-            }
-            _ => continue,
-        }
+/// Parses the SGX extensions of the PCK certificate embedded in the quote.
+pub fn get_pck_sgx_extensions(quote: &QuoteV4) -> Result<SgxExtensions> {
+    let raw_cert_data = QeReportCertData::from_bytes(&quote.signature.qe_cert_data.cert_data);
+    get_sgx_extensions_from_pem_chain(&raw_cert_data.qe_cert_data.cert_data)
+}
+
+/// Parses the SGX extensions of the first certificate of a PEM encoded PCK cert chain.
+pub fn get_sgx_extensions_from_pem_chain(pem_chain: &[u8]) -> Result<SgxExtensions> {
+    let pem = parse_pem(pem_chain).map_err(|e| TdxError::X509(format!("{:?}", e)))?;
+    let cert_chain = parse_certchain(&pem);
+    let pck = cert_chain
+        .first()
+        .ok_or_else(|| TdxError::X509("PCK certificate not found".to_string()))?;
+
+    SgxExtensions::from_cert(pck)
+}
+
+/// Parses a V4 quote, returning an error instead of panicking on malformed input.
+pub fn parse_quote_v4(raw_quote: &[u8]) -> Result<QuoteV4> {
+    check_quote_version(raw_quote, 4)?;
+    panic::catch_unwind(|| QuoteV4::from_bytes(raw_quote))
+        .map_err(|_| TdxError::Dcap("Malformed quote".to_string()))
+}
+
+/// Parses a V3 quote, returning an error instead of panicking on malformed input.
+pub fn parse_quote_v3(raw_quote: &[u8]) -> Result<QuoteV3> {
+    check_quote_version(raw_quote, 3)?;
+    panic::catch_unwind(|| QuoteV3::from_bytes(raw_quote))
+        .map_err(|_| TdxError::Dcap("Malformed quote".to_string()))
+}
+
+fn check_quote_version(raw_quote: &[u8], version: u16) -> Result<()> {
+    if raw_quote.len() < QUOTE_HEADER_LEN {
+        return Err(TdxError::Dcap("Quote is too short".to_string()));
+    }
+    let header = QuoteHeader::from_bytes(&raw_quote[..QUOTE_HEADER_LEN]);
+    if header.version != version {
+        return Err(TdxError::Dcap(format!(
+            "Unsupported quote version: {}",
+            header.version
+        )));
     }
+    Ok(())
+}
+
+/// Derives the 64 bytes of report data bound to a nonce, ie SHA-512(nonce).
+pub fn report_data_from_nonce(nonce: &[u8]) -> [u8; 64] {
+    let mut report_data = [0u8; 64];
+    report_data.copy_from_slice(&Sha512::digest(nonce));
+    report_data
+}
+
+/// Checks that the var data returned by Azure Confidential VMs alongside a quote is bound
+/// to it: the first 32 bytes of the report data must be SHA-256(var data).
+pub fn var_data_matches_report_data(var_data: &[u8], report_data: &[u8; 64]) -> bool {
+    Sha256::digest(var_data).as_slice() == &report_data[..32]
+}
 
-    fmspc
+pub fn extract_fmspc_from_extension<'a>(cert: &'a X509Certificate<'a>) -> Result<[u8; 6]> {
+    let sgx_extensions = SgxExtensions::from_cert(cert)?;
+    Ok(sgx_extensions.fmspc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUOTE_V3: &[u8] = include_bytes!("../examples/testdata/sgx_v3_quote.bin");
+    const QUOTE_V4: &[u8] = include_bytes!("../examples/testdata/tdx_v4_quote_90c06f.bin");
+
+    #[test]
+    fn parses_quotes_of_their_version() {
+        assert_eq!(parse_quote_v3(QUOTE_V3).unwrap().header.version, 3);
+        assert_eq!(parse_quote_v4(QUOTE_V4).unwrap().header.version, 4);
+        assert!(parse_quote_v3(QUOTE_V4).is_err());
+        assert!(parse_quote_v4(QUOTE_V3).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_quotes() {
+        for len in [0, QUOTE_HEADER_LEN - 1, QUOTE_HEADER_LEN, 432, 436, 1000] {
+            assert!(parse_quote_v3(&QUOTE_V3[..len]).is_err(), "{}", len);
+            assert!(parse_quote_v4(&QUOTE_V4[..len]).is_err(), "{}", len);
+        }
+    }
 }
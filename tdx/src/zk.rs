@@ -0,0 +1,478 @@
+//! Verifies the proofs of the DCAP zkVM programs without a chain: Risc0 Groth16 seals and SP1
+//! PLONK or Groth16 proofs, as accepted by `verifyAndAttestWithZKProof`.
+//!
+//! A proof only binds the journal to the program it was made for, so the expected image ID or
+//! verification key must come from a trusted source, eg the pinned values of the zk READMEs.
+use dcap_codec::aggregation::DcapAggregation;
+use dcap_codec::{DcapProgramJournal, TimeWindow};
+use dcap_rs::types::VerifiedOutput;
+use hex::FromHex;
+use risc0_zkvm::sha::Digestible;
+use risc0_zkvm::{
+    Digest, Groth16Receipt, Groth16ReceiptVerifierParameters, InnerReceipt, Receipt, ReceiptClaim,
+    VerifierContext,
+};
+use sp1_verifier::{Groth16Verifier, PlonkVerifier, GROTH16_VK_BYTES, PLONK_VK_BYTES};
+
+use crate::error::{Result, TdxError};
+use crate::journal::{parse_verified_output, CommittedCollaterals};
+
+/// Length of a Risc0 Groth16 seal, without the selector of the verifier.
+const RISC0_GROTH16_SEAL_LEN: usize = 256;
+/// Control roots of the Risc0 Groth16 verifiers accepted besides the one of the risc0-zkvm
+/// version in use, which only differ by their control root: Groth16 V2.1 (selector
+/// `f536085a`, risc0-zkvm 2.1 and 2.2).
+const RISC0_GROTH16_CONTROL_ROOTS: &[&str] =
+    &["884389273e128b32475b334dec75ee619b77cb33d41c332021fe7e44c746ee60"];
+const SELECTOR_LEN: usize = 4;
+
+/// A proof of a journal, in the encoding of the `proofBytes` of `verifyAndAttestWithZKProof`.
+#[derive(Clone, Copy, Debug)]
+pub enum ZkProof<'a> {
+    /// The seal of a Risc0 Groth16 receipt, with or without the 4 bytes selector of the
+    /// Groth16 verifier parameters.
+    Risc0Groth16(&'a [u8]),
+    /// `SP1ProofWithPublicValues::bytes` of a PLONK proof.
+    Sp1Plonk(&'a [u8]),
+    /// `SP1ProofWithPublicValues::bytes` of a Groth16 proof.
+    Sp1Groth16(&'a [u8]),
+}
+
+/// The decoded journal of a verified proof of the DCAP program.
+#[derive(Debug)]
+pub struct VerifiedJournal {
+    pub verified_output: VerifiedOutput,
+    /// The verification time chosen by the prover, in seconds since the Unix epoch.
+    pub current_time: u64,
+    pub collaterals: CommittedCollaterals,
+    /// With time binding, the validity of the collaterals that `current_time` is within.
+    pub time_window: Option<TimeWindow>,
+}
+
+/// Verifies `proof` of `journal` for the program `program_id`: the image ID of the Risc0 guest,
+/// or the verification key hash of the SP1 program (`SP1VerifyingKey::bytes32`).
+pub fn verify_proof(proof: ZkProof, program_id: &[u8; 32], journal: &[u8]) -> Result<()> {
+    match proof {
+        ZkProof::Risc0Groth16(seal) => verify_risc0_groth16(seal, program_id, journal),
+        ZkProof::Sp1Plonk(proof) => verify_sp1("PLONK", || {
+            PlonkVerifier::verify(proof, journal, &vkey_hash(program_id), *PLONK_VK_BYTES)
+                .map_err(|e| e.to_string())
+        }),
+        ZkProof::Sp1Groth16(proof) => verify_sp1("Groth16", || {
+            Groth16Verifier::verify(proof, journal, &vkey_hash(program_id), *GROTH16_VK_BYTES)
+                .map_err(|e| e.to_string())
+        }),
+    }
+}
+
+/// Verifies `proof` of the journal of the DCAP program (`DcapProgramJournal`), see
+/// `verify_proof`, and decodes it. Journals holding a failure are rejected, and so are journals
+/// whose time window does not contain `now`, in seconds since the Unix epoch.
+///
+/// Journals without a time window (inputs without `bind_time`) are accepted whatever `now`:
+/// their `current_time` is chosen by the prover and must be checked by the caller.
+pub fn verify_journal(
+    proof: ZkProof,
+    program_id: &[u8; 32],
+    journal: &[u8],
+    now: u64,
+) -> Result<VerifiedJournal> {
+    verify_proof(proof, program_id, journal)?;
+    let journal = decode_journal(journal)?;
+    check_time_window(&journal, now)?;
+    Ok(journal)
+}
+
+/// Verifies `proof` of the journal of the aggregation program `aggregation_program_id`, and
+/// decodes the journals it aggregates, see `decode_aggregation`.
+pub fn verify_aggregation(
+    proof: ZkProof,
+    aggregation_program_id: &[u8; 32],
+    program_id: &[u8; 32],
+    journal: &[u8],
+    now: u64,
+) -> Result<Vec<Result<VerifiedJournal>>> {
+    verify_proof(proof, aggregation_program_id, journal)?;
+    decode_aggregation(journal, program_id, now)
+}
+
+/// Decodes the journal of the aggregation program (`DcapAggregation`), without verifying its
+/// proof. The aggregation is rejected unless its journals were proven by the DCAP program
+/// `program_id`, as the aggregation programs commit the journals of any program.
+///
+/// Each aggregated journal is decoded and checked as by `verify_journal`, and returned in the
+/// order of the aggregation: one failure does not invalidate the other journals. Batch
+/// journals are not decoded, check their `program_id` with `DcapAggregation::decode`.
+pub fn decode_aggregation(
+    journal: &[u8],
+    program_id: &[u8; 32],
+    now: u64,
+) -> Result<Vec<Result<VerifiedJournal>>> {
+    let aggregation =
+        DcapAggregation::decode(journal).map_err(|e| TdxError::Encoding(e.to_string()))?;
+    aggregation
+        .check_program_id(&[*program_id])
+        .map_err(|e| TdxError::Dcap(e.to_string()))?;
+    Ok(aggregation
+        .journals
+        .iter()
+        .map(|journal| {
+            let journal = decode_journal(journal)?;
+            check_time_window(&journal, now)?;
+            Ok(journal)
+        })
+        .collect())
+}
+
+/// Rejects journals whose collaterals were not all valid at `now`.
+pub fn check_time_window(journal: &VerifiedJournal, now: u64) -> Result<()> {
+    match journal.time_window {
+        Some(window) if now > window.not_after => Err(TdxError::Dcap(format!(
+            "The collaterals of the proof expired at {}, before {}",
+            window.not_after, now
+        ))),
+        Some(window) if now < window.not_before => Err(TdxError::Dcap(format!(
+            "The collaterals of the proof are only valid from {}, after {}",
+            window.not_before, now
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Decodes the journal of the DCAP program, without verifying its proof or its time window.
+pub fn decode_journal(journal: &[u8]) -> Result<VerifiedJournal> {
+    let journal =
+        DcapProgramJournal::decode(journal).map_err(|e| TdxError::Encoding(e.to_string()))?;
+    let output = match journal {
+        DcapProgramJournal::Success(output) => output,
+        DcapProgramJournal::Failure(code) => {
+            return Err(TdxError::Dcap(format!(
+                "The journal holds a failure: {}",
+                code
+            )))
+        }
+    };
+    Ok(VerifiedJournal {
+        verified_output: parse_verified_output(&output.verified_output)?,
+        current_time: output.current_time,
+        collaterals: CommittedCollaterals {
+            tcbinfo_content_hash: output.tcbinfo_content_hash,
+            qeidentity_content_hash: output.qeidentity_content_hash,
+            sgx_intel_root_ca_cert_hash: output.sgx_intel_root_ca_cert_hash,
+            sgx_tcb_signing_cert_hash: output.sgx_tcb_signing_cert_hash,
+            sgx_intel_root_ca_crl_hash: output.sgx_intel_root_ca_crl_hash,
+            sgx_pck_crl_hash: output.sgx_pck_crl_hash,
+        },
+        time_window: output.time_window,
+    })
+}
+
+fn verify_risc0_groth16(seal: &[u8], image_id: &[u8; 32], journal: &[u8]) -> Result<()> {
+    let (verifier_parameters, seal) = match seal.len() {
+        RISC0_GROTH16_SEAL_LEN => (Groth16ReceiptVerifierParameters::default(), seal),
+        len if len == SELECTOR_LEN + RISC0_GROTH16_SEAL_LEN => {
+            let (selector, seal) = seal.split_at(SELECTOR_LEN);
+            let verifier_parameters = risc0_groth16_verifier_parameters()
+                .find(|parameters| parameters.digest().as_bytes()[..SELECTOR_LEN] == *selector)
+                .ok_or_else(|| {
+                    TdxError::Signature(format!(
+                        "Unknown Groth16 verifier selector: {}",
+                        hex::encode(selector)
+                    ))
+                })?;
+            (verifier_parameters, seal)
+        }
+        len => {
+            return Err(TdxError::Encoding(format!(
+                "Invalid Groth16 seal length: {}",
+                len
+            )))
+        }
+    };
+
+    let image_id = Digest::from(*image_id);
+    let claim = ReceiptClaim::ok(image_id, journal.to_vec());
+    let receipt = Receipt::new(
+        InnerReceipt::Groth16(Groth16Receipt::new(
+            seal.to_vec(),
+            claim.into(),
+            verifier_parameters.digest(),
+        )),
+        journal.to_vec(),
+    );
+    let context = VerifierContext::default().with_groth16_verifier_parameters(verifier_parameters);
+    receipt
+        .verify_with_context(&context, image_id)
+        .map_err(|e| TdxError::Signature(format!("Invalid Risc0 Groth16 seal: {}", e)))
+}
+
+/// The Groth16 verifier parameters of the seals that are accepted, selected by the first 4
+/// bytes of their digest like the verifier router of `verifyAndAttestWithZKProof` does: the
+/// ones of the risc0-zkvm version in use, and the ones of the older verifiers in
+/// `RISC0_GROTH16_CONTROL_ROOTS`. Seals without a selector are verified with the former.
+fn risc0_groth16_verifier_parameters() -> impl Iterator<Item = Groth16ReceiptVerifierParameters> {
+    let current = Groth16ReceiptVerifierParameters::default();
+    let previous =
+        RISC0_GROTH16_CONTROL_ROOTS
+            .iter()
+            .map(|control_root| Groth16ReceiptVerifierParameters {
+                control_root: Digest::from_hex(control_root).unwrap(),
+                ..Groth16ReceiptVerifierParameters::default()
+            });
+    std::iter::once(current).chain(previous)
+}
+
+// The SP1 verifiers panic on some malformed proofs instead of returning an error.
+fn verify_sp1(
+    proof_system: &str,
+    verify: impl FnOnce() -> std::result::Result<(), String>,
+) -> Result<()> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(verify))
+        .unwrap_or_else(|_| Err("Malformed proof".to_string()))
+        .map_err(|e| TdxError::Signature(format!("Invalid SP1 {} proof: {}", proof_system, e)))
+}
+
+fn vkey_hash(program_id: &[u8; 32]) -> String {
+    format!("0x{}", hex::encode(program_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collateral::Collaterals;
+    use crate::utils::parse_quote_v4;
+    use crate::Tdx;
+    use dcap_codec::DcapProgramOutput;
+    use sha2::{Digest as _, Sha256};
+    use std::path::Path;
+
+    const QUOTE: &[u8] = include_bytes!("../examples/testdata/tdx_v4_quote_90c06f.bin");
+    /// Within the validity of the TCB Info and QE Identity of the test collaterals.
+    const VERIFICATION_TIME: u64 = 1749600000;
+    const WINDOW: TimeWindow = TimeWindow {
+        not_before: 1749081600,
+        not_after: 1751673600,
+    };
+
+    fn journal(time_window: Option<TimeWindow>) -> Vec<u8> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/testdata/collaterals");
+        let collaterals = Collaterals::read_from_dir(&dir).unwrap();
+        let verified_output = Tdx::new()
+            .verify_attestation_report_with_collaterals(
+                &parse_quote_v4(QUOTE).unwrap(),
+                &collaterals,
+                VERIFICATION_TIME,
+            )
+            .unwrap();
+        DcapProgramJournal::Success(Box::new(DcapProgramOutput {
+            verified_output: verified_output.to_bytes(),
+            current_time: VERIFICATION_TIME,
+            tcbinfo_content_hash: [1; 32],
+            qeidentity_content_hash: [2; 32],
+            sgx_intel_root_ca_cert_hash: [3; 32],
+            sgx_tcb_signing_cert_hash: [4; 32],
+            sgx_intel_root_ca_crl_hash: [5; 32],
+            sgx_pck_crl_hash: [6; 32],
+            time_window,
+        }))
+        .encode()
+        .unwrap()
+    }
+
+    #[test]
+    fn decodes_journals() {
+        let decoded = decode_journal(&journal(Some(WINDOW))).unwrap();
+        assert_eq!(decoded.current_time, VERIFICATION_TIME);
+        assert_eq!(decoded.collaterals.sgx_pck_crl_hash, [6; 32]);
+        assert_eq!(decoded.time_window, Some(WINDOW));
+        assert_eq!(decoded.verified_output.quote_version, 4);
+
+        let failure = DcapProgramJournal::Failure(dcap_codec::ErrorCode::InvalidQuote)
+            .encode()
+            .unwrap();
+        assert!(matches!(decode_journal(&failure), Err(TdxError::Dcap(_))));
+        assert!(matches!(
+            decode_journal(&[0, 1, 2]),
+            Err(TdxError::Encoding(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_expired_time_windows() {
+        let bound = decode_journal(&journal(Some(WINDOW))).unwrap();
+        check_time_window(&bound, VERIFICATION_TIME).unwrap();
+        check_time_window(&bound, WINDOW.not_after).unwrap();
+        check_time_window(&bound, WINDOW.not_before).unwrap();
+        assert!(check_time_window(&bound, WINDOW.not_after + 1).is_err());
+        assert!(check_time_window(&bound, WINDOW.not_before - 1).is_err());
+
+        let unbound = decode_journal(&journal(None)).unwrap();
+        check_time_window(&unbound, u64::MAX).unwrap();
+    }
+
+    #[test]
+    fn decodes_aggregations_of_the_dcap_program() {
+        let program_id = [7; 32];
+        let failure = DcapProgramJournal::Failure(dcap_codec::ErrorCode::InvalidQuote)
+            .encode()
+            .unwrap();
+        let aggregation = DcapAggregation {
+            program_id,
+            journals: vec![journal(Some(WINDOW)), failure, journal(None)],
+        }
+        .encode()
+        .unwrap();
+
+        let journals = decode_aggregation(&aggregation, &program_id, VERIFICATION_TIME).unwrap();
+        assert_eq!(journals.len(), 3);
+        assert_eq!(journals[0].as_ref().unwrap().time_window, Some(WINDOW));
+        assert!(matches!(journals[1], Err(TdxError::Dcap(_))));
+        assert_eq!(journals[2].as_ref().unwrap().time_window, None);
+
+        let journals = decode_aggregation(&aggregation, &program_id, WINDOW.not_after + 1).unwrap();
+        assert!(journals[0].is_err());
+        assert!(journals[2].is_ok());
+
+        // Journals of another program are not trusted.
+        assert!(matches!(
+            decode_aggregation(&aggregation, &[8; 32], VERIFICATION_TIME),
+            Err(TdxError::Dcap(_))
+        ));
+        assert!(matches!(
+            decode_aggregation(&aggregation[1..], &program_id, VERIFICATION_TIME),
+            Err(TdxError::Encoding(_))
+        ));
+        assert!(matches!(
+            verify_aggregation(
+                ZkProof::Risc0Groth16(&[1; RISC0_GROTH16_SEAL_LEN]),
+                &[9; 32],
+                &program_id,
+                &aggregation,
+                VERIFICATION_TIME,
+            ),
+            Err(TdxError::Signature(_))
+        ));
+    }
+
+    // The test receipt of risc0-ethereum-contracts 2.2.0 (`test/TestReceipt.sol`), a seal of
+    // the Groth16 V2.1 verifier.
+    const TEST_RECEIPT_SEAL: &str = "f536085a1d323acff28d9c5ed9dc0cb8cf1b01f4596515aad4483ab8610b7c284f043ed81d74715567d7225580413feab5c59e32b0268bb290629957d06e1437bf2fb13f11b4d40f00a2e9a7462ff787fd9b8ebedbc22f25bc2a7be3a89394c00ed7018f02065b43f3e6ecec419f48bdd3f42f292503ccbca34a379b424af0a53cfe53660d693cbd13ee5ea7f38282d4d4be2a7bbdff3ba24cb460caa5b024bdb1f8003406b7d223e2110dab31b4194d5667e16264650924e1b5c00a1375b55318ed15732af940c906657fbc94daaad6da027941e0c01119bfe579ecd0431bf32065a1062d4d5e0f91c308a0ef80ed43fd14d8e84921a28214700ef8d6a8f02bea7e72a2";
+    const TEST_RECEIPT_JOURNAL: &[u8] = b"just a simple receipt";
+    const TEST_RECEIPT_IMAGE_ID: &str =
+        "e462bc1dc34b9dfe4af9243a5fc56f0e5423a88ace7e575fa051364109a8bfa0";
+
+    #[test]
+    fn verifies_risc0_groth16_seals() {
+        let seal = hex::decode(TEST_RECEIPT_SEAL).unwrap();
+        let image_id: [u8; 32] = hex::decode(TEST_RECEIPT_IMAGE_ID)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        // The receipt was made before risc0-zkvm 2.3, which has other verifier parameters.
+        assert_eq!(&seal[..SELECTOR_LEN], &[0xf5, 0x36, 0x08, 0x5a]);
+        let current = Groth16ReceiptVerifierParameters::default().digest();
+        assert_ne!(&seal[..SELECTOR_LEN], &current.as_bytes()[..SELECTOR_LEN]);
+
+        verify_proof(
+            ZkProof::Risc0Groth16(&seal),
+            &image_id,
+            TEST_RECEIPT_JOURNAL,
+        )
+        .unwrap();
+        // Seals without a selector are verified with the current verifier parameters.
+        let result = verify_proof(
+            ZkProof::Risc0Groth16(&seal[SELECTOR_LEN..]),
+            &image_id,
+            TEST_RECEIPT_JOURNAL,
+        );
+        assert!(matches!(result, Err(TdxError::Signature(_))));
+
+        let mut other_image_id = image_id;
+        other_image_id[0] ^= 1;
+        for (image_id, journal) in [
+            (&other_image_id, TEST_RECEIPT_JOURNAL),
+            (&image_id, b"just a simple receipU".as_slice()),
+            (&image_id, b"".as_slice()),
+        ] {
+            let result = verify_proof(ZkProof::Risc0Groth16(&seal), image_id, journal);
+            assert!(matches!(result, Err(TdxError::Signature(_))));
+        }
+        // The seal is not a DCAP journal.
+        assert!(matches!(
+            verify_journal(
+                ZkProof::Risc0Groth16(&seal),
+                &image_id,
+                TEST_RECEIPT_JOURNAL,
+                VERIFICATION_TIME,
+            ),
+            Err(TdxError::Encoding(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_risc0_seals() {
+        let journal = journal(Some(WINDOW));
+        let image_id = [7; 32];
+        let selector = &Groth16ReceiptVerifierParameters::default().digest();
+        let selector = &selector.as_bytes()[..SELECTOR_LEN];
+
+        let result = verify_proof(ZkProof::Risc0Groth16(&[0; 100]), &image_id, &journal);
+        assert!(matches!(result, Err(TdxError::Encoding(_))));
+
+        let seal = [
+            [0xff; SELECTOR_LEN].as_slice(),
+            &[0; RISC0_GROTH16_SEAL_LEN],
+        ]
+        .concat();
+        let result = verify_proof(ZkProof::Risc0Groth16(&seal), &image_id, &journal);
+        assert!(matches!(result, Err(TdxError::Signature(e)) if e.contains("selector")));
+
+        for seal in [
+            vec![0; RISC0_GROTH16_SEAL_LEN],
+            vec![1; RISC0_GROTH16_SEAL_LEN],
+            [selector, &[1; RISC0_GROTH16_SEAL_LEN]].concat(),
+        ] {
+            let result = verify_proof(ZkProof::Risc0Groth16(&seal), &image_id, &journal);
+            assert!(
+                matches!(result, Err(TdxError::Signature(_))),
+                "{:?}",
+                result
+            );
+        }
+
+        // The proof is checked before the journal is decoded.
+        let result = verify_journal(
+            ZkProof::Risc0Groth16(&[1; RISC0_GROTH16_SEAL_LEN]),
+            &image_id,
+            &journal,
+            VERIFICATION_TIME,
+        );
+        assert!(matches!(result, Err(TdxError::Signature(_))));
+    }
+
+    #[test]
+    fn rejects_invalid_sp1_proofs() {
+        let journal = journal(Some(WINDOW));
+        let vkey = [7; 32];
+        // The SP1 verifiers select their key with the first 4 bytes of its SHA-256 digest.
+        let selector = |vk: &[u8]| Sha256::digest(vk)[..SELECTOR_LEN].to_vec();
+
+        let plonk = [selector(&PLONK_VK_BYTES), vec![1; 768]].concat();
+        let groth16 = [selector(&GROTH16_VK_BYTES), vec![1; 256]].concat();
+        for proof in [
+            ZkProof::Sp1Plonk(&[]),
+            ZkProof::Sp1Plonk(&groth16),
+            ZkProof::Sp1Plonk(&plonk),
+            ZkProof::Sp1Groth16(&[]),
+            ZkProof::Sp1Groth16(&plonk),
+            ZkProof::Sp1Groth16(&groth16),
+        ] {
+            let result = verify_proof(proof, &vkey, &journal);
+            assert!(
+                matches!(result, Err(TdxError::Signature(_))),
+                "{:?}",
+                result
+            );
+        }
+    }
+}
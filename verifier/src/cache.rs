@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use tdx::collateral::{CollateralSource, Collaterals};
+use tdx::error::Result;
+use tdx::pccs::pcs::IPCSDao::CA;
+use tdx::tcb::SignedTcbInfo;
+
+use crate::metrics::Metrics;
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+struct CacheKey {
+    fmspc: String,
+    pck_type: &'static str,
+    quote_version: u32,
+}
+
+struct CacheEntry {
+    collaterals: Collaterals,
+    expires_at: Instant,
+}
+
+/// In-memory cache of the collaterals retrieved from a `CollateralSource`.
+///
+/// Entries are kept for the configured TTL, and never past the `nextUpdate` of their TCB Info.
+pub struct CollateralCache {
+    source: CollateralSource,
+    ttl: Duration,
+    entries: Mutex<HashMap<CacheKey, CacheEntry>>,
+    metrics: Arc<Metrics>,
+}
+
+impl CollateralCache {
+    pub fn new(source: CollateralSource, ttl: Duration, metrics: Arc<Metrics>) -> Self {
+        CollateralCache {
+            source,
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            metrics,
+        }
+    }
+
+    pub async fn get(&self, fmspc: &str, pck_type: CA, quote_version: u32) -> Result<Collaterals> {
+        let key = CacheKey {
+            fmspc: fmspc.to_lowercase(),
+            pck_type: pck_type_name(pck_type),
+            quote_version,
+        };
+
+        let cached = self
+            .entries
+            .lock()
+            .unwrap()
+            .get(&key)
+            .filter(|entry| entry.expires_at > Instant::now())
+            .map(|entry| entry.collaterals.clone());
+        self.metrics.record_cache_lookup(cached.is_some());
+        if let Some(collaterals) = cached {
+            return Ok(collaterals);
+        }
+
+        let collaterals = self
+            .source
+            .collaterals_for_fmspc(&key.fmspc, pck_type, quote_version)
+            .await
+            .inspect_err(|_| self.metrics.record_collateral_error())?;
+
+        let ttl = match tcb_info_time_to_next_update(&collaterals) {
+            Some(remaining) => remaining.min(self.ttl),
+            None => self.ttl,
+        };
+        if !ttl.is_zero() {
+            let mut entries = self.entries.lock().unwrap();
+            let now = Instant::now();
+            entries.retain(|_, entry| entry.expires_at > now);
+            entries.insert(
+                key,
+                CacheEntry {
+                    collaterals: collaterals.clone(),
+                    expires_at: now + ttl,
+                },
+            );
+        }
+        Ok(collaterals)
+    }
+}
+
+pub fn pck_type_name(pck_type: CA) -> &'static str {
+    match pck_type {
+        CA::PROCESSOR => "processor",
+        _ => "platform",
+    }
+}
+
+// Time left until the TCB Info must be refreshed, zero if it has expired.
+fn tcb_info_time_to_next_update(collaterals: &Collaterals) -> Option<Duration> {
+    let tcb_info = SignedTcbInfo::from_bytes(&collaterals.tcb_info)
+        .ok()?
+        .tcb_info;
+    let next_update = DateTime::parse_from_rfc3339(&tcb_info.next_update).ok()?;
+    Some(
+        (next_update.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+
+    fn testdata_collaterals() -> Collaterals {
+        let dir =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../tdx/examples/testdata/collaterals");
+        Collaterals::read_from_dir(&dir).unwrap()
+    }
+
+    /// Writes the test collaterals to a fresh directory, with the `nextUpdate` of their TCB
+    /// Info set to `next_update`.
+    fn collateral_dir(name: &str, next_update: &str) -> PathBuf {
+        let mut collaterals = testdata_collaterals();
+        let mut tcb_info: serde_json::Value =
+            serde_json::from_slice(&collaterals.tcb_info).unwrap();
+        tcb_info["tcbInfo"]["nextUpdate"] = next_update.into();
+        collaterals.tcb_info = serde_json::to_vec(&tcb_info).unwrap();
+
+        let dir =
+            std::env::temp_dir().join(format!("tdx-verifier-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        collaterals.write_to_dir(&dir).unwrap();
+        dir
+    }
+
+    fn cache_lookups(metrics: &Metrics) -> (u64, u64) {
+        let render = metrics.render();
+        let count = |result: &str| {
+            let prefix = format!(
+                "tdx_verifier_collateral_cache_total{{result=\"{}\"}} ",
+                result
+            );
+            render
+                .lines()
+                .find_map(|line| line.strip_prefix(&prefix))
+                .unwrap()
+                .parse()
+                .unwrap()
+        };
+        (count("hit"), count("miss"))
+    }
+
+    #[tokio::test]
+    async fn serves_cached_collaterals() {
+        let dir = collateral_dir("cache-hit", "2100-01-01T00:00:00Z");
+        let metrics = Arc::new(Metrics::default());
+        let cache = CollateralCache::new(
+            CollateralSource::Dir(dir.clone()),
+            Duration::from_secs(3600),
+            metrics.clone(),
+        );
+
+        let fetched = cache.get("90C06F000000", CA::PLATFORM, 4).await.unwrap();
+        // Served from the cache once the source is gone, whatever the case of the FMSPC.
+        std::fs::remove_dir_all(&dir).unwrap();
+        let cached = cache.get("90c06f000000", CA::PLATFORM, 4).await.unwrap();
+        assert_eq!(cached.tcb_info, fetched.tcb_info);
+        assert_eq!(cache_lookups(&metrics), (1, 1));
+
+        // Other quote versions and PCK CAs are cached separately.
+        assert!(cache.get("90c06f000000", CA::PLATFORM, 5).await.is_err());
+        assert!(cache.get("90c06f000000", CA::PROCESSOR, 4).await.is_err());
+        assert_eq!(cache_lookups(&metrics), (1, 3));
+    }
+
+    #[tokio::test]
+    async fn expires_cached_collaterals() {
+        let dir = collateral_dir("cache-ttl", "2100-01-01T00:00:00Z");
+        let metrics = Arc::new(Metrics::default());
+        let cache = CollateralCache::new(
+            CollateralSource::Dir(dir.clone()),
+            Duration::from_millis(100),
+            metrics.clone(),
+        );
+        cache.get("90c06f000000", CA::PLATFORM, 4).await.unwrap();
+        cache.get("90c06f000000", CA::PLATFORM, 4).await.unwrap();
+        assert_eq!(cache_lookups(&metrics), (1, 1));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(cache.get("90c06f000000", CA::PLATFORM, 4).await.is_err());
+        assert_eq!(cache_lookups(&metrics), (1, 2));
+    }
+
+    #[tokio::test]
+    async fn does_not_cache_past_next_update() {
+        // The TCB Info of the recorded collaterals is past its `nextUpdate`.
+        let dir = collateral_dir("cache-next-update", "2025-07-05T03:43:45Z");
+        let metrics = Arc::new(Metrics::default());
+        let cache = CollateralCache::new(
+            CollateralSource::Dir(dir.clone()),
+            Duration::from_secs(3600),
+            metrics.clone(),
+        );
+        cache.get("90c06f000000", CA::PLATFORM, 4).await.unwrap();
+        cache.get("90c06f000000", CA::PLATFORM, 4).await.unwrap();
+        assert_eq!(cache_lookups(&metrics), (0, 2));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+//! `tdx-verifier`: HTTP service verifying Intel TDX quotes.
+//!
+//! Endpoints:
+//! - `POST /verify`: verifies a quote, posted raw or as JSON (hex or base64, with optional
+//!   Azure var data), and returns the verification summary.
+//! - `GET /collateral/{fmspc}`: returns the collaterals of an FMSPC, as JSON or CBOR.
+//! - `GET /health` and `GET /metrics` (Prometheus text format).
+mod cache;
+mod metrics;
+mod service;
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::Parser;
+use tdx::collateral::CollateralSource;
+use tdx::pccs::PccsConfig;
+
+use crate::cache::CollateralCache;
+use crate::metrics::Metrics;
+use crate::service::{router, AppState};
+
+#[derive(Parser)]
+#[clap(
+    name = "tdx-verifier",
+    version,
+    about = "HTTP service verifying Intel TDX quotes"
+)]
+struct Cli {
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// Directory holding the collaterals, see `tdx collateral fetch`.
+    /// Collaterals are fetched from the on-chain PCCS if not set.
+    #[clap(long)]
+    collateral_dir: Option<PathBuf>,
+
+    /// RPC URL of the chain hosting the on-chain PCCS.
+    #[clap(long, env = "PCCS_RPC_URL", conflicts_with = "collateral_dir")]
+    rpc_url: Option<String>,
+
+    /// How long collaterals are cached, in seconds. Collaterals are never cached past the
+    /// `nextUpdate` of their TCB Info.
+    #[clap(long, default_value_t = 3600)]
+    cache_ttl: u64,
+
+    /// Accept the verification time chosen by the client in `POST /verify`. Clients can then
+    /// get expired collaterals or revoked certificates accepted by picking a past time.
+    #[clap(long)]
+    allow_client_time: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    let source = match cli.collateral_dir {
+        Some(dir) => CollateralSource::Dir(dir),
+        None => {
+            let mut config = PccsConfig::default();
+            if let Some(rpc_url) = cli.rpc_url {
+                config.rpc_url = rpc_url;
+            }
+            CollateralSource::Pccs(config)
+        }
+    };
+
+    let metrics = Arc::new(Metrics::default());
+    let state = Arc::new(AppState {
+        cache: CollateralCache::new(source, Duration::from_secs(cli.cache_ttl), metrics.clone()),
+        metrics,
+        allow_client_time: cli.allow_client_time,
+    });
+
+    let listener = tokio::net::TcpListener::bind(cli.listen).await?;
+    println!("tdx-verifier listening on {}", listener.local_addr()?);
+    axum::serve(listener, router(state))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
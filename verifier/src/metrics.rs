@@ -0,0 +1,108 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Counters exposed in the Prometheus text format on `/metrics`.
+#[derive(Default)]
+pub struct Metrics {
+    verify_ok: AtomicU64,
+    verify_failed: AtomicU64,
+    verify_micros: AtomicU64,
+    collateral_requests: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    collateral_errors: AtomicU64,
+}
+
+impl Metrics {
+    pub fn record_verification(&self, ok: bool, duration: Duration) {
+        if ok {
+            self.verify_ok.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.verify_failed.fetch_add(1, Ordering::Relaxed);
+        }
+        self.verify_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_collateral_request(&self) {
+        self.collateral_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_cache_lookup(&self, hit: bool) {
+        if hit {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.cache_misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_collateral_error(&self) {
+        self.collateral_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let get = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let verify_ok = get(&self.verify_ok);
+        let verify_failed = get(&self.verify_failed);
+
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, u64)]| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(out, "{}{} {}", name, labels, value);
+            }
+        };
+        metric(
+            "tdx_verifier_verifications_total",
+            "counter",
+            "Quote verifications by result.",
+            &[
+                ("{result=\"ok\"}", verify_ok),
+                ("{result=\"failed\"}", verify_failed),
+            ],
+        );
+        metric(
+            "tdx_verifier_collateral_requests_total",
+            "counter",
+            "Requests to the collateral endpoint.",
+            &[("", get(&self.collateral_requests))],
+        );
+        metric(
+            "tdx_verifier_collateral_cache_total",
+            "counter",
+            "Collateral cache lookups by result.",
+            &[
+                ("{result=\"hit\"}", get(&self.cache_hits)),
+                ("{result=\"miss\"}", get(&self.cache_misses)),
+            ],
+        );
+        metric(
+            "tdx_verifier_collateral_errors_total",
+            "counter",
+            "Failures to retrieve collaterals from the source.",
+            &[("", get(&self.collateral_errors))],
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP tdx_verifier_verification_duration_seconds Time spent verifying quotes."
+        );
+        let _ = writeln!(
+            out,
+            "# TYPE tdx_verifier_verification_duration_seconds summary"
+        );
+        let _ = writeln!(
+            out,
+            "tdx_verifier_verification_duration_seconds_sum {}",
+            get(&self.verify_micros) as f64 / 1e6
+        );
+        let _ = writeln!(
+            out,
+            "tdx_verifier_verification_duration_seconds_count {}",
+            verify_ok + verify_failed
+        );
+        out
+    }
+}
@@ -0,0 +1,444 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64_url::base64::{engine::general_purpose::STANDARD, Engine};
+use dcap_rs::types::quotes::body::QuoteBody;
+use serde::{Deserialize, Serialize};
+use tdx::collateral::Collaterals;
+use tdx::error::TdxError;
+use tdx::pccs::pcs::IPCSDao::CA;
+use tdx::report::VerificationSummary;
+use tdx::utils::{parse_quote_v4, try_get_pck_fmspc_and_issuer, var_data_matches_report_data};
+use tdx::Tdx;
+
+use crate::cache::{pck_type_name, CollateralCache};
+use crate::metrics::Metrics;
+
+const CBOR_CONTENT_TYPE: &str = "application/cbor";
+
+pub struct AppState {
+    pub cache: CollateralCache,
+    pub metrics: Arc<Metrics>,
+    /// Whether `POST /verify` accepts a verification time chosen by the client.
+    pub allow_client_time: bool,
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/verify", post(verify))
+        .route("/collateral/{fmspc}", get(collateral))
+        .route("/health", get(health))
+        .route("/metrics", get(metrics))
+        .with_state(state)
+}
+
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl ToString) -> Self {
+        ApiError {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+// Collaterals that are not available for the platform of a quote are a `404`, any other
+// failure to retrieve them a `502`.
+fn collateral_error(err: TdxError) -> ApiError {
+    match err {
+        TdxError::NotFound(_) => ApiError::new(StatusCode::NOT_FOUND, err),
+        _ => ApiError::new(StatusCode::BAD_GATEWAY, err),
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({ "error": self.message });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Encoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+impl Encoding {
+    fn decode(&self, s: &str) -> Result<Vec<u8>, ApiError> {
+        let s = s.trim();
+        match self {
+            Encoding::Hex => hex::decode(s.strip_prefix("0x").unwrap_or(s))
+                .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e)),
+            Encoding::Base64 => STANDARD
+                .decode(s)
+                .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e)),
+        }
+    }
+}
+
+/// JSON body of `POST /verify`. A raw quote can be posted instead, with any other
+/// content type.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VerifyRequest {
+    quote: String,
+    /// Encoding of `quote` and `var_data`.
+    #[serde(default)]
+    encoding: Encoding,
+    /// Var data returned by Azure Confidential VMs alongside the quote.
+    var_data: Option<String>,
+    /// Verification time in seconds since the Unix epoch, only accepted if the server allows
+    /// it (`--allow-client-time`). Defaults to now.
+    time: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VerifyResponse {
+    #[serde(flatten)]
+    summary: VerificationSummary,
+    verified_at: u64,
+    /// Whether the report data binds the var data, only set if var data was provided.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    var_data_bound: Option<bool>,
+}
+
+async fn verify(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<VerifyResponse>, ApiError> {
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let (raw_quote, var_data, time) = if is_json {
+        let request: VerifyRequest =
+            serde_json::from_slice(&body).map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))?;
+        if request.time.is_some() && !state.allow_client_time {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Client-chosen verification times are not allowed",
+            ));
+        }
+        let var_data = match &request.var_data {
+            Some(var_data) => Some(request.encoding.decode(var_data)?),
+            None => None,
+        };
+        (
+            request.encoding.decode(&request.quote)?,
+            var_data,
+            request.time,
+        )
+    } else {
+        (body.to_vec(), None, None)
+    };
+
+    let quote =
+        parse_quote_v4(&raw_quote).map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))?;
+    let (fmspc, pck_type) = try_get_pck_fmspc_and_issuer(&quote)
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))?;
+    let collaterals = state
+        .cache
+        .get(&fmspc, pck_type, quote.header.version as u32)
+        .await
+        .map_err(collateral_error)?;
+
+    let verified_at = time.unwrap_or_else(|| chrono::Utc::now().timestamp() as u64);
+    let started = Instant::now();
+    let result = tokio::task::spawn_blocking(move || {
+        Tdx::new().verify_attestation_report_with_collaterals(&quote, &collaterals, verified_at)
+    })
+    .await
+    .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    state
+        .metrics
+        .record_verification(result.is_ok(), started.elapsed());
+    let output = result.map_err(|e| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, e))?;
+
+    let var_data_bound = match (&var_data, &output.quote_body) {
+        (Some(var_data), QuoteBody::TD10QuoteBody(body)) => {
+            Some(var_data_matches_report_data(var_data, &body.report_data))
+        }
+        (Some(var_data), QuoteBody::SGXQuoteBody(report)) => {
+            Some(var_data_matches_report_data(var_data, &report.report_data))
+        }
+        (None, _) => None,
+    };
+
+    Ok(Json(VerifyResponse {
+        summary: VerificationSummary::from_verified_output(&output),
+        verified_at,
+        var_data_bound,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CollateralQuery {
+    /// CA that issued the PCK certificate, "platform" (default) or "processor".
+    pck_ca: Option<String>,
+    /// Quote version, defaults to 4.
+    version: Option<u32>,
+}
+
+/// Collaterals as JSON: the TCB Info and QE Identity as is, the DER certificates and CRLs
+/// in base64.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CollateralResponse {
+    tcb_info: serde_json::Value,
+    qe_identity: serde_json::Value,
+    root_ca: String,
+    root_ca_crl: String,
+    signing_ca: String,
+    pck_crl: String,
+    pck_type: &'static str,
+}
+
+impl CollateralResponse {
+    fn new(collaterals: &Collaterals) -> Result<Self, ApiError> {
+        let json = |bytes: &[u8]| {
+            serde_json::from_slice(bytes).map_err(|e| ApiError::new(StatusCode::BAD_GATEWAY, e))
+        };
+        Ok(CollateralResponse {
+            tcb_info: json(&collaterals.tcb_info)?,
+            qe_identity: json(&collaterals.qe_identity)?,
+            root_ca: STANDARD.encode(&collaterals.root_ca),
+            root_ca_crl: STANDARD.encode(&collaterals.root_ca_crl),
+            signing_ca: STANDARD.encode(&collaterals.signing_ca),
+            pck_crl: STANDARD.encode(&collaterals.pck_crl),
+            pck_type: pck_type_name(collaterals.pck_type),
+        })
+    }
+}
+
+/// Returns the collaterals of an FMSPC, as JSON or as CBOR (see `Collaterals::to_cbor`)
+/// if `application/cbor` is accepted.
+async fn collateral(
+    State(state): State<Arc<AppState>>,
+    Path(fmspc): Path<String>,
+    Query(query): Query<CollateralQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    state.metrics.record_collateral_request();
+
+    if fmspc.len() != 12 || hex::decode(&fmspc).is_err() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid FMSPC: {}", fmspc),
+        ));
+    }
+    let pck_type = match query.pck_ca.as_deref() {
+        None | Some("platform") => CA::PLATFORM,
+        Some("processor") => CA::PROCESSOR,
+        Some(other) => {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid PCK CA: {}", other),
+            ))
+        }
+    };
+
+    let collaterals = state
+        .cache
+        .get(&fmspc, pck_type, query.version.unwrap_or(4))
+        .await
+        .map_err(collateral_error)?;
+
+    let accepts_cbor = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains(CBOR_CONTENT_TYPE));
+    if accepts_cbor {
+        let cbor = collaterals
+            .to_cbor()
+            .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        return Ok(([(header::CONTENT_TYPE, CBOR_CONTENT_TYPE)], cbor).into_response());
+    }
+    Ok(Json(CollateralResponse::new(&collaterals)?).into_response())
+}
+
+async fn health() -> &'static str {
+    "ok"
+}
+
+async fn metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path as FsPath;
+    use std::time::Duration;
+    use tdx::collateral::CollateralSource;
+
+    const QUOTE: &[u8] = include_bytes!("../../tdx/examples/testdata/tdx_v4_quote_90c06f.bin");
+    /// Within the validity of the recorded collaterals.
+    const VERIFICATION_TIME: u64 = 1749600000;
+
+    fn state(allow_client_time: bool) -> Arc<AppState> {
+        let dir =
+            FsPath::new(env!("CARGO_MANIFEST_DIR")).join("../tdx/examples/testdata/collaterals");
+        let metrics = Arc::new(Metrics::default());
+        Arc::new(AppState {
+            cache: CollateralCache::new(
+                CollateralSource::Dir(dir),
+                Duration::from_secs(3600),
+                metrics.clone(),
+            ),
+            metrics,
+            allow_client_time,
+        })
+    }
+
+    fn json_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+        headers
+    }
+
+    async fn verify_json(
+        state: Arc<AppState>,
+        request: serde_json::Value,
+    ) -> Result<Json<VerifyResponse>, ApiError> {
+        let body = Bytes::from(serde_json::to_vec(&request).unwrap());
+        verify(State(state), json_headers(), body).await
+    }
+
+    #[tokio::test]
+    async fn verifies_quote_at_client_time() {
+        let state = state(true);
+        let request = serde_json::json!({
+            "quote": hex::encode(QUOTE),
+            "time": VERIFICATION_TIME,
+        });
+        let Json(response) = verify_json(state.clone(), request).await.unwrap();
+        assert_eq!(response.verified_at, VERIFICATION_TIME);
+        assert_eq!(response.summary.quote_version, 4);
+        assert_eq!(response.summary.platform, "TDX");
+        assert_eq!(response.summary.fmspc, "90c06f000000");
+        assert_eq!(response.var_data_bound, None);
+
+        let request = serde_json::json!({
+            "quote": STANDARD.encode(QUOTE),
+            "encoding": "base64",
+            "varData": STANDARD.encode(b"{}"),
+            "time": VERIFICATION_TIME,
+        });
+        let Json(response) = verify_json(state.clone(), request).await.unwrap();
+        assert_eq!(response.var_data_bound, Some(false));
+        assert!(state
+            .metrics
+            .render()
+            .contains("tdx_verifier_verifications_total{result=\"ok\"} 2"));
+    }
+
+    #[tokio::test]
+    async fn rejects_client_time_unless_allowed() {
+        let request = serde_json::json!({
+            "quote": hex::encode(QUOTE),
+            "time": VERIFICATION_TIME,
+        });
+        let err = verify_json(state(false), request).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        // Raw quotes are verified now, past the validity of the recorded collaterals.
+        let state = state(false);
+        let err = verify(
+            State(state.clone()),
+            HeaderMap::new(),
+            Bytes::from_static(QUOTE),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(state
+            .metrics
+            .render()
+            .contains("tdx_verifier_verifications_total{result=\"failed\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_quotes() {
+        let truncated = Bytes::copy_from_slice(&QUOTE[..100]);
+        let err = verify(State(state(true)), HeaderMap::new(), truncated)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        let request = serde_json::json!({ "quote": "not hex" });
+        let err = verify_json(state(true), request).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        let err = verify(State(state(true)), json_headers(), Bytes::from_static(b"{"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        // A quote whose body no longer matches its signature.
+        let mut forged = QUOTE.to_vec();
+        forged[48 + 520] ^= 1;
+        let request = serde_json::json!({
+            "quote": hex::encode(forged),
+            "time": VERIFICATION_TIME,
+        });
+        let err = verify_json(state(true), request).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn serves_collaterals_of_known_platforms() {
+        let query = || CollateralQuery {
+            pck_ca: None,
+            version: None,
+        };
+        let response = collateral(
+            State(state(false)),
+            Path("90c06f000000".to_string()),
+            Query(query()),
+            HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let err = collateral(
+            State(state(false)),
+            Path("00806f050000".to_string()),
+            Query(query()),
+            HeaderMap::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+
+        let err = collateral(
+            State(state(false)),
+            Path("90c06f".to_string()),
+            Query(query()),
+            HeaderMap::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+    }
+}
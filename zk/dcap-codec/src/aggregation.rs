@@ -0,0 +1,155 @@
+//! Input and journal of the aggregation programs, which verify already produced DCAP proofs
+//! and commit their journals in one proof.
+use alloc::format;
+use alloc::vec::Vec;
+
+use crate::error::{CodecError, Result};
+use crate::{length_prefix, Reader, HASH_LEN};
+
+/// Input and journal of the aggregation programs: the program whose proofs are aggregated and
+/// their journals, in the order of the proofs.
+///
+/// Layout, integers are big endian: `[program_id: 32 bytes][journal_count: u32]` followed by
+/// `[journal_len: u32][journal]` for each proof.
+///
+/// The aggregation programs verify each proof against `program_id` and commit their input
+/// unchanged, so the journals are only trusted together with the `program_id` they were
+/// proven for: the image ID of the Risc0 guest, or the verification key hash of the SP1
+/// program (`SP1VerifyingKey::hash_u32`, each word little endian). The journals are
+/// `DcapProgramJournal`s or `DcapBatchJournal`s and may hold failures.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DcapAggregation {
+    pub program_id: [u8; HASH_LEN],
+    pub journals: Vec<Vec<u8>>,
+}
+
+impl DcapAggregation {
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let journal_count = length_prefix(self.journals.len(), "journals")?;
+        let journals_len: usize = self.journals.iter().map(|journal| 4 + journal.len()).sum();
+
+        let mut output = Vec::with_capacity(HASH_LEN + 4 + journals_len);
+        output.extend_from_slice(&self.program_id);
+        output.extend_from_slice(&journal_count.to_be_bytes());
+        for journal in &self.journals {
+            output.extend_from_slice(&length_prefix(journal.len(), "journal")?.to_be_bytes());
+            output.extend_from_slice(journal);
+        }
+        Ok(output)
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(data);
+        let program_id = reader.array("program_id")?;
+        let journal_count = u32::from_be_bytes(reader.array("journal_count")?) as usize;
+        // Each journal takes at least its length prefix, do not trust the count for the capacity.
+        let mut journals = Vec::with_capacity(journal_count.min(reader.remaining() / 4));
+        for _ in 0..journal_count {
+            let journal_len = u32::from_be_bytes(reader.array("journal_len")?) as usize;
+            journals.push(reader.take(journal_len, "journal")?.to_vec());
+        }
+        reader.finish()?;
+
+        Ok(DcapAggregation {
+            program_id,
+            journals,
+        })
+    }
+
+    /// Checks that the journals were proven for one of `program_ids`, the expected image IDs
+    /// or verification key hashes of the DCAP programs. The aggregation programs commit any
+    /// `program_id`, so the journals of an aggregation must not be trusted without this check.
+    pub fn check_program_id(&self, program_ids: &[[u8; HASH_LEN]]) -> Result<()> {
+        if !program_ids.contains(&self.program_id) {
+            return Err(CodecError::Invalid(format!(
+                "program_id {:02x?} is not an expected DCAP program",
+                self.program_id
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Converts the words of a Risc0 image ID or a SP1 verification key hash to a `program_id`.
+pub fn program_id_from_words(words: [u32; 8]) -> [u8; HASH_LEN] {
+    let mut program_id = [0; HASH_LEN];
+    for (i, word) in words.iter().enumerate() {
+        program_id[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+    }
+    program_id
+}
+
+/// Converts a `program_id` back to the words expected by the zkVM verification functions.
+pub fn program_id_to_words(program_id: &[u8; HASH_LEN]) -> [u32; 8] {
+    let mut words = [0; 8];
+    for (i, word) in words.iter_mut().enumerate() {
+        *word = u32::from_le_bytes(program_id[i * 4..i * 4 + 4].try_into().unwrap());
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn aggregation() -> DcapAggregation {
+        DcapAggregation {
+            program_id: [9; HASH_LEN],
+            journals: vec![vec![1, 2, 3], vec![0, 0, 0, 2], vec![]],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let encoded = aggregation().encode().unwrap();
+        assert_eq!(encoded.len(), HASH_LEN + 4 + 3 * 4 + 7);
+        assert_eq!(&encoded[HASH_LEN..HASH_LEN + 4], &3u32.to_be_bytes());
+        assert_eq!(DcapAggregation::decode(&encoded).unwrap(), aggregation());
+    }
+
+    #[test]
+    fn rejects_invalid_aggregations() {
+        let encoded = aggregation().encode().unwrap();
+        for len in [0, HASH_LEN, HASH_LEN + 4, encoded.len() - 1] {
+            assert!(matches!(
+                DcapAggregation::decode(&encoded[..len]),
+                Err(CodecError::Truncated(_))
+            ));
+        }
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert!(matches!(
+            DcapAggregation::decode(&trailing),
+            Err(CodecError::TrailingBytes(_))
+        ));
+        let mut huge_count = encoded;
+        huge_count[HASH_LEN..HASH_LEN + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(
+            DcapAggregation::decode(&huge_count),
+            Err(CodecError::Truncated(_))
+        ));
+    }
+
+    #[test]
+    fn checks_program_id() {
+        let aggregation = aggregation();
+        aggregation
+            .check_program_id(&[[1; HASH_LEN], [9; HASH_LEN]])
+            .unwrap();
+        for program_ids in [&[][..], &[[1; HASH_LEN]]] {
+            assert!(matches!(
+                aggregation.check_program_id(program_ids),
+                Err(CodecError::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn program_id_words_round_trip() {
+        let words = [0x01020304, 0x05060708, 0, 1, u32::MAX, 42, 7, 0x80000000];
+        let program_id = program_id_from_words(words);
+        assert_eq!(&program_id[..4], &[4, 3, 2, 1]);
+        assert_eq!(program_id_to_words(&program_id), words);
+    }
+}
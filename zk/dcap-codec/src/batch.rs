@@ -0,0 +1,306 @@
+//! Input and journal of the batch programs, which verify several quotes sharing the same
+//! collaterals in one proof.
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec::Vec;
+
+use crate::error::{CodecError, Result};
+use crate::{
+    decode_failure, encode_failure, length_prefix, CollateralTrailer, ErrorCode, Reader,
+    TimeWindow, FLAG_BIND_TIME, HASH_LEN,
+};
+
+/// Input of the batch programs.
+///
+/// Layout, integers are little endian:
+/// `[current_time: u64][collaterals_len: u32][collaterals][quote_count: u32]`
+/// followed by `[quote_len: u32][quote]` for each quote, and the `[flags: u8]` of
+/// `DcapProgramInput`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DcapBatchInput {
+    /// Verification time in seconds since the Unix epoch.
+    pub current_time: u64,
+    /// The raw quotes, all of the same version and platform FMSPC.
+    pub quotes: Vec<Vec<u8>>,
+    /// The collaterals serialized with `IntelCollateral::to_bytes`.
+    pub collaterals: Vec<u8>,
+    /// See `DcapProgramInput::bind_time`.
+    pub bind_time: bool,
+}
+
+impl DcapBatchInput {
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let collaterals_len = length_prefix(self.collaterals.len(), "collaterals")?;
+        let quote_count = length_prefix(self.quotes.len(), "quotes")?;
+        let quotes_len: usize = self.quotes.iter().map(|quote| 4 + quote.len()).sum();
+
+        let mut input = Vec::with_capacity(17 + self.collaterals.len() + quotes_len);
+        input.extend_from_slice(&self.current_time.to_le_bytes());
+        input.extend_from_slice(&collaterals_len.to_le_bytes());
+        input.extend_from_slice(&self.collaterals);
+        input.extend_from_slice(&quote_count.to_le_bytes());
+        for quote in &self.quotes {
+            input.extend_from_slice(&length_prefix(quote.len(), "quote")?.to_le_bytes());
+            input.extend_from_slice(quote);
+        }
+        if self.bind_time {
+            input.push(FLAG_BIND_TIME);
+        }
+        Ok(input)
+    }
+
+    pub fn decode(input: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(input);
+        let current_time = u64::from_le_bytes(reader.array("current_time")?);
+        let collaterals_len = u32::from_le_bytes(reader.array("collaterals_len")?) as usize;
+        let collaterals = reader.take(collaterals_len, "collaterals")?.to_vec();
+        let quote_count = u32::from_le_bytes(reader.array("quote_count")?) as usize;
+        // Each quote takes at least its length prefix, do not trust the count for the capacity.
+        let mut quotes = Vec::with_capacity(quote_count.min(reader.remaining() / 4));
+        for _ in 0..quote_count {
+            let quote_len = u32::from_le_bytes(reader.array("quote_len")?) as usize;
+            quotes.push(reader.take(quote_len, "quote")?.to_vec());
+        }
+        let flags = match reader.remaining() {
+            0 => 0,
+            _ => reader.array::<1>("flags")?[0],
+        };
+        reader.finish()?;
+        if flags & !FLAG_BIND_TIME != 0 {
+            return Err(CodecError::Invalid(format!("unknown flags {:#04x}", flags)));
+        }
+
+        Ok(DcapBatchInput {
+            current_time,
+            quotes,
+            collaterals,
+            bind_time: flags & FLAG_BIND_TIME != 0,
+        })
+    }
+}
+
+/// Journal committed by the batch programs.
+///
+/// Layout, integers are big endian: `[quote_count: u16]`, then
+/// `[verified_output_len: u16][verified_output]` for each quote in the input order, then the
+/// verification time, the collateral hashes and the optional time window, as in
+/// `DcapProgramOutput`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DcapBatchOutput {
+    /// The `VerifiedOutput` of each quote, serialized with `VerifiedOutput::to_bytes`.
+    pub verified_outputs: Vec<Vec<u8>>,
+    /// Verification time in seconds since the Unix epoch.
+    pub current_time: u64,
+    pub tcbinfo_content_hash: [u8; HASH_LEN],
+    pub qeidentity_content_hash: [u8; HASH_LEN],
+    pub sgx_intel_root_ca_cert_hash: [u8; HASH_LEN],
+    pub sgx_tcb_signing_cert_hash: [u8; HASH_LEN],
+    pub sgx_intel_root_ca_crl_hash: [u8; HASH_LEN],
+    /// Hash of the PCK Platform CRL or the PCK Processor CRL.
+    pub sgx_pck_crl_hash: [u8; HASH_LEN],
+    pub time_window: Option<TimeWindow>,
+}
+
+impl DcapBatchOutput {
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        if self.verified_outputs.is_empty() {
+            return Err(CodecError::Invalid("empty batch".into()));
+        }
+        let quote_count: u16 =
+            self.verified_outputs.len().try_into().map_err(|_| {
+                CodecError::TooLong(format!("{} quotes", self.verified_outputs.len()))
+            })?;
+
+        let mut output = Vec::new();
+        output.extend_from_slice(&quote_count.to_be_bytes());
+        for verified_output in &self.verified_outputs {
+            let len: u16 = verified_output.len().try_into().map_err(|_| {
+                CodecError::TooLong(format!(
+                    "verified_output is {} bytes",
+                    verified_output.len()
+                ))
+            })?;
+            output.extend_from_slice(&len.to_be_bytes());
+            output.extend_from_slice(verified_output);
+        }
+        CollateralTrailer {
+            current_time: self.current_time,
+            hashes: [
+                self.tcbinfo_content_hash,
+                self.qeidentity_content_hash,
+                self.sgx_intel_root_ca_cert_hash,
+                self.sgx_tcb_signing_cert_hash,
+                self.sgx_intel_root_ca_crl_hash,
+                self.sgx_pck_crl_hash,
+            ],
+            time_window: self.time_window,
+        }
+        .write(&mut output);
+        Ok(output)
+    }
+
+    pub fn decode(output: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(output);
+        let quote_count = u16::from_be_bytes(reader.array("quote_count")?) as usize;
+        let mut verified_outputs = Vec::with_capacity(quote_count);
+        for _ in 0..quote_count {
+            let len = u16::from_be_bytes(reader.array("verified_output_len")?) as usize;
+            verified_outputs.push(reader.take(len, "verified_output")?.to_vec());
+        }
+        let trailer = CollateralTrailer::read(&mut reader)?;
+        reader.finish()?;
+
+        let [tcbinfo_content_hash, qeidentity_content_hash, sgx_intel_root_ca_cert_hash, sgx_tcb_signing_cert_hash, sgx_intel_root_ca_crl_hash, sgx_pck_crl_hash] =
+            trailer.hashes;
+        Ok(DcapBatchOutput {
+            verified_outputs,
+            current_time: trailer.current_time,
+            tcbinfo_content_hash,
+            qeidentity_content_hash,
+            sgx_intel_root_ca_cert_hash,
+            sgx_tcb_signing_cert_hash,
+            sgx_intel_root_ca_crl_hash,
+            sgx_pck_crl_hash,
+            time_window: trailer.time_window,
+        })
+    }
+}
+
+/// Journal committed by the batch programs, whether the verification succeeded or not.
+///
+/// A failure is committed as `[0u16][error_code: u16]`, as in `DcapProgramJournal`. Batches
+/// hold at least one quote, so the two cannot be confused.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DcapBatchJournal {
+    Success(Box<DcapBatchOutput>),
+    Failure(ErrorCode),
+}
+
+impl DcapBatchJournal {
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        match self {
+            DcapBatchJournal::Success(output) => output.encode(),
+            DcapBatchJournal::Failure(code) => Ok(encode_failure(*code)),
+        }
+    }
+
+    pub fn decode(journal: &[u8]) -> Result<Self> {
+        if let Some(code) = decode_failure(journal)? {
+            return Ok(DcapBatchJournal::Failure(code));
+        }
+        DcapBatchOutput::decode(journal).map(|output| DcapBatchJournal::Success(Box::new(output)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CodecError;
+    use alloc::vec;
+
+    fn input(bind_time: bool) -> DcapBatchInput {
+        DcapBatchInput {
+            current_time: 1749600000,
+            quotes: vec![vec![4; 10], vec![], vec![5; 3]],
+            collaterals: vec![7; 5],
+            bind_time,
+        }
+    }
+
+    fn output(time_window: Option<TimeWindow>) -> DcapBatchOutput {
+        DcapBatchOutput {
+            verified_outputs: vec![vec![1, 2, 3], vec![4]],
+            current_time: 1749600000,
+            tcbinfo_content_hash: [1; HASH_LEN],
+            qeidentity_content_hash: [2; HASH_LEN],
+            sgx_intel_root_ca_cert_hash: [3; HASH_LEN],
+            sgx_tcb_signing_cert_hash: [4; HASH_LEN],
+            sgx_intel_root_ca_crl_hash: [5; HASH_LEN],
+            sgx_pck_crl_hash: [6; HASH_LEN],
+            time_window,
+        }
+    }
+
+    #[test]
+    fn input_round_trip() {
+        for bind_time in [false, true] {
+            let encoded = input(bind_time).encode().unwrap();
+            assert_eq!(encoded.len(), 16 + 5 + 3 * 4 + 13 + bind_time as usize);
+            assert_eq!(DcapBatchInput::decode(&encoded).unwrap(), input(bind_time));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_inputs() {
+        let encoded = input(false).encode().unwrap();
+        assert!(matches!(
+            DcapBatchInput::decode(&encoded[..encoded.len() - 1]),
+            Err(CodecError::Truncated(_))
+        ));
+        let mut trailing = input(true).encode().unwrap();
+        trailing.push(FLAG_BIND_TIME);
+        assert!(matches!(
+            DcapBatchInput::decode(&trailing),
+            Err(CodecError::TrailingBytes(_))
+        ));
+        let mut unknown_flags = encoded.clone();
+        unknown_flags.push(0x80);
+        assert!(matches!(
+            DcapBatchInput::decode(&unknown_flags),
+            Err(CodecError::Invalid(_))
+        ));
+
+        // A quote count larger than the input must not be trusted.
+        let mut huge_count = encoded;
+        huge_count[17..21].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            DcapBatchInput::decode(&huge_count),
+            Err(CodecError::Truncated(_))
+        ));
+    }
+
+    #[test]
+    fn journal_round_trip() {
+        for time_window in [
+            None,
+            Some(TimeWindow {
+                not_before: 1749081600,
+                not_after: 1751673600,
+            }),
+        ] {
+            let journal = DcapBatchJournal::Success(Box::new(output(time_window)));
+            let encoded = journal.encode().unwrap();
+            assert_eq!(&encoded[..2], &2u16.to_be_bytes());
+            assert_eq!(DcapBatchJournal::decode(&encoded).unwrap(), journal);
+        }
+
+        let failure = DcapBatchJournal::Failure(ErrorCode::BatchVersionMismatch);
+        let encoded = failure.encode().unwrap();
+        assert_eq!(encoded, [0, 0, 0, 8]);
+        assert_eq!(DcapBatchJournal::decode(&encoded).unwrap(), failure);
+    }
+
+    #[test]
+    fn rejects_invalid_journals() {
+        let mut empty = output(None);
+        empty.verified_outputs.clear();
+        assert!(matches!(empty.encode(), Err(CodecError::Invalid(_))));
+
+        let encoded = output(None).encode().unwrap();
+        assert!(matches!(
+            DcapBatchJournal::decode(&encoded[..encoded.len() - 1]),
+            Err(CodecError::Truncated(_))
+        ));
+        let mut trailing = encoded;
+        trailing.push(0);
+        assert!(matches!(
+            DcapBatchJournal::decode(&trailing),
+            Err(CodecError::Truncated(_))
+        ));
+        trailing.extend_from_slice(&[0; 16]);
+        assert!(matches!(
+            DcapBatchJournal::decode(&trailing),
+            Err(CodecError::TrailingBytes(_))
+        ));
+    }
+}
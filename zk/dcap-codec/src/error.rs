@@ -0,0 +1,29 @@
+use alloc::string::String;
+use core::fmt::Display;
+
+pub type Result<T> = core::result::Result<T, CodecError>;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CodecError {
+    /// The data ends before the field.
+    Truncated(String),
+    /// The data continues after the last field.
+    TrailingBytes(String),
+    /// A field is too long for its length prefix.
+    TooLong(String),
+    /// A field has an unknown value.
+    Invalid(String),
+}
+
+impl Display for CodecError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CodecError::Truncated(err) => write!(f, "Truncated: {}", err),
+            CodecError::TrailingBytes(err) => write!(f, "TrailingBytes: {}", err),
+            CodecError::TooLong(err) => write!(f, "TooLong: {}", err),
+            CodecError::Invalid(err) => write!(f, "Invalid: {}", err),
+        }
+    }
+}
+
+impl core::error::Error for CodecError {}
@@ -0,0 +1,546 @@
+//! Encoding of the input and the journal of the DCAP zkVM programs.
+//!
+//! The guests (Risc0 and SP1) and their hosts share these types, so that the layout is
+//! defined in a single place.
+#![no_std]
+
+extern crate alloc;
+
+pub mod aggregation;
+pub mod batch;
+pub mod error;
+pub mod profile;
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec::Vec;
+
+use error::{CodecError, Result};
+
+/// Length of a collateral hash in the journal.
+pub const HASH_LEN: usize = 32;
+
+/// Length of the journal after the serialized `VerifiedOutput`: the verification time and
+/// the six collateral hashes.
+pub const OUTPUT_TRAILER_LEN: usize = 8 + 6 * HASH_LEN;
+
+/// Input flag requiring the verification time to be within the validity of the collaterals.
+pub const FLAG_BIND_TIME: u8 = 1;
+
+/// Input of the DCAP programs.
+///
+/// Layout, integers are little endian:
+/// `[current_time: u64][quote_len: u32][collaterals_len: u32][quote][collaterals][flags: u8]`
+///
+/// The flags byte is only present when a flag is set, so inputs without flags keep the
+/// original layout.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DcapProgramInput {
+    /// Verification time in seconds since the Unix epoch.
+    pub current_time: u64,
+    /// The raw quote.
+    pub quote: Vec<u8>,
+    /// The collaterals serialized with `IntelCollateral::to_bytes`.
+    pub collaterals: Vec<u8>,
+    /// Requires `current_time` to be within the validity of the TCB Info, the QE Identity and
+    /// the CRLs, and commits that window to the journal.
+    pub bind_time: bool,
+}
+
+impl DcapProgramInput {
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let quote_len = length_prefix(self.quote.len(), "quote")?;
+        let collaterals_len = length_prefix(self.collaterals.len(), "collaterals")?;
+
+        let mut input = Vec::with_capacity(16 + self.quote.len() + self.collaterals.len());
+        input.extend_from_slice(&self.current_time.to_le_bytes());
+        input.extend_from_slice(&quote_len.to_le_bytes());
+        input.extend_from_slice(&collaterals_len.to_le_bytes());
+        input.extend_from_slice(&self.quote);
+        input.extend_from_slice(&self.collaterals);
+        if self.bind_time {
+            input.push(FLAG_BIND_TIME);
+        }
+        Ok(input)
+    }
+
+    pub fn decode(input: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(input);
+        let current_time = u64::from_le_bytes(reader.array("current_time")?);
+        let quote_len = u32::from_le_bytes(reader.array("quote_len")?) as usize;
+        let collaterals_len = u32::from_le_bytes(reader.array("collaterals_len")?) as usize;
+        let quote = reader.take(quote_len, "quote")?.to_vec();
+        let collaterals = reader.take(collaterals_len, "collaterals")?.to_vec();
+        let flags = match reader.remaining() {
+            0 => 0,
+            _ => reader.array::<1>("flags")?[0],
+        };
+        reader.finish()?;
+        if flags & !FLAG_BIND_TIME != 0 {
+            return Err(CodecError::Invalid(format!("unknown flags {:#04x}", flags)));
+        }
+
+        Ok(DcapProgramInput {
+            current_time,
+            quote,
+            collaterals,
+            bind_time: flags & FLAG_BIND_TIME != 0,
+        })
+    }
+}
+
+/// Journal committed by the DCAP programs, the `output` of `verifyAndAttestWithZKProof`.
+///
+/// Layout, integers are big endian:
+/// `[verified_output_len: u16][verified_output][current_time: u64][tcbinfo_content_hash]`
+/// `[qeidentity_content_hash][sgx_intel_root_ca_cert_hash][sgx_tcb_signing_cert_hash]`
+/// `[sgx_intel_root_ca_crl_hash][sgx_pck_crl_hash]`, followed by the time window
+/// `[not_before: u64][not_after: u64]` when the input has `bind_time` set.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DcapProgramOutput {
+    /// The `VerifiedOutput` serialized with `VerifiedOutput::to_bytes`.
+    pub verified_output: Vec<u8>,
+    /// Verification time in seconds since the Unix epoch.
+    pub current_time: u64,
+    pub tcbinfo_content_hash: [u8; HASH_LEN],
+    pub qeidentity_content_hash: [u8; HASH_LEN],
+    pub sgx_intel_root_ca_cert_hash: [u8; HASH_LEN],
+    pub sgx_tcb_signing_cert_hash: [u8; HASH_LEN],
+    pub sgx_intel_root_ca_crl_hash: [u8; HASH_LEN],
+    /// Hash of the PCK Platform CRL or the PCK Processor CRL.
+    pub sgx_pck_crl_hash: [u8; HASH_LEN],
+    /// Window in which all the collaterals are valid, only committed when the input has
+    /// `bind_time` set.
+    pub time_window: Option<TimeWindow>,
+}
+
+/// Intersection of the validity periods of the collaterals, in seconds since the Unix epoch:
+/// from the latest `issueDate` / `thisUpdate` to the earliest `nextUpdate`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TimeWindow {
+    pub not_before: u64,
+    pub not_after: u64,
+}
+
+impl TimeWindow {
+    pub fn contains(&self, time: u64) -> bool {
+        self.not_before <= time && time <= self.not_after
+    }
+}
+
+impl DcapProgramOutput {
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let verified_output_len: u16 = self.verified_output.len().try_into().map_err(|_| {
+            CodecError::TooLong(format!(
+                "verified_output is {} bytes",
+                self.verified_output.len()
+            ))
+        })?;
+
+        let mut output = Vec::with_capacity(2 + self.verified_output.len() + OUTPUT_TRAILER_LEN);
+        output.extend_from_slice(&verified_output_len.to_be_bytes());
+        output.extend_from_slice(&self.verified_output);
+        self.collaterals().write(&mut output);
+        Ok(output)
+    }
+
+    pub fn decode(output: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(output);
+        let verified_output_len = u16::from_be_bytes(reader.array("verified_output_len")?);
+        let verified_output = reader
+            .take(verified_output_len as usize, "verified_output")?
+            .to_vec();
+        let collaterals = CollateralTrailer::read(&mut reader)?;
+        reader.finish()?;
+        Ok(collaterals.into_output(verified_output))
+    }
+
+    fn collaterals(&self) -> CollateralTrailer {
+        CollateralTrailer {
+            current_time: self.current_time,
+            hashes: [
+                self.tcbinfo_content_hash,
+                self.qeidentity_content_hash,
+                self.sgx_intel_root_ca_cert_hash,
+                self.sgx_tcb_signing_cert_hash,
+                self.sgx_intel_root_ca_crl_hash,
+                self.sgx_pck_crl_hash,
+            ],
+            time_window: self.time_window,
+        }
+    }
+}
+
+/// The part of the journal after the verified outputs, shared by the single and batch
+/// programs: the verification time, the collateral hashes and the optional time window.
+pub(crate) struct CollateralTrailer {
+    pub(crate) current_time: u64,
+    pub(crate) hashes: [[u8; HASH_LEN]; 6],
+    pub(crate) time_window: Option<TimeWindow>,
+}
+
+impl CollateralTrailer {
+    pub(crate) fn write(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&self.current_time.to_be_bytes());
+        for hash in &self.hashes {
+            output.extend_from_slice(hash);
+        }
+        if let Some(window) = &self.time_window {
+            output.extend_from_slice(&window.not_before.to_be_bytes());
+            output.extend_from_slice(&window.not_after.to_be_bytes());
+        }
+    }
+
+    pub(crate) fn read(reader: &mut Reader) -> Result<Self> {
+        Ok(CollateralTrailer {
+            current_time: u64::from_be_bytes(reader.array("current_time")?),
+            hashes: [
+                reader.array("tcbinfo_content_hash")?,
+                reader.array("qeidentity_content_hash")?,
+                reader.array("sgx_intel_root_ca_cert_hash")?,
+                reader.array("sgx_tcb_signing_cert_hash")?,
+                reader.array("sgx_intel_root_ca_crl_hash")?,
+                reader.array("sgx_pck_crl_hash")?,
+            ],
+            time_window: match reader.remaining() {
+                0 => None,
+                _ => Some(TimeWindow {
+                    not_before: u64::from_be_bytes(reader.array("not_before")?),
+                    not_after: u64::from_be_bytes(reader.array("not_after")?),
+                }),
+            },
+        })
+    }
+
+    /// Builds the journal of a single quote verification.
+    pub(crate) fn into_output(self, verified_output: Vec<u8>) -> DcapProgramOutput {
+        let [tcbinfo_content_hash, qeidentity_content_hash, sgx_intel_root_ca_cert_hash, sgx_tcb_signing_cert_hash, sgx_intel_root_ca_crl_hash, sgx_pck_crl_hash] =
+            self.hashes;
+        DcapProgramOutput {
+            verified_output,
+            current_time: self.current_time,
+            tcbinfo_content_hash,
+            qeidentity_content_hash,
+            sgx_intel_root_ca_cert_hash,
+            sgx_tcb_signing_cert_hash,
+            sgx_intel_root_ca_crl_hash,
+            sgx_pck_crl_hash,
+            time_window: self.time_window,
+        }
+    }
+}
+
+/// Why a DCAP program rejected its input.
+///
+/// Failures detected by the programs are committed to the journal with their code, failures
+/// inside the dcap-rs verification still abort the program.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u16)]
+pub enum ErrorCode {
+    /// The input does not follow the `DcapProgramInput` layout.
+    InvalidInput = 1,
+    /// The quote is shorter than its header, body or signature data.
+    InvalidQuote = 2,
+    UnsupportedQuoteVersion = 3,
+    /// The TEE type is not supported by the quote version.
+    UnsupportedTeeType = 4,
+    /// The body type or size of a V5 quote is not supported. Reserved, the programs reject
+    /// V5 quotes with `UnsupportedQuoteVersion` as they do not verify them yet.
+    UnsupportedQuoteBody = 5,
+    /// The collaterals do not hold exactly one of the PCK Platform and Processor CRLs, or
+    /// their validity cannot be read.
+    InvalidCollaterals = 6,
+    /// The verification time is outside the validity of the collaterals, with `bind_time`.
+    TimeOutsideCollateralValidity = 7,
+    /// The quotes of a batch do not have the same version, so they cannot share the TCB Info.
+    BatchVersionMismatch = 8,
+}
+
+impl ErrorCode {
+    pub fn from_u16(code: u16) -> Option<Self> {
+        match code {
+            1 => Some(ErrorCode::InvalidInput),
+            2 => Some(ErrorCode::InvalidQuote),
+            3 => Some(ErrorCode::UnsupportedQuoteVersion),
+            4 => Some(ErrorCode::UnsupportedTeeType),
+            5 => Some(ErrorCode::UnsupportedQuoteBody),
+            6 => Some(ErrorCode::InvalidCollaterals),
+            7 => Some(ErrorCode::TimeOutsideCollateralValidity),
+            8 => Some(ErrorCode::BatchVersionMismatch),
+            _ => None,
+        }
+    }
+}
+
+impl core::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?} ({})", self, *self as u16)
+    }
+}
+
+/// Journal committed by the DCAP programs, whether the verification succeeded or not.
+///
+/// A failure is committed as an empty `verified_output` followed by the error code:
+/// `[0u16][error_code: u16]`, big endian. A serialized `VerifiedOutput` is never empty, so
+/// the on-chain verifier rejects these journals.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DcapProgramJournal {
+    Success(Box<DcapProgramOutput>),
+    Failure(ErrorCode),
+}
+
+impl DcapProgramJournal {
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        match self {
+            DcapProgramJournal::Success(output) => output.encode(),
+            DcapProgramJournal::Failure(code) => Ok(encode_failure(*code)),
+        }
+    }
+
+    pub fn decode(journal: &[u8]) -> Result<Self> {
+        if let Some(code) = decode_failure(journal)? {
+            return Ok(DcapProgramJournal::Failure(code));
+        }
+        DcapProgramOutput::decode(journal)
+            .map(|output| DcapProgramJournal::Success(Box::new(output)))
+    }
+}
+
+/// Encodes a failure journal: `[0u16][error_code: u16]`.
+pub(crate) fn encode_failure(code: ErrorCode) -> Vec<u8> {
+    let mut journal = Vec::with_capacity(4);
+    journal.extend_from_slice(&0u16.to_be_bytes());
+    journal.extend_from_slice(&(code as u16).to_be_bytes());
+    journal
+}
+
+/// Returns the error code of a failure journal, `None` for the journal of a success.
+pub(crate) fn decode_failure(journal: &[u8]) -> Result<Option<ErrorCode>> {
+    if journal.len() < 2 || journal[..2] != [0, 0] {
+        return Ok(None);
+    }
+    let mut reader = Reader::new(&journal[2..]);
+    let code = u16::from_be_bytes(reader.array("error_code")?);
+    reader.finish()?;
+    ErrorCode::from_u16(code)
+        .map(Some)
+        .ok_or_else(|| CodecError::Invalid(format!("unknown error code {}", code)))
+}
+
+pub(crate) fn length_prefix(len: usize, field: &str) -> Result<u32> {
+    len.try_into()
+        .map_err(|_| CodecError::TooLong(format!("{} is {} bytes", field, len)))
+}
+
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Reader { data, offset: 0 }
+    }
+
+    pub(crate) fn take(&mut self, len: usize, field: &str) -> Result<&'a [u8]> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| {
+                CodecError::Truncated(format!(
+                    "{} needs {} bytes at offset {}, {} left",
+                    field,
+                    len,
+                    self.offset,
+                    self.data.len() - self.offset
+                ))
+            })?;
+        let slice = &self.data[self.offset..end];
+        self.offset = end;
+        Ok(slice)
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self, field: &str) -> Result<[u8; N]> {
+        Ok(self.take(N, field)?.try_into().unwrap())
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len() - self.offset
+    }
+
+    pub(crate) fn finish(self) -> Result<()> {
+        if self.offset != self.data.len() {
+            return Err(CodecError::TrailingBytes(format!(
+                "{} bytes after the last field",
+                self.data.len() - self.offset
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn input(bind_time: bool) -> DcapProgramInput {
+        DcapProgramInput {
+            current_time: 1749600000,
+            quote: vec![4; 10],
+            collaterals: vec![7; 5],
+            bind_time,
+        }
+    }
+
+    fn output(time_window: Option<TimeWindow>) -> DcapProgramOutput {
+        DcapProgramOutput {
+            verified_output: vec![1, 2, 3],
+            current_time: 1749600000,
+            tcbinfo_content_hash: [1; HASH_LEN],
+            qeidentity_content_hash: [2; HASH_LEN],
+            sgx_intel_root_ca_cert_hash: [3; HASH_LEN],
+            sgx_tcb_signing_cert_hash: [4; HASH_LEN],
+            sgx_intel_root_ca_crl_hash: [5; HASH_LEN],
+            sgx_pck_crl_hash: [6; HASH_LEN],
+            time_window,
+        }
+    }
+
+    #[test]
+    fn input_round_trip() {
+        let encoded = input(false).encode().unwrap();
+        assert_eq!(encoded.len(), 16 + 10 + 5);
+        assert_eq!(&encoded[..8], &1749600000u64.to_le_bytes());
+        assert_eq!(&encoded[8..12], &10u32.to_le_bytes());
+        assert_eq!(DcapProgramInput::decode(&encoded).unwrap(), input(false));
+
+        let encoded = input(true).encode().unwrap();
+        assert_eq!(encoded.last(), Some(&FLAG_BIND_TIME));
+        assert_eq!(DcapProgramInput::decode(&encoded).unwrap(), input(true));
+    }
+
+    #[test]
+    fn rejects_invalid_inputs() {
+        let encoded = input(true).encode().unwrap();
+        for len in [0, 7, 15, 20, 30] {
+            assert!(matches!(
+                DcapProgramInput::decode(&encoded[..len]),
+                Err(CodecError::Truncated(_))
+            ));
+        }
+
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert!(matches!(
+            DcapProgramInput::decode(&trailing),
+            Err(CodecError::TrailingBytes(_))
+        ));
+
+        let mut unknown_flags = encoded;
+        *unknown_flags.last_mut().unwrap() = 0x02;
+        assert!(matches!(
+            DcapProgramInput::decode(&unknown_flags),
+            Err(CodecError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn output_round_trip() {
+        let encoded = output(None).encode().unwrap();
+        assert_eq!(encoded.len(), 2 + 3 + OUTPUT_TRAILER_LEN);
+        assert_eq!(&encoded[..2], &3u16.to_be_bytes());
+        assert_eq!(&encoded[5..13], &1749600000u64.to_be_bytes());
+        assert_eq!(DcapProgramOutput::decode(&encoded).unwrap(), output(None));
+
+        let window = TimeWindow {
+            not_before: 1749081600,
+            not_after: 1751673600,
+        };
+        let encoded = output(Some(window)).encode().unwrap();
+        assert_eq!(encoded.len(), 2 + 3 + OUTPUT_TRAILER_LEN + 16);
+        assert_eq!(&encoded[encoded.len() - 8..], &1751673600u64.to_be_bytes());
+        assert_eq!(
+            DcapProgramOutput::decode(&encoded).unwrap(),
+            output(Some(window))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_outputs() {
+        let encoded = output(None).encode().unwrap();
+        assert!(matches!(
+            DcapProgramOutput::decode(&encoded[..encoded.len() - 1]),
+            Err(CodecError::Truncated(_))
+        ));
+        // A partial time window.
+        let mut partial_window = encoded.clone();
+        partial_window.extend_from_slice(&[0; 8]);
+        assert!(matches!(
+            DcapProgramOutput::decode(&partial_window),
+            Err(CodecError::Truncated(_))
+        ));
+        let mut trailing = output(Some(TimeWindow {
+            not_before: 0,
+            not_after: 1,
+        }))
+        .encode()
+        .unwrap();
+        trailing.push(0);
+        assert!(matches!(
+            DcapProgramOutput::decode(&trailing),
+            Err(CodecError::TrailingBytes(_))
+        ));
+
+        let mut too_long = output(None);
+        too_long.verified_output = vec![0; u16::MAX as usize + 1];
+        assert!(matches!(too_long.encode(), Err(CodecError::TooLong(_))));
+    }
+
+    #[test]
+    fn journal_round_trip() {
+        let success = DcapProgramJournal::Success(Box::new(output(Some(TimeWindow {
+            not_before: 1749081600,
+            not_after: 1751673600,
+        }))));
+        let encoded = success.encode().unwrap();
+        assert_eq!(DcapProgramJournal::decode(&encoded).unwrap(), success);
+
+        let failure = DcapProgramJournal::Failure(ErrorCode::TimeOutsideCollateralValidity);
+        let encoded = failure.encode().unwrap();
+        assert_eq!(encoded, [0, 0, 0, 7]);
+        assert_eq!(DcapProgramJournal::decode(&encoded).unwrap(), failure);
+    }
+
+    #[test]
+    fn rejects_invalid_failures() {
+        assert!(matches!(
+            DcapProgramJournal::decode(&[0, 0, 0]),
+            Err(CodecError::Truncated(_))
+        ));
+        assert!(matches!(
+            DcapProgramJournal::decode(&[0, 0, 0, 1, 0]),
+            Err(CodecError::TrailingBytes(_))
+        ));
+        assert!(matches!(
+            DcapProgramJournal::decode(&[0, 0, 0, 99]),
+            Err(CodecError::Invalid(_))
+        ));
+        for code in 1..=8 {
+            assert_eq!(ErrorCode::from_u16(code).unwrap() as u16, code);
+        }
+    }
+
+    #[test]
+    fn time_window_is_inclusive() {
+        let window = TimeWindow {
+            not_before: 10,
+            not_after: 20,
+        };
+        assert!(!window.contains(9));
+        assert!(window.contains(10));
+        assert!(window.contains(20));
+        assert!(!window.contains(21));
+    }
+}
@@ -0,0 +1,63 @@
+//! Phases of the verification reported by the guests for cycle profiling.
+use alloc::format;
+use alloc::string::String;
+
+/// Prefix of the lines written by the Risc0 guests to their stdout, one per phase:
+/// `dcap-cycles: <phase> <cycles>`. The SP1 programs use the cycle tracker of the SP1
+/// executor instead.
+pub const CYCLE_REPORT_PREFIX: &str = "dcap-cycles: ";
+
+/// A phase of the verification performed by the guests. A batch runs `VerifyQuote` once per
+/// quote, the cycles of each run are added up.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Phase {
+    /// Decoding the `DcapProgramInput` or `DcapBatchInput`, and reading the quote headers.
+    DecodeInput,
+    /// Deserializing the `IntelCollateral`.
+    ParseCollaterals,
+    /// Parsing the TCB Info, the QE Identity, the certificates and the CRLs, and hashing them
+    /// for the journal.
+    HashCollaterals,
+    /// The dcap-rs verification of a quote: parsing its PCK certificate chain, checking the
+    /// certificate, CRL, QE report and quote signatures, and matching the TCB levels. These
+    /// steps are private to dcap-rs, behind `verify_quote_dcapv3` and `verify_quote_dcapv4`,
+    /// so they cannot be split into phases of their own.
+    VerifyQuote,
+    /// Encoding the journal.
+    EncodeJournal,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 5] = [
+        Phase::DecodeInput,
+        Phase::ParseCollaterals,
+        Phase::HashCollaterals,
+        Phase::VerifyQuote,
+        Phase::EncodeJournal,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Phase::DecodeInput => "decode_input",
+            Phase::ParseCollaterals => "parse_collaterals",
+            Phase::HashCollaterals => "hash_collaterals",
+            Phase::VerifyQuote => "verify_quote",
+            Phase::EncodeJournal => "encode_journal",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Phase> {
+        Phase::ALL.into_iter().find(|phase| phase.as_str() == name)
+    }
+}
+
+/// Formats the report line of a phase, see `CYCLE_REPORT_PREFIX`.
+pub fn format_cycle_report(phase: Phase, cycles: u64) -> String {
+    format!("{}{} {}", CYCLE_REPORT_PREFIX, phase.as_str(), cycles)
+}
+
+/// Parses a line formatted by `format_cycle_report`.
+pub fn parse_cycle_report(line: &str) -> Option<(Phase, u64)> {
+    let (name, cycles) = line.strip_prefix(CYCLE_REPORT_PREFIX)?.split_once(' ')?;
+    Some((Phase::from_name(name)?, cycles.trim().parse().ok()?))
+}
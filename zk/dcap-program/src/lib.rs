@@ -0,0 +1,569 @@
+//! Quote verification of the DCAP zkVM programs, shared by the Risc0 and SP1 guests.
+//!
+//! The guests only read their input, call `run` or `run_batch` and commit the returned
+//! journal. Failures detected here are committed with their `ErrorCode`, failures inside the
+//! dcap-rs verification abort the program.
+//!
+//! The `_tracked` variants report the boundaries of each `Phase` to a `PhaseTracker`, that the
+//! guests implement with the cycle counter of their zkVM.
+use chrono::DateTime;
+use dcap_codec::batch::{DcapBatchInput, DcapBatchJournal, DcapBatchOutput};
+use dcap_codec::profile::format_cycle_report;
+pub use dcap_codec::profile::Phase;
+use dcap_codec::{DcapProgramInput, DcapProgramJournal, DcapProgramOutput, ErrorCode, TimeWindow};
+use dcap_rs::types::{
+    collaterals::IntelCollateral,
+    quotes::{version_3::QuoteV3, version_4::QuoteV4, QuoteHeader},
+};
+use dcap_rs::utils::cert::{hash_crl_keccak256, hash_x509_keccak256};
+use dcap_rs::utils::enclave_identity::get_enclave_identityv2_content_hash;
+use dcap_rs::utils::quotes::version_3::verify_quote_dcapv3;
+use dcap_rs::utils::quotes::version_4::verify_quote_dcapv4;
+use dcap_rs::utils::tcbinfo::{get_tcbinfov2_content_hash, get_tcbinfov3_content_hash};
+
+const QUOTE_HEADER_LEN: usize = 48;
+const SGX_REPORT_BODY_LEN: usize = 384;
+const TD10_REPORT_BODY_LEN: usize = 584;
+const SGX_TEE_TYPE: u32 = 0x00000000;
+const TDX_TEE_TYPE: u32 = 0x00000081;
+// The lengths of the 8 members of a serialized `IntelCollateral`.
+const COLLATERAL_LENS_LEN: usize = 4 * 8;
+
+/// Receives the start and the end of each phase of the verification. Phases do not nest.
+pub trait PhaseTracker {
+    fn start(&mut self, phase: Phase);
+    fn end(&mut self, phase: Phase);
+}
+
+/// Tracks nothing.
+impl PhaseTracker for () {
+    fn start(&mut self, _phase: Phase) {}
+    fn end(&mut self, _phase: Phase) {}
+}
+
+/// Prints the cycles spent in each phase with `format_cycle_report`, reading them from the
+/// cycle counter of the zkVM. Used by the Risc0 guests with `env::cycle_count`.
+pub struct CycleReporter<F: FnMut() -> u64> {
+    cycle_count: F,
+    start: u64,
+}
+
+impl<F: FnMut() -> u64> CycleReporter<F> {
+    pub fn new(cycle_count: F) -> Self {
+        CycleReporter {
+            cycle_count,
+            start: 0,
+        }
+    }
+}
+
+impl<F: FnMut() -> u64> PhaseTracker for CycleReporter<F> {
+    fn start(&mut self, _phase: Phase) {
+        self.start = (self.cycle_count)();
+    }
+
+    fn end(&mut self, phase: Phase) {
+        let cycles = (self.cycle_count)().saturating_sub(self.start);
+        println!("{}", format_cycle_report(phase, cycles));
+    }
+}
+
+/// Prints the markers of the SP1 cycle tracker, which adds up the cycles of each phase in the
+/// `cycle_tracker` of the execution report.
+pub struct Sp1CycleTracker;
+
+impl PhaseTracker for Sp1CycleTracker {
+    fn start(&mut self, phase: Phase) {
+        println!("cycle-tracker-report-start: {}", phase.as_str());
+    }
+
+    fn end(&mut self, phase: Phase) {
+        println!("cycle-tracker-report-end: {}", phase.as_str());
+    }
+}
+
+fn track<T>(tracker: &mut impl PhaseTracker, phase: Phase, f: impl FnOnce() -> T) -> T {
+    tracker.start(phase);
+    let result = f();
+    tracker.end(phase);
+    result
+}
+
+/// Verifies a `DcapProgramInput` and returns the encoded `DcapProgramJournal`.
+pub fn run(input: &[u8]) -> Vec<u8> {
+    run_tracked(input, &mut ())
+}
+
+pub fn run_tracked(input: &[u8], tracker: &mut impl PhaseTracker) -> Vec<u8> {
+    let journal = match verify_tracked(input, tracker) {
+        Ok(output) => DcapProgramJournal::Success(Box::new(output)),
+        Err(code) => DcapProgramJournal::Failure(code),
+    };
+    track(tracker, Phase::EncodeJournal, || journal.encode().unwrap())
+}
+
+/// Verifies a `DcapBatchInput` and returns the encoded `DcapBatchJournal`.
+pub fn run_batch(input: &[u8]) -> Vec<u8> {
+    run_batch_tracked(input, &mut ())
+}
+
+pub fn run_batch_tracked(input: &[u8], tracker: &mut impl PhaseTracker) -> Vec<u8> {
+    let journal = match verify_batch_tracked(input, tracker) {
+        Ok(output) => DcapBatchJournal::Success(Box::new(output)),
+        Err(code) => DcapBatchJournal::Failure(code),
+    };
+    track(tracker, Phase::EncodeJournal, || journal.encode().unwrap())
+}
+
+pub fn verify(input: &[u8]) -> Result<DcapProgramOutput, ErrorCode> {
+    verify_tracked(input, &mut ())
+}
+
+pub fn verify_tracked(
+    input: &[u8],
+    tracker: &mut impl PhaseTracker,
+) -> Result<DcapProgramOutput, ErrorCode> {
+    let (input, header) = track(tracker, Phase::DecodeInput, || {
+        let input = DcapProgramInput::decode(input).map_err(|_| ErrorCode::InvalidInput)?;
+        let header = quote_header(&input.quote)?;
+        Ok::<_, ErrorCode>((input, header))
+    })?;
+    let DcapProgramInput {
+        current_time,
+        quote,
+        collaterals,
+        bind_time,
+    } = input;
+
+    let intel_collaterals = track(tracker, Phase::ParseCollaterals, || {
+        parse_collaterals(&collaterals)
+    })?;
+    let commitment = track(tracker, Phase::HashCollaterals, || {
+        commit_collaterals(&intel_collaterals, header.version, current_time, bind_time)
+    })?;
+    let verified_output = track(tracker, Phase::VerifyQuote, || {
+        verify_quote(&quote, &header, &intel_collaterals, current_time)
+    })?;
+
+    Ok(DcapProgramOutput {
+        verified_output,
+        current_time,
+        tcbinfo_content_hash: commitment.tcbinfo_content_hash,
+        qeidentity_content_hash: commitment.qeidentity_content_hash,
+        sgx_intel_root_ca_cert_hash: commitment.sgx_intel_root_ca_cert_hash,
+        sgx_tcb_signing_cert_hash: commitment.sgx_tcb_signing_cert_hash,
+        sgx_intel_root_ca_crl_hash: commitment.sgx_intel_root_ca_crl_hash,
+        sgx_pck_crl_hash: commitment.sgx_pck_crl_hash,
+        time_window: commitment.time_window,
+    })
+}
+
+/// Verifies all the quotes of a batch against the same collaterals, which are hashed once.
+pub fn verify_batch(input: &[u8]) -> Result<DcapBatchOutput, ErrorCode> {
+    verify_batch_tracked(input, &mut ())
+}
+
+pub fn verify_batch_tracked(
+    input: &[u8],
+    tracker: &mut impl PhaseTracker,
+) -> Result<DcapBatchOutput, ErrorCode> {
+    let (input, headers) = track(tracker, Phase::DecodeInput, || {
+        let input = DcapBatchInput::decode(input).map_err(|_| ErrorCode::InvalidInput)?;
+        if input.quotes.is_empty() {
+            return Err(ErrorCode::InvalidInput);
+        }
+        let headers = input
+            .quotes
+            .iter()
+            .map(|quote| quote_header(quote))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((input, headers))
+    })?;
+    let DcapBatchInput {
+        current_time,
+        quotes,
+        collaterals,
+        bind_time,
+    } = input;
+
+    // The TCB Info version depends on the quote version.
+    let version = headers[0].version;
+    if headers.iter().any(|header| header.version != version) {
+        return Err(ErrorCode::BatchVersionMismatch);
+    }
+
+    let intel_collaterals = track(tracker, Phase::ParseCollaterals, || {
+        parse_collaterals(&collaterals)
+    })?;
+    let commitment = track(tracker, Phase::HashCollaterals, || {
+        commit_collaterals(&intel_collaterals, version, current_time, bind_time)
+    })?;
+    let verified_outputs = quotes
+        .iter()
+        .zip(&headers)
+        .map(|(quote, header)| {
+            track(tracker, Phase::VerifyQuote, || {
+                verify_quote(quote, header, &intel_collaterals, current_time)
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(DcapBatchOutput {
+        verified_outputs,
+        current_time,
+        tcbinfo_content_hash: commitment.tcbinfo_content_hash,
+        qeidentity_content_hash: commitment.qeidentity_content_hash,
+        sgx_intel_root_ca_cert_hash: commitment.sgx_intel_root_ca_cert_hash,
+        sgx_tcb_signing_cert_hash: commitment.sgx_tcb_signing_cert_hash,
+        sgx_intel_root_ca_crl_hash: commitment.sgx_intel_root_ca_crl_hash,
+        sgx_pck_crl_hash: commitment.sgx_pck_crl_hash,
+        time_window: commitment.time_window,
+    })
+}
+
+fn quote_header(quote: &[u8]) -> Result<QuoteHeader, ErrorCode> {
+    if quote.len() < QUOTE_HEADER_LEN {
+        return Err(ErrorCode::InvalidQuote);
+    }
+    Ok(QuoteHeader::from_bytes(&quote[..QUOTE_HEADER_LEN]))
+}
+
+/// Verifies a quote, dispatching on the version of its header, and returns the serialized
+/// `VerifiedOutput`.
+///
+/// The dcap-rs revision used by the programs only verifies V3 and V4 quotes, V5 (TD 1.5)
+/// quotes are rejected with `UnsupportedQuoteVersion`.
+fn verify_quote(
+    quote: &[u8],
+    header: &QuoteHeader,
+    intel_collaterals: &IntelCollateral,
+    current_time: u64,
+) -> Result<Vec<u8>, ErrorCode> {
+    let verified_output = match header.version {
+        3 => {
+            if header.tee_type != SGX_TEE_TYPE {
+                return Err(ErrorCode::UnsupportedTeeType);
+            }
+            check_quote_len(quote, SGX_REPORT_BODY_LEN)?;
+            let quote = QuoteV3::from_bytes(quote);
+            verify_quote_dcapv3(&quote, intel_collaterals, current_time)
+        }
+        4 => {
+            let body_len = match header.tee_type {
+                SGX_TEE_TYPE => SGX_REPORT_BODY_LEN,
+                TDX_TEE_TYPE => TD10_REPORT_BODY_LEN,
+                _ => return Err(ErrorCode::UnsupportedTeeType),
+            };
+            check_quote_len(quote, body_len)?;
+            let quote = QuoteV4::from_bytes(quote);
+            verify_quote_dcapv4(&quote, intel_collaterals, current_time)
+        }
+        _ => return Err(ErrorCode::UnsupportedQuoteVersion),
+    };
+    Ok(verified_output.to_bytes())
+}
+
+/// Checks that a V3 or V4 quote holds its body and all of its signature data, which dcap-rs
+/// parses without bounds checks: `[header][body][signature_data_len: u32][signature_data]`.
+fn check_quote_len(quote: &[u8], body_len: usize) -> Result<(), ErrorCode> {
+    let len_offset = QUOTE_HEADER_LEN + body_len;
+    let signature_data_len = quote
+        .get(len_offset..len_offset + 4)
+        .ok_or(ErrorCode::InvalidQuote)?;
+    let signature_data_len = u32::from_le_bytes(signature_data_len.try_into().unwrap()) as usize;
+    if quote.len() - len_offset - 4 < signature_data_len {
+        return Err(ErrorCode::InvalidQuote);
+    }
+    Ok(())
+}
+
+/// Deserializes the collaterals after checking the lengths of their members, which dcap-rs
+/// slices without bounds checks: eight `u32` lengths, then the members in the same order and
+/// nothing else.
+/// The PCK certificate chain and one of the PCK CRLs can be empty, the others cannot.
+fn parse_collaterals(collaterals: &[u8]) -> Result<IntelCollateral, ErrorCode> {
+    let lens = collaterals
+        .get(..COLLATERAL_LENS_LEN)
+        .ok_or(ErrorCode::InvalidCollaterals)?
+        .chunks_exact(4)
+        .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+        .collect::<Vec<_>>();
+    let members_len = lens
+        .iter()
+        .try_fold(0usize, |total, len| total.checked_add(*len))
+        .ok_or(ErrorCode::InvalidCollaterals)?;
+    if collaterals.len() - COLLATERAL_LENS_LEN != members_len {
+        return Err(ErrorCode::InvalidCollaterals);
+    }
+    // TCB Info, QE Identity, Root CA, TCB Signing CA and Root CA CRL.
+    if [0, 1, 2, 3, 5].iter().any(|&member| lens[member] == 0) {
+        return Err(ErrorCode::InvalidCollaterals);
+    }
+    Ok(IntelCollateral::from_bytes(collaterals))
+}
+
+/// The collateral hashes committed to the journal, and the time window with `bind_time`.
+struct CollateralCommitment {
+    tcbinfo_content_hash: [u8; 32],
+    qeidentity_content_hash: [u8; 32],
+    sgx_intel_root_ca_cert_hash: [u8; 32],
+    sgx_tcb_signing_cert_hash: [u8; 32],
+    sgx_intel_root_ca_crl_hash: [u8; 32],
+    sgx_pck_crl_hash: [u8; 32],
+    time_window: Option<TimeWindow>,
+}
+
+fn commit_collaterals(
+    intel_collaterals: &IntelCollateral,
+    quote_version: u16,
+    current_time: u64,
+    bind_time: bool,
+) -> Result<CollateralCommitment, ErrorCode> {
+    // check either only platform or processor crls is provided. not both
+    let sgx_platform_crl_is_found = intel_collaterals.get_sgx_pck_platform_crl().is_some();
+    let sgx_processor_crl_is_found = intel_collaterals.get_sgx_pck_processor_crl().is_some();
+    if sgx_platform_crl_is_found == sgx_processor_crl_is_found {
+        return Err(ErrorCode::InvalidCollaterals);
+    }
+
+    let (tcbinfo_content_hash, tcb_info_validity) = match quote_version {
+        3 => {
+            let tcb_info_v2 = intel_collaterals.get_tcbinfov2();
+            (
+                get_tcbinfov2_content_hash(&tcb_info_v2),
+                (
+                    tcb_info_v2.tcb_info.issue_date,
+                    tcb_info_v2.tcb_info.next_update,
+                ),
+            )
+        }
+        4 => {
+            let tcb_info_v3 = intel_collaterals.get_tcbinfov3();
+            (
+                get_tcbinfov3_content_hash(&tcb_info_v3),
+                (
+                    tcb_info_v3.tcb_info.issue_date,
+                    tcb_info_v3.tcb_info.next_update,
+                ),
+            )
+        }
+        _ => return Err(ErrorCode::UnsupportedQuoteVersion),
+    };
+
+    let qeidentity = intel_collaterals.get_qeidentityv2();
+    let qeidentity_content_hash = get_enclave_identityv2_content_hash(&qeidentity);
+
+    let sgx_intel_root_ca_cert_hash =
+        hash_x509_keccak256(&intel_collaterals.get_sgx_intel_root_ca());
+
+    let sgx_tcb_signing_cert_hash = hash_x509_keccak256(&intel_collaterals.get_sgx_tcb_signing());
+
+    let sgx_intel_root_ca_crl = intel_collaterals
+        .get_sgx_intel_root_ca_crl()
+        .ok_or(ErrorCode::InvalidCollaterals)?;
+    let sgx_intel_root_ca_crl_hash = hash_crl_keccak256(&sgx_intel_root_ca_crl);
+
+    let sgx_pck_crl = if sgx_platform_crl_is_found {
+        intel_collaterals.get_sgx_pck_platform_crl().unwrap()
+    } else {
+        intel_collaterals.get_sgx_pck_processor_crl().unwrap()
+    };
+    let sgx_pck_crl_hash = hash_crl_keccak256(&sgx_pck_crl);
+
+    // current_time is chosen by the host. With bind_time, it must be within the validity of the
+    // collaterals and that window is committed to the journal, so that verifiers can reject
+    // proofs made with stale collaterals or an arbitrary time.
+    let time_window = if bind_time {
+        let mut window = TimeWindow {
+            not_before: 0,
+            not_after: u64::MAX,
+        };
+        narrow(
+            &mut window,
+            parse_date(&tcb_info_validity.0)?,
+            parse_date(&tcb_info_validity.1)?,
+        );
+        narrow(
+            &mut window,
+            parse_date(&qeidentity.enclave_identity.issue_date)?,
+            parse_date(&qeidentity.enclave_identity.next_update)?,
+        );
+        for crl in [&sgx_intel_root_ca_crl, &sgx_pck_crl] {
+            let next_update = crl.next_update().ok_or(ErrorCode::InvalidCollaterals)?;
+            narrow(
+                &mut window,
+                timestamp(crl.last_update().timestamp())?,
+                timestamp(next_update.timestamp())?,
+            );
+        }
+        if !window.contains(current_time) {
+            return Err(ErrorCode::TimeOutsideCollateralValidity);
+        }
+        Some(window)
+    } else {
+        None
+    };
+
+    Ok(CollateralCommitment {
+        tcbinfo_content_hash,
+        qeidentity_content_hash,
+        sgx_intel_root_ca_cert_hash,
+        sgx_tcb_signing_cert_hash,
+        sgx_intel_root_ca_crl_hash,
+        sgx_pck_crl_hash,
+        time_window,
+    })
+}
+
+fn narrow(window: &mut TimeWindow, not_before: u64, not_after: u64) {
+    window.not_before = window.not_before.max(not_before);
+    window.not_after = window.not_after.min(not_after);
+}
+
+fn parse_date(date: &str) -> Result<u64, ErrorCode> {
+    let date = DateTime::parse_from_rfc3339(date).map_err(|_| ErrorCode::InvalidCollaterals)?;
+    timestamp(date.timestamp())
+}
+
+fn timestamp(seconds: i64) -> Result<u64, ErrorCode> {
+    u64::try_from(seconds).map_err(|_| ErrorCode::InvalidCollaterals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(version: u16, tee_type: u32, body_len: usize, signature_data_len: u32) -> Vec<u8> {
+        let mut quote = vec![0; QUOTE_HEADER_LEN + body_len];
+        quote[..2].copy_from_slice(&version.to_le_bytes());
+        quote[4..8].copy_from_slice(&tee_type.to_le_bytes());
+        quote.extend_from_slice(&signature_data_len.to_le_bytes());
+        quote.extend_from_slice(&vec![0; signature_data_len as usize]);
+        quote
+    }
+
+    fn verify_truncated(quote: &[u8]) -> Result<Vec<u8>, ErrorCode> {
+        let header = quote_header(quote)?;
+        verify_quote(quote, &header, &IntelCollateral::new(), 0)
+    }
+
+    #[test]
+    fn checks_quote_len() {
+        let sgx = quote(3, SGX_TEE_TYPE, SGX_REPORT_BODY_LEN, 100);
+        check_quote_len(&sgx, SGX_REPORT_BODY_LEN).unwrap();
+        let tdx = quote(4, TDX_TEE_TYPE, TD10_REPORT_BODY_LEN, 100);
+        check_quote_len(&tdx, TD10_REPORT_BODY_LEN).unwrap();
+
+        for len in [
+            QUOTE_HEADER_LEN,
+            QUOTE_HEADER_LEN + 583,
+            QUOTE_HEADER_LEN + 587,
+            tdx.len() - 1,
+        ] {
+            assert_eq!(
+                check_quote_len(&tdx[..len], TD10_REPORT_BODY_LEN),
+                Err(ErrorCode::InvalidQuote)
+            );
+        }
+        let huge = quote(4, TDX_TEE_TYPE, TD10_REPORT_BODY_LEN, 0);
+        let mut huge = huge[..huge.len() - 4].to_vec();
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(
+            check_quote_len(&huge, TD10_REPORT_BODY_LEN),
+            Err(ErrorCode::InvalidQuote)
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_quotes_before_parsing() {
+        assert_eq!(verify_truncated(&[0; 47]), Err(ErrorCode::InvalidQuote));
+        let sgx = quote(3, SGX_TEE_TYPE, SGX_REPORT_BODY_LEN, 100);
+        assert_eq!(
+            verify_truncated(&sgx[..sgx.len() - 1]),
+            Err(ErrorCode::InvalidQuote)
+        );
+        // A TDX body is longer than a SGX one, the TEE type selects the expected length.
+        let tdx = quote(4, TDX_TEE_TYPE, SGX_REPORT_BODY_LEN, 100);
+        assert_eq!(verify_truncated(&tdx), Err(ErrorCode::InvalidQuote));
+    }
+
+    fn collaterals(lens: [u32; 8]) -> Vec<u8> {
+        let mut collaterals = lens
+            .iter()
+            .flat_map(|len| len.to_le_bytes())
+            .collect::<Vec<_>>();
+        for len in lens {
+            collaterals.extend_from_slice(&vec![0xab; len as usize]);
+        }
+        collaterals
+    }
+
+    #[test]
+    fn checks_collateral_lens() {
+        let lens = [10, 20, 30, 40, 0, 50, 0, 60];
+        let parsed = parse_collaterals(&collaterals(lens)).unwrap();
+        assert_eq!(parsed.to_bytes(), collaterals(lens));
+
+        let valid = collaterals(lens);
+        for len in [
+            0,
+            COLLATERAL_LENS_LEN - 1,
+            COLLATERAL_LENS_LEN,
+            valid.len() - 1,
+        ] {
+            assert!(matches!(
+                parse_collaterals(&valid[..len]),
+                Err(ErrorCode::InvalidCollaterals)
+            ));
+        }
+        let mut trailing = valid.clone();
+        trailing.push(0);
+        assert!(matches!(
+            parse_collaterals(&trailing),
+            Err(ErrorCode::InvalidCollaterals)
+        ));
+        // The lengths add up past usize::MAX on 32-bit zkVMs.
+        let huge = [u32::MAX, u32::MAX, 1, 1, 0, 1, 0, 1]
+            .iter()
+            .flat_map(|len| len.to_le_bytes())
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            parse_collaterals(&huge),
+            Err(ErrorCode::InvalidCollaterals)
+        ));
+    }
+
+    #[test]
+    fn requires_all_collaterals_but_the_pck_chain_and_a_pck_crl() {
+        for member in [0, 1, 2, 3, 5] {
+            let mut lens = [10, 20, 30, 40, 0, 50, 0, 60];
+            lens[member] = 0;
+            assert!(matches!(
+                parse_collaterals(&collaterals(lens)),
+                Err(ErrorCode::InvalidCollaterals)
+            ));
+        }
+        parse_collaterals(&collaterals([10, 20, 30, 40, 70, 50, 60, 0])).unwrap();
+    }
+
+    #[test]
+    fn rejects_unsupported_quotes() {
+        let tdx_v3 = quote(3, TDX_TEE_TYPE, TD10_REPORT_BODY_LEN, 100);
+        assert_eq!(
+            verify_truncated(&tdx_v3),
+            Err(ErrorCode::UnsupportedTeeType)
+        );
+        let unknown_tee = quote(4, 0x42, TD10_REPORT_BODY_LEN, 100);
+        assert_eq!(
+            verify_truncated(&unknown_tee),
+            Err(ErrorCode::UnsupportedTeeType)
+        );
+        for version in [2, 5] {
+            let quote = quote(version, TDX_TEE_TYPE, TD10_REPORT_BODY_LEN, 100);
+            assert_eq!(
+                verify_truncated(&quote),
+                Err(ErrorCode::UnsupportedQuoteVersion)
+            );
+        }
+    }
+}
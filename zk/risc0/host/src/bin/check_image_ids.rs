@@ -0,0 +1,72 @@
+//! Compares the image IDs of the embedded guest programs with the pinned ones.
+//!
+//! Refuses to run unless the guests were built reproducibly, with `RISC0_USE_DOCKER=1`.
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use clap::Parser;
+
+#[derive(Parser)]
+struct Args {
+    /// The pinned image IDs, by method name
+    #[arg(long, default_value = concat!(env!("CARGO_MANIFEST_DIR"), "/../image-ids.json"))]
+    manifest: PathBuf,
+    /// Writes the computed image IDs to the manifest instead of comparing them
+    #[arg(long)]
+    update: bool,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    if !methods::REPRODUCIBLE_BUILD {
+        bail!(
+            "The guests were built without RISC0_USE_DOCKER, their image IDs are not \
+             reproducible. Run RISC0_USE_DOCKER=1 cargo run --bin check-image-ids"
+        );
+    }
+    let computed = host::image_ids()?;
+
+    if args.update {
+        std::fs::write(
+            &args.manifest,
+            serde_json::to_string_pretty(&computed)? + "\n",
+        )?;
+        println!("Image IDs written to {}", args.manifest.display());
+        return Ok(());
+    }
+
+    let pinned: BTreeMap<String, String> = serde_json::from_slice(&std::fs::read(&args.manifest)?)?;
+    let mut mismatches = 0;
+    for (name, image_id) in &computed {
+        match pinned.get(name) {
+            Some(pinned) if normalize(pinned) == normalize(image_id) => {
+                println!("{}: {} matches", name, image_id);
+            }
+            Some(pinned) => {
+                println!("{}: {} does not match {}", name, image_id, pinned);
+                mismatches += 1;
+            }
+            None => {
+                println!("{}: {} is not pinned", name, image_id);
+                mismatches += 1;
+            }
+        }
+    }
+    for name in pinned.keys().filter(|name| !computed.contains_key(*name)) {
+        println!("{}: pinned but not built", name);
+        mismatches += 1;
+    }
+    if mismatches > 0 {
+        bail!(
+            "{} image ID(s) differ from {}",
+            mismatches,
+            args.manifest.display()
+        );
+    }
+    Ok(())
+}
+
+fn normalize(image_id: &str) -> String {
+    image_id.trim_start_matches("0x").to_lowercase()
+}
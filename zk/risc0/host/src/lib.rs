@@ -0,0 +1,399 @@
+//! Generates Risc0 proofs of the DCAP quote verification performed by the guest programs.
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use anyhow::{bail, Result};
+use dcap_codec::aggregation::DcapAggregation;
+use dcap_codec::batch::DcapBatchInput;
+use dcap_codec::profile::parse_cycle_report;
+use dcap_codec::DcapProgramInput;
+use dcap_rs::types::collaterals::IntelCollateral;
+use methods::{
+    DCAP_AGGREGATION_GUEST_ELF, DCAP_AGGREGATION_GUEST_ID, DCAP_BATCH_GUEST_ELF,
+    DCAP_BATCH_GUEST_ID, DCAP_GUEST_ELF, DCAP_GUEST_ID,
+};
+use risc0_zkvm::{
+    compute_image_id, default_executor, default_prover, BonsaiProver, Digest, ExecutorEnv,
+    InnerReceipt, Prover, ProverOpts, Receipt,
+};
+use serde::{Deserialize, Serialize};
+
+/// `ZkCoProcessorType` of Risc0 in the DCAP attestation contract.
+pub const ZK_COPROCESSOR_RISC0: u8 = 1;
+
+/// File names written by `DcapProof::write_artifacts`.
+pub const MANIFEST_FILE: &str = "proof.json";
+pub const JOURNAL_FILE: &str = "journal.bin";
+pub const SEAL_FILE: &str = "seal.bin";
+pub const RECEIPT_FILE: &str = "receipt.bin";
+/// File name of the `CycleReport` written by the `--execute-only` mode of the host binary.
+pub const CYCLE_REPORT_FILE: &str = "cycles.json";
+
+/// Where the proof is generated.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Backend {
+    /// Selected by `RISC0_PROVER`, or Bonsai when `BONSAI_API_URL` and `BONSAI_API_KEY` are set.
+    #[default]
+    Default,
+    Bonsai,
+    /// In-process, requires the `prove` feature.
+    #[cfg(feature = "prove")]
+    Local,
+}
+
+/// Kind of receipt produced.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum ProofSystem {
+    /// One STARK per segment, the fastest to produce.
+    #[default]
+    Composite,
+    /// A single STARK.
+    Succinct,
+    /// A Groth16 SNARK that can be verified on-chain.
+    Groth16,
+}
+
+impl ProofSystem {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProofSystem::Composite => "composite",
+            ProofSystem::Succinct => "succinct",
+            ProofSystem::Groth16 => "groth16",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProveOptions {
+    pub backend: Backend,
+    pub proof_system: ProofSystem,
+    /// Requires the verification time to be within the validity of the collaterals, and
+    /// commits that window to the journal.
+    pub bind_time: bool,
+}
+
+pub struct DcapProof {
+    /// The journal committed by the guest. For a single quote, the `output` of
+    /// `verifyAndAttestWithZKProof`.
+    pub journal: Vec<u8>,
+    /// For Groth16, the seal verified on-chain. Otherwise the bincode encoded inner receipt.
+    pub seal: Vec<u8>,
+    pub image_id: [u8; 32],
+    pub proof_system: ProofSystem,
+    pub receipt: Receipt,
+}
+
+/// Summary of a proof written to `proof.json`, holding what is needed to submit it with
+/// `verifyAndAttestWithZKProof`. Byte fields are 0x prefixed hex strings.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofManifest {
+    pub zkvm: String,
+    pub zk_coprocessor: u8,
+    pub proof_system: String,
+    pub image_id: String,
+    pub journal: String,
+    pub seal: String,
+}
+
+impl DcapProof {
+    pub fn manifest(&self) -> ProofManifest {
+        ProofManifest {
+            zkvm: "risc0".to_string(),
+            zk_coprocessor: ZK_COPROCESSOR_RISC0,
+            proof_system: self.proof_system.as_str().to_string(),
+            image_id: format!("0x{}", hex::encode(self.image_id)),
+            journal: format!("0x{}", hex::encode(&self.journal)),
+            seal: format!("0x{}", hex::encode(&self.seal)),
+        }
+    }
+
+    /// Writes the proof to `dir`, which is created if needed:
+    /// - `proof.json`: the `ProofManifest`;
+    /// - `journal.bin`: the raw journal;
+    /// - `seal.bin`: the raw seal;
+    /// - `receipt.bin`: the bincode encoded `Receipt`, to verify or aggregate the proof later.
+    pub fn write_artifacts(&self, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::write(
+            dir.join(MANIFEST_FILE),
+            serde_json::to_string_pretty(&self.manifest())?,
+        )?;
+        fs::write(dir.join(JOURNAL_FILE), &self.journal)?;
+        fs::write(dir.join(SEAL_FILE), &self.seal)?;
+        fs::write(dir.join(RECEIPT_FILE), bincode::serialize(&self.receipt)?)?;
+        Ok(())
+    }
+
+    /// Reads a proof written by `write_artifacts`.
+    pub fn read_artifacts(dir: &Path) -> Result<Self> {
+        let manifest: ProofManifest = serde_json::from_slice(&fs::read(dir.join(MANIFEST_FILE))?)?;
+        let proof_system = match manifest.proof_system.as_str() {
+            "composite" => ProofSystem::Composite,
+            "succinct" => ProofSystem::Succinct,
+            "groth16" => ProofSystem::Groth16,
+            other => bail!("unknown proof system {}", other),
+        };
+        let image_id = hex::decode(manifest.image_id.trim_start_matches("0x"))?;
+        let Ok(image_id) = <[u8; 32]>::try_from(image_id) else {
+            bail!("invalid image ID in {}", MANIFEST_FILE);
+        };
+        let receipt: Receipt = bincode::deserialize(&fs::read(dir.join(RECEIPT_FILE))?)?;
+        Ok(DcapProof {
+            journal: receipt.journal.bytes.clone(),
+            seal: fs::read(dir.join(SEAL_FILE))?,
+            image_id,
+            proof_system,
+            receipt,
+        })
+    }
+}
+
+/// Cycles spent by an execution of a guest, see `execute`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycleReport {
+    pub zkvm: String,
+    /// `dcap` or `dcap-batch`.
+    pub program: String,
+    /// User cycles of the whole execution, over all the segments.
+    pub total_cycles: u64,
+    /// Cycles spent in each `Phase` of the verification, by phase name. The remaining cycles
+    /// are spent reading the input, committing the journal and reporting the phases.
+    pub phases: BTreeMap<String, u64>,
+    /// The journal, as a 0x prefixed hex string.
+    pub output: String,
+}
+
+/// The image ID of the DCAP guest program.
+pub fn image_id() -> [u8; 32] {
+    Digest::from(DCAP_GUEST_ID).into()
+}
+
+/// The image ID of the DCAP batch guest program.
+pub fn batch_image_id() -> [u8; 32] {
+    Digest::from(DCAP_BATCH_GUEST_ID).into()
+}
+
+/// The image ID of the aggregation guest program.
+pub fn aggregation_image_id() -> [u8; 32] {
+    Digest::from(DCAP_AGGREGATION_GUEST_ID).into()
+}
+
+/// The image IDs computed from the embedded ELFs of the guest programs, by method name, as
+/// 0x prefixed hex strings. See `image-ids.json` for the pinned values.
+pub fn image_ids() -> Result<BTreeMap<String, String>> {
+    [
+        ("dcap_guest", DCAP_GUEST_ELF),
+        ("dcap_batch_guest", DCAP_BATCH_GUEST_ELF),
+        ("dcap_aggregation_guest", DCAP_AGGREGATION_GUEST_ELF),
+    ]
+    .into_iter()
+    .map(|(name, elf)| {
+        let image_id = compute_image_id(elf)?;
+        Ok((name.to_string(), format!("0x{}", hex::encode(image_id))))
+    })
+    .collect()
+}
+
+/// Serializes the guest input, see `DcapProgramInput`.
+pub fn serialize_input(
+    quote: &[u8],
+    collaterals: &IntelCollateral,
+    current_time: u64,
+    bind_time: bool,
+) -> Result<Vec<u8>> {
+    let input = DcapProgramInput {
+        current_time,
+        quote: quote.to_vec(),
+        collaterals: collaterals.to_bytes(),
+        bind_time,
+    };
+    Ok(input.encode()?)
+}
+
+/// Serializes the batch guest input, see `DcapBatchInput`.
+pub fn serialize_batch_input(
+    quotes: &[Vec<u8>],
+    collaterals: &IntelCollateral,
+    current_time: u64,
+    bind_time: bool,
+) -> Result<Vec<u8>> {
+    let input = DcapBatchInput {
+        current_time,
+        quotes: quotes.to_vec(),
+        collaterals: collaterals.to_bytes(),
+        bind_time,
+    };
+    Ok(input.encode()?)
+}
+
+/// Executes the guest without proving, and reports the cycles spent in each phase of the
+/// verification of `quote`.
+pub fn execute(
+    quote: &[u8],
+    collaterals: &IntelCollateral,
+    current_time: u64,
+    bind_time: bool,
+) -> Result<CycleReport> {
+    let input = serialize_input(quote, collaterals, current_time, bind_time)?;
+    execute_guest("dcap", DCAP_GUEST_ELF, &input)
+}
+
+/// Executes the batch guest without proving, see `execute`.
+pub fn execute_batch(
+    quotes: &[Vec<u8>],
+    collaterals: &IntelCollateral,
+    current_time: u64,
+    bind_time: bool,
+) -> Result<CycleReport> {
+    if quotes.is_empty() {
+        bail!("no quote to execute");
+    }
+    let input = serialize_batch_input(quotes, collaterals, current_time, bind_time)?;
+    execute_guest("dcap-batch", DCAP_BATCH_GUEST_ELF, &input)
+}
+
+/// Proves the verification of `quote` against `collaterals` at `current_time` (seconds since
+/// the Unix epoch), and checks the receipt against the image ID.
+pub fn prove(
+    quote: &[u8],
+    collaterals: &IntelCollateral,
+    current_time: u64,
+    options: &ProveOptions,
+) -> Result<DcapProof> {
+    let input = serialize_input(quote, collaterals, current_time, options.bind_time)?;
+    let env = ExecutorEnv::builder().write_slice(&input).build()?;
+    prove_guest(DCAP_GUEST_ELF, DCAP_GUEST_ID, env, options)
+}
+
+/// Proves the verification of `quotes`, which share the same `collaterals`, in a single
+/// receipt of the batch guest. Its journal is a `DcapBatchJournal`.
+pub fn prove_batch(
+    quotes: &[Vec<u8>],
+    collaterals: &IntelCollateral,
+    current_time: u64,
+    options: &ProveOptions,
+) -> Result<DcapProof> {
+    if quotes.is_empty() {
+        bail!("no quote to prove");
+    }
+    let input = serialize_batch_input(quotes, collaterals, current_time, options.bind_time)?;
+    let env = ExecutorEnv::builder().write_slice(&input).build()?;
+    prove_guest(DCAP_BATCH_GUEST_ELF, DCAP_BATCH_GUEST_ID, env, options)
+}
+
+/// Aggregates proofs of the same guest, made by `prove` or `prove_batch`, in a single receipt
+/// of the aggregation guest. Its journal is a `DcapAggregation` holding their image ID and
+/// journals, in the order of `proofs`.
+///
+/// The aggregation guest commits whatever image ID it is given, so proofs of any other guest
+/// than the DCAP guest and the DCAP batch guest are rejected here.
+///
+/// The receipts are added as assumptions verified by the guest, so they must be `Composite`
+/// or `Succinct`. The assumptions are only resolved with the `Succinct` or `Groth16` proof
+/// system, a `Composite` aggregation keeps them as conditions of the receipt.
+pub fn aggregate(proofs: &[DcapProof], options: &ProveOptions) -> Result<DcapProof> {
+    let Some(first) = proofs.first() else {
+        bail!("no proof to aggregate");
+    };
+    let image_id = first.image_id;
+    if image_id != self::image_id() && image_id != batch_image_id() {
+        bail!(
+            "0x{} is not the image ID of the DCAP guest nor of the DCAP batch guest",
+            hex::encode(image_id)
+        );
+    }
+
+    let mut builder = ExecutorEnv::builder();
+    for proof in proofs {
+        if proof.image_id != image_id {
+            bail!("the proofs to aggregate are not from the same guest");
+        }
+        if matches!(proof.receipt.inner, InnerReceipt::Groth16(_)) {
+            bail!("Groth16 receipts cannot be aggregated");
+        }
+        proof.receipt.verify(image_id)?;
+        builder.add_assumption(proof.receipt.clone());
+    }
+    let input = DcapAggregation {
+        program_id: image_id,
+        journals: proofs.iter().map(|proof| proof.journal.clone()).collect(),
+    }
+    .encode()?;
+    let env = builder.write_slice(&input).build()?;
+    prove_guest(
+        DCAP_AGGREGATION_GUEST_ELF,
+        DCAP_AGGREGATION_GUEST_ID,
+        env,
+        options,
+    )
+}
+
+fn execute_guest(program: &str, elf: &[u8], input: &[u8]) -> Result<CycleReport> {
+    // The guests print the cycles of each phase to their stdout.
+    let mut stdout = Vec::new();
+    let env = ExecutorEnv::builder()
+        .write_slice(input)
+        .stdout(&mut stdout)
+        .build()?;
+    let session = default_executor().execute(env, elf)?;
+
+    let mut phases = BTreeMap::new();
+    for line in String::from_utf8_lossy(&stdout).lines() {
+        if let Some((phase, cycles)) = parse_cycle_report(line) {
+            *phases.entry(phase.as_str().to_string()).or_insert(0) += cycles;
+        }
+    }
+    Ok(CycleReport {
+        zkvm: "risc0".to_string(),
+        program: program.to_string(),
+        total_cycles: session
+            .segments
+            .iter()
+            .map(|segment| u64::from(segment.cycles))
+            .sum(),
+        phases,
+        output: format!("0x{}", hex::encode(&session.journal.bytes)),
+    })
+}
+
+fn prove_guest(
+    elf: &[u8],
+    image_id: [u32; 8],
+    env: ExecutorEnv,
+    options: &ProveOptions,
+) -> Result<DcapProof> {
+    let prover: Rc<dyn Prover> = match options.backend {
+        Backend::Default => default_prover(),
+        Backend::Bonsai => Rc::new(BonsaiProver::new("bonsai")),
+        #[cfg(feature = "prove")]
+        Backend::Local => Rc::new(risc0_zkvm::LocalProver::new("local")),
+    };
+    let prover_opts = match options.proof_system {
+        ProofSystem::Composite => ProverOpts::composite(),
+        ProofSystem::Succinct => ProverOpts::succinct(),
+        ProofSystem::Groth16 => ProverOpts::groth16(),
+    };
+    let receipt = prover.prove_with_opts(env, elf, &prover_opts)?.receipt;
+    receipt.verify(image_id)?;
+
+    let seal = match (&receipt.inner, options.proof_system) {
+        (InnerReceipt::Groth16(groth16_receipt), _) => groth16_receipt.seal.clone(),
+        // Dev mode produces fake receipts whatever the requested proof system.
+        (inner, ProofSystem::Groth16) if !matches!(inner, InnerReceipt::Fake(_)) => {
+            bail!("the prover did not return a Groth16 receipt")
+        }
+        (inner, _) => bincode::serialize(inner)?,
+    };
+
+    Ok(DcapProof {
+        journal: receipt.journal.bytes.clone(),
+        seal,
+        image_id: Digest::from(image_id).into(),
+        proof_system: options.proof_system,
+        receipt,
+    })
+}
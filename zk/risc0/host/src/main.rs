@@ -1,23 +1,54 @@
-// These constants represent the RISC-V ELF and the image ID generated by risc0-build.
-// The ELF is used for proving and the ID is used for verification.
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use dcap_codec::DcapProgramJournal;
 use dcap_rs::types::{collaterals::IntelCollateral, VerifiedOutput};
-use methods::{DCAP_GUEST_ELF, DCAP_GUEST_ID};
-use risc0_zkvm::{compute_image_id, default_prover, ExecutorEnv, InnerReceipt, ProverOpts};
+use host::{execute, prove, Backend, ProofSystem, ProveOptions, CYCLE_REPORT_FILE};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ProverArg {
+    /// `RISC0_PROVER`, or Bonsai when `BONSAI_API_URL` and `BONSAI_API_KEY` are set
+    Default,
+    Bonsai,
+    /// In-process, requires the `prove` feature
+    Local,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ProofSystemArg {
+    Composite,
+    Succinct,
+    Groth16,
+}
+
+/// Proves the verification of the sample quote and collaterals in `host/data`.
+#[derive(Parser)]
+struct Args {
+    #[arg(long, value_enum, default_value = "default")]
+    prover: ProverArg,
+    /// Defaults to groth16 when BONSAI_API_KEY is set, composite otherwise
+    #[arg(long, value_enum)]
+    proof_system: Option<ProofSystemArg>,
+    /// Requires the verification time to be within the validity of the collaterals
+    #[arg(long)]
+    bind_time: bool,
+    /// Executes the guest without proving and prints the cycle report as JSON
+    #[arg(long)]
+    execute_only: bool,
+    /// Directory where the proof artifacts or the cycle report are written, see the README
+    #[arg(long, env = "DCAP_PROOF_OUT_DIR")]
+    out_dir: Option<PathBuf>,
+}
 
 fn main() {
+    let args = Args::parse();
+
     // Initialize tracing. In order to view logs, run `RUST_LOG=info cargo run`
     tracing_subscriber::fmt()
         .with_env_filter(tracing_subscriber::filter::EnvFilter::from_default_env())
         .init();
 
-    println!(
-        "ImageID: {}",
-        compute_image_id(DCAP_GUEST_ELF).unwrap().to_string()
-    );
-
-    // let quote = hex::decode("030002000000000009000e00939a7233f79c4ca9940a0db3957f0607ad04024c9dfb382baf51ca3e5d6cb6e6000000000c0c100fffff0100000000000000000000000000000000000000000000000000000000000000000000000000000000000500000000000000e700000000000000a4f45c39dac622cb1dd32ddb35a52ec92db41d0fa88a1c911c49e59c534f61cd00000000000000000000000000000000000000000000000000000000000000001bda23eb3a807dfe735ddcebbfa2eac05e04a00df2804296612f770b594180ba0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000ca100000e78d2532cbef391dea9a477119bc505b47e187f6f045636cce8bcf41604a099232eee31b3ef3827c442eb5d5981610480deb0625ed4b01c1ac2b0fb43e05efdeab8af342a611fb608193d9a47b8111654172adf2dabd2d428d28ebe094b9baa1f8f7e240b015af174d4f58a6b201946eee2097af02ed554909779ea2d9f3c1020c0c100fffff0100000000000000000000000000000000000000000000000000000000000000000000000000000000001500000000000000e700000000000000192aa50ce1c0cef03ccf89e7b5b16b0d7978f5c2b1edcf774d87702e8154d8bf00000000000000000000000000000000000000000000000000000000000000008c4f5775d796503e96137f77c68a829a0056ac8ded70140b081b094490c57bff00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000001000900000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000a82754acc7010b3c087c6425ccf47033f711fa44776c6df3cf744864a063657b00000000000000000000000000000000000000000000000000000000000000006cf7ecfde138b32bbf6aec5e260f8bb6277cc2876ea144c3995d2afc0e6baa3525d91884672bf2832c23a6ebf85a165b45af53c836a31168ff7deaec0dd9c82c2000000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f0500620e00002d2d2d2d2d424547494e2043455254494649434154452d2d2d2d2d0a4d494945386a4343424a6d674177494241674956414b7750766270377a6f7a50754144646b792b6f526e356f36704d754d416f4743437147534d343942414d430a4d484178496a416742674e5642414d4d47556c756447567349464e4857434251513073675547786864475a76636d306751304578476a415942674e5642416f4d0a45556c756447567349454e76636e4276636d4630615739754d5251774567594456515148444174545957353059534244624746795954454c4d416b47413155450a4341774351304578437a414a42674e5642415954416c56544d4234584454497a4d4467794e4449784d7a557a4d6c6f5844544d774d4467794e4449784d7a557a0a4d6c6f77634445694d434147413155454177775a535735305a5777675530645949464244537942445a584a3061575a70593246305a5445614d426747413155450a43677752535735305a577767513239796347397959585270623234784644415342674e564241634d43314e68626e526849454e7359584a684d517377435159440a5651514944414a445154454c4d416b474131554542684d4356564d775754415442676371686b6a4f5051494242676771686b6a4f50514d4242774e43414154450a764b6a754b66376969723832686d2b4d5a4151452b6847643349716d53396235634e63484a754b7a5a445970626f35496a344c7a7176704f503830706f4152730a59504233594e355537704d3777644936314b66716f344944446a434341776f77487759445652306a42426777466f41556c5739647a62306234656c4153636e550a3944504f4156634c336c5177617759445652306642475177596a42676f46366758495a616148523063484d364c79396863476b7564484a316333526c5a484e6c0a636e5a705932567a4c6d6c75644756734c6d4e766253397a5a3367765932567964476c6d61574e6864476c76626939324d7939775932746a636d772f593245390a6347786864475a76636d306d5a57356a62325270626d63395a4756794d4230474131556444675157424251695a7667373930317a3171554d3874534c754358580a6571314c6f54414f42674e56485138424166384542414d434273417744415944565230544151482f4241497741444343416a734743537147534962345451454e0a41515343416977776767496f4d42344743697147534962345451454e41514545454358343464705036434c5154772f785543575448306b776767466c42676f710a686b69472b453042445145434d4949425654415142677371686b69472b45304244514543415149424444415142677371686b69472b45304244514543416749420a4444415142677371686b69472b4530424451454341774942417a415142677371686b69472b4530424451454342414942417a415242677371686b69472b4530420a4451454342514943415038774551594c4b6f5a496876684e41513042416759434167442f4d42414743797147534962345451454e41514948416745424d4241470a43797147534962345451454e41514949416745414d42414743797147534962345451454e4151494a416745414d42414743797147534962345451454e4151494b0a416745414d42414743797147534962345451454e4151494c416745414d42414743797147534962345451454e4151494d416745414d42414743797147534962340a5451454e4151494e416745414d42414743797147534962345451454e4151494f416745414d42414743797147534962345451454e41514950416745414d4241470a43797147534962345451454e41514951416745414d42414743797147534962345451454e415149524167454e4d42384743797147534962345451454e415149530a4242414d44414d442f2f38424141414141414141414141414d42414743697147534962345451454e41514d45416741414d42514743697147534962345451454e0a4151514542674267616741414144415042676f71686b69472b45304244514546436745424d42344743697147534962345451454e4151594545424531784169510a72743945363234433159516b497034775241594b4b6f5a496876684e41513042427a41324d42414743797147534962345451454e415163424151482f4d4241470a43797147534962345451454e41516343415145414d42414743797147534962345451454e41516344415145414d416f4743437147534d343942414d43413063410a4d45514349445a6f63514c6478362b4f2b586d4f6b766f6b654133345a617261342b6539534e5877344b68396d5876574169415479695a6e495932474f3466670a4938673342666c4e434f56446e42505270507559377274484e77335470513d3d0a2d2d2d2d2d454e442043455254494649434154452d2d2d2d2d0a2d2d2d2d2d424547494e2043455254494649434154452d2d2d2d2d0a4d4949436c6a4343416a32674177494241674956414a567658633239472b487051456e4a3150517a7a674658433935554d416f4743437147534d343942414d430a4d476778476a415942674e5642414d4d45556c756447567349464e48574342536232393049454e424d526f77474159445651514b4442464a626e526c624342440a62334a7762334a6864476c76626a45554d424947413155454277774c553246756447456751327868636d4578437a414a42674e564241674d416b4e424d5173770a435159445651514745774a56557a4165467730784f4441314d6a45784d4455774d5442614677307a4d7a41314d6a45784d4455774d5442614d484178496a41670a42674e5642414d4d47556c756447567349464e4857434251513073675547786864475a76636d306751304578476a415942674e5642416f4d45556c75644756730a49454e76636e4276636d4630615739754d5251774567594456515148444174545957353059534244624746795954454c4d416b474131554543417743513045780a437a414a42674e5642415954416c56544d466b77457759484b6f5a497a6a3043415159494b6f5a497a6a304441516344516741454e53422f377432316c58534f0a3243757a7078773734654a423732457944476757357258437478327456544c7136684b6b367a2b5569525a436e71523770734f766771466553786c6d546c4a6c0a65546d693257597a33714f42757a43427544416642674e5648534d4547444157674251695a517a575770303069664f44744a5653763141624f536347724442530a42674e5648523845537a424a4d45656752614244686b466f64485277637a6f764c324e6c636e52705a6d6c6a5958526c63793530636e567a6447566b633256790a646d6c6a5a584d75615735305a577775593239744c306c756447567355306459556d397664454e424c6d526c636a416442674e5648513445466751556c5739640a7a62306234656c4153636e553944504f4156634c336c517744675944565230504151482f42415144416745474d42494741315564457745422f7751494d4159420a4166384341514177436759494b6f5a497a6a30454177494452774177524149675873566b6930772b6936565947573355462f32327561586530594a446a3155650a6e412b546a44316169356343494359623153416d4435786b66545670766f34556f79695359787244574c6d5552344349394e4b7966504e2b0a2d2d2d2d2d454e442043455254494649434154452d2d2d2d2d0a2d2d2d2d2d424547494e2043455254494649434154452d2d2d2d2d0a4d4949436a7a4343416a53674177494241674955496d554d316c71644e496e7a6737535655723951477a6b6e42717777436759494b6f5a497a6a3045417749770a614445614d4267474131554541777752535735305a5777675530645949464a766233516751304578476a415942674e5642416f4d45556c756447567349454e760a636e4276636d4630615739754d5251774567594456515148444174545957353059534244624746795954454c4d416b47413155454341774351304578437a414a0a42674e5642415954416c56544d423458445445344d4455794d5445774e4455784d466f58445451354d54497a4d54497a4e546b314f566f77614445614d4267470a4131554541777752535735305a5777675530645949464a766233516751304578476a415942674e5642416f4d45556c756447567349454e76636e4276636d46300a615739754d5251774567594456515148444174545957353059534244624746795954454c4d416b47413155454341774351304578437a414a42674e56424159540a416c56544d466b77457759484b6f5a497a6a3043415159494b6f5a497a6a3044415163445167414543366e45774d4449595a4f6a2f69505773437a61454b69370a314f694f534c52466857476a626e42564a66566e6b59347533496a6b4459594c304d784f346d717379596a6c42616c54565978465032734a424b357a6c4b4f420a757a43427544416642674e5648534d4547444157674251695a517a575770303069664f44744a5653763141624f5363477244425342674e5648523845537a424a0a4d45656752614244686b466f64485277637a6f764c324e6c636e52705a6d6c6a5958526c63793530636e567a6447566b63325679646d6c6a5a584d75615735300a5a577775593239744c306c756447567355306459556d397664454e424c6d526c636a416442674e564851344546675155496d554d316c71644e496e7a673753560a55723951477a6b6e4271777744675944565230504151482f42415144416745474d42494741315564457745422f7751494d4159424166384341514577436759490a4b6f5a497a6a3045417749445351417752674968414f572f35516b522b533943695344634e6f6f774c7550524c735747662f59693747535839344267775477670a41694541344a306c72486f4d732b586f356f2f7358364f39515778485241765a55474f6452513763767152586171493d0a2d2d2d2d2d454e442043455254494649434154452d2d2d2d2d0a00").unwrap();
-
-    let v4_quote = hex::decode("040002008100000000000000939a7233f79c4ca9940a0db3957f060728fa333a41ec7e302625d24f400f3f2400000000040102000000000000000000000000009790d89a10210ec6968a773cee2ca05b5aa97309f36727a968527be4606fc19e6f73acce350946c9d46a9bf7a63f843000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000001000000080e702060000000000f2dd2696f69b950645832bdc095ffd11247eeff687eeacdb57a58d2ddb9a9f94fea40c961e19460c00ffa31420ecbc180000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000998204508d58dcbfebe5e11c48669f7a921ac2da744dfb7d014ecdff2acdff1c9f665fdad52aadacf296a1df9909eb2383d100224f1716aeb431f7cb3cf028197dbd872487f27b0f6329ab17647dc9953c7014109818634f879e6550bc60f93eecfc42ff4d49278bfdbb0c77e570f4490cff10a2ee1ac11fbd2c2b49fa6cfa3cf1a1cb755c72522dd8a689e9d47906a000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000278e753482976c8a7351fe2113609c7350d491cdae3d449eefc202fa41b2ad6840239cc2ba084c2d594b4e6dabeae0fcbf71c96daf0d0c9ecf0e9810c0457900cc10000079d2386455606243552b2b6f5d04ce8b99657b8b9bf25e348b925805f5c4ae2cc1e5ccff090592bb5e55ce99be1693ba9b67cc879d6fd1b0edc9524d161b2ca97aac15abe68a4571ae7d0d0de5765ecb2b76c89890e38a66fd861e76f2608e40fdf39a81de66f69bdc7ab862b59fac83dbdc28162b3b002c55b963578aa6e33b0600461000000202181a03ff0005000000000000000000000000000000000000000000000000000000000000000000000000000000001500000000000000e70000000000000086fc4e0ec2c5ddcebac97062c0a0142a97c18a7a755147bcbc3fe17d6529781d0000000000000000000000000000000000000000000000000000000000000000dc9e2a7c6f948f17474e34a7fc43ed030f7c1563f1babddf6340c82e0e54a8c50000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000200050000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000099cb765d61aa180a03b3b47f229cdaf6f637878298f7cabb4ac0d3b8cadb2a4d000000000000000000000000000000000000000000000000000000000000000029412e75597cda6a12e47037f72b5aa49e3380698d7d838099da88f3854de1c5c147bd31ca67804aa1f04773c9450a27da34de6ca7f4c7f55dc393532ee92fa12000000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f05005e0e00002d2d2d2d2d424547494e2043455254494649434154452d2d2d2d2d0a4d49494538444343424a616741774942416749554554536676464e524551574669324759572b506b34684b2f35596b77436759494b6f5a497a6a3045417749770a634445694d434147413155454177775a535735305a577767553064594946424453794251624746305a6d397962534244515445614d42674741315545436777520a535735305a577767513239796347397959585270623234784644415342674e564241634d43314e68626e526849454e7359584a684d51737743515944565151490a44414a445154454c4d416b474131554542684d4356564d774868634e4d6a51774e4449354d6a45314d6a49315768634e4d7a45774e4449354d6a45314d6a49310a576a42774d534977494159445651514444426c4a626e526c624342545231676755454e4c49454e6c636e52705a6d6c6a5958526c4d526f77474159445651514b0a4442464a626e526c6243424462334a7762334a6864476c76626a45554d424947413155454277774c553246756447456751327868636d4578437a414a42674e560a4241674d416b4e424d517377435159445651514745774a56557a425a4d424d4742797147534d34394167454743437147534d343941774548413049414244456f0a55684e526c5830545a54707071637338316838374d35684c2f6f7356654d53512b594e506636497961476e6c4e5a704a384b713657414d2f304565643554474c0a623053564a566e7372716e5362674f343071716a67674d4d4d4949444344416642674e5648534d4547444157674253566231334e765276683655424a796454300a4d383442567776655644427242674e56485238455a4442694d47436758714263686c706f64485277637a6f764c32467761533530636e567a6447566b633256790a646d6c6a5a584d75615735305a577775593239744c334e6e6543396a5a584a3061575a7059324630615739754c3359304c33426a61324e796244396a595431770a624746305a6d397962535a6c626d4e765a476c755a7a316b5a584977485159445652304f424259454645346b4a624b444e64644f717933447779394d55744e640a6a5878694d41344741315564447745422f775145417749477744414d42674e5648524d4241663845416a41414d4949434f51594a4b6f5a496876684e415130420a424949434b6a4343416959774867594b4b6f5a496876684e41513042415151516978544b4d66734f6c6b6d4742492b7a747a6c4a647a434341574d47436971470a534962345451454e41514977676746544d42414743797147534962345451454e41514942416745434d42414743797147534962345451454e41514943416745430a4d42414743797147534962345451454e41514944416745434d42414743797147534962345451454e41514945416745434d42414743797147534962345451454e0a41514946416745444d42414743797147534962345451454e41514947416745424d42414743797147534962345451454e41514948416745414d424147437971470a534962345451454e41514949416745444d42414743797147534962345451454e4151494a416745414d42414743797147534962345451454e4151494b416745410a4d42414743797147534962345451454e4151494c416745414d42414743797147534962345451454e4151494d416745414d42414743797147534962345451454e0a4151494e416745414d42414743797147534962345451454e4151494f416745414d42414743797147534962345451454e41514950416745414d424147437971470a534962345451454e41514951416745414d42414743797147534962345451454e415149524167454e4d42384743797147534962345451454e41514953424241430a41674943417745414177414141414141414141414d42414743697147534962345451454e41514d45416741414d42514743697147534962345451454e415151450a42704441627741414144415042676f71686b69472b45304244514546436745424d42344743697147534962345451454e41515945454a6a4973754b2f6349456f0a42317249566e3247765677775241594b4b6f5a496876684e41513042427a41324d42414743797147534962345451454e415163424151482f4d424147437971470a534962345451454e415163434151482f4d42414743797147534962345451454e415163444151482f4d416f4743437147534d343942414d43413067414d4555430a4946474853786344784143755051754d6c7a653277512f78463949624b354a37376368784a614f6c41537a5441694541725a6d4c62344f4643526c376a4478570a482b4c4554662b71386d62523433597645496d736b42476a4a576f3d0a2d2d2d2d2d454e442043455254494649434154452d2d2d2d2d0a2d2d2d2d2d424547494e2043455254494649434154452d2d2d2d2d0a4d4949436c6a4343416a32674177494241674956414a567658633239472b487051456e4a3150517a7a674658433935554d416f4743437147534d343942414d430a4d476778476a415942674e5642414d4d45556c756447567349464e48574342536232393049454e424d526f77474159445651514b4442464a626e526c624342440a62334a7762334a6864476c76626a45554d424947413155454277774c553246756447456751327868636d4578437a414a42674e564241674d416b4e424d5173770a435159445651514745774a56557a4165467730784f4441314d6a45784d4455774d5442614677307a4d7a41314d6a45784d4455774d5442614d484178496a41670a42674e5642414d4d47556c756447567349464e4857434251513073675547786864475a76636d306751304578476a415942674e5642416f4d45556c75644756730a49454e76636e4276636d4630615739754d5251774567594456515148444174545957353059534244624746795954454c4d416b474131554543417743513045780a437a414a42674e5642415954416c56544d466b77457759484b6f5a497a6a3043415159494b6f5a497a6a304441516344516741454e53422f377432316c58534f0a3243757a7078773734654a423732457944476757357258437478327456544c7136684b6b367a2b5569525a436e71523770734f766771466553786c6d546c4a6c0a65546d693257597a33714f42757a43427544416642674e5648534d4547444157674251695a517a575770303069664f44744a5653763141624f536347724442530a42674e5648523845537a424a4d45656752614244686b466f64485277637a6f764c324e6c636e52705a6d6c6a5958526c63793530636e567a6447566b633256790a646d6c6a5a584d75615735305a577775593239744c306c756447567355306459556d397664454e424c6d526c636a416442674e5648513445466751556c5739640a7a62306234656c4153636e553944504f4156634c336c517744675944565230504151482f42415144416745474d42494741315564457745422f7751494d4159420a4166384341514177436759494b6f5a497a6a30454177494452774177524149675873566b6930772b6936565947573355462f32327561586530594a446a3155650a6e412b546a44316169356343494359623153416d4435786b66545670766f34556f79695359787244574c6d5552344349394e4b7966504e2b0a2d2d2d2d2d454e442043455254494649434154452d2d2d2d2d0a2d2d2d2d2d424547494e2043455254494649434154452d2d2d2d2d0a4d4949436a7a4343416a53674177494241674955496d554d316c71644e496e7a6737535655723951477a6b6e42717777436759494b6f5a497a6a3045417749770a614445614d4267474131554541777752535735305a5777675530645949464a766233516751304578476a415942674e5642416f4d45556c756447567349454e760a636e4276636d4630615739754d5251774567594456515148444174545957353059534244624746795954454c4d416b47413155454341774351304578437a414a0a42674e5642415954416c56544d423458445445344d4455794d5445774e4455784d466f58445451354d54497a4d54497a4e546b314f566f77614445614d4267470a4131554541777752535735305a5777675530645949464a766233516751304578476a415942674e5642416f4d45556c756447567349454e76636e4276636d46300a615739754d5251774567594456515148444174545957353059534244624746795954454c4d416b47413155454341774351304578437a414a42674e56424159540a416c56544d466b77457759484b6f5a497a6a3043415159494b6f5a497a6a3044415163445167414543366e45774d4449595a4f6a2f69505773437a61454b69370a314f694f534c52466857476a626e42564a66566e6b59347533496a6b4459594c304d784f346d717379596a6c42616c54565978465032734a424b357a6c4b4f420a757a43427544416642674e5648534d4547444157674251695a517a575770303069664f44744a5653763141624f5363477244425342674e5648523845537a424a0a4d45656752614244686b466f64485277637a6f764c324e6c636e52705a6d6c6a5958526c63793530636e567a6447566b63325679646d6c6a5a584d75615735300a5a577775593239744c306c756447567355306459556d397664454e424c6d526c636a416442674e564851344546675155496d554d316c71644e496e7a673753560a55723951477a6b6e4271777744675944565230504151482f42415144416745474d42494741315564457745422f7751494d4159424166384341514577436759490a4b6f5a497a6a3045417749445351417752674968414f572f35516b522b533943695344634e6f6f774c7550524c735747662f59693747535839344267775477670a41694541344a306c72486f4d732b586f356f2f7358364f39515778485241765a55474f6452513763767152586171493d0a2d2d2d2d2d454e442043455254494649434154452d2d2d2d2d0a0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let v4_quote = hex::decode(include_str!("../data/quote.hex").trim()).unwrap();
 
     let mut intel_collaterals = IntelCollateral::new();
     intel_collaterals.set_tcbinfo_bytes(include_bytes!("../data/tcbinfo-tdx-v3.json"));
@@ -26,109 +57,81 @@ fn main() {
         "../data/Intel_SGX_Provisioning_Certification_RootCA.cer"
     ));
     intel_collaterals.set_sgx_tcb_signing_der(include_bytes!("../data/signing_cert.der"));
-    intel_collaterals
-        .set_sgx_intel_root_ca_crl_der(include_bytes!("../data/intel_root_crl.der"));
+    intel_collaterals.set_sgx_intel_root_ca_crl_der(include_bytes!("../data/intel_root_crl.der"));
     intel_collaterals.set_sgx_platform_crl_der(include_bytes!("../data/pck_platform_crl.der"));
 
-    let intel_collaterals_bytes = intel_collaterals.to_bytes();
+    // The sample collaterals are valid at this time.
+    let current_time = 1749095100u64;
 
-    // // get current time in seconds since epoch
-    // let current_time = std::time::SystemTime::now()
-    //     .duration_since(std::time::UNIX_EPOCH)
-    //     .unwrap()
-    //     .as_secs();
+    if args.execute_only {
+        let report = execute(&v4_quote, &intel_collaterals, current_time, args.bind_time).unwrap();
+        let report = serde_json::to_string_pretty(&report).unwrap();
+        println!("{}", report);
+        if let Some(out_dir) = &args.out_dir {
+            std::fs::create_dir_all(out_dir).unwrap();
+            std::fs::write(out_dir.join(CYCLE_REPORT_FILE), report).unwrap();
+        }
+        return;
+    }
 
-    let current_time = 1749095100u64;
-    let current_time_bytes = current_time.to_le_bytes();
-
-    // ZL: perform a simple serialization of the inputs
-    // [current_time: u64][quote_len: u32][intel_collaterals_len: u32][quote: var][intel_collaterals: var]
-    let quote_len = v4_quote.len() as u32;
-    let intel_collaterals_bytes_len = intel_collaterals_bytes.len() as u32;
-    let total_len = 8 + 4 + 4 + quote_len + intel_collaterals_bytes_len;
-
-    let mut input = Vec::with_capacity(total_len as usize);
-    input.extend_from_slice(&current_time_bytes);
-    input.extend_from_slice(&quote_len.to_le_bytes());
-    input.extend_from_slice(&intel_collaterals_bytes_len.to_le_bytes());
-    input.extend_from_slice(&v4_quote);
-    input.extend_from_slice(&intel_collaterals_bytes);
-
-    // println!("serialized collaterals: {}", hex::encode(&intel_collaterals_bytes));
-
-    let env = ExecutorEnv::builder()
-        .write_slice(input.as_slice())
-        .build()
-        .unwrap();
-
-    // Obtain the default prover.
-    let prover = default_prover();
-
-    let current_time = std::time::Instant::now();
-    // Produce a receipt by proving the specified ELF binary.
-    // let receipt = prover.prove(env, DCAP_GUEST_ELF).unwrap().receipt;
-    let prover_opts = if std::env::var("BONSAI_API_KEY").is_ok() {
-        ProverOpts::groth16()
-    } else {
-        ProverOpts::default()
+    println!("ImageID: {}", hex::encode(host::image_id()));
+
+    let backend = match args.prover {
+        ProverArg::Default => Backend::Default,
+        ProverArg::Bonsai => Backend::Bonsai,
+        #[cfg(feature = "prove")]
+        ProverArg::Local => Backend::Local,
+        #[cfg(not(feature = "prove"))]
+        ProverArg::Local => {
+            eprintln!("the local prover requires the `prove` feature");
+            std::process::exit(1);
+        }
     };
-    let receipt = prover
-        .prove_with_opts(env, DCAP_GUEST_ELF, &prover_opts)
-        .unwrap()
-        .receipt;
-    let elapsed_time = current_time.elapsed();
-    println!("Proving time: {:?}", elapsed_time);
-
-    if let InnerReceipt::Groth16(ref groth16_receipt) = receipt.inner {
-        println!(
-            "Groth16 Seal bytes: {}",
-            hex::encode(groth16_receipt.seal.as_slice())
-        );
-        println!(
-            "Output bytes: {}",
-            hex::encode(receipt.journal.bytes.clone().as_slice())
-        )
+    let proof_system = match args.proof_system {
+        Some(ProofSystemArg::Composite) => ProofSystem::Composite,
+        Some(ProofSystemArg::Succinct) => ProofSystem::Succinct,
+        Some(ProofSystemArg::Groth16) => ProofSystem::Groth16,
+        None if std::env::var("BONSAI_API_KEY").is_ok() => ProofSystem::Groth16,
+        None => ProofSystem::Composite,
+    };
+    let options = ProveOptions {
+        backend,
+        proof_system,
+        bind_time: args.bind_time,
+    };
+
+    let start = std::time::Instant::now();
+    let proof = prove(&v4_quote, &intel_collaterals, current_time, &options).unwrap();
+    println!("Proving time: {:?}", start.elapsed());
+
+    if let ProofSystem::Groth16 = options.proof_system {
+        println!("Groth16 Seal bytes: {}", hex::encode(&proof.seal));
+    }
+    println!("Output bytes: {}", hex::encode(&proof.journal));
+
+    if let Some(out_dir) = &args.out_dir {
+        proof.write_artifacts(out_dir).unwrap();
+        println!("Proof artifacts written to {}", out_dir.display());
     }
 
-    // TODO: Implement code for retrieving receipt journal here.
-
-    // Optional: Verify receipt to confirm that recipients will also be able to
-    // verify your receipt
-    receipt.verify(DCAP_GUEST_ID).unwrap();
-    // For example:
-    // let _output: Vec<u8> = receipt.journal.decode().unwrap();
-    let output = receipt.journal.bytes;
-
-    // manually parse the output
-    let mut offset: usize = 0;
-    let output_len = u16::from_be_bytes(output[offset..offset + 2].try_into().unwrap());
-    offset += 2;
-    let verified_output = VerifiedOutput::from_bytes(&output[offset..offset + output_len as usize]);
-    offset += output_len as usize;
-    let current_time = u64::from_be_bytes(output[offset..offset + 8].try_into().unwrap());
-    offset += 8;
-    let tcbinfo_root_hash = &output[offset..offset + 32];
-    offset += 32;
-    let enclaveidentity_root_hash = &output[offset..offset + 32];
-    offset += 32;
-    let root_cert_hash = &output[offset..offset + 32];
-    offset += 32;
-    let signing_cert_hash = &output[offset..offset + 32];
-    offset += 32;
-    let root_crl_hash = &output[offset..offset + 32];
-    offset += 32;
-    let pck_crl_hash = &output[offset..offset + 32];
-
-    // println!("Hello, world! I generated a proof of guest execution! \n{:?}.", verified_output);
+    let output = match DcapProgramJournal::decode(&proof.journal).unwrap() {
+        DcapProgramJournal::Success(output) => output,
+        DcapProgramJournal::Failure(code) => {
+            println!("Verification failed: {}", code);
+            return;
+        }
+    };
+    let verified_output = VerifiedOutput::from_bytes(&output.verified_output);
+
     println!("Verified Output: {:?}", verified_output);
-    println!("Current time: {}", current_time);
-    println!("TCB Info Root Hash: {:?}", tcbinfo_root_hash);
+    println!("Current time: {}", output.current_time);
+    println!("TCB Info Root Hash: {:?}", output.tcbinfo_content_hash);
     println!(
         "Enclave Identity Root Hash: {:?}",
-        enclaveidentity_root_hash
+        output.qeidentity_content_hash
     );
-    println!("Root Cert Hash: {:?}", root_cert_hash);
-    println!("Signing Cert Hash: {:?}", signing_cert_hash);
-    println!("RootCRL Hash: {:?}", root_crl_hash);
-    println!("PCK CRL Hash: {:?}", pck_crl_hash);
+    println!("Root Cert Hash: {:?}", output.sgx_intel_root_ca_cert_hash);
+    println!("Signing Cert Hash: {:?}", output.sgx_tcb_signing_cert_hash);
+    println!("RootCRL Hash: {:?}", output.sgx_intel_root_ca_crl_hash);
+    println!("PCK CRL Hash: {:?}", output.sgx_pck_crl_hash);
 }
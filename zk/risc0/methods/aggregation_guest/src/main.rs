@@ -0,0 +1,24 @@
+#![no_main]
+use std::io::Read;
+
+use dcap_codec::aggregation::DcapAggregation;
+use risc0_zkvm::guest::env::{self};
+use risc0_zkvm::Digest;
+
+risc0_zkvm::guest::entry!(main);
+
+fn main() {
+    // read the image ID and the journals of the receipts added as assumptions by the host
+    let mut input = Vec::new();
+    env::stdin().read_to_end(&mut input).unwrap();
+    let aggregation = DcapAggregation::decode(&input).unwrap();
+
+    // each journal must have been proven by the image ID, the assumptions are resolved when
+    // the receipt of this guest is made succinct
+    let image_id = Digest::from(aggregation.program_id);
+    for journal in &aggregation.journals {
+        env::verify(image_id, journal).unwrap();
+    }
+
+    env::commit_slice(&aggregation.encode().unwrap());
+}
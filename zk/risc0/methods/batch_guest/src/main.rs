@@ -0,0 +1,20 @@
+#![no_main]
+use std::io::Read;
+
+use dcap_program::CycleReporter;
+use risc0_zkvm::guest::env::{self};
+
+risc0_zkvm::guest::entry!(main);
+
+fn main() {
+    // read the quotes and their shared collaterals passed from host
+    let mut input = Vec::new();
+    env::stdin().read_to_end(&mut input).unwrap();
+
+    // the cycles of each verification phase are printed to the host, see `dcap_codec::profile`
+    // the collateral hashes are committed once, followed by the outputs in the order of the quotes
+    env::commit_slice(&dcap_program::run_batch_tracked(
+        &input,
+        &mut CycleReporter::new(env::cycle_count),
+    ));
+}
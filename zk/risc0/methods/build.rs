@@ -5,14 +5,22 @@ use std::path::PathBuf;
 fn main() {
     let manifest_dir = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap());
     let mut builder = GuestOptionsBuilder::default();
+    println!("cargo:rerun-if-env-changed=RISC0_USE_DOCKER");
     if std::env::var("RISC0_USE_DOCKER").is_ok() {
+        // Read by `REPRODUCIBLE_BUILD`.
+        println!("cargo:rustc-env=DCAP_GUESTS_DOCKER_BUILD=1");
         let docker_options = DockerOptionsBuilder::default()
-            .root_dir(manifest_dir.join("../"))
+            // The guests depend on zk/dcap-program, outside of the risc0 workspace.
+            .root_dir(manifest_dir.join("../../"))
             .build()
             .unwrap();
 
         builder.use_docker(docker_options);
     }
     let guest_options = builder.build().unwrap();
-    embed_methods_with_options(HashMap::from([("dcap_guest", guest_options)]));
+    embed_methods_with_options(HashMap::from([
+        ("dcap_guest", guest_options.clone()),
+        ("dcap_batch_guest", guest_options.clone()),
+        ("dcap_aggregation_guest", guest_options),
+    ]));
 }
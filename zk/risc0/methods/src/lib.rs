@@ -1 +1,5 @@
 include!(concat!(env!("OUT_DIR"), "/methods.rs"));
+
+/// Whether the guests were built in the RISC Zero Docker image, with `RISC0_USE_DOCKER`. The
+/// image IDs of guests built with the local toolchain are not reproducible.
+pub const REPRODUCIBLE_BUILD: bool = option_env!("DCAP_GUESTS_DOCKER_BUILD").is_some();
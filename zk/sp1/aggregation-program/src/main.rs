@@ -0,0 +1,21 @@
+#![no_main]
+sp1_zkvm::entrypoint!(main);
+
+use dcap_codec::aggregation::{program_id_to_words, DcapAggregation};
+use sha2::{Digest, Sha256};
+
+pub fn main() {
+    // Read the verification key hash and the public values of the proofs written by the script
+    let input = sp1_zkvm::io::read_vec();
+    let aggregation = DcapAggregation::decode(&input).unwrap();
+
+    // each proof must have been produced by the program of the verification key, the proofs
+    // themselves are read from the stdin by the zkVM
+    let vkey = program_id_to_words(&aggregation.program_id);
+    for public_values in &aggregation.journals {
+        let public_values_digest = Sha256::digest(public_values);
+        sp1_zkvm::lib::verify::verify_sp1_proof(&vkey, &public_values_digest.into());
+    }
+
+    sp1_zkvm::io::commit_slice(&aggregation.encode().unwrap());
+}
@@ -0,0 +1,16 @@
+#![no_main]
+use dcap_program::Sp1CycleTracker;
+
+sp1_zkvm::entrypoint!(main);
+
+pub fn main() {
+    // Read the quotes and their shared collaterals
+    let input = sp1_zkvm::io::read_vec();
+
+    // the cycles of each verification phase are added up by the SP1 cycle tracker
+    // the collateral hashes are committed once, followed by the outputs in the order of the quotes
+    sp1_zkvm::io::commit_slice(&dcap_program::run_batch_tracked(
+        &input,
+        &mut Sp1CycleTracker,
+    ));
+}
@@ -2,14 +2,23 @@ use sp1_build::{build_program_with_args, BuildArgs};
 use sp1_sdk::SP1_CIRCUIT_VERSION;
 
 fn main() {
+    build_program("../program", "dcap-sp1-guest-program-elf");
+    build_program("../batch-program", "dcap-sp1-batch-program-elf");
+    build_program("../aggregation-program", "dcap-sp1-aggregation-program-elf");
+}
+
+fn build_program(path: &str, elf_name: &str) {
     build_program_with_args(
-        "../program",
+        path,
         BuildArgs {
             output_directory: Some("../elf".to_string()),
-            elf_name: Some("dcap-sp1-guest-program-elf".to_string()),
+            elf_name: Some(elf_name.to_string()),
             docker: true,
             tag: SP1_CIRCUIT_VERSION.to_string(),
+            // The programs depend on zk/dcap-program, outside of the sp1 workspace, so the
+            // whole zk directory is mounted in the build container.
+            workspace_directory: Some(concat!(env!("CARGO_MANIFEST_DIR"), "/../..").to_string()),
             ..Default::default()
         },
     )
-}
\ No newline at end of file
+}
@@ -0,0 +1,66 @@
+//! Compares the verification keys of the embedded programs with the pinned ones.
+//!
+//! The programs are always built in the SP1 Docker image, see `build.rs`.
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use clap::Parser;
+
+#[derive(Parser)]
+struct Args {
+    /// The pinned verification keys, by package name
+    #[arg(long, default_value = concat!(env!("CARGO_MANIFEST_DIR"), "/../vkeys.json"))]
+    manifest: PathBuf,
+    /// Writes the computed verification keys to the manifest instead of comparing them
+    #[arg(long)]
+    update: bool,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let computed = dcap_script::vkeys();
+
+    if args.update {
+        std::fs::write(
+            &args.manifest,
+            serde_json::to_string_pretty(&computed)? + "\n",
+        )?;
+        println!("Verification keys written to {}", args.manifest.display());
+        return Ok(());
+    }
+
+    let pinned: BTreeMap<String, String> = serde_json::from_slice(&std::fs::read(&args.manifest)?)?;
+    let mut mismatches = 0;
+    for (name, vkey) in &computed {
+        match pinned.get(name) {
+            Some(pinned) if normalize(pinned) == normalize(vkey) => {
+                println!("{}: {} matches", name, vkey);
+            }
+            Some(pinned) => {
+                println!("{}: {} does not match {}", name, vkey, pinned);
+                mismatches += 1;
+            }
+            None => {
+                println!("{}: {} is not pinned", name, vkey);
+                mismatches += 1;
+            }
+        }
+    }
+    for name in pinned.keys().filter(|name| !computed.contains_key(*name)) {
+        println!("{}: pinned but not built", name);
+        mismatches += 1;
+    }
+    if mismatches > 0 {
+        bail!(
+            "{} verification key(s) differ from {}",
+            mismatches,
+            args.manifest.display()
+        );
+    }
+    Ok(())
+}
+
+fn normalize(vkey: &str) -> String {
+    vkey.trim_start_matches("0x").to_lowercase()
+}
@@ -1,12 +1,82 @@
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use dcap_codec::DcapProgramJournal;
 use dcap_rs::types::{collaterals::IntelCollateral, VerifiedOutput};
-use sp1_sdk::{utils, HashableKey, ProverClient, SP1Stdin, include_elf};
+use dcap_script::{execute, prove, Backend, ProofSystem, ProveOptions, CYCLE_REPORT_FILE};
+use sp1_sdk::utils;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ProverArg {
+    /// Selected by `SP1_PROVER`
+    Env,
+    Cpu,
+    /// Requires the `cuda` feature
+    Cuda,
+    /// Requires the `network` feature
+    Network,
+    Mock,
+}
 
-pub const DCAP_ELF: &[u8] = include_elf!("dcap-sp1-guest-program");
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ProofSystemArg {
+    Core,
+    Compressed,
+    Plonk,
+    Groth16,
+}
+
+/// Executes and proves the verification of the sample quote and collaterals in `data`.
+#[derive(Parser)]
+struct Args {
+    #[arg(long, value_enum, default_value = "env")]
+    prover: ProverArg,
+    #[arg(long, value_enum, default_value = "plonk")]
+    proof_system: ProofSystemArg,
+    /// Requires the verification time to be within the validity of the collaterals
+    #[arg(long)]
+    bind_time: bool,
+    /// Executes the program without proving and prints the cycle report as JSON
+    #[arg(long)]
+    execute_only: bool,
+    /// Directory where the proof artifacts or the cycle report are written, see the README
+    #[arg(long, env = "DCAP_PROOF_OUT_DIR")]
+    out_dir: Option<PathBuf>,
+}
 
 fn main() {
     utils::setup_logger();
-
-    let v4_quote = hex::decode("040002008100000000000000939a7233f79c4ca9940a0db3957f060728fa333a41ec7e302625d24f400f3f2400000000040102000000000000000000000000009790d89a10210ec6968a773cee2ca05b5aa97309f36727a968527be4606fc19e6f73acce350946c9d46a9bf7a63f843000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000001000000080e702060000000000f2dd2696f69b950645832bdc095ffd11247eeff687eeacdb57a58d2ddb9a9f94fea40c961e19460c00ffa31420ecbc180000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000998204508d58dcbfebe5e11c48669f7a921ac2da744dfb7d014ecdff2acdff1c9f665fdad52aadacf296a1df9909eb2383d100224f1716aeb431f7cb3cf028197dbd872487f27b0f6329ab17647dc9953c7014109818634f879e6550bc60f93eecfc42ff4d49278bfdbb0c77e570f4490cff10a2ee1ac11fbd2c2b49fa6cfa3cf1a1cb755c72522dd8a689e9d47906a000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000278e753482976c8a7351fe2113609c7350d491cdae3d449eefc202fa41b2ad6840239cc2ba084c2d594b4e6dabeae0fcbf71c96daf0d0c9ecf0e9810c0457900cc10000079d2386455606243552b2b6f5d04ce8b99657b8b9bf25e348b925805f5c4ae2cc1e5ccff090592bb5e55ce99be1693ba9b67cc879d6fd1b0edc9524d161b2ca97aac15abe68a4571ae7d0d0de5765ecb2b76c89890e38a66fd861e76f2608e40fdf39a81de66f69bdc7ab862b59fac83dbdc28162b3b002c55b963578aa6e33b0600461000000202181a03ff0005000000000000000000000000000000000000000000000000000000000000000000000000000000001500000000000000e70000000000000086fc4e0ec2c5ddcebac97062c0a0142a97c18a7a755147bcbc3fe17d6529781d0000000000000000000000000000000000000000000000000000000000000000dc9e2a7c6f948f17474e34a7fc43ed030f7c1563f1babddf6340c82e0e54a8c50000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000200050000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000099cb765d61aa180a03b3b47f229cdaf6f637878298f7cabb4ac0d3b8cadb2a4d000000000000000000000000000000000000000000000000000000000000000029412e75597cda6a12e47037f72b5aa49e3380698d7d838099da88f3854de1c5c147bd31ca67804aa1f04773c9450a27da34de6ca7f4c7f55dc393532ee92fa12000000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f05005e0e00002d2d2d2d2d424547494e2043455254494649434154452d2d2d2d2d0a4d49494538444343424a616741774942416749554554536676464e524551574669324759572b506b34684b2f35596b77436759494b6f5a497a6a3045417749770a634445694d434147413155454177775a535735305a577767553064594946424453794251624746305a6d397962534244515445614d42674741315545436777520a535735305a577767513239796347397959585270623234784644415342674e564241634d43314e68626e526849454e7359584a684d51737743515944565151490a44414a445154454c4d416b474131554542684d4356564d774868634e4d6a51774e4449354d6a45314d6a49315768634e4d7a45774e4449354d6a45314d6a49310a576a42774d534977494159445651514444426c4a626e526c624342545231676755454e4c49454e6c636e52705a6d6c6a5958526c4d526f77474159445651514b0a4442464a626e526c6243424462334a7762334a6864476c76626a45554d424947413155454277774c553246756447456751327868636d4578437a414a42674e560a4241674d416b4e424d517377435159445651514745774a56557a425a4d424d4742797147534d34394167454743437147534d343941774548413049414244456f0a55684e526c5830545a54707071637338316838374d35684c2f6f7356654d53512b594e506636497961476e6c4e5a704a384b713657414d2f304565643554474c0a623053564a566e7372716e5362674f343071716a67674d4d4d4949444344416642674e5648534d4547444157674253566231334e765276683655424a796454300a4d383442567776655644427242674e56485238455a4442694d47436758714263686c706f64485277637a6f764c32467761533530636e567a6447566b633256790a646d6c6a5a584d75615735305a577775593239744c334e6e6543396a5a584a3061575a7059324630615739754c3359304c33426a61324e796244396a595431770a624746305a6d397962535a6c626d4e765a476c755a7a316b5a584977485159445652304f424259454645346b4a624b444e64644f717933447779394d55744e640a6a5878694d41344741315564447745422f775145417749477744414d42674e5648524d4241663845416a41414d4949434f51594a4b6f5a496876684e415130420a424949434b6a4343416959774867594b4b6f5a496876684e41513042415151516978544b4d66734f6c6b6d4742492b7a747a6c4a647a434341574d47436971470a534962345451454e41514977676746544d42414743797147534962345451454e41514942416745434d42414743797147534962345451454e41514943416745430a4d42414743797147534962345451454e41514944416745434d42414743797147534962345451454e41514945416745434d42414743797147534962345451454e0a41514946416745444d42414743797147534962345451454e41514947416745424d42414743797147534962345451454e41514948416745414d424147437971470a534962345451454e41514949416745444d42414743797147534962345451454e4151494a416745414d42414743797147534962345451454e4151494b416745410a4d42414743797147534962345451454e4151494c416745414d42414743797147534962345451454e4151494d416745414d42414743797147534962345451454e0a4151494e416745414d42414743797147534962345451454e4151494f416745414d42414743797147534962345451454e41514950416745414d424147437971470a534962345451454e41514951416745414d42414743797147534962345451454e415149524167454e4d42384743797147534962345451454e41514953424241430a41674943417745414177414141414141414141414d42414743697147534962345451454e41514d45416741414d42514743697147534962345451454e415151450a42704441627741414144415042676f71686b69472b45304244514546436745424d42344743697147534962345451454e41515945454a6a4973754b2f6349456f0a42317249566e3247765677775241594b4b6f5a496876684e41513042427a41324d42414743797147534962345451454e415163424151482f4d424147437971470a534962345451454e415163434151482f4d42414743797147534962345451454e415163444151482f4d416f4743437147534d343942414d43413067414d4555430a4946474853786344784143755051754d6c7a653277512f78463949624b354a37376368784a614f6c41537a5441694541725a6d4c62344f4643526c376a4478570a482b4c4554662b71386d62523433597645496d736b42476a4a576f3d0a2d2d2d2d2d454e442043455254494649434154452d2d2d2d2d0a2d2d2d2d2d424547494e2043455254494649434154452d2d2d2d2d0a4d4949436c6a4343416a32674177494241674956414a567658633239472b487051456e4a3150517a7a674658433935554d416f4743437147534d343942414d430a4d476778476a415942674e5642414d4d45556c756447567349464e48574342536232393049454e424d526f77474159445651514b4442464a626e526c624342440a62334a7762334a6864476c76626a45554d424947413155454277774c553246756447456751327868636d4578437a414a42674e564241674d416b4e424d5173770a435159445651514745774a56557a4165467730784f4441314d6a45784d4455774d5442614677307a4d7a41314d6a45784d4455774d5442614d484178496a41670a42674e5642414d4d47556c756447567349464e4857434251513073675547786864475a76636d306751304578476a415942674e5642416f4d45556c75644756730a49454e76636e4276636d4630615739754d5251774567594456515148444174545957353059534244624746795954454c4d416b474131554543417743513045780a437a414a42674e5642415954416c56544d466b77457759484b6f5a497a6a3043415159494b6f5a497a6a304441516344516741454e53422f377432316c58534f0a3243757a7078773734654a423732457944476757357258437478327456544c7136684b6b367a2b5569525a436e71523770734f766771466553786c6d546c4a6c0a65546d693257597a33714f42757a43427544416642674e5648534d4547444157674251695a517a575770303069664f44744a5653763141624f536347724442530a42674e5648523845537a424a4d45656752614244686b466f64485277637a6f764c324e6c636e52705a6d6c6a5958526c63793530636e567a6447566b633256790a646d6c6a5a584d75615735305a577775593239744c306c756447567355306459556d397664454e424c6d526c636a416442674e5648513445466751556c5739640a7a62306234656c4153636e553944504f4156634c336c517744675944565230504151482f42415144416745474d42494741315564457745422f7751494d4159420a4166384341514177436759494b6f5a497a6a30454177494452774177524149675873566b6930772b6936565947573355462f32327561586530594a446a3155650a6e412b546a44316169356343494359623153416d4435786b66545670766f34556f79695359787244574c6d5552344349394e4b7966504e2b0a2d2d2d2d2d454e442043455254494649434154452d2d2d2d2d0a2d2d2d2d2d424547494e2043455254494649434154452d2d2d2d2d0a4d4949436a7a4343416a53674177494241674955496d554d316c71644e496e7a6737535655723951477a6b6e42717777436759494b6f5a497a6a3045417749770a614445614d4267474131554541777752535735305a5777675530645949464a766233516751304578476a415942674e5642416f4d45556c756447567349454e760a636e4276636d4630615739754d5251774567594456515148444174545957353059534244624746795954454c4d416b47413155454341774351304578437a414a0a42674e5642415954416c56544d423458445445344d4455794d5445774e4455784d466f58445451354d54497a4d54497a4e546b314f566f77614445614d4267470a4131554541777752535735305a5777675530645949464a766233516751304578476a415942674e5642416f4d45556c756447567349454e76636e4276636d46300a615739754d5251774567594456515148444174545957353059534244624746795954454c4d416b47413155454341774351304578437a414a42674e56424159540a416c56544d466b77457759484b6f5a497a6a3043415159494b6f5a497a6a3044415163445167414543366e45774d4449595a4f6a2f69505773437a61454b69370a314f694f534c52466857476a626e42564a66566e6b59347533496a6b4459594c304d784f346d717379596a6c42616c54565978465032734a424b357a6c4b4f420a757a43427544416642674e5648534d4547444157674251695a517a575770303069664f44744a5653763141624f5363477244425342674e5648523845537a424a0a4d45656752614244686b466f64485277637a6f764c324e6c636e52705a6d6c6a5958526c63793530636e567a6447566b63325679646d6c6a5a584d75615735300a5a577775593239744c306c756447567355306459556d397664454e424c6d526c636a416442674e564851344546675155496d554d316c71644e496e7a673753560a55723951477a6b6e4271777744675944565230504151482f42415144416745474d42494741315564457745422f7751494d4159424166384341514577436759490a4b6f5a497a6a3045417749445351417752674968414f572f35516b522b533943695344634e6f6f774c7550524c735747662f59693747535839344267775477670a41694541344a306c72486f4d732b586f356f2f7358364f39515778485241765a55474f6452513763767152586171493d0a2d2d2d2d2d454e442043455254494649434154452d2d2d2d2d0a0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let args = Args::parse();
+
+    let backend = match args.prover {
+        ProverArg::Env => Backend::Env,
+        ProverArg::Cpu => Backend::Cpu,
+        #[cfg(feature = "cuda")]
+        ProverArg::Cuda => Backend::Cuda,
+        #[cfg(feature = "network")]
+        ProverArg::Network => Backend::Network,
+        ProverArg::Mock => Backend::Mock,
+        #[allow(unreachable_patterns)]
+        other => {
+            eprintln!(
+                "the {:?} prover is not enabled by the features of this build",
+                other
+            );
+            std::process::exit(1);
+        }
+    };
+    let options = ProveOptions {
+        backend,
+        proof_system: match args.proof_system {
+            ProofSystemArg::Core => ProofSystem::Core,
+            ProofSystemArg::Compressed => ProofSystem::Compressed,
+            ProofSystemArg::Plonk => ProofSystem::Plonk,
+            ProofSystemArg::Groth16 => ProofSystem::Groth16,
+        },
+        bind_time: args.bind_time,
+    };
+
+    let v4_quote = hex::decode(include_str!("../../data/quote.hex").trim()).unwrap();
 
     let mut intel_collaterals = IntelCollateral::new();
     intel_collaterals.set_tcbinfo_bytes(include_bytes!("../../data/tcbinfo-tdx-v3.json"));
@@ -19,67 +89,46 @@ fn main() {
         .set_sgx_intel_root_ca_crl_der(include_bytes!("../../data/intel_root_ca_crl.der"));
     intel_collaterals.set_sgx_platform_crl_der(include_bytes!("../../data/pck_platform_crl.der"));
 
-    let intel_collaterals_bytes = intel_collaterals.to_bytes();
-
-    // get current time in seconds since epoch
-    // let current_time = std::time::SystemTime::now()
-    //     .duration_since(std::time::UNIX_EPOCH)
-    //     .unwrap()
-    //     .as_secs();
+    // The sample collaterals are valid at this time.
     let current_time = 1739589300u64;
-    let current_time_bytes = current_time.to_le_bytes();
-
-    // ZL: perform a simple serialization of the inputs
-    // [current_time: u64][quote_len: u32][intel_collaterals_len: u32][quote: var][intel_collaterals: var]
-    let quote_len = v4_quote.len() as u32;
-    let intel_collaterals_bytes_len = intel_collaterals_bytes.len() as u32;
-    let total_len = 8 + 4 + 4 + quote_len + intel_collaterals_bytes_len;
-
-    let mut input = Vec::with_capacity(total_len as usize);
-    input.extend_from_slice(&current_time_bytes);
-    input.extend_from_slice(&quote_len.to_le_bytes());
-    input.extend_from_slice(&intel_collaterals_bytes_len.to_le_bytes());
-    input.extend_from_slice(&v4_quote);
-    input.extend_from_slice(&intel_collaterals_bytes);
-
-    let mut stdin = SP1Stdin::new();
-    stdin.write_slice(&input);
-
-    let client = ProverClient::from_env();
 
     // Execute the program first
-    let (ret, report) = client.execute(DCAP_ELF, &stdin).run().unwrap();
-    println!(
-        "executed program with {} cycles",
-        report.total_instruction_count()
-    );
-    // println!("{:?}", report);
-
-    // Generate the proof
-    let (pk, vk) = client.setup(DCAP_ELF);
-    // let proof = client.prove(&pk, &stdin).groth16().run().unwrap();
-    let proof = client.prove(&pk, &stdin).plonk().run().unwrap();
-
-    let ret_slice = ret.as_slice();
-    let output_len = u16::from_be_bytes([ret_slice[0], ret_slice[1]]) as usize;
-    let mut output = Vec::with_capacity(output_len);
-    output.extend_from_slice(&ret_slice[2..2 + output_len]);
-
-    let proof_bytes = proof.bytes();
-
-    println!("Execution Output: {}", hex::encode(ret_slice));
-    println!(
-        "Proof pub value: {}",
-        hex::encode(proof.public_values.as_slice())
-    );
-    println!("VK: {}", vk.bytes32().to_string().as_str());
-    println!("Proof: {}", hex::encode(&proof_bytes));
-    println!("Proof selector: {}", hex::encode(&proof_bytes[..4]));
+    let report = execute(&v4_quote, &intel_collaterals, current_time, args.bind_time).unwrap();
+    if args.execute_only {
+        let report = serde_json::to_string_pretty(&report).unwrap();
+        println!("{}", report);
+        if let Some(out_dir) = &args.out_dir {
+            std::fs::create_dir_all(out_dir).unwrap();
+            std::fs::write(out_dir.join(CYCLE_REPORT_FILE), report).unwrap();
+        }
+        return;
+    }
+    println!("executed program with {} cycles", report.total_cycles);
+
+    // Generate and verify the proof
+    let proof = prove(&v4_quote, &intel_collaterals, current_time, &options).unwrap();
+    println!("Successfully verified proof.");
 
-    let parsed_output = VerifiedOutput::from_bytes(&output);
+    if let Some(out_dir) = &args.out_dir {
+        proof.write_artifacts(out_dir).unwrap();
+        println!("Proof artifacts written to {}", out_dir.display());
+    }
+
+    println!("Execution Output: {}", report.output);
+    let output = match DcapProgramJournal::decode(&proof.public_values).unwrap() {
+        DcapProgramJournal::Success(output) => output,
+        DcapProgramJournal::Failure(code) => {
+            println!("Verification failed: {}", code);
+            return;
+        }
+    };
+    println!("Proof pub value: {}", hex::encode(&proof.public_values));
+    println!("VK: {}", proof.vkey);
+    println!("Proof: {}", hex::encode(&proof.proof));
+    if let ProofSystem::Plonk | ProofSystem::Groth16 = options.proof_system {
+        println!("Proof selector: {}", hex::encode(&proof.proof[..4]));
+    }
+
+    let parsed_output = VerifiedOutput::from_bytes(&output.verified_output);
     println!("{:?}", parsed_output);
-
-    // Verify proof
-    client.verify(&proof, &vk).expect("Failed to verify proof");
-    println!("Successfully verified proof.");
 }
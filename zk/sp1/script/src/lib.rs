@@ -0,0 +1,430 @@
+//! Generates SP1 proofs of the DCAP quote verification performed by the guest programs.
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use dcap_codec::aggregation::{program_id_from_words, DcapAggregation};
+use dcap_codec::batch::DcapBatchInput;
+use dcap_codec::DcapProgramInput;
+use dcap_rs::types::collaterals::IntelCollateral;
+use serde::{Deserialize, Serialize};
+use sp1_sdk::{
+    include_elf, HashableKey, Prover, ProverClient, SP1Proof, SP1ProofWithPublicValues, SP1Stdin,
+    SP1VerifyingKey,
+};
+
+pub const DCAP_ELF: &[u8] = include_elf!("dcap-sp1-guest-program");
+pub const DCAP_BATCH_ELF: &[u8] = include_elf!("dcap-sp1-batch-program");
+pub const DCAP_AGGREGATION_ELF: &[u8] = include_elf!("dcap-sp1-aggregation-program");
+
+/// `ZkCoProcessorType` of SP1 in the DCAP attestation contract.
+pub const ZK_COPROCESSOR_SP1: u8 = 2;
+
+/// File names written by `DcapProof::write_artifacts`.
+pub const MANIFEST_FILE: &str = "proof.json";
+pub const PUBLIC_VALUES_FILE: &str = "public_values.bin";
+pub const PROOF_FILE: &str = "proof.bin";
+pub const PROOF_WITH_PUBLIC_VALUES_FILE: &str = "proof_with_public_values.bin";
+/// File name of the `CycleReport` written by the `--execute-only` mode of the `dcap` binary.
+pub const CYCLE_REPORT_FILE: &str = "cycles.json";
+
+/// Where the proof is generated.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Backend {
+    /// Selected by `SP1_PROVER`.
+    #[default]
+    Env,
+    Cpu,
+    /// Requires the `cuda` feature.
+    #[cfg(feature = "cuda")]
+    Cuda,
+    /// Succinct prover network, authenticated with `NETWORK_PRIVATE_KEY`.
+    /// Requires the `network` feature.
+    #[cfg(feature = "network")]
+    Network,
+    /// Executes the program without proving, for testing.
+    Mock,
+}
+
+/// Kind of proof produced.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum ProofSystem {
+    /// One STARK per shard.
+    Core,
+    /// A single STARK.
+    Compressed,
+    /// A PLONK SNARK that can be verified on-chain.
+    #[default]
+    Plonk,
+    /// A Groth16 SNARK that can be verified on-chain.
+    Groth16,
+}
+
+impl ProofSystem {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProofSystem::Core => "core",
+            ProofSystem::Compressed => "compressed",
+            ProofSystem::Plonk => "plonk",
+            ProofSystem::Groth16 => "groth16",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProveOptions {
+    pub backend: Backend,
+    pub proof_system: ProofSystem,
+    /// Requires the verification time to be within the validity of the collaterals, and
+    /// commits that window to the journal.
+    pub bind_time: bool,
+}
+
+pub struct DcapProof {
+    /// The public values committed by the program. For a single quote, the `output` of
+    /// `verifyAndAttestWithZKProof`.
+    pub public_values: Vec<u8>,
+    /// For PLONK and Groth16, the proof verified on-chain. Otherwise the bincode encoded proof.
+    pub proof: Vec<u8>,
+    /// The verification key of the program, as a 0x prefixed hex string.
+    pub vkey: String,
+    pub proof_system: ProofSystem,
+    pub proof_with_public_values: SP1ProofWithPublicValues,
+}
+
+/// Summary of a proof written to `proof.json`, holding what is needed to submit it with
+/// `verifyAndAttestWithZKProof`. Byte fields are 0x prefixed hex strings.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofManifest {
+    pub zkvm: String,
+    pub zk_coprocessor: u8,
+    pub proof_system: String,
+    pub vkey: String,
+    pub public_values: String,
+    pub proof: String,
+}
+
+impl DcapProof {
+    pub fn manifest(&self) -> ProofManifest {
+        ProofManifest {
+            zkvm: "sp1".to_string(),
+            zk_coprocessor: ZK_COPROCESSOR_SP1,
+            proof_system: self.proof_system.as_str().to_string(),
+            vkey: self.vkey.clone(),
+            public_values: format!("0x{}", hex::encode(&self.public_values)),
+            proof: format!("0x{}", hex::encode(&self.proof)),
+        }
+    }
+
+    /// Writes the proof to `dir`, which is created if needed:
+    /// - `proof.json`: the `ProofManifest`;
+    /// - `public_values.bin`: the raw public values;
+    /// - `proof.bin`: the raw proof bytes;
+    /// - `proof_with_public_values.bin`: the `SP1ProofWithPublicValues` saved by the SDK, to
+    ///   verify or aggregate the proof later.
+    pub fn write_artifacts(&self, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::write(
+            dir.join(MANIFEST_FILE),
+            serde_json::to_string_pretty(&self.manifest())?,
+        )?;
+        fs::write(dir.join(PUBLIC_VALUES_FILE), &self.public_values)?;
+        fs::write(dir.join(PROOF_FILE), &self.proof)?;
+        self.proof_with_public_values
+            .save(dir.join(PROOF_WITH_PUBLIC_VALUES_FILE))?;
+        Ok(())
+    }
+
+    /// Reads a proof written by `write_artifacts`.
+    pub fn read_artifacts(dir: &Path) -> Result<Self> {
+        let manifest: ProofManifest = serde_json::from_slice(&fs::read(dir.join(MANIFEST_FILE))?)?;
+        let proof_system = match manifest.proof_system.as_str() {
+            "core" => ProofSystem::Core,
+            "compressed" => ProofSystem::Compressed,
+            "plonk" => ProofSystem::Plonk,
+            "groth16" => ProofSystem::Groth16,
+            other => bail!("unknown proof system {}", other),
+        };
+        let proof_with_public_values =
+            SP1ProofWithPublicValues::load(dir.join(PROOF_WITH_PUBLIC_VALUES_FILE))?;
+        Ok(DcapProof {
+            public_values: proof_with_public_values.public_values.to_vec(),
+            proof: fs::read(dir.join(PROOF_FILE))?,
+            vkey: manifest.vkey,
+            proof_system,
+            proof_with_public_values,
+        })
+    }
+}
+
+/// Cycles spent by an execution of a program, see `execute`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycleReport {
+    pub zkvm: String,
+    /// `dcap` or `dcap-batch`.
+    pub program: String,
+    /// Instructions executed over all the shards.
+    pub total_cycles: u64,
+    /// Cycles spent in each `Phase` of the verification, by phase name. The remaining cycles
+    /// are spent reading the input, committing the public values and reporting the phases.
+    pub phases: BTreeMap<String, u64>,
+    /// The public values, as a 0x prefixed hex string.
+    pub output: String,
+}
+
+/// The verification keys of the embedded programs, by package name, as 0x prefixed hex
+/// strings. See `vkeys.json` for the pinned values.
+pub fn vkeys() -> BTreeMap<String, String> {
+    // The verification key does not depend on the prover.
+    let client = ProverClient::builder().cpu().build();
+    [
+        ("dcap-sp1-guest-program", DCAP_ELF),
+        ("dcap-sp1-batch-program", DCAP_BATCH_ELF),
+        ("dcap-sp1-aggregation-program", DCAP_AGGREGATION_ELF),
+    ]
+    .into_iter()
+    .map(|(name, elf)| (name.to_string(), client.setup(elf).1.bytes32()))
+    .collect()
+}
+
+/// Serializes the program input, see `DcapProgramInput`.
+pub fn serialize_input(
+    quote: &[u8],
+    collaterals: &IntelCollateral,
+    current_time: u64,
+    bind_time: bool,
+) -> Result<Vec<u8>> {
+    let input = DcapProgramInput {
+        current_time,
+        quote: quote.to_vec(),
+        collaterals: collaterals.to_bytes(),
+        bind_time,
+    };
+    Ok(input.encode()?)
+}
+
+/// Serializes the batch program input, see `DcapBatchInput`.
+pub fn serialize_batch_input(
+    quotes: &[Vec<u8>],
+    collaterals: &IntelCollateral,
+    current_time: u64,
+    bind_time: bool,
+) -> Result<Vec<u8>> {
+    let input = DcapBatchInput {
+        current_time,
+        quotes: quotes.to_vec(),
+        collaterals: collaterals.to_bytes(),
+        bind_time,
+    };
+    Ok(input.encode()?)
+}
+
+/// Sets up the keys, proves and verifies the proof with the given prover client.
+/// The clients returned by `ProverClient::builder()` have no common trait object.
+macro_rules! prove_with {
+    ($client:expr, $elf:expr, $stdin:expr, $proof_system:expr) => {{
+        let client = $client;
+        let (pk, vk) = client.setup($elf);
+        let builder = client.prove(&pk, $stdin);
+        let proof = match $proof_system {
+            ProofSystem::Core => builder.core().run()?,
+            ProofSystem::Compressed => builder.compressed().run()?,
+            ProofSystem::Plonk => builder.plonk().run()?,
+            ProofSystem::Groth16 => builder.groth16().run()?,
+        };
+        client.verify(&proof, &vk)?;
+        (proof, vk)
+    }};
+}
+
+/// Executes the program without proving, and reports the cycles spent in each phase of the
+/// verification of `quote`.
+pub fn execute(
+    quote: &[u8],
+    collaterals: &IntelCollateral,
+    current_time: u64,
+    bind_time: bool,
+) -> Result<CycleReport> {
+    let mut stdin = SP1Stdin::new();
+    stdin.write_slice(&serialize_input(
+        quote,
+        collaterals,
+        current_time,
+        bind_time,
+    )?);
+    execute_program("dcap", DCAP_ELF, &stdin)
+}
+
+/// Executes the batch program without proving, see `execute`.
+pub fn execute_batch(
+    quotes: &[Vec<u8>],
+    collaterals: &IntelCollateral,
+    current_time: u64,
+    bind_time: bool,
+) -> Result<CycleReport> {
+    if quotes.is_empty() {
+        bail!("no quote to execute");
+    }
+    let mut stdin = SP1Stdin::new();
+    stdin.write_slice(&serialize_batch_input(
+        quotes,
+        collaterals,
+        current_time,
+        bind_time,
+    )?);
+    execute_program("dcap-batch", DCAP_BATCH_ELF, &stdin)
+}
+
+/// Proves the verification of `quote` against `collaterals` at `current_time` (seconds since
+/// the Unix epoch).
+pub fn prove(
+    quote: &[u8],
+    collaterals: &IntelCollateral,
+    current_time: u64,
+    options: &ProveOptions,
+) -> Result<DcapProof> {
+    let mut stdin = SP1Stdin::new();
+    stdin.write_slice(&serialize_input(
+        quote,
+        collaterals,
+        current_time,
+        options.bind_time,
+    )?);
+    prove_program(DCAP_ELF, &stdin, options)
+}
+
+/// Proves the verification of `quotes`, which share the same `collaterals`, in a single proof
+/// of the batch program. Its public values are a `DcapBatchJournal`.
+pub fn prove_batch(
+    quotes: &[Vec<u8>],
+    collaterals: &IntelCollateral,
+    current_time: u64,
+    options: &ProveOptions,
+) -> Result<DcapProof> {
+    if quotes.is_empty() {
+        bail!("no quote to prove");
+    }
+    let mut stdin = SP1Stdin::new();
+    stdin.write_slice(&serialize_batch_input(
+        quotes,
+        collaterals,
+        current_time,
+        options.bind_time,
+    )?);
+    prove_program(DCAP_BATCH_ELF, &stdin, options)
+}
+
+/// Aggregates proofs of the same program, made by `prove` or `prove_batch`, in a single proof
+/// of the aggregation program. Its public values are a `DcapAggregation` holding the
+/// verification key hash of the program and the public values of the proofs, in the order of
+/// `proofs`.
+///
+/// The aggregation program commits whatever verification key it is given, so proofs of any
+/// other program than `DCAP_ELF` and `DCAP_BATCH_ELF` are rejected here. The proofs are
+/// verified by the aggregation program, so they must be `Compressed`.
+pub fn aggregate(proofs: &[DcapProof], options: &ProveOptions) -> Result<DcapProof> {
+    let Some(first) = proofs.first() else {
+        bail!("no proof to aggregate");
+    };
+    // The verification key does not depend on the prover.
+    let client = ProverClient::builder().cpu().build();
+    let Some(vk) = [DCAP_ELF, DCAP_BATCH_ELF]
+        .into_iter()
+        .map(|elf| client.setup(elf).1)
+        .find(|vk| vk.bytes32() == first.vkey)
+    else {
+        bail!(
+            "{} is not the verification key of the DCAP program nor of the DCAP batch program",
+            first.vkey
+        );
+    };
+
+    let input = DcapAggregation {
+        program_id: program_id_from_words(vk.hash_u32()),
+        journals: proofs
+            .iter()
+            .map(|proof| proof.public_values.clone())
+            .collect(),
+    };
+    let mut stdin = SP1Stdin::new();
+    stdin.write_slice(&input.encode()?);
+    for proof in proofs {
+        if proof.vkey != first.vkey {
+            bail!("the proofs to aggregate are not from the same program");
+        }
+        let SP1Proof::Compressed(reduce_proof) = &proof.proof_with_public_values.proof else {
+            bail!("only compressed proofs can be aggregated");
+        };
+        client.verify(&proof.proof_with_public_values, &vk)?;
+        stdin.write_proof(*reduce_proof.clone(), vk.vk.clone());
+    }
+    prove_program(DCAP_AGGREGATION_ELF, &stdin, options)
+}
+
+fn execute_program(program: &str, elf: &[u8], stdin: &SP1Stdin) -> Result<CycleReport> {
+    // Execution does not depend on the prover, the CPU prover needs no GPU nor network access.
+    let (public_values, report) = ProverClient::builder()
+        .cpu()
+        .build()
+        .execute(elf, stdin)
+        .run()?;
+    Ok(CycleReport {
+        zkvm: "sp1".to_string(),
+        program: program.to_string(),
+        total_cycles: report.total_instruction_count(),
+        phases: report
+            .cycle_tracker
+            .iter()
+            .map(|(phase, cycles)| (phase.clone(), *cycles))
+            .collect(),
+        output: format!("0x{}", hex::encode(public_values.as_slice())),
+    })
+}
+
+fn prove_program(elf: &[u8], stdin: &SP1Stdin, options: &ProveOptions) -> Result<DcapProof> {
+    let (proof, vk): (SP1ProofWithPublicValues, SP1VerifyingKey) = match options.backend {
+        Backend::Env => prove_with!(ProverClient::from_env(), elf, stdin, options.proof_system),
+        Backend::Cpu => prove_with!(
+            ProverClient::builder().cpu().build(),
+            elf,
+            stdin,
+            options.proof_system
+        ),
+        #[cfg(feature = "cuda")]
+        Backend::Cuda => prove_with!(
+            ProverClient::builder().cuda().build(),
+            elf,
+            stdin,
+            options.proof_system
+        ),
+        #[cfg(feature = "network")]
+        Backend::Network => prove_with!(
+            ProverClient::builder().network().build(),
+            elf,
+            stdin,
+            options.proof_system
+        ),
+        Backend::Mock => prove_with!(
+            ProverClient::builder().mock().build(),
+            elf,
+            stdin,
+            options.proof_system
+        ),
+    };
+
+    let proof_bytes = match options.proof_system {
+        ProofSystem::Plonk | ProofSystem::Groth16 => proof.bytes(),
+        ProofSystem::Core | ProofSystem::Compressed => bincode::serialize(&proof)?,
+    };
+
+    Ok(DcapProof {
+        public_values: proof.public_values.to_vec(),
+        proof: proof_bytes,
+        vkey: vk.bytes32(),
+        proof_system: options.proof_system,
+        proof_with_public_values: proof,
+    })
+}